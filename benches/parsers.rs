@@ -0,0 +1,52 @@
+//! Benchmarks for the product page parsing path, run against the pages in
+//! `fixtures/`.
+//!
+//! Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ransaq::saq::{detailed_info, extract_detailed_info, extract_linked_data};
+
+/// Product pages bundled in `fixtures/`, by name.
+const PAGES: &[(&str, &str)] = &[
+    ("wine", include_str!("../fixtures/product_wine.html")),
+    ("beer", include_str!("../fixtures/product_beer.html")),
+    ("spirit", include_str!("../fixtures/product_spirit.html")),
+];
+
+/// Benchmarks HTML parsing and the extraction of JSON-LD and detailed info.
+fn bench_extract(c: &mut Criterion) {
+    for (name, html) in PAGES {
+        c.bench_function(&format!("parse_document/{name}"), |b| {
+            b.iter(|| scraper::Html::parse_document(black_box(html)))
+        });
+
+        let document = scraper::Html::parse_document(html);
+
+        c.bench_function(&format!("extract_linked_data/{name}"), |b| {
+            b.iter(|| extract_linked_data(black_box(&document)).unwrap())
+        });
+
+        c.bench_function(&format!("extract_detailed_info/{name}"), |b| {
+            b.iter(|| extract_detailed_info(black_box(&document)).unwrap())
+        });
+    }
+}
+
+/// Benchmarks the individual detailed info field parsers.
+fn bench_detailed_info(c: &mut Criterion) {
+    c.bench_function("parse_grape_varieties", |b| {
+        b.iter(|| {
+            detailed_info::parse_grape_varieties(black_box(
+                "Zinfandel 80 %, Petite sirah 16 %, Carignan 4 %, Cabernet sauvignon",
+            ))
+            .unwrap()
+        })
+    });
+
+    c.bench_function("parse_size", |b| {
+        b.iter(|| detailed_info::parse_size(black_box("6 x 296 ml")).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_extract, bench_detailed_info);
+criterion_main!(benches);
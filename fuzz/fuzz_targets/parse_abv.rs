@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ransaq::saq::detailed_info::parse_abv;
+
+fuzz_target!(|text: &str| {
+    let _ = parse_abv(text);
+});
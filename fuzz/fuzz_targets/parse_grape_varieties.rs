@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ransaq::saq::detailed_info::parse_grape_varieties;
+
+fuzz_target!(|text: &str| {
+    if let Ok(varieties) = parse_grape_varieties(text) {
+        for variety in varieties {
+            assert!(!variety.name.is_empty());
+            assert!(variety.percentage.unwrap_or(0) <= 100);
+        }
+    }
+});
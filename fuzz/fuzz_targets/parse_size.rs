@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ransaq::saq::detailed_info::parse_size;
+
+fuzz_target!(|text: &str| {
+    if let Ok(size) = parse_size(text) {
+        assert!(size.container_count > 0);
+        assert!(size.container_milliliters > 0);
+    }
+});
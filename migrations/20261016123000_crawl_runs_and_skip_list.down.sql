@@ -0,0 +1,3 @@
+drop table skip_list;
+drop table crawl_errors;
+drop table crawl_runs;
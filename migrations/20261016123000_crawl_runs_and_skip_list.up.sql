@@ -0,0 +1,27 @@
+create table crawl_runs (
+  id integer primary key,
+  status text check (status in ('running', 'completed', 'failed')) not null default 'running',
+  started_at text not null default (datetime('now', 'utc')),
+  finished_at text
+) strict;
+
+create table crawl_errors (
+  id integer primary key,
+  crawl_run_id integer references crawl_runs(id) not null,
+  url text not null,
+  error_class text check (error_class in ('http_client_error', 'http_server_error', 'network', 'parse', 'database')) not null,
+  message text not null,
+  created_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create index crawl_errors__url on crawl_errors(url);
+
+create table skip_list (
+  id integer primary key,
+  url text not null,
+  error_class text check (error_class in ('http_client_error', 'http_server_error', 'network', 'parse', 'database')) not null,
+  created_at text not null default (datetime('now', 'utc')),
+  expires_at text not null
+) strict;
+
+create unique index skip_list__url on skip_list(url);
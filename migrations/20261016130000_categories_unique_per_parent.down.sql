@@ -0,0 +1,4 @@
+-- Fails if categories with the same name exist under different parents.
+drop index categories__parent_category_id__name;
+
+create unique index categories__name on categories(name);
@@ -0,0 +1,9 @@
+-- Category names are only unique among siblings (i.e. "Red" under both "Wine"
+-- and "Cider"). Root categories have a NULL parent, which unique indexes treat as
+-- distinct values, hence the `coalesce`.
+--
+-- Categories that were previously collapsed into one row keep whichever parent
+-- they were last crawled with, and get split back up as products are recrawled.
+drop index categories__name;
+
+create unique index categories__parent_category_id__name on categories(coalesce(parent_category_id, 0), name);
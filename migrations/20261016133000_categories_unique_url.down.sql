@@ -0,0 +1,4 @@
+-- Fails if categories with the same name exist under the same parent.
+drop index categories__url;
+
+create unique index categories__parent_category_id__name on categories(coalesce(parent_category_id, 0), name);
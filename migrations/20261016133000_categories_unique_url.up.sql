@@ -0,0 +1,32 @@
+-- Categories are identified by their listing URL from now on, as display names
+-- change over time and the same name can appear in several places of the tree.
+drop index categories__parent_category_id__name;
+
+-- Backfill: categories sharing a URL (i.e. renamed between crawls) are merged into
+-- the most recently created one.
+create temp table category_merges as
+  select c.id as old_id, (select max(c2.id) from categories c2 where c2.url = c.url) as new_id
+  from categories c
+  where c.id != (select max(c2.id) from categories c2 where c2.url = c.url);
+
+delete from product_categories
+where category_id in (select old_id from category_merges)
+and exists (
+  select 1 from product_categories pc
+  join category_merges m on m.new_id = pc.category_id
+  where m.old_id = product_categories.category_id and pc.product_id = product_categories.product_id
+);
+
+update product_categories
+set category_id = (select new_id from category_merges where old_id = category_id)
+where category_id in (select old_id from category_merges);
+
+update categories
+set parent_category_id = (select new_id from category_merges where old_id = parent_category_id)
+where parent_category_id in (select old_id from category_merges);
+
+delete from categories where id in (select old_id from category_merges);
+
+drop table category_merges;
+
+create unique index categories__url on categories(url);
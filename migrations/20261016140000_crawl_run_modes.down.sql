@@ -0,0 +1 @@
+alter table crawl_runs drop column mode;
@@ -0,0 +1 @@
+alter table crawl_runs add column mode text check (mode in ('full', 'new_arrivals')) not null default 'full';
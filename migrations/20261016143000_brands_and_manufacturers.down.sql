@@ -0,0 +1,5 @@
+alter table products drop column manufacturer_id;
+alter table products drop column brand_id;
+
+drop table manufacturers;
+drop table brands;
@@ -0,0 +1,16 @@
+create table brands (
+  id integer primary key,
+  name text not null
+) strict;
+
+create unique index brands__name on brands(name);
+
+create table manufacturers (
+  id integer primary key,
+  name text not null
+) strict;
+
+create unique index manufacturers__name on manufacturers(name);
+
+alter table products add column brand_id integer references brands(id);
+alter table products add column manufacturer_id integer references manufacturers(id);
@@ -0,0 +1,11 @@
+create table product_identifiers (
+  id integer primary key,
+  product_id integer references products(id) not null,
+  scheme text check (scheme in ('upc', 'gtin', 'gtin13', 'mpn')) not null,
+  value text not null,
+  created_at text not null default (datetime('now', 'utc')),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index product_identifiers__product_id__scheme on product_identifiers(product_id, scheme);
+create index product_identifiers__scheme__value on product_identifiers(scheme, value);
@@ -0,0 +1,4 @@
+alter table products drop column seller_id;
+alter table products drop column price_valid_until;
+
+drop table sellers;
@@ -0,0 +1,9 @@
+create table sellers (
+  id integer primary key,
+  name text not null
+) strict;
+
+create unique index sellers__name on sellers(name);
+
+alter table products add column price_valid_until text check (date(price_valid_until) = price_valid_until);
+alter table products add column seller_id integer references sellers(id);
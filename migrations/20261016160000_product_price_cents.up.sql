@@ -0,0 +1,69 @@
+-- SQLite can't change a column's type in place, so `products` has to be rebuilt
+-- (see 20261016120000_nullable_product_description_and_image.up.sql).
+create temp table product_grape_varieties_backup as select * from product_grape_varieties;
+create temp table product_special_features_backup as select * from product_special_features;
+create temp table product_categories_backup as select * from product_categories;
+create temp table product_identifiers_backup as select * from product_identifiers;
+
+delete from product_grape_varieties;
+delete from product_special_features;
+delete from product_categories;
+delete from product_identifiers;
+
+create table products_new (
+  id integer primary key,
+  saq_code text not null,
+  upc_code text,
+  name text not null,
+  description text,
+  image_url text,
+  availability text check (availability in ('back_order', 'discontinued', 'in_stock', 'in_store_only', 'limited_availability', 'online_only', 'out_of_stock', 'pre_order', 'pre_sale', 'sold_out')) not null,
+  item_condition text check (item_condition in ('damaged', 'new', 'refurbished', 'used')) not null,
+  price_cents integer check (price_cents > 0) not null,
+  producer_id integer references producers(id),
+  promoting_agent_id integer references promoting_agents(id),
+  abv_percentage real,
+  container_count integer,
+  container_milliliters integer,
+  color_id integer references colors(id),
+  region_id integer references regions(id),
+  country_id integer references countries(id),
+  product_of_quebec text check (product_of_quebec in ('bottled_in_quebec', 'made_in_quebec', 'origine_quebec')),
+  sugar_content_equality text check (sugar_content_equality in ('>', '<', '=')),
+  sugar_content_grams_per_liter real,
+  regulated_designation_id integer references regulated_designations(id),
+  designation_of_origin_id integer references designations_of_origin(id),
+  classification_id integer references classifications(id),
+  created_at text not null default (datetime('now', 'utc')), 
+  updated_at text not null default (datetime('now', 'utc')),
+  brand_id integer references brands(id),
+  manufacturer_id integer references manufacturers(id),
+  price_valid_until text check (date(price_valid_until) = price_valid_until),
+  seller_id integer references sellers(id)
+) strict;
+
+insert into products_new select
+  id, saq_code, upc_code, name, description, image_url, availability, item_condition,
+  cast(round(price_cad * 100) as integer), producer_id, promoting_agent_id, abv_percentage, container_count,
+  container_milliliters, color_id, region_id, country_id, product_of_quebec,
+  sugar_content_equality, sugar_content_grams_per_liter, regulated_designation_id,
+  designation_of_origin_id, classification_id, created_at, updated_at, brand_id,
+  manufacturer_id, price_valid_until, seller_id
+from products;
+
+drop table products;
+
+alter table products_new rename to products;
+
+create unique index products__saq_code on products(saq_code);
+create unique index products__upc_code on products(upc_code);
+
+insert into product_grape_varieties select * from product_grape_varieties_backup;
+insert into product_special_features select * from product_special_features_backup;
+insert into product_categories select * from product_categories_backup;
+insert into product_identifiers select * from product_identifiers_backup;
+
+drop table product_grape_varieties_backup;
+drop table product_special_features_backup;
+drop table product_categories_backup;
+drop table product_identifiers_backup;
@@ -0,0 +1,2 @@
+alter table products drop column description_changed_at;
+alter table products drop column description_hash;
@@ -0,0 +1,2 @@
+alter table products add column description_hash text;
+alter table products add column description_changed_at text;
@@ -0,0 +1 @@
+alter table crawl_errors drop column action;
@@ -0,0 +1 @@
+alter table crawl_errors add column action text check (action in ('retry', 'skip', 'abort'));
@@ -0,0 +1 @@
+alter table products add column partial integer not null default 0 check (partial in (0, 1));
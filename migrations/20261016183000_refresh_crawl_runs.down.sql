@@ -0,0 +1,7 @@
+delete from crawl_errors where crawl_run_id in (select id from crawl_runs where mode = 'refresh');
+delete from crawl_runs where mode = 'refresh';
+
+alter table crawl_runs add column mode_old text check (mode_old in ('full', 'new_arrivals', 'completion')) not null default 'full';
+update crawl_runs set mode_old = mode;
+alter table crawl_runs drop column mode;
+alter table crawl_runs rename column mode_old to mode;
@@ -0,0 +1,6 @@
+-- SQLite can't alter column constraints in place, so `mode` is swapped for a
+-- new column allowing refresh runs.
+alter table crawl_runs add column mode_new text check (mode_new in ('full', 'new_arrivals', 'completion', 'refresh')) not null default 'full';
+update crawl_runs set mode_new = mode;
+alter table crawl_runs drop column mode;
+alter table crawl_runs rename column mode_new to mode;
@@ -0,0 +1,12 @@
+create table field_mismatches (
+  id integer primary key,
+  crawl_run_id integer references crawl_runs(id) not null,
+  saq_code text not null,
+  field text check (field in ('name', 'availability', 'price_cents')) not null,
+  listing_value text not null,
+  product_page_value text not null,
+  chosen text check (chosen in ('listing', 'product_page')) not null,
+  created_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create index field_mismatches__crawl_run_id on field_mismatches(crawl_run_id);
@@ -0,0 +1,2 @@
+drop trigger products__record_changes;
+drop table product_changes;
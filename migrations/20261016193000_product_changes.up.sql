@@ -0,0 +1,20 @@
+create table product_changes (
+  id integer primary key,
+  product_id integer references products(id) not null,
+  old_price_cents integer not null,
+  new_price_cents integer not null,
+  old_availability text not null,
+  new_availability text not null,
+  changed_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create index product_changes__product_id on product_changes(product_id);
+create index product_changes__changed_at on product_changes(changed_at);
+
+create trigger products__record_changes
+after update of price_cents, availability on products
+when old.price_cents != new.price_cents or old.availability != new.availability
+begin
+  insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability)
+  values (new.id, old.price_cents, new.price_cents, old.availability, new.availability);
+end;
@@ -0,0 +1 @@
+drop table metrics_history;
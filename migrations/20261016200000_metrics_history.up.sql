@@ -0,0 +1,10 @@
+create table metrics_history (
+  id integer primary key,
+  crawl_run_id integer references crawl_runs(id) not null,
+  name text not null,
+  value real not null,
+  products integer not null,
+  created_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index metrics_history__crawl_run_id_name on metrics_history(crawl_run_id, name);
@@ -0,0 +1,2 @@
+alter table crawl_runs drop column note;
+alter table crawl_runs drop column tags;
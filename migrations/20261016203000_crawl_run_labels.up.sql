@@ -0,0 +1,2 @@
+alter table crawl_runs add column tags text not null default '[]' check (json_valid(tags) and json_type(tags) = 'array');
+alter table crawl_runs add column note text;
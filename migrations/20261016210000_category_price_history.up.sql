@@ -0,0 +1,12 @@
+create table category_price_history (
+  id integer primary key,
+  crawl_run_id integer references crawl_runs(id) not null,
+  category_id integer references categories(id) not null,
+  products integer not null,
+  mean_price_cents real not null,
+  median_price_cents real not null,
+  created_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index category_price_history__crawl_run_id__category_id on category_price_history(crawl_run_id, category_id);
+create index category_price_history__category_id on category_price_history(category_id);
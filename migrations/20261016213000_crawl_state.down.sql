@@ -0,0 +1 @@
+drop table crawl_state;
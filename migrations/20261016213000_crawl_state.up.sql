@@ -0,0 +1,6 @@
+create table crawl_state (
+  crawl_run_id integer primary key references crawl_runs(id),
+  next_page integer not null check (next_page > 0),
+  pending text not null default '[]' check (json_valid(pending) and json_type(pending) = 'array'),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
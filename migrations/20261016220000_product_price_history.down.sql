@@ -0,0 +1 @@
+drop view product_price_history;
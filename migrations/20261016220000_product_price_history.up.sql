@@ -0,0 +1,19 @@
+-- Every price each product was crawled at, derived from the price changes
+-- recorded in `product_changes` so that nothing has to be written twice.
+-- `product_change_id` orders changes recorded within the same second.
+create view product_price_history as
+-- The price the product was first crawled at, which is the old price of its
+-- first recorded change, if any
+select p.id as product_id,
+  coalesce(
+    (select pc.old_price_cents from product_changes pc
+     where pc.product_id = p.id order by pc.changed_at, pc.id limit 1),
+    p.price_cents
+  ) as price_cents,
+  p.created_at as recorded_at,
+  null as product_change_id
+from products p
+union all
+select product_id, new_price_cents, changed_at, id
+from product_changes
+where new_price_cents != old_price_cents;
@@ -0,0 +1 @@
+alter table products drop column detailed_info_source;
@@ -0,0 +1,4 @@
+-- Where each product's "Detailed Info" was found on its page, to track down
+-- pages that only parse thanks to a fallback. Null until the next crawl.
+alter table products add column detailed_info_source text
+    check (detailed_info_source in ('selector', 'fallback_selector', 'mage_init'));
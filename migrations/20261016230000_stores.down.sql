@@ -0,0 +1,2 @@
+drop table geocoded_postal_codes;
+drop table stores;
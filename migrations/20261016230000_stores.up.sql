@@ -0,0 +1,24 @@
+-- SAQ stores, imported with `ransaq stores import`. Stores listed without
+-- coordinates get those of their postal code, if it could be geocoded.
+create table stores (
+  id integer primary key,
+  saq_store_id text not null,
+  name text not null,
+  address text not null,
+  city text not null,
+  postal_code text not null,
+  latitude real check (latitude between -90 and 90),
+  longitude real check (longitude between -180 and 180),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index stores__saq_store_id on stores(saq_store_id);
+
+-- Coordinates of the postal codes geocoded so far, so that each one is only
+-- looked up once.
+create table geocoded_postal_codes (
+  postal_code text primary key,
+  latitude real not null check (latitude between -90 and 90),
+  longitude real not null check (longitude between -180 and 180),
+  geocoded_at text not null default (datetime('now', 'utc'))
+) strict;
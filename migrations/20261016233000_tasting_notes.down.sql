@@ -0,0 +1,3 @@
+drop table product_food_pairings;
+drop table food_pairings;
+drop table tasting_notes;
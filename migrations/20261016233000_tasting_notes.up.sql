@@ -0,0 +1,32 @@
+-- The tasting section of product pages. Products without one (i.e. spirits
+-- and beers) have no row.
+create table tasting_notes (
+  product_id integer primary key references products(id),
+  taste_tag text,
+  aromas text not null default '[]' check (json_valid(aromas) and json_type(aromas) = 'array'),
+  acidity text,
+  sweetness text,
+  body text,
+  mouthfeel text,
+  wood text,
+  serving_temperature text,
+  created_at text not null default (datetime('now', 'utc')),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create table food_pairings (
+  id integer primary key,
+  name text not null
+) strict;
+
+create unique index food_pairings__name on food_pairings(name);
+
+create table product_food_pairings (
+  id integer primary key,
+  product_id integer references products(id) not null,
+  food_pairing_id integer references food_pairings(id) not null,
+  created_at text not null default (datetime('now', 'utc')),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index product_food_pairings__product_id__food_pairing_id on product_food_pairings(product_id, food_pairing_id);
@@ -0,0 +1,3 @@
+drop table reviews;
+alter table products drop column rating_count;
+alter table products drop column rating_value;
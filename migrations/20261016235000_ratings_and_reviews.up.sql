@@ -0,0 +1,22 @@
+-- The aggregate rating from the product's JSON-LD, if it has one, and how
+-- many ratings (or reviews) it is based on.
+alter table products add column rating_value real check (rating_value >= 0);
+alter table products add column rating_count integer check (rating_count >= 0);
+
+-- Reviews from the product's JSON-LD. They have no identifier of their own,
+-- so they're told apart by a hash of their author, date, title and body.
+create table reviews (
+  id integer primary key,
+  product_id integer references products(id) not null,
+  review_hash text not null,
+  author text,
+  title text,
+  body text,
+  rating_value real,
+  best_rating real,
+  published_on text,
+  created_at text not null default (datetime('now', 'utc')),
+  updated_at text not null default (datetime('now', 'utc'))
+) strict;
+
+create unique index reviews__product_id__review_hash on reviews(product_id, review_hash);
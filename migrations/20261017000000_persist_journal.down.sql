@@ -0,0 +1 @@
+drop table persist_journal;
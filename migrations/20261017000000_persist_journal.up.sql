@@ -0,0 +1,6 @@
+create table persist_journal (
+  url text primary key,
+  saq_code text not null,
+  crawl_run_id integer not null references crawl_runs(id),
+  started_at text not null default (datetime('now', 'utc'))
+) strict;
@@ -0,0 +1,30 @@
+drop view product_price_history;
+
+create view product_price_history as
+select p.id as product_id,
+  coalesce(
+    (select pc.old_price_cents from product_changes pc
+     where pc.product_id = p.id order by pc.changed_at, pc.id limit 1),
+    p.price_cents
+  ) as price_cents,
+  p.created_at as recorded_at,
+  null as product_change_id
+from products p
+union all
+select product_id, new_price_cents, changed_at, id
+from product_changes
+where new_price_cents != old_price_cents;
+
+drop trigger products__record_changes;
+
+create trigger products__record_changes
+after update of price_cents, availability on products
+when old.price_cents != new.price_cents or old.availability != new.availability
+begin
+  insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability)
+  values (new.id, old.price_cents, new.price_cents, old.availability, new.availability);
+end;
+
+alter table product_changes drop column new_regular_price_cents;
+alter table product_changes drop column old_regular_price_cents;
+alter table products drop column regular_price_cents;
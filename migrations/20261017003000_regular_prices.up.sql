@@ -0,0 +1,46 @@
+-- The price before the discount while the product is on sale, null
+-- otherwise. `price_cents` remains the price the product sells at.
+alter table products add column regular_price_cents integer check (regular_price_cents > 0);
+
+-- Changes recorded from here on also track the regular price, so that sales
+-- starting and ending show up in the history
+alter table product_changes add column old_regular_price_cents integer;
+alter table product_changes add column new_regular_price_cents integer;
+
+drop trigger products__record_changes;
+
+create trigger products__record_changes
+after update of price_cents, availability, regular_price_cents on products
+when old.price_cents != new.price_cents or old.availability != new.availability
+  or old.regular_price_cents is not new.regular_price_cents
+begin
+  insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability,
+    new_availability, old_regular_price_cents, new_regular_price_cents)
+  values (new.id, old.price_cents, new.price_cents, old.availability, new.availability,
+    old.regular_price_cents, new.regular_price_cents);
+end;
+
+drop view product_price_history;
+
+-- Same as before, with the regular price alongside the price. Changes
+-- recorded before regular prices were tracked have none.
+create view product_price_history as
+select p.id as product_id,
+  coalesce(
+    (select pc.old_price_cents from product_changes pc
+     where pc.product_id = p.id order by pc.changed_at, pc.id limit 1),
+    p.price_cents
+  ) as price_cents,
+  (case when exists (select 1 from product_changes pc where pc.product_id = p.id)
+    then (select pc.old_regular_price_cents from product_changes pc
+          where pc.product_id = p.id order by pc.changed_at, pc.id limit 1)
+    else p.regular_price_cents end
+  ) as regular_price_cents,
+  p.created_at as recorded_at,
+  null as product_change_id
+from products p
+union all
+select product_id, new_price_cents, new_regular_price_cents, changed_at, id
+from product_changes
+where new_price_cents != old_price_cents
+  or new_regular_price_cents is not old_regular_price_cents;
@@ -0,0 +1 @@
+alter table crawl_state drop column listing;
@@ -0,0 +1,4 @@
+-- What the crawl was listing products from and when it stops (see
+-- `CrawlListing`), so that it's only resumed with the same settings.
+-- Checkpoints saved before this was recorded can't be resumed.
+alter table crawl_state add column listing text check (json_valid(listing));
@@ -0,0 +1 @@
+drop table unlocatable_postal_codes;
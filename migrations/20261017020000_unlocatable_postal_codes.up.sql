@@ -0,0 +1,7 @@
+-- Postal codes the geocoder didn't know, so that they aren't looked up again
+-- every time someone asks for them. They're retried once the entry is a
+-- month old, in case the geocoder learned about them since.
+create table unlocatable_postal_codes (
+  postal_code text primary key,
+  looked_up_at text not null default (datetime('now', 'utc'))
+) strict;
@@ -0,0 +1,28 @@
+drop view product_price_history;
+
+create view product_price_history as
+select p.id as product_id,
+  coalesce(
+    (select pc.old_price_cents from product_changes pc
+     where pc.product_id = p.id order by pc.changed_at, pc.id limit 1),
+    p.price_cents
+  ) as price_cents,
+  (case when exists (select 1 from product_changes pc where pc.product_id = p.id)
+    then (select pc.old_regular_price_cents from product_changes pc
+          where pc.product_id = p.id order by pc.changed_at, pc.id limit 1)
+    else p.regular_price_cents end
+  ) as regular_price_cents,
+  p.created_at as recorded_at,
+  null as product_change_id
+from products p
+union all
+select product_id, new_price_cents, new_regular_price_cents, changed_at, id
+from product_changes
+where new_price_cents != old_price_cents
+  or new_regular_price_cents is not old_regular_price_cents;
+
+drop trigger products__record_baseline;
+
+alter table products drop column baseline_recorded_at;
+alter table products drop column baseline_regular_price_cents;
+alter table products drop column baseline_price_cents;
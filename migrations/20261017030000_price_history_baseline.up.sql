@@ -0,0 +1,40 @@
+-- The first point of each product's price history. It used to be derived from
+-- the old price of the product's earliest recorded change, which stops being
+-- true once older changes are deleted (i.e. by `ransaq purge`), so it's stored
+-- instead and moved forward along with the history.
+alter table products add column baseline_price_cents integer;
+alter table products add column baseline_regular_price_cents integer;
+alter table products add column baseline_recorded_at text;
+
+update products set (baseline_price_cents, baseline_regular_price_cents, baseline_recorded_at) = (
+  select h.price_cents, h.regular_price_cents, h.recorded_at from product_price_history h
+  where h.product_id = products.id and h.product_change_id is null
+);
+
+-- Products start their history at the price they were first crawled at.
+-- Copies of existing rows (i.e. when replacing a database) bring their own.
+create trigger products__record_baseline
+after insert on products
+when new.baseline_recorded_at is null
+begin
+  update products set
+    baseline_price_cents = new.price_cents,
+    baseline_regular_price_cents = new.regular_price_cents,
+    baseline_recorded_at = new.created_at
+  where id = new.id;
+end;
+
+drop view product_price_history;
+
+create view product_price_history as
+select id as product_id,
+  baseline_price_cents as price_cents,
+  baseline_regular_price_cents as regular_price_cents,
+  baseline_recorded_at as recorded_at,
+  null as product_change_id
+from products
+union all
+select product_id, new_price_cents, new_regular_price_cents, changed_at, id
+from product_changes
+where new_price_cents != old_price_cents
+  or new_regular_price_cents is not old_regular_price_cents;
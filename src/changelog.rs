@@ -0,0 +1,230 @@
+//! Human-readable summaries of what changed in the catalog (new arrivals,
+//! delistings and notable price drops), suitable for posting to a blog or
+//! newsletter.
+//!
+//! The changes come from [`db::Client::changelog`](crate::db::Client::changelog)
+//! and are rendered as Markdown or HTML by `ransaq changelog`.
+
+use crate::db::{Changelog, ChangelogProduct, PriceDrop};
+use crate::saq::money::Price;
+use chrono_tz::Tz;
+use std::fmt::Write;
+
+/// Price drops smaller than this percentage of the old price are left out
+/// unless `ransaq changelog --min-drop` says otherwise.
+pub const DEFAULT_MIN_DROP_PERCENT: f64 = 10.0;
+
+/// A section of the changelog: its title and one line per entry.
+struct Section {
+    /// The section's title.
+    title: &'static str,
+    /// The section's entries, already escaped for the output format.
+    entries: Vec<String>,
+}
+
+/// Describes a new arrival or delisting.
+fn product_entry(product: &ChangelogProduct, escape: fn(&str) -> String) -> String {
+    format!(
+        "{} ({}), {} $",
+        escape(&product.name),
+        escape(&product.saq_code),
+        Price::from_cents(product.price_cents)
+    )
+}
+
+/// Describes a price drop.
+fn price_drop_entry(drop: &PriceDrop, escape: fn(&str) -> String) -> String {
+    format!(
+        "{} ({}), {} $ → {} $ (-{:.0}%)",
+        escape(&drop.name),
+        escape(&drop.saq_code),
+        Price::from_cents(drop.old_price_cents),
+        Price::from_cents(drop.new_price_cents),
+        drop.drop_percent
+    )
+}
+
+/// Splits `changelog` into sections, escaping text with `escape`.
+fn sections(changelog: &Changelog, escape: fn(&str) -> String) -> [Section; 3] {
+    [
+        Section {
+            title: "New arrivals",
+            entries: changelog
+                .new_arrivals
+                .iter()
+                .map(|product| product_entry(product, escape))
+                .collect(),
+        },
+        Section {
+            title: "Delistings",
+            entries: changelog
+                .delistings
+                .iter()
+                .map(|product| {
+                    format!(
+                        "{}, now {}",
+                        product_entry(product, escape),
+                        product.availability.replace('_', " ")
+                    )
+                })
+                .collect(),
+        },
+        Section {
+            title: "Notable price drops",
+            entries: changelog
+                .price_drops
+                .iter()
+                .map(|drop| price_drop_entry(drop, escape))
+                .collect(),
+        },
+    ]
+}
+
+/// The changelog's title, with its start date in `timezone`.
+fn title(changelog: &Changelog, timezone: Tz) -> String {
+    format!(
+        "Changes since {}",
+        changelog.since.with_timezone(&timezone).format("%Y-%m-%d")
+    )
+}
+
+/// Escapes the characters Markdown would otherwise interpret.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes the characters HTML would otherwise interpret.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Renders `changelog` as Markdown, with dates in `timezone`.
+pub fn markdown(changelog: &Changelog, timezone: Tz) -> String {
+    let mut out = format!("# {}\n", title(changelog, timezone));
+
+    for section in sections(changelog, escape_markdown) {
+        write!(
+            out,
+            "\n## {} ({})\n\n",
+            section.title,
+            section.entries.len()
+        )
+        .unwrap();
+        if section.entries.is_empty() {
+            out.push_str("None.\n");
+        }
+        for entry in section.entries {
+            writeln!(out, "- {entry}").unwrap();
+        }
+    }
+
+    out
+}
+
+/// Renders `changelog` as an HTML fragment, with dates in `timezone`.
+pub fn html(changelog: &Changelog, timezone: Tz) -> String {
+    let mut out = format!("<h1>{}</h1>\n", title(changelog, timezone));
+
+    for section in sections(changelog, escape_html) {
+        writeln!(
+            out,
+            "<h2>{} ({})</h2>",
+            section.title,
+            section.entries.len()
+        )
+        .unwrap();
+        if section.entries.is_empty() {
+            out.push_str("<p>None.</p>\n");
+            continue;
+        }
+        out.push_str("<ul>\n");
+        for entry in section.entries {
+            writeln!(out, "  <li>{entry}</li>").unwrap();
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    /// A changelog with one new arrival and one price drop.
+    fn changelog() -> Changelog {
+        let since = Utc.with_ymd_and_hms(2026, 10, 9, 4, 0, 0).unwrap();
+
+        Changelog {
+            since,
+            new_arrivals: vec![ChangelogProduct {
+                saq_code: "123".to_string(),
+                name: "Rosé <Brut> & co".to_string(),
+                price_cents: 2495,
+                availability: "in_stock".to_string(),
+                changed_at: since,
+            }],
+            delistings: vec![],
+            price_drops: vec![PriceDrop {
+                saq_code: "456".to_string(),
+                name: "Cuvée_2020".to_string(),
+                old_price_cents: 2000,
+                new_price_cents: 1500,
+                drop_percent: 25.0,
+                changed_at: since,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            r#"# Changes since 2026-10-09
+
+## New arrivals (1)
+
+- Rosé \<Brut\> & co (123), 24.95 $
+
+## Delistings (0)
+
+None.
+
+## Notable price drops (1)
+
+- Cuvée\_2020 (456), 20.00 $ → 15.00 $ (-25%)
+"#,
+            markdown(&changelog(), chrono_tz::America::Montreal)
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            r#"<h1>Changes since 2026-10-09</h1>
+<h2>New arrivals (1)</h2>
+<ul>
+  <li>Rosé &lt;Brut&gt; &amp; co (123), 24.95 $</li>
+</ul>
+<h2>Delistings (0)</h2>
+<p>None.</p>
+<h2>Notable price drops (1)</h2>
+<ul>
+  <li>Cuvée_2020 (456), 20.00 $ → 15.00 $ (-25%)</li>
+</ul>
+"#,
+            html(&changelog(), chrono_tz::America::Montreal)
+        );
+    }
+}
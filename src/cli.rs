@@ -0,0 +1,1212 @@
+//! Command-line interface.
+//!
+//! Running `ransaq` without a subcommand is equivalent to `ransaq crawl`.
+//!
+//! Commands printing results accept `--output json` to print them as JSON
+//! instead, for use from scripts. Field names match the corresponding structs
+//! (i.e. [`db::FieldMismatch`]) and timestamps are in RFC 3339 form, in UTC.
+
+use crate::config::Config;
+use crate::crawler::simulate::{self, Simulation};
+use crate::db::{CrawlRunMode, DbSerialize};
+use crate::filter::Filter;
+use crate::saq::money::Price;
+use crate::selftest::{self, Status};
+use crate::stores::{self, Geocoder};
+use crate::{changelog, crawler, db, export, regress, repl, serve};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A crawler for the SAQ's product catalog.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// The command to run.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// How to print results.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+    /// Also load settings from `.env.<PROFILE>`, which must set its own
+    /// `DATABASE_URL` (defaults to `RANSAQ_PROFILE`).
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+}
+
+/// Formats results can be printed in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Tab-separated lines, meant to be read by people.
+    Text,
+    /// A single pretty-printed JSON document on stdout.
+    Json,
+}
+
+/// Formats `ransaq schema` can describe the database in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    /// A Mermaid ER diagram.
+    Mermaid,
+    /// A Graphviz graph.
+    Dot,
+    /// The statements creating the tables, indexes and triggers.
+    Sql,
+}
+
+/// Formats `ransaq changelog` can be rendered in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangelogFormat {
+    /// Markdown.
+    Markdown,
+    /// An HTML fragment.
+    Html,
+}
+
+/// Top-level subcommands.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Crawl the entire product catalog.
+    Crawl(CrawlArgs),
+    /// Crawl the products previously captured as partial records or with
+    /// key fields missing (i.e. no description or detailed info) again.
+    ///
+    /// Pages failing that way are often fixed later on, so this is meant to be
+    /// run some time after a crawl.
+    Complete,
+    /// Inspect and manage the pages skipped during crawls.
+    #[command(name = "skiplist", subcommand)]
+    SkipList(SkipListCommand),
+    /// Manage the store directory and find the stores nearest to a postal
+    /// code (see the `stores` module docs).
+    #[command(subcommand)]
+    Stores(StoresCommand),
+    /// Compare the products captured by a crawl against the sitemap, listing
+    /// the URLs of products that were never visited.
+    Coverage {
+        /// The crawl to check (defaults to the latest one).
+        #[arg(long)]
+        crawl_run: Option<i64>,
+    },
+    /// List the product fields whose value differed between the catalog
+    /// listing and the product page during a crawl, and which one was kept.
+    Mismatches {
+        /// The crawl to check (defaults to the latest full one).
+        #[arg(long)]
+        crawl_run: Option<i64>,
+    },
+    /// Show monthly trends across crawls: average price changes by category,
+    /// new products, and how many products changed availability.
+    Trends,
+    /// Show the recorded values of the price indices (see
+    /// `RANSAQ_PRICE_INDICES`), one line per crawl.
+    Metrics {
+        /// Only show this index.
+        name: Option<String>,
+    },
+    /// Show the mean and median price of each category, one line per full
+    /// crawl.
+    CategoryPrices {
+        /// Only show categories with this name.
+        category: Option<String>,
+    },
+    /// Show every price a product was crawled at, oldest first.
+    PriceHistory {
+        /// The product's SAQ code.
+        saq_code: String,
+    },
+    /// Export every product as JSON Lines, one denormalized product per line
+    /// (see the `export` module docs), without the fields listed in
+    /// RANSAQ_REDACT_FIELDS.
+    Export {
+        /// Write to this file rather than stdout.
+        path: Option<PathBuf>,
+    },
+    /// Serve the crawled catalog over a read-only HTTP API (see the `serve`
+    /// module docs for its endpoints).
+    Serve {
+        /// The address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+    },
+    /// List the products matching a filter expression, i.e.
+    /// `country:France color:red price<25 -grape:gamay` (see the `filter`
+    /// module docs for the syntax).
+    ///
+    /// Quote values containing spaces within the expression, i.e.
+    /// `ransaq query 'grape:"pinot noir"'`.
+    Query {
+        /// The filter expression, whose terms may be given as separate arguments.
+        #[arg(required = true, allow_hyphen_values = true)]
+        filter: Vec<String>,
+        /// Maximum number of products to list.
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Merge the products, lookups and price history of another ransaq
+    /// database (i.e. crawled from a second machine) into this one, keeping
+    /// the most recently updated version of each product.
+    Merge {
+        /// The path of the other database.
+        path: PathBuf,
+    },
+    /// Copy a small random subset of the products, along with everything
+    /// related to them, into a new database (i.e. to attach to a bug report).
+    /// Crawl bookkeeping and stores are left out.
+    SampleDb {
+        /// Number of products to copy.
+        #[arg(long, default_value_t = 100)]
+        products: u32,
+        /// The path of the new database, which mustn't exist yet.
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Permanently delete products along with their price and availability
+    /// history, categories, identifiers, etc.
+    Purge {
+        /// Delete the product with this SAQ code.
+        #[arg(long, required_unless_present = "before", conflicts_with = "before")]
+        product: Option<String>,
+        /// Delete products that haven't been updated since this date
+        /// (`YYYY-MM-DD`, in the configured time zone), and older price and
+        /// availability changes of the other products. Their price history
+        /// then starts from the last price they had before that date.
+        #[arg(long)]
+        before: Option<NaiveDate>,
+        /// Only report what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete lookup rows (producers, regions, categories, etc.) that no
+    /// product refers to anymore.
+    Gc {
+        /// Only report what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Describe the tables of the database and the relations between them,
+    /// as read from the database itself.
+    Schema {
+        /// How to describe the schema (ignored with `--output json`).
+        #[arg(long, value_enum, default_value_t = SchemaFormat::Mermaid)]
+        format: SchemaFormat,
+    },
+    /// Summarize new arrivals, delistings and notable price drops, i.e. for a
+    /// weekly blog post or newsletter.
+    Changelog {
+        /// Only include changes since this date (`YYYY-MM-DD`, in the
+        /// configured time zone). Defaults to a week ago.
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Leave out price drops smaller than this percentage.
+        #[arg(long, default_value_t = changelog::DEFAULT_MIN_DROP_PERCENT)]
+        min_drop: f64,
+        /// How to render the changelog (ignored with `--output json`).
+        #[arg(long, value_enum, default_value_t = ChangelogFormat::Markdown)]
+        format: ChangelogFormat,
+    },
+    /// Check the database for corruption, broken foreign keys and products
+    /// breaking invariants (i.e. without a category), exiting with an error if
+    /// anything is wrong.
+    Check,
+    /// Check that this machine can run a crawl to completion: that the
+    /// database is writable, in WAL mode on a local filesystem and up to date
+    /// with the migrations, that the site can be reached over HTTPS, and that
+    /// the clock is right (see the `selftest` module docs). Exits with an
+    /// error if any check fails.
+    Selftest,
+    /// Crawl a synthetic catalog served locally into a scratch database and
+    /// report the throughput, to benchmark changes to the crawler without
+    /// touching saq.com. Must be run from a checkout of the repository.
+    Simulate {
+        /// Number of catalog pages.
+        #[arg(long, default_value_t = 20)]
+        pages: u32,
+        /// Number of products listed on each catalog page.
+        #[arg(long, default_value_t = 24)]
+        products_per_page: u32,
+        /// How long the server waits before each response, in milliseconds.
+        #[arg(long, default_value_t = 100)]
+        latency_ms: u64,
+    },
+    /// Parse a directory of archived pages and compare the output with their
+    /// JSON snapshots, exiting with an error if anything changed.
+    ParserRegress {
+        /// Directory holding the pages, as `.html` files.
+        dir: PathBuf,
+        /// Directory holding the snapshots (defaults to `snapshots` inside
+        /// the pages directory).
+        #[arg(long)]
+        snapshots: Option<PathBuf>,
+        /// Write new and changed snapshots rather than fail.
+        #[arg(long)]
+        update: bool,
+    },
+    /// Explore the database interactively with read-only SQL queries.
+    Repl {
+        /// Number of rows to print before pausing, 0 to never pause.
+        #[arg(long, default_value_t = 40)]
+        page_size: usize,
+    },
+    /// Show when the catalog was last crawled and when each table was last
+    /// updated, to tell how stale the data is.
+    Stats,
+}
+
+/// Options for `ransaq crawl`, overriding the corresponding [`Config`] settings.
+#[derive(Args, Debug, Default)]
+pub struct CrawlArgs {
+    /// Number of products per catalog page (24, 48 or 96).
+    ///
+    /// Larger pages are slower to load but require fewer requests, compare the
+    /// page timings logged at the end of the crawl to pick one.
+    #[arg(long)]
+    pub page_size: Option<u32>,
+    /// Only crawl the first few catalog pages, sorted by newest products first.
+    ///
+    /// This is much cheaper than a full crawl and meant to be run frequently
+    /// in between them.
+    #[arg(long)]
+    pub new_arrivals: bool,
+    /// Number of catalog pages to crawl with `--new-arrivals`.
+    #[arg(long, requires = "new_arrivals")]
+    pub pages: Option<u32>,
+    /// Update the availability and price of known products from the catalog
+    /// pages, only fetching the pages of new products and of those whose
+    /// listing changed.
+    ///
+    /// This is much cheaper than a full crawl, which is still needed to pick up
+    /// changes to anything else.
+    #[arg(long, conflicts_with = "new_arrivals")]
+    pub refresh: bool,
+    /// Crawl into an in-memory copy of the database, written to disk once the
+    /// crawl is over.
+    ///
+    /// This is much faster on slow disks (i.e. SD cards), but everything
+    /// captured is lost if the crawl is interrupted.
+    #[arg(long)]
+    pub in_memory: bool,
+    /// Reduce memory usage at the expense of speed, i.e. to crawl on devices
+    /// with 512MB of RAM.
+    #[arg(long)]
+    pub low_memory: bool,
+    /// Tag the crawl (i.e. `--tag weekly`), can be repeated. Replaces the tags
+    /// set through `RANSAQ_CRAWL_TAGS`.
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+    /// Attach a note to the crawl (i.e. `--note "after parser fix"`).
+    #[arg(long)]
+    pub note: Option<String>,
+    /// Send at most this many requests per second, however many product pages
+    /// are fetched concurrently. Replaces `RANSAQ_RATE_LIMIT`.
+    #[arg(long)]
+    pub rate_limit: Option<f64>,
+    /// Number of product pages fetched concurrently when the crawl starts,
+    /// within `RANSAQ_MIN_CONCURRENCY` and `RANSAQ_MAX_CONCURRENCY`. Replaces
+    /// `RANSAQ_CONCURRENCY`.
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+    /// Number of products that can be waiting to be crawled at once. Replaces
+    /// `RANSAQ_QUEUE_SIZE`.
+    #[arg(long)]
+    pub queue_size: Option<usize>,
+    /// Pick up the most recent crawl in the same mode where it left off if it
+    /// was interrupted, instead of starting from the first catalog page. It
+    /// has to be resumed with the same category, page size and stop conditions
+    /// (--max-pages, --max-products and --until-known).
+    #[arg(long)]
+    pub resume: bool,
+    /// Stop after this many catalog pages.
+    #[arg(long)]
+    pub max_pages: Option<u32>,
+    /// Stop once this many products from the catalog were queued.
+    #[arg(long)]
+    pub max_products: Option<usize>,
+    /// Stop at the first catalog page whose products were all crawled
+    /// recently, for cheap top-up crawls between full ones.
+    #[arg(long, requires = "new_arrivals")]
+    pub until_known: bool,
+    /// Only crawl the listing of this category (i.e. `wine/red-wine`, or its
+    /// full URL) rather than the whole catalog.
+    #[arg(long)]
+    pub category: Option<String>,
+}
+
+/// Subcommands of `ransaq stores`.
+#[derive(Subcommand, Debug)]
+pub enum StoresCommand {
+    /// Import a JSON store list, updating the stores already imported and
+    /// geocoding the ones listed without coordinates.
+    Import {
+        /// The store list.
+        path: PathBuf,
+    },
+    /// List the stores nearest to a postal code, closest first.
+    Near {
+        /// The postal code (i.e. `H2V 4H1`).
+        postal_code: String,
+        /// Maximum number of stores to list.
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+/// Subcommands of `ransaq skiplist`.
+#[derive(Subcommand, Debug)]
+pub enum SkipListCommand {
+    /// List the pages currently being skipped.
+    List,
+    /// Stop skipping a page.
+    Remove {
+        /// The URL of the page.
+        url: String,
+    },
+}
+
+/// Runs the command specified in `cli`.
+pub async fn run(cli: Cli, config: &Config) -> Result<()> {
+    let output = cli.output;
+
+    match cli
+        .command
+        .unwrap_or_else(|| Command::Crawl(CrawlArgs::default()))
+    {
+        Command::Crawl(args) => run_crawl(args, config).await,
+        Command::Complete => crawler::crawl(config, CrawlRunMode::Completion).await,
+        Command::SkipList(command) => run_skip_list(command, output, config).await,
+        Command::Stores(command) => run_stores(command, output, config).await,
+        Command::Coverage { crawl_run } => run_coverage(crawl_run, output, config).await,
+        Command::Mismatches { crawl_run } => run_mismatches(crawl_run, output).await,
+        Command::Trends => run_trends(output, config).await,
+        Command::Metrics { name } => run_metrics(name, output, config).await,
+        Command::CategoryPrices { category } => run_category_prices(category, output, config).await,
+        Command::PriceHistory { saq_code } => run_price_history(&saq_code, output, config).await,
+        Command::Export { path } => run_export(path.as_deref(), config).await,
+        Command::Serve { listen } => run_serve(listen, config).await,
+        Command::Query { filter, limit } => run_query(&filter.join(" "), limit, output).await,
+        Command::Merge { path } => run_merge(&path, output).await,
+        Command::SampleDb { products, out } => run_sample_db(products, &out, output).await,
+        Command::Purge {
+            product,
+            before,
+            dry_run,
+        } => run_purge(product, before, dry_run, output, config).await,
+        Command::Gc { dry_run } => run_gc(dry_run, output).await,
+        Command::Schema { format } => run_schema(format, output).await,
+        Command::Check => run_check(output).await,
+        Command::Selftest => run_selftest(output, config).await,
+        Command::Simulate {
+            pages,
+            products_per_page,
+            latency_ms,
+        } => {
+            let simulation = Simulation {
+                pages,
+                products_per_page,
+                latency: Duration::from_millis(latency_ms),
+            };
+            run_simulate(simulation, output, config).await
+        }
+        Command::ParserRegress {
+            dir,
+            snapshots,
+            update,
+        } => run_parser_regress(&dir, snapshots, update, output),
+        Command::Changelog {
+            since,
+            min_drop,
+            format,
+        } => run_changelog(since, min_drop, format, output, config).await,
+        Command::Repl { page_size } => {
+            let db = db::Client::new_from_env().await?;
+            repl::run(&db, page_size).await
+        }
+        Command::Stats => run_stats(output, config).await,
+    }
+}
+
+/// Runs `ransaq crawl` with `args` applied on top of `config`.
+async fn run_crawl(args: CrawlArgs, config: &Config) -> Result<()> {
+    let mut config = config.clone();
+
+    if let Some(page_size) = args.page_size {
+        config.page_size = Some(page_size);
+    }
+
+    if let Some(pages) = args.pages {
+        config.new_arrivals_pages = pages;
+    }
+
+    if args.in_memory {
+        config.in_memory = true;
+    }
+
+    if args.low_memory {
+        config.low_memory = true;
+    }
+
+    if !args.tags.is_empty() {
+        config.crawl_tags = args.tags;
+    }
+
+    if args.note.is_some() {
+        config.crawl_note = args.note;
+    }
+
+    if args.rate_limit.is_some() {
+        config.rate_limit = args.rate_limit;
+    }
+
+    if let Some(concurrency) = args.concurrency {
+        config.initial_concurrency = concurrency;
+    }
+
+    if let Some(queue_size) = args.queue_size {
+        config.queue_size = queue_size;
+    }
+
+    if args.resume {
+        config.resume = true;
+    }
+
+    if args.max_pages.is_some() {
+        config.max_pages = args.max_pages;
+    }
+
+    if args.max_products.is_some() {
+        config.max_products = args.max_products;
+    }
+
+    if args.until_known {
+        config.until_known = true;
+    }
+
+    if args.category.is_some() {
+        config.category = args.category;
+    }
+
+    config.validate()?;
+
+    let mode = if args.new_arrivals {
+        CrawlRunMode::NewArrivals
+    } else if args.refresh {
+        CrawlRunMode::Refresh
+    } else {
+        CrawlRunMode::Full
+    };
+
+    crawler::crawl(&config, mode).await
+}
+
+/// Formats a timestamp in the configured [time zone](Config::timezone).
+fn local_time(timestamp: DateTime<Utc>, timezone: Tz) -> String {
+    timestamp
+        .with_timezone(&timezone)
+        .format("%Y-%m-%d %H:%M:%S %Z")
+        .to_string()
+}
+
+/// Returns the start of `date` in `timezone`.
+fn local_midnight(date: NaiveDate, timezone: Tz) -> Result<DateTime<Utc>> {
+    let midnight = timezone
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .ok_or_else(|| eyre!("{} has no midnight in {}", date, timezone))?;
+
+    Ok(midnight.with_timezone(&Utc))
+}
+
+/// Prints `value` as pretty-printed JSON on stdout.
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+
+    Ok(())
+}
+
+/// Runs `ransaq coverage`, printing missing URLs to stdout and a summary to stderr.
+async fn run_coverage(
+    crawl_run_id: Option<i64>,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let coverage = crawler::coverage::coverage(config, crawl_run_id).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&coverage);
+    }
+
+    for url in &coverage.missing_urls {
+        println!("{url}");
+    }
+
+    eprintln!(
+        "crawl {} (started {}): captured {} of {} products listed in the sitemap ({} missing, {} not listed)",
+        coverage.crawl_run_id,
+        local_time(coverage.started_at, config.timezone),
+        coverage.captured_products,
+        coverage.sitemap_products,
+        coverage.missing_urls.len(),
+        coverage.unlisted_products
+    );
+
+    Ok(())
+}
+
+/// Runs `ransaq mismatches`.
+async fn run_mismatches(crawl_run_id: Option<i64>, output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+
+    let crawl_run_id = match crawl_run_id {
+        Some(id) => id,
+        None => db
+            .latest_crawl_run_id()
+            .await?
+            .ok_or_else(|| eyre!("no crawls have been recorded yet"))?,
+    };
+
+    let mismatches = db.field_mismatches(crawl_run_id).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&mismatches);
+    }
+
+    for mismatch in mismatches {
+        println!(
+            "{}\t{}\tlisting={}\tproduct_page={}\tkept {}",
+            mismatch.saq_code,
+            mismatch.field,
+            mismatch.listing_value,
+            mismatch.product_page_value,
+            mismatch.chosen
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq query`.
+async fn run_query(filter: &str, limit: i64, output: OutputFormat) -> Result<()> {
+    let filter = filter.parse::<Filter>()?;
+    let db = db::Client::new_from_env().await?;
+    let products = db.search(&filter, limit).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&products);
+    }
+
+    for product in products {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            product.saq_code,
+            product.name,
+            Price::from_cents(product.price_cents),
+            product.availability,
+            product.country.as_deref().unwrap_or("-"),
+            product.color.as_deref().unwrap_or("-")
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq sample-db`.
+async fn run_sample_db(products: u32, out: &Path, output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let report = db.sample_into(out, products).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&report);
+    }
+
+    println!("products\t{}", report.products);
+    for (table, rows) in &report.rows {
+        println!("{table}\t{rows}");
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq merge`.
+async fn run_merge(path: &Path, output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let report = db.merge_from(path).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&report);
+    }
+
+    for (table, added) in &report.lookups_added {
+        println!("{table}\t{added} added");
+    }
+    println!("categories\t{} added", report.categories_added);
+    println!(
+        "products\t{} added\t{} updated\t{} kept",
+        report.products_added.len(),
+        report.products_updated.len(),
+        report.products_kept
+    );
+    println!("product_changes\t{} added", report.changes_added);
+
+    Ok(())
+}
+
+/// Runs `ransaq purge`, deleting either `product` or what came `before`.
+async fn run_purge(
+    product: Option<String>,
+    before: Option<NaiveDate>,
+    dry_run: bool,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+
+    let report = match (product, before) {
+        (Some(saq_code), _) => db.purge_product(&saq_code, dry_run).await?,
+        (None, Some(date)) => {
+            db.purge_before(local_midnight(date, config.timezone)?, dry_run)
+                .await?
+        }
+        (None, None) => return Err(eyre!("either --product or --before is required")),
+    };
+
+    if output == OutputFormat::Json {
+        return print_json(&report);
+    }
+
+    let verb = match dry_run {
+        true => "would be deleted",
+        false => "deleted",
+    };
+    for saq_code in &report.products {
+        println!("{saq_code}");
+    }
+    println!("products\t{} {verb}", report.products.len());
+    println!("product_changes\t{} {verb}", report.changes);
+    println!("field_mismatches\t{} {verb}", report.mismatches);
+
+    Ok(())
+}
+
+/// Runs `ransaq gc`.
+async fn run_gc(dry_run: bool, output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let report = db.gc(dry_run).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&report);
+    }
+
+    let verb = match dry_run {
+        true => "would be deleted",
+        false => "deleted",
+    };
+    for (table, orphans) in &report.orphans {
+        println!("{table}\t{orphans} {verb}");
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq schema`.
+async fn run_schema(format: SchemaFormat, output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let schema = db.schema().await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&schema);
+    }
+
+    match format {
+        SchemaFormat::Mermaid => print!("{}", schema.mermaid()),
+        SchemaFormat::Dot => print!("{}", schema.dot()),
+        SchemaFormat::Sql => print!("{}", schema.sql()),
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq check`, failing if any problem is found.
+async fn run_check(output: OutputFormat) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let problems = db.check().await?;
+
+    match output {
+        OutputFormat::Json => print_json(&problems)?,
+        OutputFormat::Text => {
+            for problem in &problems {
+                println!("{}\t{}", problem.check, problem.message);
+            }
+        }
+    }
+
+    match problems.len() {
+        0 => Ok(()),
+        count => Err(eyre!("found {} problems with the database", count)),
+    }
+}
+
+/// Runs `ransaq selftest`.
+async fn run_selftest(output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let checks = selftest::run(&db, config).await;
+
+    match output {
+        OutputFormat::Json => print_json(&checks)?,
+        OutputFormat::Text => {
+            for check in &checks {
+                let status = match check.status {
+                    Status::Pass => "ok",
+                    Status::Skip => "skipped",
+                    Status::Fail => "FAILED",
+                };
+                println!("{}\t{}\t{}", check.check, status, check.message);
+            }
+        }
+    }
+
+    match checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count()
+    {
+        0 => Ok(()),
+        count => Err(eyre!("{} of {} checks failed", count, checks.len())),
+    }
+}
+
+/// Runs `ransaq simulate`.
+async fn run_simulate(simulation: Simulation, output: OutputFormat, config: &Config) -> Result<()> {
+    let report = simulate::simulate(config, simulation).await?;
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Text => {
+            println!(
+                "persisted {} of {} products in {:.1}s ({:.1} products/s)",
+                report.persisted_products,
+                report.catalog_products,
+                report.elapsed_secs,
+                report.products_per_second
+            );
+            println!("{} requests, {} errors", report.requests, report.errors);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq parser-regress`.
+fn run_parser_regress(
+    dir: &Path,
+    snapshots: Option<PathBuf>,
+    update: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let snapshots = snapshots.unwrap_or_else(|| dir.join("snapshots"));
+    let results = regress::run(dir, &snapshots, update)?;
+
+    match output {
+        OutputFormat::Json => print_json(&results)?,
+        OutputFormat::Text => {
+            for result in &results {
+                match &result.outcome {
+                    regress::Outcome::New => println!("new\t{}", result.page),
+                    regress::Outcome::Unchanged => println!("unchanged\t{}", result.page),
+                    regress::Outcome::Changed(differences) => {
+                        println!("changed\t{}", result.page);
+                        for difference in differences {
+                            println!("  {difference}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let changed = results
+        .iter()
+        .filter(|result| result.outcome != regress::Outcome::Unchanged)
+        .count();
+    match (changed, update) {
+        (0, _) => Ok(()),
+        (_, true) => {
+            eprintln!("updated {changed} snapshots in {snapshots:?}");
+            Ok(())
+        }
+        (_, false) => Err(eyre!(
+            "{} of {} pages don't match their snapshots, rerun with --update to accept the changes",
+            changed,
+            results.len()
+        )),
+    }
+}
+
+/// Runs `ransaq changelog`.
+async fn run_changelog(
+    since: Option<NaiveDate>,
+    min_drop: f64,
+    format: ChangelogFormat,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let since = match since {
+        Some(date) => date,
+        None => Utc::now().with_timezone(&config.timezone).date_naive() - chrono::Duration::days(7),
+    };
+
+    let db = db::Client::new_from_env().await?;
+    let changes = db
+        .changelog(local_midnight(since, config.timezone)?, min_drop)
+        .await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&changes);
+    }
+
+    match format {
+        ChangelogFormat::Markdown => print!("{}", changelog::markdown(&changes, config.timezone)),
+        ChangelogFormat::Html => print!("{}", changelog::html(&changes, config.timezone)),
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq trends`.
+async fn run_trends(output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let trends = db.trends().await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&trends);
+    }
+
+    println!("month\tcategory\taverage price change\tprice changes");
+    for trend in &trends.price_changes {
+        println!(
+            "{}\t{}\t{:+.1}%\t{}",
+            trend.month, trend.category, trend.average_change_percent, trend.changes
+        );
+    }
+
+    println!("\nmonth\tnew products");
+    for trend in &trends.new_listings {
+        println!("{}\t{}", trend.month, trend.new_products);
+    }
+
+    println!("\nmonth\tavailability changes\tproducts\tchurn");
+    for trend in &trends.availability_churn {
+        println!(
+            "{}\t{}\t{}\t{:.1}%",
+            trend.month, trend.changed_products, trend.products, trend.churn_percent
+        );
+    }
+
+    println!("\nstarted\tcrawl\tmode\ttags\tnote");
+    for label in &trends.crawl_labels {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            local_time(label.started_at, config.timezone),
+            label.crawl_run_id,
+            label.mode,
+            label.tags.join(","),
+            label.note.as_deref().unwrap_or("")
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq metrics`.
+async fn run_metrics(name: Option<String>, output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let history = db.metrics_history(name.as_deref()).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&history);
+    }
+
+    for point in history {
+        println!(
+            "{}\t{}\t{}\t{:.2}\t{} products",
+            point.name,
+            point.crawl_run_id,
+            local_time(point.created_at, config.timezone),
+            point.value,
+            point.products
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq category-prices`.
+async fn run_category_prices(
+    category: Option<String>,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let history = db.category_price_history(category.as_deref()).await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&history);
+    }
+
+    for point in history {
+        println!(
+            "{}\t{}\t{}\t{}\tmean {}\tmedian {}\t{} products",
+            point.category,
+            point.category_url,
+            point.crawl_run_id,
+            local_time(point.started_at, config.timezone),
+            Price::from_cents(point.mean_price_cents.round() as i64),
+            Price::from_cents(point.median_price_cents.round() as i64),
+            point.products
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq price-history`.
+async fn run_price_history(saq_code: &str, output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let history = db.price_history(saq_code).await?;
+
+    if history.is_empty() {
+        return Err(eyre!("no product with SAQ code {:?}", saq_code));
+    }
+
+    if output == OutputFormat::Json {
+        return print_json(&history);
+    }
+
+    for point in history {
+        let regular_price = match point.regular_price_cents {
+            Some(cents) => format!("\tregular {}", Price::from_cents(cents)),
+            None => String::new(),
+        };
+        println!(
+            "{}\t{}{}",
+            local_time(point.recorded_at, config.timezone),
+            Price::from_cents(point.price_cents),
+            regular_price
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq export`, writing to `path` or stdout.
+async fn run_export(path: Option<&Path>, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let redacted = &config.redact_fields;
+
+    match path {
+        Some(path) => {
+            let file = BufWriter::new(File::create(path)?);
+            let products = export::write_jsonl(&db, redacted, file).await?;
+            println!("exported {products} products to {}", path.display());
+        }
+        None => {
+            export::write_jsonl(&db, redacted, BufWriter::new(std::io::stdout().lock())).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq serve`.
+async fn run_serve(listen: SocketAddr, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!("serving on http://{}", listener.local_addr()?);
+
+    serve::serve(db, config.redact_fields.clone(), listener).await
+}
+
+/// The output of `ransaq stats --output json`.
+#[derive(Serialize)]
+struct Stats {
+    /// When the last completed crawl finished, keyed by crawl mode (i.e.
+    /// `new_arrivals`).
+    last_completed_crawls: BTreeMap<String, Option<DateTime<Utc>>>,
+    /// How recently each table was written to.
+    tables: Vec<db::TableFreshness>,
+}
+
+/// Runs `ransaq stats`.
+async fn run_stats(output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let format = |timestamp: Option<DateTime<Utc>>| match timestamp {
+        Some(timestamp) => local_time(timestamp, config.timezone),
+        None => "never".to_string(),
+    };
+
+    let mut last_completed_crawls = vec![];
+    for (label, mode) in [
+        ("full", CrawlRunMode::Full),
+        ("new arrivals", CrawlRunMode::NewArrivals),
+        ("refresh", CrawlRunMode::Refresh),
+        ("completion", CrawlRunMode::Completion),
+    ] {
+        let finished_at = db.last_completed_crawl_at(mode).await?;
+        last_completed_crawls.push((label, mode, finished_at));
+    }
+    let tables = db.table_freshness().await?;
+
+    if output == OutputFormat::Json {
+        return print_json(&Stats {
+            last_completed_crawls: last_completed_crawls
+                .into_iter()
+                .map(|(_, mode, finished_at)| (mode.db_serialize().to_string(), finished_at))
+                .collect(),
+            tables,
+        });
+    }
+
+    for (label, _, finished_at) in last_completed_crawls {
+        println!("last {} crawl\t{}", label, format(finished_at));
+    }
+
+    for table in tables {
+        println!(
+            "{}\t{} rows\tupdated {}",
+            table.table,
+            table.rows,
+            format(table.last_updated_at)
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq stores` subcommands.
+async fn run_stores(command: StoresCommand, output: OutputFormat, config: &Config) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+    let geocoder = Geocoder::new(config.geocoder_url.clone(), &config.proxies)?;
+
+    match command {
+        StoresCommand::Import { path } => {
+            let report = stores::import(&db, &geocoder, stores::read_store_list(&path)?).await?;
+
+            if output == OutputFormat::Json {
+                return print_json(&report);
+            }
+
+            println!(
+                "imported {} stores ({} geocoded)",
+                report.imported, report.geocoded
+            );
+            for saq_store_id in &report.unlocated {
+                println!("could not locate store {saq_store_id}");
+            }
+        }
+        StoresCommand::Near { postal_code, limit } => {
+            let nearby = stores::near(&db, &geocoder, &postal_code, limit).await?;
+
+            if output == OutputFormat::Json {
+                return print_json(&nearby);
+            }
+
+            for nearby in nearby {
+                println!(
+                    "{:.1} km\t{}\t{}, {}\t{}",
+                    nearby.distance_km,
+                    nearby.store.name,
+                    nearby.store.address,
+                    nearby.store.city,
+                    nearby.store.postal_code
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `ransaq skiplist` subcommands.
+async fn run_skip_list(
+    command: SkipListCommand,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
+    let db = db::Client::new_from_env().await?;
+
+    match command {
+        SkipListCommand::List => {
+            let entries = db.active_skip_list().await?;
+
+            if output == OutputFormat::Json {
+                return print_json(&entries);
+            }
+
+            for entry in entries {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.url,
+                    entry.error_class,
+                    local_time(entry.created_at, config.timezone),
+                    local_time(entry.expires_at, config.timezone)
+                );
+            }
+        }
+        SkipListCommand::Remove { url } => {
+            if !db.remove_from_skip_list(&url).await? {
+                return Err(eyre!("{:?} is not on the skip list", url));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format() {
+        let cli = Cli::try_parse_from(["ransaq", "stats"]).unwrap();
+        assert_eq!(OutputFormat::Text, cli.output);
+
+        for args in [
+            ["ransaq", "--output", "json", "stats"],
+            ["ransaq", "stats", "--output", "json"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert_eq!(OutputFormat::Json, cli.output);
+        }
+
+        assert!(Cli::try_parse_from(["ransaq", "stats", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_query_args() {
+        let cli = Cli::try_parse_from([
+            "ransaq",
+            "query",
+            "--limit",
+            "5",
+            "-country:France",
+            "price<25",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Some(Command::Query { filter, limit }) => {
+                assert_eq!("-country:France price<25", filter.join(" "));
+                assert_eq!(5, limit);
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Detection of crawls whose outcome deviates sharply from previous ones.
+//!
+//! Site changes tend to break crawls silently (i.e. a catalog page listing
+//! fewer products, or product pages failing to parse), so each completed crawl
+//! records a few metrics in the `metrics_history` table and compares them with
+//! the average of the last [`BASELINE_CRAWLS`] crawls in the same mode.
+//! Anomalies are logged and handed to [`Hooks::on_anomaly`](super::hooks::Hooks::on_anomaly).
+
+use crate::db::{self, CrawlRunMode};
+use color_eyre::eyre::Result;
+use std::fmt;
+
+/// Number of products captured by a crawl.
+pub const PRODUCTS_METRIC: &str = "crawl.products";
+
+/// Fraction of the products visited by a crawl which failed without being
+/// retried.
+pub const ERROR_RATE_METRIC: &str = "crawl.error_rate";
+
+/// Number of previous crawls the baseline is computed from.
+pub const BASELINE_CRAWLS: i64 = 5;
+
+/// Fraction of the baseline's products a crawl can lose before it is
+/// considered anomalous.
+const MAX_PRODUCTS_DROP: f64 = 0.3;
+
+/// Increase in error rate (in absolute terms) over the baseline above which a
+/// crawl is considered anomalous.
+const MAX_ERROR_RATE_INCREASE: f64 = 0.05;
+
+/// A crawl metric that deviates sharply from its baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    /// The metric's name (i.e. [`PRODUCTS_METRIC`]).
+    pub metric: &'static str,
+    /// The value for the current crawl.
+    pub value: f64,
+    /// The average value over the previous crawls.
+    pub baseline: f64,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was {:.3} against a baseline of {:.3}",
+            self.metric, self.value, self.baseline
+        )
+    }
+}
+
+/// Whether `value` deviates enough from `baseline` to be an anomaly for `metric`.
+fn is_anomalous(metric: &str, value: f64, baseline: f64) -> bool {
+    match metric {
+        PRODUCTS_METRIC => value < baseline * (1.0 - MAX_PRODUCTS_DROP),
+        ERROR_RATE_METRIC => value > baseline + MAX_ERROR_RATE_INCREASE,
+        _ => false,
+    }
+}
+
+/// Records the metrics of the given completed crawl and returns those that
+/// deviate from the baseline of previous crawls in the same `mode`.
+///
+/// Only [full](CrawlRunMode::Full) and [new arrivals](CrawlRunMode::NewArrivals)
+/// crawls are checked, as the number of products other crawls visit varies
+/// too much from one to the next.
+pub async fn check_crawl(
+    db: &db::Client,
+    crawl_run_id: i64,
+    mode: CrawlRunMode,
+) -> Result<Vec<Anomaly>> {
+    if !matches!(mode, CrawlRunMode::Full | CrawlRunMode::NewArrivals) {
+        return Ok(vec![]);
+    }
+
+    let products = db.crawl_run_saq_codes(crawl_run_id).await?.len() as i64;
+    let errors = db.count_crawl_errors(crawl_run_id).await?;
+    let error_rate = errors as f64 / (products + errors).max(1) as f64;
+
+    let mut anomalies = vec![];
+
+    for (metric, value) in [
+        (PRODUCTS_METRIC, products as f64),
+        (ERROR_RATE_METRIC, error_rate),
+    ] {
+        db.record_metric(crawl_run_id, metric, value, products)
+            .await?;
+
+        let baseline = db
+            .metric_baseline(metric, mode, crawl_run_id, BASELINE_CRAWLS)
+            .await?;
+
+        if let Some(baseline) = baseline {
+            if is_anomalous(metric, value, baseline) {
+                anomalies.push(Anomaly {
+                    metric,
+                    value,
+                    baseline,
+                });
+            }
+        }
+    }
+
+    Ok(anomalies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use crate::db::CrawlRunStatus;
+
+    #[test]
+    fn test_is_anomalous() {
+        assert!(!is_anomalous(PRODUCTS_METRIC, 9000.0, 10000.0));
+        assert!(is_anomalous(PRODUCTS_METRIC, 6000.0, 10000.0));
+        assert!(!is_anomalous(ERROR_RATE_METRIC, 0.03, 0.01));
+        assert!(is_anomalous(ERROR_RATE_METRIC, 0.2, 0.01));
+    }
+
+    #[tokio::test]
+    async fn test_check_crawl() -> Result<()> {
+        let db = TestDb::new().await?;
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+
+        for saq_code in ["first", "second"] {
+            db.insert_product(saq_code, saq_code, 100).await?;
+        }
+
+        // Without previous crawls there's nothing to compare against
+        assert!(check_crawl(&db, crawl_run_id, CrawlRunMode::Full)
+            .await?
+            .is_empty());
+        db.finish_crawl_run(crawl_run_id, CrawlRunStatus::Completed)
+            .await?;
+
+        // Nothing was updated during this crawl, and everything failed
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        sqlx::query(
+            "update crawl_runs set started_at = datetime('now', 'utc', '+1 hour') where id = ?1",
+        )
+        .bind(crawl_run_id)
+        .execute(db.pool())
+        .await?;
+        db.record_crawl_error(
+            crawl_run_id,
+            "https://www.saq.com/en/first",
+            crate::crawler::errors::ErrorClass::Parse,
+            crate::crawler::errors::ErrorAction::Skip,
+            "parse error",
+        )
+        .await?;
+
+        let anomalies = check_crawl(&db, crawl_run_id, CrawlRunMode::Full).await?;
+        assert_eq!(
+            vec![PRODUCTS_METRIC, ERROR_RATE_METRIC],
+            anomalies.iter().map(|a| a.metric).collect::<Vec<_>>()
+        );
+        assert_eq!(2.0, anomalies[0].baseline);
+
+        Ok(())
+    }
+}
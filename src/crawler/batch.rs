@@ -0,0 +1,64 @@
+//! Accumulates items shared between tasks until there are enough to process
+//! them together.
+
+use std::sync::Mutex;
+
+/// A collection of items that is handed back once it reaches a given size.
+#[derive(Debug)]
+pub struct Batch<T> {
+    /// The items accumulated so far.
+    items: Mutex<Vec<T>>,
+    /// Number of items after which the batch is full.
+    size: usize,
+}
+
+impl<T> Batch<T> {
+    /// Creates an empty batch holding up to `size` items (at least one).
+    pub fn new(size: usize) -> Batch<T> {
+        let size = size.max(1);
+
+        Batch {
+            items: Mutex::new(Vec::with_capacity(size)),
+            size,
+        }
+    }
+
+    /// Adds `item` to the batch, returning all the accumulated items if the
+    /// batch is now full. The batch is then empty again.
+    pub fn push(&self, item: T) -> Option<Vec<T>> {
+        let mut items = self.items.lock().unwrap();
+        items.push(item);
+
+        if items.len() < self.size {
+            return None;
+        }
+
+        Some(std::mem::replace(
+            &mut *items,
+            Vec::with_capacity(self.size),
+        ))
+    }
+
+    /// Removes and returns the accumulated items, however many there are.
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut *self.items.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch() {
+        let batch = Batch::new(3);
+
+        assert_eq!(None, batch.push(1));
+        assert_eq!(None, batch.push(2));
+        assert_eq!(Some(vec![1, 2, 3]), batch.push(3));
+
+        assert_eq!(None, batch.push(4));
+        assert_eq!(vec![4], batch.take());
+        assert!(batch.take().is_empty());
+    }
+}
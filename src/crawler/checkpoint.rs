@@ -0,0 +1,170 @@
+//! Periodic checkpoints of a crawl's progress, so that an interrupted crawl
+//! can be resumed (with `ransaq crawl --resume`) instead of starting over.
+//!
+//! A checkpoint records the next catalog page to fetch along with the
+//! products queued from earlier pages which weren't persisted or skipped yet,
+//! and what the crawl was listing them from (see [`db::CrawlListing`]) as
+//! page numbers only make sense for that listing.
+//! Resuming queues those products again before carrying on from that page.
+//! Products persisted after the last checkpoint get crawled again, which is
+//! harmless.
+//!
+//! Checkpoints are saved every [`CHECKPOINT_INTERVAL`] and once the crawl
+//! stops, and deleted once it completes. Only the most recent crawl in a
+//! given mode can be resumed, see [`db::Client::resumable_crawl_run`].
+
+use crate::db::{self, CrawlListing, CrawlState, UnfinishedProduct};
+use color_eyre::eyre::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// How often checkpoints are saved while a crawl is running.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What a checkpoint is made of.
+#[derive(Debug)]
+struct Progress {
+    /// See [`CrawlState::next_page`].
+    next_page: u32,
+    /// Pending products' SKUs, by URL.
+    pending: BTreeMap<String, String>,
+}
+
+/// Tracks a crawl's progress, shared between the tasks making it.
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// What the crawl lists products from.
+    listing: CrawlListing,
+    /// The progress so far.
+    progress: Mutex<Progress>,
+}
+
+impl Checkpoint {
+    /// Starts tracking a crawl from the given state (i.e. a fresh one starting
+    /// from page 1, or the last checkpoint of an interrupted one).
+    pub fn new(state: &CrawlState) -> Checkpoint {
+        Checkpoint {
+            listing: state.listing.clone(),
+            progress: Mutex::new(Progress {
+                next_page: state.next_page,
+                pending: state
+                    .pending
+                    .iter()
+                    .map(|product| (product.url.clone(), product.sku.clone()))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Records that the product at `url` was queued, returning `false` if it
+    /// already was (i.e. when a resumed crawl lists it again).
+    pub fn queued(&self, url: &str, sku: &str) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+
+        if progress.pending.contains_key(url) {
+            return false;
+        }
+        progress.pending.insert(url.to_string(), sku.to_string());
+        true
+    }
+
+    /// Records that every product of catalog page `page_number` was queued.
+    pub fn page_queued(&self, page_number: u32) {
+        self.progress.lock().unwrap().next_page = page_number + 1;
+    }
+
+    /// Records that the product at `url` was persisted or skipped.
+    pub fn done(&self, url: &str) {
+        self.progress.lock().unwrap().pending.remove(url);
+    }
+
+    /// Copies the progress so far.
+    pub fn state(&self) -> CrawlState {
+        let progress = self.progress.lock().unwrap();
+
+        CrawlState {
+            listing: self.listing.clone(),
+            next_page: progress.next_page,
+            pending: progress
+                .pending
+                .iter()
+                .map(|(url, sku)| UnfinishedProduct {
+                    url: url.clone(),
+                    sku: sku.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Saves the progress so far as the checkpoint of `crawl_run_id`.
+    pub async fn save(&self, db: &db::Client, crawl_run_id: i64) -> Result<()> {
+        db.save_crawl_state(crawl_run_id, &self.state()).await
+    }
+
+    /// Saves the progress every [`CHECKPOINT_INTERVAL`] until the returned
+    /// task is aborted.
+    pub fn save_every(
+        self: Arc<Self>,
+        db: db::Client,
+        crawl_run_id: i64,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(CHECKPOINT_INTERVAL);
+            // The first tick completes immediately
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.save(&db, crawl_run_id).await {
+                    warn!(?err, "failed to save crawl checkpoint");
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint() {
+        let listing = CrawlListing {
+            url: "https://www.saq.com/en/products".to_string(),
+            page_size: None,
+            max_pages: None,
+            max_products: None,
+            until_known: false,
+        };
+        let checkpoint = Checkpoint::new(&CrawlState {
+            listing: listing.clone(),
+            next_page: 3,
+            pending: vec![UnfinishedProduct {
+                url: "https://www.saq.com/en/1".to_string(),
+                sku: "1".to_string(),
+            }],
+        });
+
+        // Products still pending from before aren't queued twice
+        assert!(!checkpoint.queued("https://www.saq.com/en/1", "1"));
+        assert!(checkpoint.queued("https://www.saq.com/en/2", "2"));
+        assert!(checkpoint.queued("https://www.saq.com/en/3", "3"));
+        checkpoint.page_queued(3);
+        checkpoint.done("https://www.saq.com/en/1");
+        checkpoint.done("https://www.saq.com/en/3");
+
+        assert_eq!(
+            CrawlState {
+                listing,
+                next_page: 4,
+                pending: vec![UnfinishedProduct {
+                    url: "https://www.saq.com/en/2".to_string(),
+                    sku: "2".to_string(),
+                }],
+            },
+            checkpoint.state()
+        );
+    }
+}
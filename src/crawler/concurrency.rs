@@ -0,0 +1,199 @@
+//! Adaptive limiting of the number of products crawled concurrently.
+//!
+//! The limit follows an [AIMD](https://en.wikipedia.org/wiki/Additive_increase/multiplicative_decrease)
+//! scheme similar to TCP congestion control: it grows by one after a full
+//! "window" of fast, successful requests, and is halved whenever the site
+//! starts responding slowly or asks us to back off.
+
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::info;
+
+/// The result of a product request, as far as the [`AdaptiveLimit`] is concerned.
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    /// The request succeeded after the given amount of time.
+    Success(Duration),
+    /// The site responded with a `429 Too Many Requests` or a server error.
+    Overloaded,
+}
+
+/// Mutable state of an [`AdaptiveLimit`].
+#[derive(Debug)]
+struct State {
+    /// Current number of requests allowed to be in flight.
+    limit: usize,
+    /// Number of requests currently in flight.
+    active: usize,
+    /// Fast successful requests since the limit last changed.
+    successes: usize,
+    /// Completed requests since the limit was last decreased.
+    since_decrease: usize,
+}
+
+/// A concurrency limit adjusted based on observed request [`Outcome`]s.
+pub struct AdaptiveLimit {
+    /// The current limit and usage.
+    state: Mutex<State>,
+    /// Notified whenever a permit is released or the limit increases.
+    released: Notify,
+    /// Lower bound for the limit.
+    min: usize,
+    /// Upper bound for the limit.
+    max: usize,
+    /// Requests slower than this are treated as a sign of overload.
+    latency_target: Duration,
+}
+
+/// Allows a single request to be in flight until dropped.
+pub struct Permit<'a> {
+    /// The limit this permit was acquired from.
+    limit: &'a AdaptiveLimit,
+}
+
+impl AdaptiveLimit {
+    /// Creates a limit starting at `initial` (clamped to `min..=max`).
+    pub fn new(initial: usize, min: usize, max: usize, latency_target: Duration) -> AdaptiveLimit {
+        let limit = initial.clamp(min, max);
+
+        AdaptiveLimit {
+            state: Mutex::new(State {
+                limit,
+                active: 0,
+                successes: 0,
+                since_decrease: limit,
+            }),
+            released: Notify::new(),
+            min,
+            max,
+            latency_target,
+        }
+    }
+
+    /// The current limit.
+    pub fn current(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Waits until fewer than the current limit of requests are in flight.
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            let released = self.released.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.active < state.limit {
+                    state.active += 1;
+                    return Permit { limit: self };
+                }
+            }
+
+            released.await;
+        }
+    }
+
+    /// Adjusts the limit based on the outcome of a request.
+    ///
+    /// Decreases only happen once per window of `limit` completed requests
+    /// so that a single burst of errors doesn't collapse the limit to `min`.
+    pub fn record(&self, outcome: Outcome) {
+        let mut state = self.state.lock().unwrap();
+        state.since_decrease += 1;
+
+        match outcome {
+            Outcome::Success(latency) if latency <= self.latency_target => {
+                state.successes += 1;
+
+                if state.successes >= state.limit && state.limit < self.max {
+                    state.limit += 1;
+                    state.successes = 0;
+                    info!(limit = state.limit, "increasing concurrency");
+                    drop(state);
+                    self.released.notify_one();
+                }
+            }
+            _ => {
+                if state.since_decrease < state.limit {
+                    return;
+                }
+
+                let limit = (state.limit / 2).max(self.min);
+                if limit != state.limit {
+                    info!(limit, ?outcome, "decreasing concurrency");
+                }
+
+                state.limit = limit;
+                state.successes = 0;
+                state.since_decrease = 0;
+            }
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limit.state.lock().unwrap().active -= 1;
+        self.limit.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Outcome = Outcome::Success(Duration::from_millis(10));
+    const SLOW: Outcome = Outcome::Success(Duration::from_secs(10));
+
+    #[test]
+    fn test_additive_increase() {
+        let limit = AdaptiveLimit::new(2, 1, 3, Duration::from_secs(1));
+
+        limit.record(FAST);
+        assert_eq!(2, limit.current());
+        limit.record(FAST);
+        assert_eq!(3, limit.current());
+
+        for _ in 0..10 {
+            limit.record(FAST);
+        }
+        assert_eq!(3, limit.current());
+    }
+
+    #[test]
+    fn test_multiplicative_decrease() {
+        let limit = AdaptiveLimit::new(8, 1, 16, Duration::from_secs(1));
+
+        limit.record(Outcome::Overloaded);
+        assert_eq!(4, limit.current());
+
+        // Within the same window
+        limit.record(SLOW);
+        assert_eq!(4, limit.current());
+
+        for _ in 0..3 {
+            limit.record(Outcome::Overloaded);
+        }
+        assert_eq!(2, limit.current());
+
+        for _ in 0..10 {
+            limit.record(Outcome::Overloaded);
+        }
+        assert_eq!(1, limit.current());
+    }
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let limit = AdaptiveLimit::new(1, 1, 2, Duration::from_secs(1));
+
+        let permit = limit.acquire().await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), limit.acquire())
+                .await
+                .is_err()
+        );
+
+        drop(permit);
+        let _permit = limit.acquire().await;
+    }
+}
@@ -0,0 +1,129 @@
+//! Checks how much of the catalog a crawl actually captured.
+//!
+//! Crawls discover products by paging through the catalog, so products shifting
+//! between pages mid-crawl (or pagination wrapping around early) can go unnoticed.
+//! The sitemap provides an independent list of products to compare against.
+
+use crate::config::Config;
+use crate::db;
+use crate::saq::sitemap::product_saq_code;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// The result of comparing a crawl against the sitemap.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Coverage {
+    /// The crawl that was checked.
+    pub crawl_run_id: i64,
+    /// When the crawl started.
+    pub started_at: DateTime<Utc>,
+    /// Number of product pages listed in the sitemap.
+    pub sitemap_products: usize,
+    /// Number of sitemap products that were captured by the crawl.
+    pub captured_products: usize,
+    /// URLs of products listed in the sitemap that the crawl never captured.
+    pub missing_urls: Vec<String>,
+    /// Number of products captured by the crawl that the sitemap doesn't list.
+    pub unlisted_products: usize,
+}
+
+/// Compares the products listed in the sitemap with the SAQ codes of the
+/// products captured by `crawl_run_id` (or the latest crawl).
+pub async fn coverage(config: &Config, crawl_run_id: Option<i64>) -> Result<Coverage> {
+    let client = super::saq_client(config)?;
+    let db = db::Client::new_from_env().await?;
+
+    let crawl_run_id = match crawl_run_id {
+        Some(id) => id,
+        None => db
+            .latest_crawl_run_id()
+            .await?
+            .ok_or_else(|| eyre!("no crawls have been recorded yet"))?,
+    };
+
+    let started_at = db
+        .crawl_run_started_at(crawl_run_id)
+        .await?
+        .ok_or_else(|| eyre!("crawl {} does not exist", crawl_run_id))?;
+
+    let captured = db.crawl_run_saq_codes(crawl_run_id).await?;
+    let sitemap_urls = client.sitemap_product_urls().await?;
+
+    Ok(compare(crawl_run_id, started_at, sitemap_urls, captured))
+}
+
+/// Does the actual comparison on behalf of [`coverage`].
+fn compare(
+    crawl_run_id: i64,
+    started_at: DateTime<Utc>,
+    sitemap_urls: Vec<String>,
+    captured: Vec<String>,
+) -> Coverage {
+    let mut captured = captured.into_iter().collect::<HashSet<_>>();
+
+    let mut sitemap_products = 0;
+    let mut missing_urls = vec![];
+
+    let mut seen = HashSet::new();
+    for url in sitemap_urls {
+        let saq_code = match product_saq_code(&url) {
+            Some(saq_code) => saq_code,
+            None => continue,
+        };
+
+        if !seen.insert(saq_code.clone()) {
+            continue;
+        }
+        sitemap_products += 1;
+
+        if !captured.remove(&saq_code) {
+            missing_urls.push(url);
+        }
+    }
+
+    missing_urls.sort();
+
+    Coverage {
+        crawl_run_id,
+        started_at,
+        sitemap_products,
+        captured_products: sitemap_products - missing_urls.len(),
+        missing_urls,
+        unlisted_products: captured.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let sitemap_urls = vec![
+            "https://www.saq.com/en/3".to_string(),
+            "https://www.saq.com/en/1".to_string(),
+            "https://www.saq.com/en/2".to_string(),
+            "https://www.saq.com/en/2".to_string(),
+            "https://www.saq.com/en/products/wine".to_string(),
+        ];
+        let captured = vec!["2".to_string(), "4".to_string()];
+        let started_at = Utc::now();
+
+        assert_eq!(
+            Coverage {
+                crawl_run_id: 1,
+                started_at,
+                sitemap_products: 3,
+                captured_products: 1,
+                missing_urls: vec![
+                    "https://www.saq.com/en/1".to_string(),
+                    "https://www.saq.com/en/3".to_string()
+                ],
+                unlisted_products: 1,
+            },
+            compare(1, started_at, sitemap_urls, captured)
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Classification of errors encountered while crawling individual pages.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use color_eyre::Report;
+use reqwest::StatusCode;
+use std::str::FromStr;
+
+/// Broad categories of crawl errors, used to tell apart failures caused by
+/// a specific page from ones affecting the crawl as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The server responded with a 4xx status code.
+    HttpClientError,
+    /// The server responded with a 5xx status code.
+    HttpServerError,
+    /// The request failed before a response was received (timeouts,
+    /// connection resets, etc.).
+    Network,
+    /// The response was received but its contents couldn't be extracted.
+    Parse,
+    /// Persisting the extracted data failed.
+    Database,
+}
+
+impl ErrorClass {
+    /// Every error class.
+    pub const ALL: [ErrorClass; 5] = [
+        ErrorClass::HttpClientError,
+        ErrorClass::HttpServerError,
+        ErrorClass::Network,
+        ErrorClass::Parse,
+        ErrorClass::Database,
+    ];
+
+    /// Determines the class of `err` by looking for known error types in
+    /// its chain of causes. Anything unrecognized is assumed to be a
+    /// parsing failure as that is where most ad-hoc errors originate.
+    pub fn classify(err: &Report) -> ErrorClass {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                return match err.status() {
+                    Some(status) if status.is_server_error() => ErrorClass::HttpServerError,
+                    Some(status) if status.is_client_error() => ErrorClass::HttpClientError,
+                    _ => ErrorClass::Network,
+                };
+            }
+
+            if cause.downcast_ref::<sqlx::Error>().is_some() {
+                return ErrorClass::Database;
+            }
+        }
+
+        ErrorClass::Parse
+    }
+
+    /// Whether errors of this class are likely to be caused by the page
+    /// itself, and will therefore keep happening on subsequent crawls.
+    pub fn is_page_specific(&self) -> bool {
+        match self {
+            ErrorClass::HttpClientError | ErrorClass::HttpServerError | ErrorClass::Parse => true,
+            ErrorClass::Network | ErrorClass::Database => false,
+        }
+    }
+}
+
+impl FromStr for ErrorClass {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "http_client_error" => Ok(ErrorClass::HttpClientError),
+            "http_server_error" => Ok(ErrorClass::HttpServerError),
+            "network" => Ok(ErrorClass::Network),
+            "parse" => Ok(ErrorClass::Parse),
+            "database" => Ok(ErrorClass::Database),
+            _ => Err(eyre!("{:?} is not an error class", s)),
+        }
+    }
+}
+
+/// What to do about a product that failed to be crawled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Crawl the product again, up to [`Config::max_retries`](crate::config::Config::max_retries)
+    /// times before skipping it.
+    Retry,
+    /// Record the error and move on to the next product.
+    Skip,
+    /// Record the error and stop the crawl.
+    Abort,
+}
+
+impl FromStr for ErrorAction {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "retry" => Ok(ErrorAction::Retry),
+            "skip" => Ok(ErrorAction::Skip),
+            "abort" => Ok(ErrorAction::Abort),
+            _ => Err(eyre!(
+                "{:?} is not an error action (retry, skip or abort)",
+                s
+            )),
+        }
+    }
+}
+
+/// The [`ErrorAction`] to take for each [`ErrorClass`].
+///
+/// By default transient errors are retried, errors caused by the page itself
+/// are skipped, and database errors abort the crawl as they're likely to
+/// affect every product.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorPolicy {
+    /// See [`ErrorClass::HttpClientError`].
+    pub http_client_error: ErrorAction,
+    /// See [`ErrorClass::HttpServerError`].
+    pub http_server_error: ErrorAction,
+    /// See [`ErrorClass::Network`].
+    pub network: ErrorAction,
+    /// See [`ErrorClass::Parse`].
+    pub parse: ErrorAction,
+    /// See [`ErrorClass::Database`].
+    pub database: ErrorAction,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        ErrorPolicy {
+            http_client_error: ErrorAction::Skip,
+            http_server_error: ErrorAction::Retry,
+            network: ErrorAction::Retry,
+            parse: ErrorAction::Skip,
+            database: ErrorAction::Abort,
+        }
+    }
+}
+
+impl ErrorPolicy {
+    /// The action configured for errors of the given class.
+    pub fn action(&self, class: ErrorClass) -> ErrorAction {
+        match class {
+            ErrorClass::HttpClientError => self.http_client_error,
+            ErrorClass::HttpServerError => self.http_server_error,
+            ErrorClass::Network => self.network,
+            ErrorClass::Parse => self.parse,
+            ErrorClass::Database => self.database,
+        }
+    }
+
+    /// Changes the action configured for errors of the given class.
+    pub fn set(&mut self, class: ErrorClass, action: ErrorAction) {
+        let field = match class {
+            ErrorClass::HttpClientError => &mut self.http_client_error,
+            ErrorClass::HttpServerError => &mut self.http_server_error,
+            ErrorClass::Network => &mut self.network,
+            ErrorClass::Parse => &mut self.parse,
+            ErrorClass::Database => &mut self.database,
+        };
+        *field = action;
+    }
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = Report;
+
+    /// Parses a comma-separated list of `class=action` pairs (i.e.
+    /// `parse=abort,network=skip`), applied on top of the default policy.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut policy = ErrorPolicy::default();
+
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (class, action) = pair
+                .split_once('=')
+                .ok_or_else(|| eyre!("expected class=action, got {:?}", pair))?;
+            let class = class.trim().parse()?;
+            let action = action
+                .trim()
+                .parse()
+                .wrap_err_with(|| format!("invalid action for {class:?}"))?;
+            policy.set(class, action);
+        }
+
+        Ok(policy)
+    }
+}
+
+/// The HTTP status code of the response that caused `err`, if any.
+pub fn status(err: &Report) -> Option<StatusCode> {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .find_map(|err| err.status())
+}
+
+/// Whether `err` indicates the site is overloaded or rate limiting us
+/// (`429 Too Many Requests` or a 5xx status code).
+pub fn is_overloaded(err: &Report) -> bool {
+    status(err)
+        .map(|status| status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    #[test]
+    fn test_classify() {
+        let parse = eyre!("SAQ code not found");
+        assert_eq!(ErrorClass::Parse, ErrorClass::classify(&parse));
+
+        let database: Result<(), _> = Err(sqlx::Error::RowNotFound);
+        let database = database.wrap_err("failed to persist product").unwrap_err();
+        assert_eq!(ErrorClass::Database, ErrorClass::classify(&database));
+    }
+
+    #[test]
+    fn test_parse_error_policy() {
+        let policy: ErrorPolicy = "parse=abort, network=skip,".parse().unwrap();
+        assert_eq!(ErrorAction::Abort, policy.action(ErrorClass::Parse));
+        assert_eq!(ErrorAction::Skip, policy.action(ErrorClass::Network));
+        assert_eq!(
+            ErrorAction::Retry,
+            policy.action(ErrorClass::HttpServerError)
+        );
+
+        assert_eq!(ErrorPolicy::default(), "".parse::<ErrorPolicy>().unwrap());
+        assert!("parse".parse::<ErrorPolicy>().is_err());
+        assert!("parse=ignore".parse::<ErrorPolicy>().is_err());
+        assert!("timeout=skip".parse::<ErrorPolicy>().is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Extension points for running custom logic while crawling, when using
+//! `ransaq` as a library.
+//!
+//! Implement [`Hooks`] (every method defaults to doing nothing) and pass it to
+//! [`crawl_with_hooks`](super::crawl_with_hooks):
+//!
+//! ```no_run
+//! use async_trait::async_trait;
+//! use color_eyre::eyre::{eyre, Result};
+//! use ransaq::config::Config;
+//! use ransaq::crawler::{crawl_with_hooks, hooks::Hooks};
+//! use ransaq::db::CrawlRunMode;
+//! use ransaq::saq::ExtractedProduct;
+//! use std::sync::Arc;
+//!
+//! struct RequireUpc;
+//!
+//! #[async_trait]
+//! impl Hooks for RequireUpc {
+//!     async fn before_persist(&self, product: &ExtractedProduct) -> Result<()> {
+//!         match product.detailed_info.upc_code {
+//!             Some(_) => Ok(()),
+//!             None => Err(eyre!("{} has no UPC code", product.detailed_info.saq_code)),
+//!         }
+//!     }
+//! }
+//!
+//! # async fn run() -> Result<()> {
+//! crawl_with_hooks(&Config::from_env()?, CrawlRunMode::Full, Arc::new(RequireUpc)).await
+//! # }
+//! ```
+//!
+//! Errors returned by the per-product hooks are handled like any other error
+//! encountered while crawling that product, according to
+//! [`Config::error_policy`](crate::config::Config::error_policy). Unless they wrap a known error type, they are
+//! [classified](super::errors::ErrorClass::classify) as parse errors.
+
+use super::anomalies::Anomaly;
+use crate::db::CrawlRunStatus;
+use crate::saq::ExtractedProduct;
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+
+/// Custom logic invoked at defined points of a crawl.
+///
+/// Hooks are shared between worker tasks and may run concurrently.
+#[async_trait]
+pub trait Hooks: Send + Sync {
+    /// Called once data has been extracted from a product page. The product
+    /// can be modified before it gets persisted.
+    async fn after_extract(&self, _product: &mut ExtractedProduct) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called right before a product is persisted, i.e. for extra validation.
+    async fn before_persist(&self, _product: &ExtractedProduct) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after a product was persisted as the row with `product_id` in
+    /// the `products` table, i.e. to write it to another sink.
+    ///
+    /// Products are persisted in batches, so this may be called some time
+    /// after [`before_persist`](Hooks::before_persist).
+    async fn after_persist(&self, _product: &ExtractedProduct, _product_id: i64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for each [`Anomaly`] detected once a crawl completes, i.e. to
+    /// send an alert. Called before [`on_crawl_end`](Hooks::on_crawl_end).
+    async fn on_anomaly(&self, _crawl_run_id: i64, _anomaly: &Anomaly) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once a crawl has finished with the given `status`.
+    async fn on_crawl_end(&self, _crawl_run_id: i64, _status: CrawlRunStatus) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// [`Hooks`] that don't do anything, used by [`crawl`](super::crawl).
+#[derive(Debug, Default)]
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
@@ -0,0 +1,125 @@
+//! Composite price indices computed at the end of each crawl.
+//!
+//! An index is the average price of a fixed basket of products (i.e. a
+//! "champagne index" made of a few popular champagnes), which makes it easy to
+//! follow prices over time without being thrown off by products entering or
+//! leaving the catalog. Indices are defined through [`Config::price_indices`](crate::config::Config::price_indices)
+//! and recorded in the `metrics_history` table.
+
+use crate::db;
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::Report;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// A named basket of products.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriceIndex {
+    /// The name the index is recorded under.
+    pub name: String,
+    /// The SAQ codes of the products in the basket.
+    pub saq_codes: Vec<String>,
+}
+
+/// The [`PriceIndex`]es to compute after each crawl.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PriceIndices(pub Vec<PriceIndex>);
+
+impl FromStr for PriceIndices {
+    type Err = Report;
+
+    /// Parses a comma-separated list of `name=code+code+...` entries (i.e.
+    /// `champagne=11766597+10264010,rose=13191791+12345678`).
+    fn from_str(s: &str) -> Result<Self> {
+        let mut indices = vec![];
+
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, saq_codes) = entry
+                .split_once('=')
+                .ok_or_else(|| eyre!("expected name=code+code, got {:?}", entry))?;
+
+            let saq_codes = saq_codes
+                .split('+')
+                .map(str::trim)
+                .filter(|code| !code.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>();
+            if saq_codes.is_empty() {
+                return Err(eyre!("index {:?} has no products", name.trim()));
+            }
+
+            indices.push(PriceIndex {
+                name: name.trim().to_string(),
+                saq_codes,
+            });
+        }
+
+        Ok(PriceIndices(indices))
+    }
+}
+
+/// Computes each of the `indices` from the current prices and records them
+/// against `crawl_run_id`.
+///
+/// Indices whose products are all missing from the database are skipped.
+pub async fn record_indices(
+    db: &db::Client,
+    crawl_run_id: i64,
+    indices: &PriceIndices,
+) -> Result<()> {
+    for index in &indices.0 {
+        let (average_cents, products) = db.average_price_cents(&index.saq_codes).await?;
+
+        let average_cents = match average_cents {
+            Some(average_cents) => average_cents,
+            None => {
+                warn!(index = %index.name, "none of the index's products are known");
+                continue;
+            }
+        };
+
+        if products < index.saq_codes.len() as i64 {
+            warn!(
+                index = %index.name,
+                products,
+                expected = index.saq_codes.len(),
+                "some of the index's products are unknown"
+            );
+        }
+
+        let value = average_cents / 100.0;
+        db.record_metric(crawl_run_id, &index.name, value, products)
+            .await?;
+        info!(index = %index.name, value, products, "recorded price index");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price_indices() {
+        assert_eq!(
+            PriceIndices(vec![
+                PriceIndex {
+                    name: "champagne".to_string(),
+                    saq_codes: vec!["11766597".to_string(), "10264010".to_string()],
+                },
+                PriceIndex {
+                    name: "rose".to_string(),
+                    saq_codes: vec!["13191791".to_string()],
+                },
+            ]),
+            "champagne=11766597+10264010, rose=13191791"
+                .parse()
+                .unwrap()
+        );
+
+        assert_eq!(PriceIndices::default(), "".parse().unwrap());
+        assert!("champagne".parse::<PriceIndices>().is_err());
+        assert!("champagne=".parse::<PriceIndices>().is_err());
+    }
+}
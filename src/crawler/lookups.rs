@@ -0,0 +1,114 @@
+//! Per-crawl memoization of lookup table upserts.
+//!
+//! Most products share the same handful of countries, colors, categories,
+//! etc., so upserting them for every product mostly results in redundant
+//! queries. A [`LookupCache`] lives for the duration of a single crawl and
+//! remembers the `id` returned for each set of inputs so that each lookup is
+//! only upserted once per crawl.
+
+use color_eyre::eyre::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Lookup table `id`s keyed by table and upsert inputs.
+#[derive(Debug, Default)]
+pub struct LookupCache {
+    /// Known `id`s, keyed by `(table, key)`.
+    ids: Mutex<HashMap<(&'static str, String), i64>>,
+    /// Number of lookups answered from the cache.
+    hits: AtomicU64,
+    /// Number of lookups which had to be upserted.
+    misses: AtomicU64,
+}
+
+impl LookupCache {
+    /// Returns the `id` previously cached for `key` in `table`, or runs
+    /// `upsert` and caches its result.
+    ///
+    /// `key` must capture every input of `upsert` (i.e. name, url and parent
+    /// for categories), otherwise upserts with different inputs would be
+    /// skipped. Failed upserts aren't cached.
+    pub async fn get_or_upsert<F, Fut>(
+        &self,
+        table: &'static str,
+        key: &str,
+        upsert: F,
+    ) -> Result<i64>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<i64>>,
+    {
+        let cache_key = (table, key.to_string());
+
+        if let Some(id) = self.ids.lock().unwrap().get(&cache_key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(*id);
+        }
+
+        // Concurrent misses for the same key may both upsert, which is
+        // harmless as the upserts are idempotent.
+        let id = upsert().await?;
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.ids.lock().unwrap().insert(cache_key, id);
+
+        Ok(id)
+    }
+
+    /// Number of upserts avoided thanks to the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of upserts that actually ran.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::eyre;
+
+    #[tokio::test]
+    async fn test_get_or_upsert() -> Result<()> {
+        let cache = LookupCache::default();
+
+        assert_eq!(
+            1,
+            cache
+                .get_or_upsert("colors", "Red", || async { Ok(1) })
+                .await?
+        );
+        assert_eq!(
+            1,
+            cache
+                .get_or_upsert("colors", "Red", || async { Ok(2) })
+                .await?
+        );
+        assert_eq!(
+            3,
+            cache
+                .get_or_upsert("regions", "Red", || async { Ok(3) })
+                .await?
+        );
+
+        let failed = cache
+            .get_or_upsert("colors", "White", || async { Err(eyre!("locked")) })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(
+            4,
+            cache
+                .get_or_upsert("colors", "White", || async { Ok(4) })
+                .await?
+        );
+
+        assert_eq!(1, cache.hits());
+        assert_eq!(3, cache.misses());
+
+        Ok(())
+    }
+}
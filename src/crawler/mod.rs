@@ -1,10 +1,53 @@
 //! Connecting logic between [`saq`](saq) and [`db`](db) to actually
 //! perform a crawl.
 
-use crate::db::{self, DbSerialize, ProductUpsertFields};
-use crate::saq::{self, ExtractedProduct};
+use crate::config::{Config, SkippableField};
+use crate::db::{
+    self, CrawlListing, CrawlRunMode, CrawlRunStatus, CrawlState, DbSerialize, IdentifierScheme,
+    ListingRefresh, ProductRelations, ProductUpsertFields, UnfinishedProduct,
+};
+use crate::saq::retry::RetryPolicy;
+use crate::saq::{self, linked_data, CatalogOrder, ExtractedProduct, SiteProfile};
+use batch::Batch;
+use checkpoint::Checkpoint;
+use color_eyre::eyre::eyre;
 use color_eyre::{Report, Result};
+use concurrency::{AdaptiveLimit, Outcome, Permit};
+use errors::{ErrorAction, ErrorClass};
 use futures_util::future::join_all;
+use hooks::{Hooks, NoHooks};
+use lookups::LookupCache;
+use queue::{Priority, PriorityQueue};
+use stats::{MemoryUsage, RequestRates, Stats};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+pub mod anomalies;
+pub mod batch;
+pub mod checkpoint;
+pub mod concurrency;
+pub mod coverage;
+pub mod errors;
+pub mod hooks;
+pub mod indices;
+pub mod lookups;
+pub mod provenance;
+pub mod queue;
+pub mod simulate;
+pub mod stats;
+
+/// Number of products persisted together, see [`db::Client::upsert_products`].
+const PERSIST_BATCH_SIZE: usize = 50;
+
+/// Number of product pages parsed at once in [low memory](Config::low_memory) mode.
+const LOW_MEMORY_PARSES: usize = 1;
+
+/// Number of database connections used in [low memory](Config::low_memory) mode.
+const LOW_MEMORY_POOL_SIZE: u32 = 2;
 
 /// Iterates through the entire product catalog page by page, fetches
 /// and parses each product page, and inserts the relevant data into
@@ -12,70 +55,439 @@ use futures_util::future::join_all;
 ///
 /// Catalog pages are fetched serially, each yielding a list of product
 /// page URLs. These are then handed to a pool of tasks to be fetched
-/// in parallel.
-///
-/// Task coordination and backpressure is handled via [`async_channel::bounded`](async_channel::bounded).
-pub async fn crawl() -> Result<()> {
-    let client = saq::Client::new()?;
-    let db = db::Client::new_from_env().await?;
-
-    let (send, receive) = async_channel::bounded(8);
-
-    let page_client = client.clone();
-    let page_task = tokio::spawn(async move {
-        let mut page_number = 1;
-        loop {
-            match page_client.page(page_number).await {
-                Ok(Some(page)) => {
-                    for product in page {
-                        if let Err(err) = send.send(product).await {
-                            return Err(Report::from(err));
-                        }
-                    }
-                    page_number += 1;
-                }
-                // We've hit the last page
-                Ok(None) => {
-                    send.close();
-                    return Ok(());
-                }
-                // There was an error fetching the current page
-                Err(err) => {
-                    send.close();
-                    return Err(err);
-                }
+/// in parallel, bounded by an [`AdaptiveLimit`].
+///
+/// Task coordination and backpressure is handled via a [`PriorityQueue`], which
+/// hands out products that have never been crawled first, followed by stale
+/// ones, so that interrupted crawls capture as much new information as possible.
+///
+/// Extracted products are persisted in batches of [`PERSIST_BATCH_SIZE`] to
+/// save on transaction overhead. Lookup tables (countries, categories, etc.)
+/// are only upserted once per crawl, see [`lookups`].
+///
+/// Products are [journaled](db::Client::journal_persists) before being
+/// persisted, and those a crash left half-written are queued first by the
+/// next crawl, whatever its mode.
+///
+/// Fields found on both the catalog listing and the product page are compared,
+/// and mismatches recorded and resolved as described in [`provenance`].
+///
+/// Throughput and latency [statistics](stats) are logged every
+/// [`Config::stats_interval`] while the crawl is running.
+///
+/// Each crawl is recorded in the `crawl_runs` table along with any product page
+/// errors. Whether a failing product is retried, skipped or aborts the crawl
+/// depends on the class of error and [`Config::error_policy`], and the action
+/// taken is recorded with the error. Pages failing repeatedly across crawls are
+/// added to the skip list (see [`record_failure`]).
+///
+/// Once a crawl completes, the [price indices](indices) defined in
+/// [`Config::price_indices`] are computed from the latest prices, and the
+/// crawl is checked for [anomalies](anomalies).
+///
+/// With [`Config::in_memory`] the crawl writes to an in-memory copy of the
+/// database, which replaces the on-disk one once the crawl is over (even if
+/// it failed).
+///
+/// With [`Config::low_memory`] only one product page is parsed at a time,
+/// and pages waiting to be parsed are kept in temporary files.
+///
+/// In [`CrawlRunMode::NewArrivals`] mode only the first
+/// [`Config::new_arrivals_pages`] pages of the catalog are crawled, sorted by
+/// newest products first.
+///
+/// Crawls of the catalog stop early after [`Config::max_pages`] pages, once
+/// [`Config::max_products`] products were queued, or (in new arrivals mode
+/// with [`Config::until_known`]) at the first page of products which were all
+/// crawled recently. With [`Config::category`] only that category's listing
+/// is crawled. As they don't see the whole catalog, such crawls don't record
+/// category prices or check for [anomalies].
+///
+/// In [`CrawlRunMode::Refresh`] mode only catalog pages are fetched for
+/// products already in the database, their availability and price being
+/// updated from the listing. Product pages are only fetched for new products
+/// and those whose listing changed.
+///
+/// Progress through the catalog is [checkpointed](checkpoint) as the crawl
+/// goes, and [`Config::resume`] picks up an interrupted crawl from its last
+/// checkpoint, as long as it lists products from the same place and stops
+/// under the same conditions.
+///
+/// In [`CrawlRunMode::Completion`] mode the catalog isn't crawled at all.
+/// Instead, products previously persisted as [partial](ExtractedProduct::partial)
+/// or missing key fields are crawled again, as pages failing that way are
+/// often fixed later on.
+pub async fn crawl(config: &Config, mode: CrawlRunMode) -> Result<()> {
+    crawl_with_hooks(config, mode, Arc::new(NoHooks)).await
+}
+
+/// Builds a client to the SAQ website following the request settings of
+/// `config` (allowed hosts, proxies, rate limit and retries).
+pub(crate) fn saq_client(config: &Config) -> Result<saq::Client> {
+    let mut client = saq::Client::with_allowed_hosts(config.allowed_hosts.clone())?
+        .retry_with(RetryPolicy {
+            max_attempts: config.request_attempts,
+            ..RetryPolicy::default()
+        })
+        .for_site(config.site.clone());
+
+    if !config.proxies.0.is_empty() {
+        client = client.with_proxies(&config.proxies)?;
+    }
+
+    if let Some(rate_limit) = config.rate_limit {
+        client = client.rate_limit(rate_limit);
+    }
+
+    if let Some(path) = &config.cookie_jar {
+        client = client.with_cookies(saq::cookies::CookieJar::load(path)?);
+    }
+
+    Ok(client)
+}
+
+/// What a crawl in the given `mode` lists products from and when it stops,
+/// according to `config`.
+pub(crate) fn crawl_listing(config: &Config, mode: CrawlRunMode) -> Result<CrawlListing> {
+    let max_pages = match (mode, config.max_pages) {
+        (CrawlRunMode::NewArrivals, Some(max_pages)) => {
+            Some(config.new_arrivals_pages.min(max_pages))
+        }
+        (CrawlRunMode::NewArrivals, None) => Some(config.new_arrivals_pages),
+        (_, max_pages) => max_pages,
+    };
+    let url = match &config.category {
+        Some(category) => config.site.category_url(category)?,
+        None => config.site.catalog_url(),
+    };
+
+    Ok(CrawlListing {
+        url,
+        page_size: config.page_size,
+        max_pages,
+        max_products: config.max_products,
+        until_known: config.until_known && mode == CrawlRunMode::NewArrivals,
+    })
+}
+
+/// Same as [`crawl`], invoking the provided [`Hooks`] along the way.
+pub async fn crawl_with_hooks(
+    config: &Config,
+    mode: CrawlRunMode,
+    hooks: Arc<dyn Hooks>,
+) -> Result<()> {
+    let mut client = saq_client(config)?;
+    let disk_db = if config.low_memory {
+        client = client.spool_to(std::env::temp_dir());
+        db::Client::new_from_env_with_pool_size(LOW_MEMORY_POOL_SIZE).await?
+    } else {
+        db::Client::new_from_env().await?
+    };
+
+    let db = match config.in_memory {
+        true => {
+            info!("staging crawl in memory");
+            disk_db.stage_in_memory().await?
+        }
+        false => disk_db.clone(),
+    };
+
+    let listing = crawl_listing(config, mode)?;
+    if config.category.is_some() {
+        info!(url = listing.url, "only crawling category");
+    }
+
+    let resumable = match config.resume {
+        true => db.resumable_crawl_run(mode).await?,
+        false => None,
+    };
+
+    let (crawl_run_id, mut state) = match resumable {
+        // Page numbers and pending products only make sense for the listing
+        // they came from, and a crawl stopping early (or not) has to keep
+        // doing so to be counted as such
+        Some((crawl_run_id, state)) if state.listing != listing => {
+            return Err(eyre!(
+                "crawl {crawl_run_id} was interrupted while crawling {:?} and can't be resumed with different settings ({:?})",
+                state.listing,
+                listing
+            ));
+        }
+        Some((crawl_run_id, state)) => {
+            info!(
+                crawl_run_id,
+                next_page = state.next_page,
+                pending = state.pending.len(),
+                "resuming crawl"
+            );
+            db.resume_crawl_run(crawl_run_id).await?;
+            (crawl_run_id, state)
+        }
+        None => {
+            if config.resume {
+                info!("no interrupted crawl to resume, starting a new one");
             }
+
+            let crawl_run_id = db.start_crawl_run(mode).await?;
+            if !config.crawl_tags.is_empty() || config.crawl_note.is_some() {
+                db.label_crawl_run(
+                    crawl_run_id,
+                    &config.crawl_tags,
+                    config.crawl_note.as_deref(),
+                )
+                .await?;
+            }
+
+            (crawl_run_id, CrawlState::new(listing))
         }
-    });
+    };
+
+    let interrupted = db.interrupted_persists().await?;
+    if !interrupted.is_empty() {
+        warn!(
+            products = interrupted.len(),
+            "persisting products left half-written by an earlier crawl again"
+        );
+    }
+    for product in interrupted {
+        if !state
+            .pending
+            .iter()
+            .any(|pending| pending.url == product.url)
+        {
+            state.pending.push(product);
+        }
+    }
+
+    let result = crawl_catalog(
+        config,
+        mode,
+        &client,
+        &db,
+        crawl_run_id,
+        state,
+        hooks.clone(),
+    )
+    .await;
+
+    if let Some(path) = &config.cookie_jar {
+        match client.cookies().save(path) {
+            Ok(()) => info!(cookies = client.cookies().len(), "saved cookies"),
+            Err(err) => warn!(?err, "failed to save cookies"),
+        }
+    }
+
+    let status = match result {
+        Ok(_) => CrawlRunStatus::Completed,
+        Err(_) => CrawlRunStatus::Failed,
+    };
+    db.finish_crawl_run(crawl_run_id, status).await?;
+    if status == CrawlRunStatus::Completed {
+        db.clear_crawl_state(crawl_run_id).await?;
+    }
 
-    let product_tasks = (0..8)
+    let mut anomalies = vec![];
+    if status == CrawlRunStatus::Completed {
+        if let Err(err) = indices::record_indices(&db, crawl_run_id, &config.price_indices).await {
+            warn!(?err, "failed to record price indices");
+        }
+
+        // Only full crawls refresh every product's price
+        if mode == CrawlRunMode::Full && config.crawls_whole_catalog() {
+            match db.record_category_prices(crawl_run_id).await {
+                Ok(categories) => info!(categories, "recorded category prices"),
+                Err(err) => warn!(?err, "failed to record category prices"),
+            }
+        }
+
+        if config.crawls_whole_catalog() {
+            match anomalies::check_crawl(&db, crawl_run_id, mode).await {
+                Ok(detected) => anomalies = detected,
+                Err(err) => warn!(?err, "failed to check crawl for anomalies"),
+            }
+        }
+    }
+
+    if config.in_memory {
+        let start = Instant::now();
+        disk_db.replace_with(&db).await?;
+        info!(elapsed = ?start.elapsed(), "wrote staged crawl to disk");
+    }
+
+    let mut hook_result = Ok(());
+    for anomaly in &anomalies {
+        error!(%anomaly, "crawl deviates from previous ones");
+        hook_result = hook_result.and(hooks.on_anomaly(crawl_run_id, anomaly).await);
+    }
+    let hook_result = hook_result.and(hooks.on_crawl_end(crawl_run_id, status).await);
+
+    result.and(hook_result)
+}
+
+/// Does the actual crawling on behalf of [`crawl`].
+async fn crawl_catalog(
+    config: &Config,
+    mode: CrawlRunMode,
+    client: &saq::Client,
+    db: &db::Client,
+    crawl_run_id: i64,
+    state: CrawlState,
+    hooks: Arc<dyn Hooks>,
+) -> Result<()> {
+    let skip_list = db
+        .active_skip_list()
+        .await?
         .into_iter()
-        .map(|_| {
+        .map(|entry| entry.url)
+        .collect::<HashSet<_>>();
+    let skip_list = Arc::new(skip_list);
+
+    let queue_capacity = config
+        .queue_size
+        .max(2 * config.page_size.unwrap_or(0) as usize);
+    let queue = Arc::new(PriorityQueue::new(queue_capacity));
+
+    let peak_request_rate = config.peak_request_rate();
+    if peak_request_rate > config.politeness_budget as f64 {
+        warn!(
+            max_concurrency = config.max_concurrency,
+            latency_target = ?config.latency_target,
+            rate_limit = ?config.rate_limit,
+            peak_requests_per_minute = format!("{peak_request_rate:.0}"),
+            politeness_budget = config.politeness_budget,
+            "crawl settings could exceed the politeness budget"
+        );
+    }
+
+    let stats = Arc::new(Stats::new(config.max_concurrency));
+    let client = &client.clone().count_requests_with(stats.request_counter());
+    let checkpoint = Arc::new(Checkpoint::new(&state));
+
+    let persister = Arc::new(Persister {
+        db: db.clone(),
+        config: config.clone(),
+        crawl_run_id,
+        lookups: LookupCache::default(),
+        hooks,
+        stats: stats.clone(),
+        batch: Batch::new(PERSIST_BATCH_SIZE),
+        checkpoint: checkpoint.clone(),
+    });
+
+    let page_task = match mode {
+        CrawlRunMode::Full | CrawlRunMode::NewArrivals | CrawlRunMode::Refresh => {
+            tokio::spawn(queue_catalog_products(
+                client.clone(),
+                db.clone(),
+                config.clone(),
+                mode,
+                state,
+                queue.clone(),
+                stats.clone(),
+                checkpoint.clone(),
+            ))
+        }
+        CrawlRunMode::Completion => tokio::spawn(queue_incomplete_products(
+            db.clone(),
+            config.clone(),
+            state.pending,
+            queue.clone(),
+        )),
+    };
+
+    let parses = Arc::new(Semaphore::new(match config.low_memory {
+        true => LOW_MEMORY_PARSES,
+        false => config.max_concurrency,
+    }));
+
+    let limit = Arc::new(AdaptiveLimit::new(
+        config.initial_concurrency,
+        config.min_concurrency,
+        config.max_concurrency,
+        config.latency_target,
+    ));
+
+    let reporter_queue = queue.clone();
+    let reporter = stats
+        .clone()
+        .report_every(config.stats_interval, move || reporter_queue.len());
+
+    // Completion crawls are made of whatever is incomplete in the database
+    // when they start, so there's nothing to resume
+    let checkpointing = mode != CrawlRunMode::Completion;
+    let checkpointer = match checkpointing {
+        true => Some(checkpoint.clone().save_every(db.clone(), crawl_run_id)),
+        false => None,
+    };
+
+    let product_tasks = (0..config.max_concurrency)
+        .map(|worker| {
             let client = client.clone();
-            let db = db.clone();
-            let receive = receive.clone();
+            let queue = queue.clone();
+            let skip_list = skip_list.clone();
+            let limit = limit.clone();
+            let parses = parses.clone();
+            let persister = persister.clone();
 
             tokio::spawn(async move {
                 loop {
-                    match receive.recv().await {
-                        Ok(product) => {
-                            let extracted = match client.product(&product).await {
-                                Ok(value) => value,
-                                Err(err) => {
-                                    receive.close();
-                                    return Err(err);
-                                }
-                            };
+                    let permit = limit.acquire().await;
 
-                            if let Err(err) = persist_product(&db, extracted).await {
-                                receive.close();
-                                return Err(err);
-                            } else {
+                    match queue.pop().await {
+                        Some(product) => {
+                            let url = &product.url;
+
+                            if skip_list.contains(url) {
+                                info!(%url, sku = %product.sku, "skipping product on skip list");
+                                persister.checkpoint.done(url);
                                 continue;
                             }
+
+                            let mut permit = Some(permit);
+                            let mut attempt = 0;
+
+                            loop {
+                                let permit = match permit.take() {
+                                    Some(permit) => permit,
+                                    None => limit.acquire().await,
+                                };
+
+                                let err = match crawl_product(
+                                    &client, &persister, &limit, &parses, &product, permit,
+                                )
+                                .await
+                                {
+                                    Ok(extracted) => {
+                                        let result =
+                                            persister.enqueue(worker, url, extracted).await;
+                                        if result.is_err() {
+                                            queue.close();
+                                        }
+                                        result?;
+                                        break;
+                                    }
+                                    Err(err) => err,
+                                };
+
+                                let can_retry = attempt < persister.config.max_retries;
+                                match persister.fail(worker, url, &err, can_retry).await {
+                                    ErrorAction::Retry => {
+                                        attempt += 1;
+                                        warn!(%url, attempt, "retrying product");
+                                    }
+                                    ErrorAction::Skip => {
+                                        persister.checkpoint.done(url);
+                                        break;
+                                    }
+                                    ErrorAction::Abort => {
+                                        queue.close();
+                                        return Err(err);
+                                    }
+                                }
+                            }
                         }
-                        // The channel is closed
-                        Err(_) => {
+                        // The queue is closed
+                        None => {
                             return Ok(());
                         }
                     }
@@ -84,74 +496,972 @@ pub async fn crawl() -> Result<()> {
         })
         .collect::<Vec<_>>();
 
-    page_task.await??;
+    let page_result = page_task.await;
+    let product_results = join_all(product_tasks).await;
+
+    // Whatever is left of the last batch
+    let flush_result = persister.flush(0, persister.batch.take()).await;
 
-    for join_result in join_all(product_tasks).await {
+    reporter.abort();
+
+    if let Some(checkpointer) = checkpointer {
+        checkpointer.abort();
+        if let Err(err) = checkpoint.save(db, crawl_run_id).await {
+            warn!(?err, "failed to save crawl checkpoint");
+        }
+    }
+
+    page_result??;
+    for join_result in product_results {
         join_result??;
     }
+    flush_result?;
+
+    stats.log_page_summary(config.page_size);
+    info!(
+        concurrency = limit.current(),
+        products = stats.products(),
+        lookup_upserts = persister.lookups.misses(),
+        lookup_upserts_saved = persister.lookups.hits(),
+        peak_rss_mb = MemoryUsage::current().map(|m| m.peak_rss_kb / 1024),
+        "finished crawling catalog"
+    );
+
+    let rates = stats.request_rates();
+    info!(
+        requests = rates.requests,
+        avg_per_minute = format!("{:.1}", rates.avg_per_minute),
+        max_per_minute = rates.max_per_minute,
+        max_burst = rates.max_burst,
+        politeness_budget = config.politeness_budget,
+        "request rates"
+    );
+    if rates.max_per_minute > config.politeness_budget as u64 {
+        warn!(
+            max_per_minute = rates.max_per_minute,
+            politeness_budget = config.politeness_budget,
+            "crawl exceeded the politeness budget"
+        );
+    }
+    if let Err(err) = record_request_rates(db, crawl_run_id, &rates).await {
+        warn!(?err, "failed to record request rates");
+    }
+
+    Ok(())
+}
+
+/// Records the rates at which the given crawl sent requests in the
+/// `metrics_history` table, so they can be reviewed with `ransaq metrics`.
+async fn record_request_rates(
+    db: &db::Client,
+    crawl_run_id: i64,
+    rates: &RequestRates,
+) -> Result<()> {
+    let products = db.crawl_run_saq_codes(crawl_run_id).await?.len() as i64;
+
+    for (name, value) in [
+        (stats::REQUESTS_PER_MINUTE_METRIC, rates.avg_per_minute),
+        (
+            stats::MAX_REQUESTS_PER_MINUTE_METRIC,
+            rates.max_per_minute as f64,
+        ),
+        (stats::MAX_BURST_METRIC, rates.max_burst as f64),
+    ] {
+        db.record_metric(crawl_run_id, name, value, products)
+            .await?;
+    }
 
     Ok(())
 }
 
+/// A product waiting in the work queue.
+#[derive(Debug)]
+struct QueuedProduct {
+    /// The product page URL.
+    url: String,
+    /// The product's SKU (i.e. its SAQ code).
+    sku: String,
+    /// What was kept of the product's catalog entry, unless it was queued from
+    /// the database (i.e. in [completion](CrawlRunMode::Completion) mode).
+    listing: Option<Listing>,
+}
+
+/// What's kept of a catalog entry to [fall back on](ExtractedProduct::fall_back_to_listing)
+/// if the product page lacks JSON-LD. Descriptions in particular make up most
+/// of a catalog entry and are left out.
+#[derive(Debug)]
+struct Listing {
+    /// The product's name.
+    name: String,
+    /// A URL for an image of the product.
+    image: String,
+    /// The product's price and availability.
+    offer: linked_data::Offer,
+}
+
+impl Listing {
+    /// Rebuilds the catalog entry of the product with the given `sku`.
+    fn product(&self, sku: &str) -> linked_data::Product {
+        linked_data::Product {
+            description: String::new(),
+            image: self.image.clone(),
+            name: self.name.clone(),
+            offers: self.offer.clone(),
+            sku: sku.to_string(),
+            category: None,
+            brand: None,
+            manufacturer: None,
+            gtin: None,
+            gtin13: None,
+            mpn: None,
+            aggregate_rating: None,
+            reviews: vec![],
+        }
+    }
+}
+
+/// A catalog page being fetched in the background, see
+/// [`queue_catalog_products`]. The request is cancelled if dropped before
+/// it's awaited (i.e. when the crawl fails).
+struct PrefetchedPage {
+    /// Fetches the page, returning how long it took alongside.
+    task: JoinHandle<(Result<Option<Vec<linked_data::Product>>>, Duration)>,
+}
+
+impl PrefetchedPage {
+    /// Starts fetching page `page_number` of the listing at `listing_url`.
+    fn start(
+        client: saq::Client,
+        listing_url: String,
+        page_number: u32,
+        page_size: Option<u32>,
+        order: CatalogOrder,
+    ) -> PrefetchedPage {
+        let task = tokio::spawn(async move {
+            let start = Instant::now();
+            let result = client
+                .listing_page(&listing_url, page_number, page_size, order)
+                .await;
+            (result, start.elapsed())
+        });
+
+        PrefetchedPage { task }
+    }
+
+    /// Waits for the page.
+    async fn finish(mut self) -> (Result<Option<Vec<linked_data::Product>>>, Duration) {
+        match (&mut self.task).await {
+            Ok(fetched) => fetched,
+            Err(err) => (Err(err.into()), Duration::ZERO),
+        }
+    }
+}
+
+impl Drop for PrefetchedPage {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Queues the products listed in the catalog for the given `mode`, see
+/// [`listing_priority`], starting from `state` (i.e. the last checkpoint of a
+/// resumed crawl).
+///
+/// While a page's products are being queued, the next page is prefetched as
+/// long as the queue has room to spare: workers are then waiting on discovery,
+/// so overlapping the two speeds things up. When the queue fills up, workers
+/// are the bottleneck and pages are only fetched once there's room for their
+/// products, keeping at most one page ahead of the queue. Prefetches go
+/// through the client's [rate limit](saq::rate_limit) like any other request.
+#[allow(clippy::too_many_arguments)]
+async fn queue_catalog_products(
+    client: saq::Client,
+    db: db::Client,
+    config: Config,
+    mode: CrawlRunMode,
+    state: CrawlState,
+    queue: Arc<PriorityQueue<QueuedProduct>>,
+    stats: Arc<Stats>,
+    checkpoint: Arc<Checkpoint>,
+) -> Result<()> {
+    let order = match mode {
+        CrawlRunMode::NewArrivals => CatalogOrder::NewArrivals,
+        _ => CatalogOrder::Availability,
+    };
+    let CrawlListing {
+        url: listing_url,
+        page_size,
+        max_pages,
+        max_products,
+        until_known,
+    } = state.listing;
+
+    // Products left over from the interrupted crawl go first, without a
+    // listing to fall back on as it wasn't kept
+    for product in state.pending {
+        let product = QueuedProduct {
+            url: product.url,
+            sku: product.sku,
+            listing: None,
+        };
+
+        if let Err(err) = queue.push(Priority::New, product).await {
+            return Err(Report::from(err));
+        }
+    }
+
+    let mut unchanged = 0;
+    let mut queued = 0;
+    let mut page_number = state.next_page;
+    let mut prefetched: Option<PrefetchedPage> = None;
+    loop {
+        let past_max_pages =
+            |page_number: u32| matches!(max_pages, Some(max_pages) if page_number > max_pages);
+
+        if past_max_pages(page_number) {
+            queue.close();
+            return Ok(());
+        }
+
+        let (result, elapsed) = match prefetched.take() {
+            Some(prefetched) => prefetched.finish().await,
+            None => {
+                let start = Instant::now();
+                let result = client
+                    .listing_page(&listing_url, page_number, page_size, order)
+                    .await;
+                (result, start.elapsed())
+            }
+        };
+
+        match result {
+            Ok(Some(page)) => {
+                stats.record_page(page.len(), elapsed);
+
+                if queue.len() + page.len() < queue.capacity() && !past_max_pages(page_number + 1) {
+                    prefetched = Some(PrefetchedPage::start(
+                        client.clone(),
+                        listing_url.clone(),
+                        page_number + 1,
+                        page_size,
+                        order,
+                    ));
+                }
+
+                let mut listed = Vec::with_capacity(page.len());
+                for product in page {
+                    match listing_priority(&db, &config, mode, &product).await {
+                        Ok(Some(priority)) => listed.push((priority, product)),
+                        Ok(None) => unchanged += 1,
+                        Err(err) => {
+                            queue.close();
+                            return Err(err);
+                        }
+                    }
+                }
+
+                if until_known
+                    && !listed.is_empty()
+                    && listed
+                        .iter()
+                        .all(|(priority, _)| *priority == Priority::Fresh)
+                {
+                    info!(page_number, "reached a page of known products, stopping");
+                    queue.close();
+                    return Ok(());
+                }
+
+                for (priority, product) in listed {
+                    let url = saq::url::canonicalize(&product.offers.url);
+                    if !checkpoint.queued(&url, &product.sku) {
+                        continue;
+                    }
+
+                    // Only what's needed to fetch the product page, or to
+                    // fall back on, is queued.
+                    let product = QueuedProduct {
+                        url,
+                        sku: product.sku.clone(),
+                        listing: Some(Listing {
+                            name: product.name,
+                            image: product.image,
+                            offer: product.offers,
+                        }),
+                    };
+
+                    if let Err(err) = queue.push(priority, product).await {
+                        return Err(Report::from(err));
+                    }
+
+                    queued += 1;
+                    if matches!(max_products, Some(max_products) if queued >= max_products) {
+                        info!(
+                            products = queued,
+                            "queued the maximum number of products, stopping"
+                        );
+                        queue.close();
+                        return Ok(());
+                    }
+                }
+                checkpoint.page_queued(page_number);
+                page_number += 1;
+            }
+            // We've hit the last page
+            Ok(None) => {
+                if mode == CrawlRunMode::Refresh {
+                    info!(unchanged, "refreshed unchanged products from listings");
+                }
+
+                queue.close();
+                return Ok(());
+            }
+            // There was an error fetching the current page
+            Err(err) => {
+                queue.close();
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Determines the [`Priority`] of a product listed in the catalog based on
+/// how stale it is, or `None` if its page doesn't need to be fetched.
+///
+/// In [refresh](CrawlRunMode::Refresh) mode the product's availability and
+/// price are [updated from the listing](db::Client::refresh_from_listing)
+/// instead, and only the pages of new or changed products are fetched.
+async fn listing_priority(
+    db: &db::Client,
+    config: &Config,
+    mode: CrawlRunMode,
+    product: &linked_data::Product,
+) -> Result<Option<Priority>> {
+    if mode == CrawlRunMode::Refresh {
+        let refresh = db
+            .refresh_from_listing(
+                &product.sku,
+                product.offers.availability.db_serialize(),
+                product.offers.price.cents(),
+            )
+            .await?;
+
+        return Ok(match refresh {
+            ListingRefresh::New => Some(Priority::New),
+            ListingRefresh::Changed => Some(Priority::Stale),
+            ListingRefresh::Unchanged => None,
+        });
+    }
+
+    Ok(
+        match db
+            .is_product_stale(&product.sku, config.stale_after_hours)
+            .await?
+        {
+            None => Some(Priority::New),
+            Some(true) => Some(Priority::Stale),
+            Some(false) => Some(Priority::Fresh),
+        },
+    )
+}
+
+/// Queues the products which are [partial](ExtractedProduct::partial) or
+/// missing key fields, see [`db::Client::incomplete_products`], after the
+/// `interrupted` ones whose writes didn't complete.
+async fn queue_incomplete_products(
+    db: db::Client,
+    config: Config,
+    interrupted: Vec<UnfinishedProduct>,
+    queue: Arc<PriorityQueue<QueuedProduct>>,
+) -> Result<()> {
+    let saq_codes = match db
+        .incomplete_products(!config.skips(SkippableField::Description))
+        .await
+    {
+        Ok(saq_codes) => saq_codes,
+        Err(err) => {
+            queue.close();
+            return Err(err);
+        }
+    };
+
+    info!(products = saq_codes.len(), "queueing incomplete products");
+
+    let interrupted_skus = interrupted
+        .iter()
+        .map(|product| product.sku.clone())
+        .collect::<HashSet<_>>();
+
+    for product in interrupted {
+        let product = QueuedProduct {
+            url: product.url,
+            sku: product.sku,
+            listing: None,
+        };
+
+        if let Err(err) = queue.push(Priority::Stale, product).await {
+            return Err(Report::from(err));
+        }
+    }
+
+    for saq_code in saq_codes {
+        if interrupted_skus.contains(&saq_code) {
+            continue;
+        }
+
+        let product = QueuedProduct {
+            url: config.site.product_url(&saq_code),
+            sku: saq_code,
+            listing: None,
+        };
+
+        if let Err(err) = queue.push(Priority::Stale, product).await {
+            return Err(Report::from(err));
+        }
+    }
+
+    queue.close();
+    Ok(())
+}
+
+/// Fetches and extracts a single product, releasing `permit` once the request
+/// completes, and prepares it to be [persisted](Persister::enqueue).
+async fn crawl_product(
+    client: &saq::Client,
+    persister: &Persister,
+    limit: &AdaptiveLimit,
+    parses: &Semaphore,
+    product: &QueuedProduct,
+    permit: Permit<'_>,
+) -> Result<ExtractedProduct> {
+    let stats = &persister.stats;
+
+    let start = Instant::now();
+    let result = client.product(&product.url).await;
+
+    match &result {
+        Ok(page) => {
+            stats.record_fetch(Some(page.status), page.elapsed);
+            limit.record(Outcome::Success(page.elapsed));
+        }
+        Err(err) => {
+            stats.record_fetch(errors::status(err), start.elapsed());
+            if errors::is_overloaded(err) {
+                limit.record(Outcome::Overloaded);
+            }
+        }
+    }
+
+    // Only requests count against the limit
+    drop(permit);
+
+    let page = result?;
+
+    let parse_permit = parses.acquire().await?;
+    let start = Instant::now();
+    let extracted = page.extract();
+    stats.record_parse(start.elapsed());
+    drop(parse_permit);
+
+    let mut extracted = extracted?;
+    if let Some(listing) = &product.listing {
+        let listing = listing.product(&product.sku);
+        match extracted.get_ld_product_mut() {
+            Ok(page) => persister.reconcile(&listing, page).await?,
+            Err(_) => extracted.fall_back_to_listing(|| listing),
+        }
+    }
+    if extracted.partial {
+        warn!(url = %product.url, "missing product linked data, using catalog listing");
+    }
+
+    persister.prepare(extracted).await
+}
+
+/// A product waiting in [`Persister::batch`], along with the URL it was
+/// crawled from.
+struct PendingProduct {
+    /// The product page URL.
+    url: String,
+    /// The product, as returned by [`Persister::prepare`].
+    product: ExtractedProduct,
+}
+
+/// Persists the products crawled by the worker tasks in batches, invoking the
+/// per-product [`Hooks`] and applying the [error policy](Config::error_policy)
+/// along the way.
+struct Persister {
+    /// The database to persist products to.
+    db: db::Client,
+    /// The crawl's configuration.
+    config: Config,
+    /// The crawl errors are recorded against.
+    crawl_run_id: i64,
+    /// Lookup table `id`s seen during the crawl.
+    lookups: LookupCache,
+    /// The hooks to invoke for each product.
+    hooks: Arc<dyn Hooks>,
+    /// The crawl's statistics.
+    stats: Arc<Stats>,
+    /// Products waiting to be persisted.
+    batch: Batch<PendingProduct>,
+    /// The crawl's progress, see [`checkpoint`].
+    checkpoint: Arc<Checkpoint>,
+}
+
+impl Persister {
+    /// Runs the hooks preceding persistence, returning the (possibly modified)
+    /// product.
+    async fn prepare(&self, mut product: ExtractedProduct) -> Result<ExtractedProduct> {
+        self.hooks.after_extract(&mut product).await?;
+        self.hooks.before_persist(&product).await?;
+
+        Ok(product)
+    }
+
+    /// Records the fields of the product `page` which differ from its
+    /// `listing`, and [resolves](provenance::resolve) them according to
+    /// [`Config::field_precedence`].
+    async fn reconcile(
+        &self,
+        listing: &linked_data::Product,
+        page: &mut linked_data::Product,
+    ) -> Result<()> {
+        let precedence = self.config.field_precedence;
+
+        for mismatch in provenance::mismatches(listing, page) {
+            warn!(
+                sku = %page.sku,
+                field = mismatch.field,
+                listing = %mismatch.listing,
+                product_page = %mismatch.product_page,
+                ?precedence,
+                "listing and product page disagree"
+            );
+            self.db
+                .record_field_mismatch(self.crawl_run_id, &page.sku, &mismatch, precedence)
+                .await?;
+        }
+
+        provenance::resolve(precedence, listing, page);
+
+        Ok(())
+    }
+
+    /// Adds `product` to the current batch, persisting the batch if it is now
+    /// full.
+    ///
+    /// Only returns an error if the crawl should be aborted.
+    async fn enqueue(&self, worker: usize, url: &str, product: ExtractedProduct) -> Result<()> {
+        let pending = PendingProduct {
+            url: url.to_string(),
+            product,
+        };
+
+        match self.batch.push(pending) {
+            Some(batch) => self.flush(worker, batch).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Persists `batch` (see [`persist_products`]), then invokes
+    /// [`Hooks::after_persist`] for each product.
+    ///
+    /// The batch is [journaled](db::Client::journal_persists) beforehand, and
+    /// products are only cleared from the journal once persisted in full.
+    ///
+    /// If the batch can't be persisted as a whole, its products are persisted
+    /// one at a time so that errors are attributed to the right product.
+    /// Failing products are handled according to the error policy, except that
+    /// they're skipped rather than retried since their pages are long gone.
+    ///
+    /// Only returns an error if the crawl should be aborted.
+    async fn flush(&self, worker: usize, batch: Vec<PendingProduct>) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let products = batch.iter().map(|p| &p.product).collect::<Vec<_>>();
+
+        let journal = batch
+            .iter()
+            .map(|p| UnfinishedProduct {
+                url: p.url.clone(),
+                sku: p.product.detailed_info.saq_code.clone(),
+            })
+            .collect::<Vec<_>>();
+        self.db
+            .journal_persists(self.crawl_run_id, &journal)
+            .await?;
+
+        let start = Instant::now();
+        let results = match persist_products(&self.db, &self.config, &self.lookups, &products).await
+        {
+            Ok(product_ids) => {
+                let elapsed = start.elapsed() / batch.len() as u32;
+                for _ in &product_ids {
+                    self.stats.record_persist(worker, elapsed);
+                }
+                product_ids.into_iter().map(Ok).collect()
+            }
+            Err(err) => {
+                warn!(
+                    ?err,
+                    products = batch.len(),
+                    "failed to persist batch, retrying products one at a time"
+                );
+
+                let mut results = Vec::with_capacity(batch.len());
+                for product in &products {
+                    let start = Instant::now();
+                    let result =
+                        persist_product(&self.db, &self.config, &self.lookups, product).await;
+                    if result.is_ok() {
+                        self.stats.record_persist(worker, start.elapsed());
+                    }
+                    results.push(result);
+                }
+                results
+            }
+        };
+
+        let persisted = batch
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(pending, _)| pending.url.as_str());
+        if let Err(err) = self.db.clear_persists(persisted).await {
+            warn!(?err, "failed to clear persisted products from the journal");
+        }
+
+        for (pending, result) in batch.iter().zip(results) {
+            let result = match result {
+                Ok(product_id) => self.hooks.after_persist(&pending.product, product_id).await,
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = result {
+                if self.fail(worker, &pending.url, &err, false).await == ErrorAction::Abort {
+                    return Err(err);
+                }
+            }
+            self.checkpoint.done(&pending.url);
+        }
+
+        Ok(())
+    }
+
+    /// Records `err`, encountered while crawling `url`, and returns what to do
+    /// about it according to [`Config::error_policy`]. Products which would
+    /// otherwise be retried are skipped unless `can_retry` is set.
+    async fn fail(&self, worker: usize, url: &str, err: &Report, can_retry: bool) -> ErrorAction {
+        let error_class = ErrorClass::classify(err);
+        let action = match self.config.error_policy.action(error_class) {
+            ErrorAction::Retry if !can_retry => ErrorAction::Skip,
+            action => action,
+        };
+
+        if action != ErrorAction::Retry {
+            self.stats.record_failure(worker);
+        }
+
+        if let Err(record_err) =
+            record_failure(&self.db, &self.config, self.crawl_run_id, url, err, action).await
+        {
+            warn!(%url, ?record_err, "failed to record crawl error");
+        }
+
+        if action == ErrorAction::Skip {
+            warn!(%url, ?error_class, ?err, "skipping product");
+        }
+
+        action
+    }
+}
+
+/// Records a failure to crawl `url` in the `crawl_errors` table, along with
+/// the `action` taken as a result.
+///
+/// If the error wasn't retried, is [page-specific](ErrorClass::is_page_specific)
+/// and the page has now failed in [`Config::skip_list_threshold`] different
+/// crawls, it is added to the skip list for [`Config::skip_list_expiry_days`].
+async fn record_failure(
+    db: &db::Client,
+    config: &Config,
+    crawl_run_id: i64,
+    url: &str,
+    err: &Report,
+    action: ErrorAction,
+) -> Result<()> {
+    let error_class = ErrorClass::classify(err);
+
+    db.record_crawl_error(crawl_run_id, url, error_class, action, &format!("{err:#}"))
+        .await?;
+
+    if action == ErrorAction::Retry || !error_class.is_page_specific() {
+        return Ok(());
+    }
+
+    let failed_runs = db.count_failed_crawl_runs(url).await?;
+    if failed_runs >= i64::from(config.skip_list_threshold) {
+        db.add_to_skip_list(url, error_class, config.skip_list_expiry_days)
+            .await?;
+        warn!(%url, ?error_class, failed_runs, "added page to skip list");
+    }
+
+    Ok(())
+}
+
+/// Whether two GTINs (UPCs being a subset of them) refer to the same product.
+///
+/// GTINs of different lengths are padded with leading zeroes when compared
+/// (i.e. a 12-digit UPC-A matches the same code as a GTIN-13 or GTIN-14).
+fn same_gtin(a: &str, b: &str) -> bool {
+    a.trim_start_matches('0') == b.trim_start_matches('0')
+}
+
+/// Collects the identifiers found in both the detailed info (`upc_code`) and
+/// the JSON-LD of a product.
+///
+/// GTINs that don't match the UPC code are logged but still returned, since
+/// it's not clear which one is right.
+fn product_identifiers<'a>(
+    upc_code: Option<&'a str>,
+    ld_product: &'a linked_data::Product,
+) -> Vec<(IdentifierScheme, &'a str)> {
+    let mut identifiers = vec![];
+
+    if let Some(upc_code) = upc_code {
+        identifiers.push((IdentifierScheme::Upc, upc_code));
+    }
+
+    if let Some(gtin) = &ld_product.gtin {
+        identifiers.push((IdentifierScheme::Gtin, gtin.as_str()));
+    }
+
+    if let Some(gtin13) = &ld_product.gtin13 {
+        identifiers.push((IdentifierScheme::Gtin13, gtin13.as_str()));
+    }
+
+    if let Some(upc_code) = upc_code {
+        for (scheme, gtin) in &identifiers[1..] {
+            if !same_gtin(upc_code, gtin) {
+                warn!(sku = %ld_product.sku, upc_code, ?scheme, gtin, "GTIN does not match UPC code");
+            }
+        }
+    }
+
+    if let Some(mpn) = &ld_product.mpn {
+        identifiers.push((IdentifierScheme::Mpn, mpn.as_str()));
+    }
+
+    identifiers
+}
+
 /// Ensures the given [`ExtractedProduct`](crate::saq::ExtractedProduct) is present
 /// and up to date in the database, updating all the necessary relations along
 /// the way.
-async fn persist_product(db: &db::Client, product: ExtractedProduct) -> Result<()> {
+///
+/// Fields listed in [`Config::skip_fields`] are persisted as `NULL`.
+///
+/// Returns the product's `id` in the `products` table.
+async fn persist_product(
+    db: &db::Client,
+    config: &Config,
+    lookups: &LookupCache,
+    product: &ExtractedProduct,
+) -> Result<i64> {
+    let product_ids = persist_products(db, config, lookups, &[product]).await?;
+
+    Ok(product_ids[0])
+}
+
+/// Same as [`persist_product`] for multiple products, upserting their rows in
+/// the `products` table along with their relations [at once](db::Client::upsert_products).
+///
+/// Returns the products' `id`s in the same order.
+async fn persist_products(
+    db: &db::Client,
+    config: &Config,
+    lookups: &LookupCache,
+    products: &[&ExtractedProduct],
+) -> Result<Vec<i64>> {
+    let description_hashes = products
+        .iter()
+        .map(|product| description_hash(product))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Lookup rows are upserted beforehand, as they are shared between
+    // products and the transaction would otherwise hold them up
+    let mut rows = Vec::with_capacity(products.len());
+    for (product, description_hash) in products.iter().zip(&description_hashes) {
+        let fields =
+            product_upsert_fields(db, config, lookups, product, description_hash.as_deref())
+                .await?;
+        let relations = product_relations(db, &config.site, lookups, product).await?;
+        rows.push((fields, relations));
+    }
+
+    db.upsert_products(rows).await
+}
+
+/// Hashes `product`'s description, unless it is [partial](ExtractedProduct::partial)
+/// and therefore has none.
+fn description_hash(product: &ExtractedProduct) -> Result<Option<String>> {
+    if product.partial {
+        return Ok(None);
+    }
+
+    Ok(Some(saq::text::content_hash(
+        &product.get_ld_product()?.description,
+    )))
+}
+
+/// Upserts the lookup rows referenced by `product` and collects everything
+/// needed to upsert its row in the `products` table.
+async fn product_upsert_fields<'a>(
+    db: &db::Client,
+    config: &Config,
+    lookups: &LookupCache,
+    product: &'a ExtractedProduct,
+    description_hash: Option<&'a str>,
+) -> Result<ProductUpsertFields<'a>> {
     let producer_id = match &product.detailed_info.producer {
-        Some(name) => Some(db.upsert_producer(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("producers", name, || db.upsert_producer(name))
+                .await?,
+        ),
         None => None,
     };
 
     let promoting_agent_id = match &product.detailed_info.promoting_agent {
-        Some(name) => Some(db.upsert_promoting_agent(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("promoting_agents", name, || db.upsert_promoting_agent(name))
+                .await?,
+        ),
         None => None,
     };
 
     let color_id = match &product.detailed_info.color {
-        Some(name) => Some(db.upsert_color(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("colors", name, || db.upsert_color(name))
+                .await?,
+        ),
         None => None,
     };
 
     let region_id = match &product.detailed_info.region {
-        Some(name) => Some(db.upsert_region(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("regions", name, || db.upsert_region(name))
+                .await?,
+        ),
         None => None,
     };
 
     let country_id = match &product.detailed_info.country {
-        Some(name) => Some(db.upsert_country(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("countries", name, || db.upsert_country(name))
+                .await?,
+        ),
         None => None,
     };
 
     let regulated_designation_id = match &product.detailed_info.regulated_designation {
-        Some(name) => Some(db.upsert_regulated_designation(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("regulated_designations", name, || {
+                    db.upsert_regulated_designation(name)
+                })
+                .await?,
+        ),
         None => None,
     };
 
     let designation_of_origin_id = match &product.detailed_info.designation_of_origin {
-        Some(name) => Some(db.upsert_designation_of_origin(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("designations_of_origin", name, || {
+                    db.upsert_designation_of_origin(name)
+                })
+                .await?,
+        ),
         None => None,
     };
 
     let classification_id = match &product.detailed_info.classification {
-        Some(name) => Some(db.upsert_classification(name).await?),
+        Some(name) => Some(
+            lookups
+                .get_or_upsert("classifications", name, || db.upsert_classification(name))
+                .await?,
+        ),
         None => None,
     };
 
     let ld_product = product.get_ld_product()?;
 
+    let brand_id = match &ld_product.brand {
+        Some(brand) => Some(
+            lookups
+                .get_or_upsert("brands", brand.name(), || db.upsert_brand(brand.name()))
+                .await?,
+        ),
+        None => None,
+    };
+
+    let manufacturer_id = match &ld_product.manufacturer {
+        Some(manufacturer) => Some(
+            lookups
+                .get_or_upsert("manufacturers", manufacturer.name(), || {
+                    db.upsert_manufacturer(manufacturer.name())
+                })
+                .await?,
+        ),
+        None => None,
+    };
+
+    let seller_id = match &ld_product.offers.seller {
+        Some(seller) => Some(
+            lookups
+                .get_or_upsert("sellers", seller.name(), || db.upsert_seller(seller.name()))
+                .await?,
+        ),
+        None => None,
+    };
+
+    if ld_product.offers.price_valid_until.is_some()
+        && ld_product.offers.price_valid_until_date().is_none()
+    {
+        warn!(
+            sku = %ld_product.sku,
+            price_valid_until = ?ld_product.offers.price_valid_until,
+            "ignoring unrecognized price validity date"
+        );
+    }
+
     let size = product.detailed_info.size.as_ref();
 
     let sugar = product.detailed_info.sugar_content.as_ref();
 
     let new_product = ProductUpsertFields {
         saq_code: &product.detailed_info.saq_code,
+        seller_id,
         upc_code: product.detailed_info.upc_code.as_deref(),
         name: &ld_product.name,
-        description: &ld_product.description,
-        image_url: &ld_product.image,
+        partial: product.partial,
+        description: (!config.skips(SkippableField::Description) && !product.partial)
+            .then_some(ld_product.description.as_str()),
+        description_hash,
+        image_url: (!config.skips(SkippableField::ImageUrl)).then_some(ld_product.image.as_str()),
         availability: ld_product.offers.availability.db_serialize(),
+        brand_id,
         item_condition: ld_product.offers.item_condition.db_serialize(),
-        price_cad: &ld_product.offers.price,
+        manufacturer_id,
+        price_cents: ld_product.offers.price.cents(),
+        price_valid_until: ld_product.offers.price_valid_until_date(),
         abv_percentage: product.detailed_info.abv_percentage,
         container_count: size.as_ref().map(|s| s.container_count),
         container_milliliters: size.as_ref().map(|s| s.container_milliliters),
@@ -170,39 +1480,772 @@ async fn persist_product(db: &db::Client, product: ExtractedProduct) -> Result<(
         regulated_designation_id,
         designation_of_origin_id,
         classification_id,
+        detailed_info_source: Some(product.detailed_info_source.db_serialize()),
+        rating_value: ld_product
+            .aggregate_rating
+            .as_ref()
+            .map(|rating| rating.rating_value),
+        rating_count: ld_product
+            .aggregate_rating
+            .as_ref()
+            .and_then(|rating| rating.count()),
+        regular_price_cents: regular_price_cents(product)?,
     };
 
-    let product_id = db.upsert_product(new_product).await?;
+    Ok(new_product)
+}
+
+/// The regular price of `product` if it is on sale, ignoring regular prices
+/// that aren't above the price it sells at.
+fn regular_price_cents(product: &ExtractedProduct) -> Result<Option<i64>> {
+    let price_cents = product.get_ld_product()?.offers.price.cents();
+
+    Ok(
+        match product.regular_price.as_ref().map(|price| price.cents()) {
+            Some(regular_price_cents) if regular_price_cents > price_cents => {
+                Some(regular_price_cents)
+            }
+            Some(regular_price_cents) => {
+                warn!(
+                    sku = %product.detailed_info.saq_code,
+                    price_cents,
+                    regular_price_cents,
+                    "ignoring regular price that isn't above the price"
+                );
+                None
+            }
+            None => None,
+        },
+    )
+}
+
+/// Upserts the lookup rows referenced by `product` and collects everything
+/// needed to update the rows related to it (special features, grape
+/// varieties, food pairings, tasting notes, reviews, categories and
+/// identifiers).
+async fn product_relations<'a>(
+    db: &db::Client,
+    site: &SiteProfile,
+    lookups: &LookupCache,
+    product: &'a ExtractedProduct,
+) -> Result<ProductRelations<'a>> {
+    let ld_product = product.get_ld_product()?;
 
     let mut special_feature_ids = vec![];
     for special_feature in product.detailed_info.special_features.iter().flatten() {
-        let special_feature_id = db.upsert_special_feature(special_feature).await?;
+        let special_feature_id = lookups
+            .get_or_upsert("special_features", special_feature, || {
+                db.upsert_special_feature(special_feature)
+            })
+            .await?;
         special_feature_ids.push(special_feature_id);
     }
 
-    db.ensure_product_special_features(product_id, special_feature_ids)
-        .await?;
-
     let mut grape_variety_ids_and_percentages = vec![];
     for variety in product.detailed_info.grape_varieties.iter().flatten() {
-        let variety_id = db.upsert_grape_variety(&variety.name).await?;
+        let variety_id = lookups
+            .get_or_upsert("grape_varieties", &variety.name, || {
+                db.upsert_grape_variety(&variety.name)
+            })
+            .await?;
         grape_variety_ids_and_percentages.push((variety_id, variety.percentage));
     }
 
-    db.ensure_product_grape_varieties(product_id, grape_variety_ids_and_percentages)
-        .await?;
+    let mut food_pairing_ids = vec![];
+    for food_pairing in &product.food_pairings {
+        let food_pairing_id = lookups
+            .get_or_upsert("food_pairings", food_pairing, || {
+                db.upsert_food_pairing(food_pairing)
+            })
+            .await?;
+        food_pairing_ids.push(food_pairing_id);
+    }
 
     let mut category_ids = vec![];
-    for category in product.extract_categories()? {
+    for category in product.extract_categories(site)? {
         let parent_category_id = category_ids.last();
-        let category_id = db
-            .upsert_category(&category.name, &category.url, parent_category_id.cloned())
+        let key = format!(
+            "{}\n{}\n{:?}",
+            category.url, category.name, parent_category_id
+        );
+        let category_id = lookups
+            .get_or_upsert("categories", &key, || {
+                db.upsert_category(&category.name, &category.url, parent_category_id.cloned())
+            })
             .await?;
         category_ids.push(category_id);
     }
 
-    db.ensure_product_categories(product_id, category_ids)
+    Ok(ProductRelations {
+        special_feature_ids,
+        grape_variety_ids_and_percentages,
+        food_pairing_ids,
+        tasting_notes: product.tasting_notes.as_ref(),
+        // Partial products' JSON-LD comes from their listing, which has no
+        // reviews
+        reviews: (!product.partial).then_some(ld_product.reviews.as_slice()),
+        category_ids,
+        identifiers: product_identifiers(product.detailed_info.upc_code.as_deref(), ld_product),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use sqlx::Row;
+
+    /// Extracts the product from one of the pages in `fixtures/`.
+    fn extract_fixture(html: &str) -> ExtractedProduct {
+        saq::parse_product_page(html).unwrap()
+    }
+
+    /// Wine fixture with a producer, grape varieties, special features, etc.
+    fn wine() -> ExtractedProduct {
+        extract_fixture(include_str!("../../fixtures/product_wine.html"))
+    }
+
+    /// Returns the `(name, percentage)` of each of the product's grape varieties.
+    async fn grape_varieties(db: &TestDb, saq_code: &str) -> Result<Vec<(String, Option<i64>)>> {
+        let rows = sqlx::query(
+            r#"select gv.name, pgv.percentage from product_grape_varieties pgv
+            join grape_varieties gv on gv.id = pgv.grape_variety_id
+            join products p on p.id = pgv.product_id
+            where p.saq_code = ?1 order by gv.name"#,
+        )
+        .bind(saq_code)
+        .fetch_all(db.pool())
         .await?;
 
-    Ok(())
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Returns the names of the product's special features.
+    async fn special_features(db: &TestDb, saq_code: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"select sf.name from product_special_features psf
+            join special_features sf on sf.id = psf.special_feature_id
+            join products p on p.id = psf.product_id
+            where p.saq_code = ?1 order by sf.name"#,
+        )
+        .bind(saq_code)
+        .fetch_all(db.pool())
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Returns the names of the product's food pairings, in the order they were
+    /// added.
+    async fn food_pairings(db: &TestDb, saq_code: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"select fp.name from product_food_pairings pfp
+            join food_pairings fp on fp.id = pfp.food_pairing_id
+            join products p on p.id = pfp.product_id
+            where p.saq_code = ?1 order by pfp.id"#,
+        )
+        .bind(saq_code)
+        .fetch_all(db.pool())
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Returns the `(taste tag, aromas)` of the product's tasting notes.
+    async fn tasting_notes(db: &TestDb, saq_code: &str) -> Result<Option<(String, String)>> {
+        let row = sqlx::query(
+            r#"select tn.taste_tag, tn.aromas from tasting_notes tn
+            join products p on p.id = tn.product_id
+            where p.saq_code = ?1"#,
+        )
+        .bind(saq_code)
+        .fetch_optional(db.pool())
+        .await?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1))))
+    }
+
+    /// Returns the `(name, parent name)` of each of the product's categories.
+    async fn categories(db: &TestDb, saq_code: &str) -> Result<Vec<(String, Option<String>)>> {
+        let rows = sqlx::query(
+            r#"select c.name, parent.name from product_categories pc
+            join categories c on c.id = pc.category_id
+            left join categories parent on parent.id = c.parent_category_id
+            join products p on p.id = pc.product_id
+            where p.saq_code = ?1 order by c.id"#,
+        )
+        .bind(saq_code)
+        .fetch_all(db.pool())
+        .await?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    #[tokio::test]
+    async fn test_persist_product() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &wine()).await?;
+
+        let product = sqlx::query(
+            r#"select p.name, p.upc_code, p.description, p.image_url, p.availability,
+                p.item_condition, p.price_cents, p.abv_percentage, p.container_count,
+                p.container_milliliters, p.product_of_quebec, p.sugar_content_equality,
+                p.sugar_content_grams_per_liter, producers.name, promoting_agents.name,
+                colors.name, regions.name, countries.name, regulated_designations.name,
+                designations_of_origin.name, p.classification_id
+            from products p
+            join producers on producers.id = p.producer_id
+            join promoting_agents on promoting_agents.id = p.promoting_agent_id
+            join colors on colors.id = p.color_id
+            join regions on regions.id = p.region_id
+            join countries on countries.id = p.country_id
+            join regulated_designations on regulated_designations.id = p.regulated_designation_id
+            join designations_of_origin on designations_of_origin.id = p.designation_of_origin_id
+            where p.saq_code = '13191791'"#,
+        )
+        .fetch_one(db.pool())
+        .await?;
+
+        assert_eq!(
+            "Château Maris Minervois La Livinière 2019",
+            product.get::<String, _>(0)
+        );
+        assert_eq!(Some("03760089460186"), product.get::<Option<&str>, _>(1));
+        assert!(product.get::<Option<&str>, _>(2).is_some());
+        assert!(product.get::<Option<&str>, _>(3).is_some());
+        assert_eq!("in_stock", product.get::<&str, _>(4));
+        assert_eq!("new", product.get::<&str, _>(5));
+        assert_eq!(2995, product.get::<i64, _>(6));
+        assert_eq!(14.5, product.get::<f64, _>(7));
+        assert_eq!(1, product.get::<i64, _>(8));
+        assert_eq!(750, product.get::<i64, _>(9));
+        assert_eq!(None, product.get::<Option<&str>, _>(10));
+        assert_eq!(Some("<"), product.get::<Option<&str>, _>(11));
+        assert_eq!(Some(1.2), product.get::<Option<f32>, _>(12));
+        assert_eq!("Château Maris", product.get::<&str, _>(13));
+        assert_eq!("Vins Balthazard Inc.", product.get::<&str, _>(14));
+        assert_eq!("Red", product.get::<&str, _>(15));
+        assert_eq!("Languedoc-Roussillon", product.get::<&str, _>(16));
+        assert_eq!("France", product.get::<&str, _>(17));
+        assert_eq!(
+            "Appellation origine contrôlée (AOC)",
+            product.get::<&str, _>(18)
+        );
+        assert_eq!("Minervois-La Livinière", product.get::<&str, _>(19));
+        assert_eq!(None, product.get::<Option<i64>, _>(20));
+
+        assert_eq!(
+            vec![
+                ("Carignan".to_string(), Some(10)),
+                ("Grenache".to_string(), Some(30)),
+                ("Syrah".to_string(), Some(60)),
+            ],
+            grape_varieties(&db, "13191791").await?
+        );
+
+        assert_eq!(
+            vec!["Biodynamic wine", "Organic product"],
+            special_features(&db, "13191791").await?
+        );
+
+        assert_eq!(
+            vec!["Game", "Grilled red meat", "Hard cheeses"],
+            food_pairings(&db, "13191791").await?
+        );
+        assert_eq!(
+            Some((
+                "Aromatic and robust".to_string(),
+                r#"["Black fruit","Garrigue","Spices"]"#.to_string()
+            )),
+            tasting_notes(&db, "13191791").await?
+        );
+
+        assert_eq!(
+            vec![
+                ("Wine".to_string(), None),
+                ("Red wine".to_string(), Some("Wine".to_string())),
+            ],
+            categories(&db, "13191791").await?
+        );
+
+        let identifiers = sqlx::query(
+            r#"select pi.scheme, pi.value from product_identifiers pi
+            join products p on p.id = pi.product_id
+            where p.saq_code = '13191791' order by pi.scheme"#,
+        )
+        .fetch_all(db.pool())
+        .await?
+        .iter()
+        .map(|row| (row.get::<String, _>(0), row.get::<String, _>(1)))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                ("gtin13".to_string(), "3760089460186".to_string()),
+                ("upc".to_string(), "03760089460186".to_string()),
+            ],
+            identifiers
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_product_description_changes() -> Result<()> {
+        let db = TestDb::new().await?;
+        let since = chrono::Utc::now() - chrono::Duration::minutes(1);
+        let html = include_str!("../../fixtures/product_wine.html");
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &wine()).await?;
+        assert!(db.description_changes_since(since).await?.is_empty());
+
+        // Cosmetic edits don't count as changes
+        let reworded = html.replace("A deep, concentrated red", "A  DEEP, concentrated red");
+        persist_product(
+            &db,
+            &Config::default(),
+            &LookupCache::default(),
+            &extract_fixture(&reworded),
+        )
+        .await?;
+        assert!(db.description_changes_since(since).await?.is_empty());
+
+        let rewritten = html.replace("A deep, concentrated red", "A bright, lively red");
+        persist_product(
+            &db,
+            &Config::default(),
+            &LookupCache::default(),
+            &extract_fixture(&rewritten),
+        )
+        .await?;
+        let changes = db.description_changes_since(since).await?;
+        assert_eq!(
+            vec!["13191791"],
+            changes
+                .iter()
+                .map(|c| c.saq_code.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_partial_product() -> Result<()> {
+        let db = TestDb::new().await?;
+        let since = chrono::Utc::now() - chrono::Duration::minutes(1);
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &wine()).await?;
+
+        let mut partial = wine();
+        let mut listing = partial.get_ld_product()?.clone();
+        listing.description = String::new();
+        listing.name = "From the listing".to_string();
+        partial
+            .linked_data
+            .retain(|ld| !matches!(ld, linked_data::LinkedData::Product(_)));
+        partial.fall_back_to_listing(|| listing);
+        assert!(partial.partial);
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &partial).await?;
+
+        let product = sqlx::query(
+            "select name, description, partial from products where saq_code = '13191791'",
+        )
+        .fetch_one(db.pool())
+        .await?;
+        assert_eq!("From the listing", product.get::<&str, _>(0));
+        assert!(product.get::<Option<&str>, _>(1).is_some());
+        assert!(product.get::<bool, _>(2));
+
+        // Completing the product clears the flag without counting as a change
+        persist_product(&db, &Config::default(), &LookupCache::default(), &wine()).await?;
+        let partial: bool =
+            sqlx::query_scalar("select partial from products where saq_code = '13191791'")
+                .fetch_one(db.pool())
+                .await?;
+        assert!(!partial);
+        assert!(db.description_changes_since(since).await?.is_empty());
+
+        Ok(())
+    }
+
+    /// Records the products it sees, optionally rejecting them.
+    #[derive(Default)]
+    struct RecordingHooks {
+        /// Whether `before_persist` should fail.
+        reject: bool,
+        /// `(saq_code, product_id)` pairs passed to `after_persist`.
+        persisted: std::sync::Mutex<Vec<(String, i64)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Hooks for RecordingHooks {
+        async fn after_extract(&self, product: &mut ExtractedProduct) -> Result<()> {
+            product.detailed_info.producer = Some("Hooked".to_string());
+            Ok(())
+        }
+
+        async fn before_persist(&self, _product: &ExtractedProduct) -> Result<()> {
+            match self.reject {
+                true => Err(color_eyre::eyre::eyre!("rejected")),
+                false => Ok(()),
+            }
+        }
+
+        async fn after_persist(&self, product: &ExtractedProduct, product_id: i64) -> Result<()> {
+            self.persisted
+                .lock()
+                .unwrap()
+                .push((product.detailed_info.saq_code.clone(), product_id));
+            Ok(())
+        }
+    }
+
+    /// Creates a [`Persister`] writing batches of `batch_size` products to `db`.
+    async fn test_persister(
+        db: &TestDb,
+        hooks: Arc<dyn Hooks>,
+        batch_size: usize,
+    ) -> Result<Persister> {
+        Ok(Persister {
+            db: (*db).clone(),
+            config: Config::default(),
+            crawl_run_id: db.start_crawl_run(CrawlRunMode::Full).await?,
+            lookups: LookupCache::default(),
+            hooks,
+            stats: Arc::new(Stats::new(1)),
+            batch: Batch::new(batch_size),
+            checkpoint: Arc::new(Checkpoint::new(&CrawlState::new(crawl_listing(
+                &Config::default(),
+                CrawlRunMode::Full,
+            )?))),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_persister_hooks() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let rejecting = Arc::new(RecordingHooks {
+            reject: true,
+            ..RecordingHooks::default()
+        });
+        let persister = test_persister(&db, rejecting, 1).await?;
+        assert!(persister.prepare(wine()).await.is_err());
+
+        let hooks = Arc::new(RecordingHooks::default());
+        let persister = test_persister(&db, hooks.clone(), 1).await?;
+        let product = persister.prepare(wine()).await?;
+        persister
+            .enqueue(0, "https://www.saq.com/en/13191791", product)
+            .await?;
+        assert_eq!(1, persister.stats.products());
+
+        let (saq_code, product_id) = hooks.persisted.lock().unwrap()[0].clone();
+        assert_eq!("13191791", saq_code);
+
+        let producer: String = sqlx::query_scalar(
+            "select producers.name from products join producers on producers.id = producer_id where products.id = ?1",
+        )
+        .bind(product_id)
+        .fetch_one(db.pool())
+        .await?;
+        assert_eq!("Hooked", producer);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persister_batches() -> Result<()> {
+        let db = TestDb::new().await?;
+        let hooks = Arc::new(RecordingHooks::default());
+        let persister = test_persister(&db, hooks.clone(), 2).await?;
+
+        let beer = extract_fixture(include_str!("../../fixtures/product_beer.html"));
+        let spirit = extract_fixture(include_str!("../../fixtures/product_spirit.html"));
+
+        persister
+            .enqueue(0, "https://www.saq.com/en/wine", wine())
+            .await?;
+        assert!(hooks.persisted.lock().unwrap().is_empty());
+
+        persister
+            .enqueue(0, "https://www.saq.com/en/beer", beer)
+            .await?;
+        assert_eq!(2, hooks.persisted.lock().unwrap().len());
+
+        persister
+            .enqueue(0, "https://www.saq.com/en/spirit", spirit)
+            .await?;
+        persister.flush(0, persister.batch.take()).await?;
+        assert_eq!(3, persister.stats.products());
+
+        let product_count: i64 = sqlx::query_scalar("select count(*) from products")
+            .fetch_one(db.pool())
+            .await?;
+        assert_eq!(3, product_count);
+
+        assert_eq!(
+            3,
+            grape_varieties(&db, "13191791").await?.len(),
+            "relations are persisted along with batches"
+        );
+        assert!(
+            db.interrupted_persists().await?.is_empty(),
+            "persisted products are cleared from the journal"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_products_atomically() -> Result<()> {
+        let db = TestDb::new().await?;
+        let beer = extract_fixture(include_str!("../../fixtures/product_beer.html"));
+        let wine = wine();
+
+        // Grape varieties which don't exist make the wine's relations fail
+        let lookups = LookupCache::default();
+        for variety in wine.detailed_info.grape_varieties.iter().flatten() {
+            lookups
+                .get_or_upsert("grape_varieties", &variety.name, || async { Ok(999) })
+                .await?;
+        }
+
+        assert!(
+            persist_products(&db, &Config::default(), &lookups, &[&beer, &wine])
+                .await
+                .is_err()
+        );
+
+        let product_count: i64 = sqlx::query_scalar("select count(*) from products")
+            .fetch_one(db.pool())
+            .await?;
+        assert_eq!(0, product_count, "nothing from a failed batch is persisted");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crawl_listing() -> Result<()> {
+        let mut config = Config {
+            max_pages: Some(10),
+            ..Config::default()
+        };
+        let full = crawl_listing(&config, CrawlRunMode::Full)?;
+        assert_eq!(config.site.catalog_url(), full.url);
+        assert_eq!(Some(10), full.max_pages);
+        assert_eq!(
+            Some(config.new_arrivals_pages),
+            crawl_listing(&config, CrawlRunMode::NewArrivals)?.max_pages
+        );
+
+        // A crawl of a category can't be resumed as one of the whole catalog
+        config.category = Some("wine/red-wine".to_string());
+        let category = crawl_listing(&config, CrawlRunMode::Full)?;
+        assert_eq!(config.site.category_url("wine/red-wine")?, category.url);
+        assert_ne!(full, category);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_gtin() {
+        assert!(same_gtin("03760089460186", "3760089460186"));
+        assert!(same_gtin("012345678905", "0012345678905"));
+        assert!(!same_gtin("03760089460186", "3760089460193"));
+    }
+
+    #[tokio::test]
+    async fn test_persist_product_updates_relations() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &wine()).await?;
+
+        let mut updated = wine();
+        updated.detailed_info.grape_varieties = Some(vec![
+            saq::detailed_info::GrapeVariety {
+                name: "Syrah".to_string(),
+                percentage: Some(70),
+            },
+            saq::detailed_info::GrapeVariety {
+                name: "Grenache".to_string(),
+                percentage: Some(30),
+            },
+        ]);
+        updated.detailed_info.special_features = None;
+        updated.food_pairings = vec!["Lamb".to_string(), "Game".to_string()];
+        updated.tasting_notes = None;
+        persist_product(&db, &Config::default(), &LookupCache::default(), &updated).await?;
+
+        let product_count: i64 = sqlx::query_scalar("select count(*) from products")
+            .fetch_one(db.pool())
+            .await?;
+        assert_eq!(1, product_count);
+
+        assert_eq!(
+            vec![
+                ("Grenache".to_string(), Some(30)),
+                ("Syrah".to_string(), Some(70)),
+            ],
+            grape_varieties(&db, "13191791").await?
+        );
+        assert!(special_features(&db, "13191791").await?.is_empty());
+        assert_eq!(vec!["Game", "Lamb"], food_pairings(&db, "13191791").await?);
+        assert_eq!(None, tasting_notes(&db, "13191791").await?);
+        assert_eq!(2, categories(&db, "13191791").await?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_product_reviews() -> Result<()> {
+        let db = TestDb::new().await?;
+        let spirit = || extract_fixture(include_str!("../../fixtures/product_spirit.html"));
+
+        /// Returns the product's rating and its reviews' `(id, author, rating)`.
+        async fn ratings(
+            db: &TestDb,
+        ) -> Result<(Option<f64>, Option<i64>, Vec<(i64, String, f64)>)> {
+            let product = sqlx::query("select rating_value, rating_count from products")
+                .fetch_one(db.pool())
+                .await?;
+            let reviews = sqlx::query("select id, author, rating_value from reviews order by id")
+                .fetch_all(db.pool())
+                .await?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2)))
+                .collect();
+
+            Ok((product.get(0), product.get(1), reviews))
+        }
+
+        persist_product(&db, &Config::default(), &LookupCache::default(), &spirit()).await?;
+        assert_eq!(
+            (
+                Some(4.3),
+                Some(2),
+                vec![(1, "Marie".to_string(), 5.0), (2, "JF".to_string(), 3.5)]
+            ),
+            ratings(&db).await?
+        );
+
+        // The second review was taken down and the first one's rating edited
+        let mut updated = spirit();
+        match &mut updated.linked_data[2] {
+            linked_data::LinkedData::Product(product) => {
+                product.reviews.truncate(1);
+                product.reviews[0]
+                    .review_rating
+                    .as_mut()
+                    .unwrap()
+                    .rating_value = 4.0;
+            }
+            _ => panic!("expected the product's linked data"),
+        }
+        persist_product(&db, &Config::default(), &LookupCache::default(), &updated).await?;
+        assert_eq!(vec![(1, "Marie".to_string(), 4.0)], ratings(&db).await?.2);
+
+        // Listings don't have ratings or reviews, which are left as they were
+        let mut partial = spirit();
+        partial.partial = true;
+        match &mut partial.linked_data[2] {
+            linked_data::LinkedData::Product(product) => {
+                product.aggregate_rating = None;
+                product.reviews.clear();
+            }
+            _ => panic!("expected the product's linked data"),
+        }
+        persist_product(&db, &Config::default(), &LookupCache::default(), &partial).await?;
+        let (rating_value, rating_count, reviews) = ratings(&db).await?;
+        assert_eq!(
+            (Some(4.3), Some(2), 1),
+            (rating_value, rating_count, reviews.len())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_product_fixtures() -> Result<()> {
+        let db = TestDb::new().await?;
+        let config = Config {
+            skip_fields: vec![SkippableField::Description, SkippableField::ImageUrl],
+            ..Config::default()
+        };
+
+        let beer = extract_fixture(include_str!("../../fixtures/product_beer.html"));
+        persist_product(&db, &config, &LookupCache::default(), &beer).await?;
+
+        let spirit = extract_fixture(include_str!("../../fixtures/product_spirit.html"));
+        persist_product(&db, &config, &LookupCache::default(), &spirit).await?;
+
+        let rows = sqlx::query(
+            r#"select saq_code, description, image_url, container_count,
+                container_milliliters, product_of_quebec
+            from products order by saq_code"#,
+        )
+        .fetch_all(db.pool())
+        .await?;
+
+        let products = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<&str, _>(0),
+                    row.get::<Option<&str>, _>(1),
+                    row.get::<Option<&str>, _>(2),
+                    row.get::<i64, _>(3),
+                    row.get::<i64, _>(4),
+                    row.get::<Option<&str>, _>(5),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                ("00000026", None, None, 1, 1140, None),
+                ("12345678", None, None, 4, 341, Some("made_in_quebec")),
+            ],
+            products
+        );
+
+        let spirit = sqlx::query(
+            r#"select brands.name, manufacturers.name
+            from products p
+            join brands on brands.id = p.brand_id
+            join manufacturers on manufacturers.id = p.manufacturer_id
+            where p.saq_code = '00000026'"#,
+        )
+        .fetch_one(db.pool())
+        .await?;
+
+        assert_eq!("Absolut", spirit.get::<&str, _>(0));
+        assert_eq!("The Absolut Company", spirit.get::<&str, _>(1));
+
+        let beer = sqlx::query(
+            r#"select p.price_valid_until, sellers.name, p.price_cents, p.regular_price_cents
+            from products p
+            join sellers on sellers.id = p.seller_id
+            where p.saq_code = '12345678'"#,
+        )
+        .fetch_one(db.pool())
+        .await?;
+
+        assert_eq!("2026-11-01", beer.get::<&str, _>(0));
+        assert_eq!("SAQ", beer.get::<&str, _>(1));
+        assert_eq!(1725, beer.get::<i64, _>(2));
+        assert_eq!(Some(1975), beer.get::<Option<i64>, _>(3));
+
+        let spirit_regular_price: Option<i64> = sqlx::query_scalar(
+            "select regular_price_cents from products where saq_code = '00000026'",
+        )
+        .fetch_one(db.pool())
+        .await?;
+        assert_eq!(None, spirit_regular_price, "products not on sale have none");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,120 @@
+//! Reconciling the fields available from both a product's catalog listing and
+//! its product page.
+//!
+//! The two usually agree, but can briefly differ (i.e. while a sale is being
+//! rolled out). Differences are recorded in the `field_mismatches` table and
+//! resolved according to [`Config::field_precedence`](crate::config::Config::field_precedence).
+
+use crate::db::DbSerialize;
+use crate::saq::linked_data::Product;
+use color_eyre::eyre::{eyre, Result};
+use std::str::FromStr;
+
+/// Where a product field's value comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldSource {
+    /// The product's entry in the catalog.
+    Listing,
+    /// The product's own page.
+    ProductPage,
+}
+
+impl FromStr for FieldSource {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "listing" => Ok(FieldSource::Listing),
+            "product_page" => Ok(FieldSource::ProductPage),
+            _ => Err(eyre!("{:?} is not a field source", s)),
+        }
+    }
+}
+
+/// A field whose value differs between a product's listing and its page.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The name of the field, as stored in the `products` table.
+    pub field: &'static str,
+    /// The value from the listing.
+    pub listing: String,
+    /// The value from the product page.
+    pub product_page: String,
+}
+
+/// Compares the fields of `listing` which are also on the product `page`.
+pub fn mismatches(listing: &Product, page: &Product) -> Vec<Mismatch> {
+    let fields = [
+        ("name", listing.name.clone(), page.name.clone()),
+        (
+            "availability",
+            listing.offers.availability.db_serialize().to_string(),
+            page.offers.availability.db_serialize().to_string(),
+        ),
+        (
+            "price_cents",
+            listing.offers.price.cents().to_string(),
+            page.offers.price.cents().to_string(),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, listing, product_page)| listing != product_page)
+        .map(|(field, listing, product_page)| Mismatch {
+            field,
+            listing,
+            product_page,
+        })
+        .collect()
+}
+
+/// Overwrites the fields of `page` with those of `listing` if the listing
+/// takes `precedence`.
+pub fn resolve(precedence: FieldSource, listing: &Product, page: &mut Product) {
+    if precedence == FieldSource::ProductPage {
+        return;
+    }
+
+    page.name = listing.name.clone();
+    page.offers.availability = listing.offers.availability.clone();
+    page.offers.price = listing.offers.price;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saq::money::Price;
+
+    /// The JSON-LD product from the wine fixture.
+    fn product() -> Product {
+        crate::saq::parse_product_page(include_str!("../../fixtures/product_wine.html"))
+            .unwrap()
+            .get_ld_product()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let page = product();
+        let mut listing = product();
+        assert!(mismatches(&listing, &page).is_empty());
+
+        listing.offers.price = Price::from_cents(2495);
+        assert_eq!(
+            vec![Mismatch {
+                field: "price_cents",
+                listing: "2495".to_string(),
+                product_page: "2995".to_string(),
+            }],
+            mismatches(&listing, &page)
+        );
+
+        let mut resolved = page.clone();
+        resolve(FieldSource::ProductPage, &listing, &mut resolved);
+        assert_eq!(2995, resolved.offers.price.cents());
+        resolve(FieldSource::Listing, &listing, &mut resolved);
+        assert_eq!(2495, resolved.offers.price.cents());
+    }
+}
@@ -0,0 +1,201 @@
+//! A bounded, multi-producer multi-consumer queue handing out items by priority.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// The order in which products are crawled, from first to last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// The product has never been crawled.
+    New = 0,
+    /// The product hasn't been updated recently.
+    Stale = 1,
+    /// The product was updated recently.
+    Fresh = 2,
+}
+
+/// Number of [`Priority`] variants.
+const PRIORITY_COUNT: usize = 3;
+
+/// Error returned when pushing to a closed [`PriorityQueue`].
+#[derive(Debug)]
+pub struct Closed;
+
+impl std::fmt::Display for Closed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "queue is closed")
+    }
+}
+
+impl std::error::Error for Closed {}
+
+/// Mutable state of a [`PriorityQueue`].
+struct State<T> {
+    /// One FIFO queue per [`Priority`].
+    queues: [VecDeque<T>; PRIORITY_COUNT],
+    /// Total number of items across all queues.
+    len: usize,
+    /// Whether the queue has been closed.
+    closed: bool,
+}
+
+/// A bounded queue similar to [`async_channel::bounded`] except that items
+/// are received in [`Priority`] order (and in FIFO order within a priority).
+///
+/// Once [closed](PriorityQueue::close), pushing fails but items already in the
+/// queue can still be popped.
+pub struct PriorityQueue<T> {
+    /// The queued items.
+    state: Mutex<State<T>>,
+    /// Maximum number of items held at once.
+    capacity: usize,
+    /// Notified whenever an item is pushed or the queue is closed.
+    pushed: Notify,
+    /// Notified whenever an item is popped or the queue is closed.
+    popped: Notify,
+}
+
+impl<T> PriorityQueue<T> {
+    /// Creates a queue holding at most `capacity` items.
+    pub fn new(capacity: usize) -> PriorityQueue<T> {
+        PriorityQueue {
+            state: Mutex::new(State {
+                queues: Default::default(),
+                len: 0,
+                closed: false,
+            }),
+            capacity,
+            pushed: Notify::new(),
+            popped: Notify::new(),
+        }
+    }
+
+    /// Adds `item` to the queue, waiting for room to free up if it is full.
+    pub async fn push(&self, priority: Priority, item: T) -> Result<(), Closed> {
+        let mut item = Some(item);
+
+        loop {
+            // Created before checking the state so that notifications sent in
+            // between aren't missed.
+            let popped = self.popped.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+
+                if state.closed {
+                    return Err(Closed);
+                }
+
+                if state.len < self.capacity {
+                    state.queues[priority as usize].push_back(item.take().unwrap());
+                    state.len += 1;
+                    drop(state);
+                    self.pushed.notify_one();
+                    return Ok(());
+                }
+            }
+
+            popped.await;
+        }
+    }
+
+    /// Removes the highest priority item from the queue, waiting for one to
+    /// be pushed if it is empty.
+    ///
+    /// Returns `None` once the queue is both closed and empty.
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            let pushed = self.pushed.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+
+                let item = state.queues.iter_mut().find_map(|queue| queue.pop_front());
+
+                if let Some(item) = item {
+                    state.len -= 1;
+                    drop(state);
+                    self.popped.notify_one();
+                    return Some(item);
+                }
+
+                if state.closed {
+                    return None;
+                }
+            }
+
+            pushed.await;
+        }
+    }
+
+    /// Number of items currently in the queue.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().len
+    }
+
+    /// Maximum number of items held at once.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether the queue currently holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Closes the queue, waking up any tasks waiting on it.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.pushed.notify_waiters();
+        self.popped.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pop_order() {
+        let queue = PriorityQueue::new(10);
+
+        queue.push(Priority::Fresh, "fresh").await.unwrap();
+        queue.push(Priority::New, "new 1").await.unwrap();
+        queue.push(Priority::Stale, "stale").await.unwrap();
+        queue.push(Priority::New, "new 2").await.unwrap();
+        queue.close();
+
+        assert!(queue.push(Priority::New, "late").await.is_err());
+
+        assert_eq!(Some("new 1"), queue.pop().await);
+        assert_eq!(Some("new 2"), queue.pop().await);
+        assert_eq!(Some("stale"), queue.pop().await);
+        assert_eq!(Some("fresh"), queue.pop().await);
+        assert_eq!(None, queue.pop().await);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let queue = Arc::new(PriorityQueue::new(1));
+
+        let producer = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                for i in 0..100 {
+                    queue.push(Priority::New, i).await.unwrap();
+                }
+                queue.close();
+            })
+        };
+
+        let mut received = vec![];
+        while let Some(i) = queue.pop().await {
+            received.push(i);
+        }
+
+        producer.await.unwrap();
+        assert_eq!((0..100).collect::<Vec<_>>(), received);
+    }
+}
@@ -0,0 +1,493 @@
+//! Crawling a synthetic catalog served locally, to benchmark changes to the
+//! crawler (scheduling, persistence, etc.) without sending a single request
+//! to the SAQ website.
+//!
+//! A [`MockServer`] serves a [`SyntheticCatalog`] of generated catalog and
+//! product pages over HTTP, waiting for a configurable latency before each
+//! response. A full crawl of it then goes through the same pipeline as a real
+//! one (fetching, parsing, persisting) into a scratch database which is
+//! deleted afterwards, and its throughput is reported.
+
+use super::{crawl_catalog, crawl_listing, saq_client, NoHooks};
+use crate::config::Config;
+use crate::db::{self, CrawlRunMode, CrawlRunStatus, CrawlState};
+use crate::saq::{AllowedHosts, SiteProfile};
+use color_eyre::eyre::{eyre, Result};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// SAQ codes of synthetic products start from here, well clear of real ones.
+const FIRST_SAQ_CODE: u32 = 90_000_000;
+
+/// Categories synthetic products are spread across, as `(name, url slug)`
+/// pairs of a top level category followed by its subcategories.
+const CATEGORIES: [[(&str, &str); 3]; 3] = [
+    [
+        ("Wine", "wine"),
+        ("Red wine", "wine/red-wine"),
+        ("White wine", "wine/white-wine"),
+    ],
+    [
+        ("Beer", "beer"),
+        ("Stout", "beer/stout"),
+        ("Lager", "beer/lager"),
+    ],
+    [
+        ("Spirit", "spirit"),
+        ("Whisky", "spirit/whisky"),
+        ("Gin", "spirit/gin"),
+    ],
+];
+
+/// Countries synthetic products come from.
+const COUNTRIES: [&str; 4] = ["France", "Italy", "Canada", "Spain"];
+
+/// Number of distinct producers among synthetic products.
+const PRODUCERS: u32 = 50;
+
+/// The shape of a synthetic catalog.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// Number of catalog pages.
+    pub pages: u32,
+    /// Number of products listed on each catalog page.
+    pub products_per_page: u32,
+    /// How long the server waits before each response.
+    pub latency: Duration,
+}
+
+/// Generates the pages of a synthetic catalog, see [`Simulation`].
+#[derive(Debug)]
+pub struct SyntheticCatalog {
+    /// The catalog's shape.
+    simulation: Simulation,
+    /// The server's URL (i.e. `http://127.0.0.1:1234`), which product URLs
+    /// point to.
+    base_url: String,
+}
+
+impl SyntheticCatalog {
+    /// The SAQ code of the `index`th product.
+    fn saq_code(index: u32) -> String {
+        (FIRST_SAQ_CODE + index).to_string()
+    }
+
+    /// The JSON-LD `Product` of the `index`th product.
+    fn linked_data(&self, index: u32) -> serde_json::Value {
+        let saq_code = Self::saq_code(index);
+
+        json!({
+            "@type": "Product",
+            "name": format!("Synthetic product {index}"),
+            "description": format!("Synthetic product number {index}."),
+            "image": format!("{}/media/{saq_code}.png", self.base_url),
+            "sku": saq_code,
+            "offers": {
+                "@type": "Offer",
+                "availability": "http://schema.org/InStock",
+                "itemCondition": "NewCondition",
+                "price": format!("{}.{:02}", 9 + index % 50, index * 37 % 100),
+                "priceCurrency": "CAD",
+                "url": format!("{}/en/{saq_code}", self.base_url),
+            }
+        })
+    }
+
+    /// Renders catalog page `page_number`, which wraps around to the last
+    /// page past the end like the real one does.
+    fn catalog_page(&self, page_number: u32) -> String {
+        let page_number = page_number.clamp(1, self.simulation.pages);
+        let first = (page_number - 1) * self.simulation.products_per_page;
+        let products = (first..first + self.simulation.products_per_page)
+            .map(|index| self.linked_data(index))
+            .collect::<Vec<_>>();
+
+        let linked_data = json!({
+            "@context": "https://schema.org",
+            "@type": "WebPage",
+            "mainEntity": {
+                "@type": "OfferCatalog",
+                "name": "Products",
+                "url": format!("{}/en/products", self.base_url),
+                "numberOfItems": products.len(),
+                "itemListElement": products,
+            }
+        });
+
+        format!(
+            r#"<!doctype html>
+<html lang="en">
+<body>
+<div class="pages"><ul class="items pages-items">
+<li class="item current"><strong class="page"><span class="label">You're currently reading page</span><span>{page_number}</span></strong></li>
+</ul></div>
+<script type="application/ld+json">{linked_data}</script>
+</body>
+</html>"#
+        )
+    }
+
+    /// Renders the page of the product with the given SAQ code, if it exists.
+    fn product_page(&self, saq_code: &str) -> Option<String> {
+        let index = saq_code.parse::<u32>().ok()?.checked_sub(FIRST_SAQ_CODE)?;
+        if index >= self.simulation.pages * self.simulation.products_per_page {
+            return None;
+        }
+
+        let categories = CATEGORIES[index as usize % CATEGORIES.len()];
+        let subcategory = 1 + index as usize % (categories.len() - 1);
+        let breadcrumbs = [categories[0], categories[subcategory]]
+            .iter()
+            .enumerate()
+            .map(|(i, (name, slug))| {
+                json!({
+                    "@type": "ListItem",
+                    "position": i + 1,
+                    "item": {"@id": format!("{}/en/products/{slug}", self.base_url), "name": name},
+                })
+            })
+            .collect::<Vec<_>>();
+        let breadcrumbs = json!({
+            "@context": "https://schema.org",
+            "@type": "BreadcrumbList",
+            "itemListElement": breadcrumbs,
+        });
+
+        let detailed_info = [
+            (
+                "Country",
+                COUNTRIES[index as usize % COUNTRIES.len()].to_string(),
+            ),
+            ("Degree of alcohol", format!("{}.5 %", 5 + index % 40)),
+            ("Size", "750 ml".to_string()),
+            ("Producer", format!("Producer {}", index % PRODUCERS)),
+            ("SAQ code", saq_code.to_string()),
+        ]
+        .iter()
+        .map(|(key, value)| {
+            format!(r#"<li><strong data-th="{key}" class="data">{value}</strong></li>"#)
+        })
+        .collect::<String>();
+
+        Some(format!(
+            r#"<!doctype html>
+<html lang="en">
+<body>
+<div class="data item content" id="product-data-item-additional"><ul>{detailed_info}</ul></div>
+<script type="application/ld+json">{breadcrumbs}</script>
+<script type="application/ld+json">{}</script>
+</body>
+</html>"#,
+            self.linked_data(index)
+        ))
+    }
+
+    /// Renders the page at `target` (a request's path and query), if it
+    /// exists.
+    fn render(&self, target: &str) -> Option<String> {
+        let url = url::Url::parse(&format!("{}{}", self.base_url, target)).ok()?;
+
+        match url.path() {
+            // Category listings are the whole catalog
+            path if path == "/en/products" || path.starts_with("/en/products/") => {
+                let page_number = url
+                    .query_pairs()
+                    .find(|(name, _)| name == "p")
+                    .and_then(|(_, value)| value.parse().ok())
+                    .unwrap_or(1);
+                Some(self.catalog_page(page_number))
+            }
+            path => self.product_page(path.strip_prefix("/en/")?),
+        }
+    }
+}
+
+/// Serves a [`SyntheticCatalog`] over HTTP on a local port until dropped.
+pub struct MockServer {
+    /// The catalog being served.
+    catalog: Arc<SyntheticCatalog>,
+    /// Number of requests received so far.
+    requests: Arc<AtomicU64>,
+    /// Accepts connections.
+    task: JoinHandle<hyper::Result<()>>,
+}
+
+impl MockServer {
+    /// Starts serving the catalog described by `simulation` on a random port.
+    pub async fn start(simulation: Simulation) -> Result<MockServer> {
+        if simulation.pages == 0 || simulation.products_per_page == 0 {
+            return Err(eyre!("the synthetic catalog needs at least one product"));
+        }
+
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let catalog = Arc::new(SyntheticCatalog {
+            simulation,
+            base_url: format!("http://{}", listener.local_addr()?),
+        });
+        let requests = Arc::new(AtomicU64::new(0));
+
+        let handler = Arc::new(MockHandler {
+            catalog: catalog.clone(),
+            requests: requests.clone(),
+        });
+        let make_service = make_service_fn(move |_| {
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let handler = handler.clone();
+                    async move { Ok::<_, Infallible>(handler.handle(request).await) }
+                }))
+            }
+        });
+        let server = Server::builder(AddrIncoming::from_listener(listener)?).serve(make_service);
+        let task = tokio::spawn(server);
+
+        Ok(MockServer {
+            catalog,
+            requests,
+            task,
+        })
+    }
+
+    /// The profile of the site being served, which is laid out like the SAQ
+    /// website.
+    pub fn site(&self) -> Result<SiteProfile> {
+        SiteProfile::from_json(
+            &json!({ "name": "simulation", "base_url": self.catalog.base_url }).to_string(),
+        )
+    }
+
+    /// Number of requests received so far.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answers requests for the pages of a [`SyntheticCatalog`].
+struct MockHandler {
+    /// The catalog being served.
+    catalog: Arc<SyntheticCatalog>,
+    /// Number of requests received so far.
+    requests: Arc<AtomicU64>,
+}
+
+impl MockHandler {
+    /// Answers `request` with the page it asks for, after the simulated
+    /// latency.
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(self.catalog.simulation.latency).await;
+
+        let target = request
+            .uri()
+            .path_and_query()
+            .map_or("/", |target| target.as_str());
+        let (status, body) = match self.catalog.render(target) {
+            Some(body) => (StatusCode::OK, body),
+            None => (StatusCode::NOT_FOUND, String::new()),
+        };
+
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        response
+    }
+}
+
+/// A database in the system's temporary directory, deleted when dropped.
+struct ScratchDb {
+    /// The database file.
+    path: PathBuf,
+}
+
+impl Drop for ScratchDb {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// How a simulated crawl went.
+#[derive(Debug, Serialize)]
+pub struct SimulationReport {
+    /// Number of products in the synthetic catalog.
+    pub catalog_products: u32,
+    /// Number of products persisted by the crawl.
+    pub persisted_products: usize,
+    /// Number of product page errors recorded by the crawl.
+    pub errors: i64,
+    /// Number of requests the server received.
+    pub requests: u64,
+    /// How long the crawl took, in seconds.
+    pub elapsed_secs: f64,
+    /// Products persisted per second.
+    pub products_per_second: f64,
+}
+
+/// Runs a full crawl of the synthetic catalog described by `simulation`
+/// with the given settings, into a scratch database.
+pub async fn simulate(config: &Config, simulation: Simulation) -> Result<SimulationReport> {
+    let catalog_products = simulation.pages * simulation.products_per_page;
+    let server = MockServer::start(simulation).await?;
+
+    let mut config = config.clone();
+    config.allowed_hosts = AllowedHosts(vec!["127.0.0.1".to_string()]);
+    config.site = server.site()?;
+
+    let mut client = saq_client(&config)?;
+    if config.low_memory {
+        client = client.spool_to(std::env::temp_dir());
+    }
+
+    let scratch = ScratchDb {
+        path: std::env::temp_dir().join(format!("ransaq-simulation-{}.sqlite", std::process::id())),
+    };
+    let db = db::Client::new(&format!("sqlite:{}", scratch.path.display())).await?;
+    db.migrate().await?;
+
+    info!(catalog_products, "starting simulated crawl");
+    let start = Instant::now();
+
+    let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+    let state = CrawlState::new(crawl_listing(&config, CrawlRunMode::Full)?);
+    let result = crawl_catalog(
+        &config,
+        CrawlRunMode::Full,
+        &client,
+        &db,
+        crawl_run_id,
+        state,
+        Arc::new(NoHooks),
+    )
+    .await;
+
+    let elapsed = start.elapsed();
+    let status = match result {
+        Ok(_) => CrawlRunStatus::Completed,
+        Err(_) => CrawlRunStatus::Failed,
+    };
+    db.finish_crawl_run(crawl_run_id, status).await?;
+    result?;
+
+    let persisted_products = db.crawl_run_saq_codes(crawl_run_id).await?.len();
+    let errors = db.count_crawl_errors(crawl_run_id).await?;
+
+    Ok(SimulationReport {
+        catalog_products,
+        persisted_products,
+        errors,
+        requests: server.requests(),
+        elapsed_secs: elapsed.as_secs_f64(),
+        products_per_second: persisted_products as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_catalog() {
+        let catalog = SyntheticCatalog {
+            simulation: Simulation {
+                pages: 2,
+                products_per_page: 3,
+                latency: Duration::ZERO,
+            },
+            base_url: "http://127.0.0.1:1234".to_string(),
+        };
+
+        let site = SiteProfile::from_json(r#"{"base_url": "http://127.0.0.1:1234"}"#).unwrap();
+
+        let page = site
+            .parse_catalog_page(&catalog.render("/en/products?p=2").unwrap())
+            .unwrap();
+        assert_eq!(2, page.current_page);
+        assert_eq!(
+            vec!["90000003", "90000004", "90000005"],
+            page.products
+                .iter()
+                .map(|product| product.sku.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "http://127.0.0.1:1234/en/90000003",
+            page.products[0].offers.url
+        );
+
+        // Past the end, the pagination wraps around to the last page
+        let page = site
+            .parse_catalog_page(&catalog.render("/en/products?p=3").unwrap())
+            .unwrap();
+        assert_eq!(2, page.current_page);
+
+        let product = site
+            .parse_product_page(&catalog.render("/en/90000004").unwrap())
+            .unwrap();
+        assert_eq!("90000004", product.detailed_info.saq_code);
+        assert_eq!(2, product.extract_categories(&site).unwrap().len());
+
+        assert!(catalog.render("/en/90000006").is_none());
+        assert!(catalog.render("/en/sitemap.xml").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_simulate() -> Result<()> {
+        let simulation = Simulation {
+            pages: 2,
+            products_per_page: 5,
+            latency: Duration::ZERO,
+        };
+
+        let report = simulate(&Config::default(), simulation.clone()).await?;
+        assert_eq!(10, report.catalog_products);
+        assert_eq!(10, report.persisted_products);
+        assert_eq!(0, report.errors);
+        // Each product page, plus the catalog pages and the one past the end
+        assert_eq!(13, report.requests);
+
+        let mut config = Config {
+            max_pages: Some(1),
+            ..Config::default()
+        };
+        let report = simulate(&config, simulation.clone()).await?;
+        assert_eq!(5, report.persisted_products);
+
+        config.max_pages = None;
+        config.max_products = Some(3);
+        let report = simulate(&config, simulation.clone()).await?;
+        assert_eq!(3, report.persisted_products);
+
+        config.max_products = None;
+        config.category = Some("wine/red-wine".to_string());
+        let report = simulate(&config, simulation).await?;
+        assert_eq!(10, report.persisted_products);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+//! Request and persistence statistics gathered over the course of a crawl.
+//!
+//! These are logged periodically (see [`Stats::report_every`]) to make it
+//! easy to tell whether HTTP requests or SQLite writes are the bottleneck.
+//! The process' [memory usage](MemoryUsage) is included on platforms that
+//! report it, to spot regressions such as whole pages being held in memory.
+//!
+//! Requests are also counted per second to report the [rates](RequestRates)
+//! the site was crawled at, which is useful to show the crawler stays within
+//! a [politeness budget](crate::config::Config::politeness_budget). They're
+//! counted by the [client](crate::saq::Client::count_requests_with) as they're sent,
+//! so that retries and requests whose page was never recorded (i.e. failed
+//! catalog pages) count too.
+
+use crate::saq::RequestCounter;
+use reqwest::StatusCode;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Average number of requests per minute sent by a crawl.
+pub const REQUESTS_PER_MINUTE_METRIC: &str = "crawl.requests_per_minute";
+
+/// Highest number of requests sent by a crawl within a single minute.
+pub const MAX_REQUESTS_PER_MINUTE_METRIC: &str = "crawl.max_requests_per_minute";
+
+/// Highest number of requests sent by a crawl within a single second.
+pub const MAX_BURST_METRIC: &str = "crawl.max_burst";
+
+/// Counters for a single worker task.
+#[derive(Debug, Default)]
+struct WorkerStats {
+    /// Products successfully persisted.
+    products: AtomicU64,
+    /// Products that failed to be crawled or persisted.
+    failures: AtomicU64,
+}
+
+/// Crawl-wide counters, shared between worker tasks.
+#[derive(Debug)]
+pub struct Stats {
+    /// Catalog pages fetched.
+    pages: AtomicU64,
+    /// Total time spent fetching and parsing catalog pages.
+    page_micros: AtomicU64,
+    /// Products listed across all catalog pages.
+    listed_products: AtomicU64,
+    /// Products successfully persisted.
+    products: AtomicU64,
+    /// Number of product page fetches timed in `fetch_micros`.
+    fetches: AtomicU64,
+    /// Total time spent fetching product pages.
+    fetch_micros: AtomicU64,
+    /// Number of product pages timed in `parse_micros`.
+    parses: AtomicU64,
+    /// Total time spent extracting data from product pages.
+    parse_micros: AtomicU64,
+    /// Number of products timed in `persist_micros`.
+    persists: AtomicU64,
+    /// Total time spent persisting products.
+    persist_micros: AtomicU64,
+    /// Number of product page responses by status code (or `error` when no
+    /// response was received).
+    statuses: Mutex<BTreeMap<String, u64>>,
+    /// Per-worker counters, indexed by worker number.
+    workers: Vec<WorkerStats>,
+    /// Counts every request sent to the site, see
+    /// [`request_counter`](Stats::request_counter).
+    requests: Arc<RequestCounter>,
+}
+
+/// The rates at which requests were sent to the site over a crawl.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RequestRates {
+    /// Total number of requests.
+    pub requests: u64,
+    /// Average number of requests per minute.
+    pub avg_per_minute: f64,
+    /// Highest number of requests sent within a single minute.
+    pub max_per_minute: u64,
+    /// Highest number of requests sent within a single second.
+    pub max_burst: u64,
+}
+
+impl RequestRates {
+    /// Computes rates from the number of requests sent during each second
+    /// (counted from the start of the crawl) over `elapsed`.
+    fn from_counts(counts: &BTreeMap<u64, u64>, elapsed: Duration) -> RequestRates {
+        let requests = counts.values().sum::<u64>();
+
+        let mut per_minute = BTreeMap::<u64, u64>::new();
+        for (second, count) in counts {
+            *per_minute.entry(second / 60).or_default() += count;
+        }
+
+        // Crawls shorter than a minute are averaged over a whole minute, so
+        // short bursts don't look like sustained rates.
+        let minutes = (elapsed.as_secs_f64() / 60.0).max(1.0);
+
+        RequestRates {
+            requests,
+            avg_per_minute: requests as f64 / minutes,
+            max_per_minute: per_minute.values().copied().max().unwrap_or(0),
+            max_burst: counts.values().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+/// A point-in-time copy of the cumulative counters in [`Stats`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Snapshot {
+    /// See [`Stats::pages`].
+    pages: u64,
+    /// See [`Stats::page_micros`].
+    page_micros: u64,
+    /// See [`Stats::listed_products`].
+    listed_products: u64,
+    /// See [`Stats::products`].
+    products: u64,
+    /// See [`Stats::fetches`].
+    fetches: u64,
+    /// See [`Stats::fetch_micros`].
+    fetch_micros: u64,
+    /// See [`Stats::parses`].
+    parses: u64,
+    /// See [`Stats::parse_micros`].
+    parse_micros: u64,
+    /// See [`Stats::persists`].
+    persists: u64,
+    /// See [`Stats::persist_micros`].
+    persist_micros: u64,
+}
+
+/// Memory used by the process, as reported by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Current resident set size in kilobytes.
+    pub rss_kb: u64,
+    /// Highest resident set size since the process started, in kilobytes.
+    pub peak_rss_kb: u64,
+}
+
+impl MemoryUsage {
+    /// Reads the process' memory usage from `/proc/self/status`, returning
+    /// `None` on platforms without it (i.e. anything but Linux).
+    pub fn current() -> Option<MemoryUsage> {
+        MemoryUsage::parse(&std::fs::read_to_string("/proc/self/status").ok()?)
+    }
+
+    /// Parses the `VmRSS` and `VmHWM` lines of `/proc/<pid>/status`.
+    fn parse(status: &str) -> Option<MemoryUsage> {
+        let field = |name: &str| {
+            status.lines().find_map(|line| {
+                line.strip_prefix(name)?
+                    .strip_prefix(':')?
+                    .trim()
+                    .strip_suffix("kB")?
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+        };
+
+        Some(MemoryUsage {
+            rss_kb: field("VmRSS")?,
+            peak_rss_kb: field("VmHWM")?,
+        })
+    }
+}
+
+/// Adds `elapsed` to a counter of microseconds.
+fn add_duration(counter: &AtomicU64, elapsed: Duration) {
+    counter.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+/// Average duration in milliseconds given a number of microseconds and
+/// samples, or `0.0` if there are no samples.
+fn average_ms(micros: u64, count: u64) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+
+    micros as f64 / count as f64 / 1000.0
+}
+
+impl Stats {
+    /// Creates empty statistics for the given number of workers.
+    pub fn new(workers: usize) -> Stats {
+        Stats {
+            pages: AtomicU64::default(),
+            page_micros: AtomicU64::default(),
+            listed_products: AtomicU64::default(),
+            products: AtomicU64::default(),
+            fetches: AtomicU64::default(),
+            fetch_micros: AtomicU64::default(),
+            parses: AtomicU64::default(),
+            parse_micros: AtomicU64::default(),
+            persists: AtomicU64::default(),
+            persist_micros: AtomicU64::default(),
+            statuses: Mutex::default(),
+            workers: (0..workers).map(|_| WorkerStats::default()).collect(),
+            requests: Arc::default(),
+        }
+    }
+
+    /// Counts the requests sent to the site, to be given to the client
+    /// sending them (see [`Client::count_requests_with`](crate::saq::Client::count_requests_with)).
+    pub fn request_counter(&self) -> Arc<RequestCounter> {
+        self.requests.clone()
+    }
+
+    /// Records a catalog page listing `products` fetched and parsed in `elapsed`.
+    pub fn record_page(&self, products: usize, elapsed: Duration) {
+        self.pages.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.page_micros, elapsed);
+        self.listed_products
+            .fetch_add(products as u64, Ordering::Relaxed);
+    }
+
+    /// Records a product page request that resulted in `status` (or no
+    /// response at all) after `elapsed`.
+    pub fn record_fetch(&self, status: Option<StatusCode>, elapsed: Duration) {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.fetch_micros, elapsed);
+
+        let key = match status {
+            Some(status) => status.as_u16().to_string(),
+            None => "error".to_string(),
+        };
+        *self.statuses.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    /// Records the time taken to extract data from a product page.
+    pub fn record_parse(&self, elapsed: Duration) {
+        self.parses.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.parse_micros, elapsed);
+    }
+
+    /// Records a product successfully persisted by `worker` in `elapsed`.
+    pub fn record_persist(&self, worker: usize, elapsed: Duration) {
+        self.persists.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.persist_micros, elapsed);
+        self.products.fetch_add(1, Ordering::Relaxed);
+        self.workers[worker]
+            .products
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a product `worker` failed to crawl or persist.
+    pub fn record_failure(&self, worker: usize) {
+        self.workers[worker]
+            .failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Total number of products persisted so far.
+    pub fn products(&self) -> u64 {
+        self.products.load(Ordering::Relaxed)
+    }
+
+    /// Rates at which requests were sent so far.
+    pub fn request_rates(&self) -> RequestRates {
+        let (counts, elapsed) = self.requests.per_second();
+        RequestRates::from_counts(&counts, elapsed)
+    }
+
+    /// Logs how long catalog pages took to load over the entire crawl, to help
+    /// pick a [page size](crate::config::Config::page_size).
+    pub fn log_page_summary(&self, page_size: Option<u32>) {
+        let snapshot = self.snapshot();
+
+        info!(
+            page_size,
+            pages = snapshot.pages,
+            avg_page_ms = format!("{:.1}", average_ms(snapshot.page_micros, snapshot.pages)),
+            ms_per_listed_product = format!(
+                "{:.1}",
+                average_ms(snapshot.page_micros, snapshot.listed_products)
+            ),
+            "catalog page stats"
+        );
+    }
+
+    /// Copies the current value of the cumulative counters.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pages: self.pages.load(Ordering::Relaxed),
+            page_micros: self.page_micros.load(Ordering::Relaxed),
+            listed_products: self.listed_products.load(Ordering::Relaxed),
+            products: self.products.load(Ordering::Relaxed),
+            fetches: self.fetches.load(Ordering::Relaxed),
+            fetch_micros: self.fetch_micros.load(Ordering::Relaxed),
+            parses: self.parses.load(Ordering::Relaxed),
+            parse_micros: self.parse_micros.load(Ordering::Relaxed),
+            persists: self.persists.load(Ordering::Relaxed),
+            persist_micros: self.persist_micros.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Logs a summary of activity since `previous`, `elapsed` ago, along with
+    /// the current `queue_depth`.
+    fn report(&self, previous: &Snapshot, elapsed: Duration, queue_depth: usize) -> Snapshot {
+        let current = self.snapshot();
+
+        let products = current.products - previous.products;
+        let products_per_sec = products as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+
+        let avg_page_ms = average_ms(
+            current.page_micros - previous.page_micros,
+            current.pages - previous.pages,
+        );
+        let avg_fetch_ms = average_ms(
+            current.fetch_micros - previous.fetch_micros,
+            current.fetches - previous.fetches,
+        );
+        let avg_parse_ms = average_ms(
+            current.parse_micros - previous.parse_micros,
+            current.parses - previous.parses,
+        );
+        let avg_persist_ms = average_ms(
+            current.persist_micros - previous.persist_micros,
+            current.persists - previous.persists,
+        );
+
+        let statuses = self.statuses.lock().unwrap().clone();
+        let memory = MemoryUsage::current();
+
+        info!(
+            total_products = current.products,
+            products_per_sec = format!("{products_per_sec:.2}"),
+            pages = current.pages,
+            avg_page_ms = format!("{avg_page_ms:.1}"),
+            avg_fetch_ms = format!("{avg_fetch_ms:.1}"),
+            avg_parse_ms = format!("{avg_parse_ms:.1}"),
+            avg_persist_ms = format!("{avg_persist_ms:.1}"),
+            queue_depth,
+            ?statuses,
+            rss_mb = memory.map(|m| m.rss_kb / 1024),
+            peak_rss_mb = memory.map(|m| m.peak_rss_kb / 1024),
+            "crawl stats"
+        );
+
+        for (worker, stats) in self.workers.iter().enumerate() {
+            debug!(
+                worker,
+                products = stats.products.load(Ordering::Relaxed),
+                failures = stats.failures.load(Ordering::Relaxed),
+                "worker stats"
+            );
+        }
+
+        current
+    }
+
+    /// Logs a summary every `interval` until the returned task is aborted.
+    /// `queue_depth` is called to obtain the number of products waiting to
+    /// be crawled.
+    pub fn report_every(
+        self: Arc<Self>,
+        interval: Duration,
+        queue_depth: impl Fn() -> usize + Send + 'static,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+
+            let mut previous = Snapshot::default();
+            let mut previous_at = Instant::now();
+
+            loop {
+                ticker.tick().await;
+                previous = self.report(&previous, previous_at.elapsed(), queue_depth());
+                previous_at = Instant::now();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let stats = Stats::new(2);
+
+        stats.record_fetch(Some(StatusCode::OK), Duration::from_millis(100));
+        stats.record_fetch(Some(StatusCode::OK), Duration::from_millis(300));
+        stats.record_fetch(None, Duration::from_millis(50));
+        stats.record_parse(Duration::from_millis(10));
+        stats.record_persist(1, Duration::from_millis(4));
+        stats.record_failure(0);
+        stats.record_page(24, Duration::from_millis(1200));
+
+        let snapshot = stats.report(&Snapshot::default(), Duration::from_secs(1), 3);
+        assert_eq!(1, snapshot.products);
+        assert_eq!(3, snapshot.fetches);
+        assert_eq!(450_000, snapshot.fetch_micros);
+        assert_eq!(1, snapshot.pages);
+        assert_eq!(24, snapshot.listed_products);
+        assert_eq!(1, stats.products());
+
+        let statuses = stats.statuses.lock().unwrap();
+        assert_eq!(Some(&2), statuses.get("200"));
+        assert_eq!(Some(&1), statuses.get("error"));
+    }
+
+    #[test]
+    fn test_request_rates() {
+        let counts = BTreeMap::from([(0, 3), (1, 5), (59, 2), (60, 4), (150, 1)]);
+
+        assert_eq!(
+            RequestRates {
+                requests: 15,
+                avg_per_minute: 6.0,
+                max_per_minute: 10,
+                max_burst: 5,
+            },
+            RequestRates::from_counts(&counts, Duration::from_secs(150))
+        );
+
+        // Short crawls are averaged over a whole minute
+        let rates = RequestRates::from_counts(&counts, Duration::from_secs(10));
+        assert_eq!(15.0, rates.avg_per_minute);
+
+        assert_eq!(
+            RequestRates::default(),
+            RequestRates::from_counts(&BTreeMap::new(), Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_parse_memory_usage() {
+        let status =
+            "Name:\transaq\nVmPeak:\t  812345 kB\nVmHWM:\t   98304 kB\nVmRSS:\t   65536 kB\n";
+        assert_eq!(
+            Some(MemoryUsage {
+                rss_kb: 65536,
+                peak_rss_kb: 98304,
+            }),
+            MemoryUsage::parse(status)
+        );
+
+        assert_eq!(None, MemoryUsage::parse("Name:\transaq\n"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Listing the category tree along with how many products each category
+//! holds.
+
+use super::Client;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// A category, as listed by [`Client::categories`].
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CategoryListing {
+    /// The category's id, which `parent_category_id` refers to.
+    pub id: i64,
+    /// The category's name.
+    pub name: String,
+    /// The category's listing URL.
+    pub url: String,
+    /// The category's parent, `None` for top level categories.
+    pub parent_category_id: Option<i64>,
+    /// Number of products in the category.
+    pub product_count: i64,
+}
+
+impl Client {
+    /// Lists every category, ordered by name.
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> color_eyre::Result<()> {
+    /// let db = ransaq::db::Client::new_in_memory_with_sample_data().await?;
+    ///
+    /// let wine = db
+    ///     .categories()
+    ///     .await?
+    ///     .into_iter()
+    ///     .find(|category| category.name == "Wine")
+    ///     .unwrap();
+    /// assert_eq!(3, wine.product_count);
+    /// assert_eq!(None, wine.parent_category_id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn categories(&self) -> Result<Vec<CategoryListing>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let categories = sqlx::query_as!(
+            CategoryListing,
+            r#"select c.id as "id!", c.name, c.url, c.parent_category_id,
+                count(pc.id) as "product_count!: i64"
+            from categories c
+            left join product_categories pc on pc.category_id = c.id
+            group by c.id
+            order by c.name"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(categories)
+    }
+}
@@ -0,0 +1,214 @@
+//! What changed in the catalog over a period, for the
+//! [changelog](crate::changelog) rendered by `ransaq changelog`.
+
+use super::Client;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// A product that arrived or was delisted.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChangelogProduct {
+    /// The product's SAQ code.
+    pub saq_code: String,
+    /// The product's name.
+    pub name: String,
+    /// The product's current price, in cents.
+    pub price_cents: i64,
+    /// The product's availability as of the change.
+    pub availability: String,
+    /// When the product was first crawled or delisted.
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A price drop.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PriceDrop {
+    /// The product's SAQ code.
+    pub saq_code: String,
+    /// The product's name.
+    pub name: String,
+    /// The price before the drop, in cents.
+    pub old_price_cents: i64,
+    /// The price after the drop, in cents.
+    pub new_price_cents: i64,
+    /// The drop, as a percentage of the old price.
+    pub drop_percent: f64,
+    /// When the price dropped.
+    pub changed_at: DateTime<Utc>,
+}
+
+/// New arrivals, delistings and notable price drops since a given time.
+#[derive(Debug, Serialize)]
+pub struct Changelog {
+    /// The start of the period covered.
+    pub since: DateTime<Utc>,
+    /// Products first crawled during the period, oldest first.
+    pub new_arrivals: Vec<ChangelogProduct>,
+    /// Products that became discontinued or sold out during the period,
+    /// oldest first.
+    pub delistings: Vec<ChangelogProduct>,
+    /// Price drops during the period, largest first.
+    pub price_drops: Vec<PriceDrop>,
+}
+
+impl Client {
+    /// Lists what changed in the catalog `since` the given time, only
+    /// including price drops of at least `min_drop_percent`.
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> color_eyre::Result<()> {
+    /// let db = ransaq::db::Client::new_in_memory_with_sample_data().await?;
+    ///
+    /// let since = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+    /// let changelog = db.changelog(since, 10.0).await?;
+    /// assert_eq!("Dieu du Ciel! Péché Mortel", changelog.price_drops[0].name);
+    /// assert_eq!(1, changelog.price_drops.len());
+    /// assert_eq!("10264010", changelog.delistings[0].saq_code);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn changelog(
+        &self,
+        since: DateTime<Utc>,
+        min_drop_percent: f64,
+    ) -> Result<Changelog> {
+        let mut conn = self.pool.acquire().await?;
+        let since_text = since.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let new_arrivals = sqlx::query_as!(
+            ChangelogProduct,
+            r#"select saq_code, name, price_cents, availability,
+                created_at as "changed_at: DateTime<Utc>"
+            from products where created_at >= ?1
+            order by created_at, saq_code"#,
+            since_text
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let delistings = sqlx::query_as!(
+            ChangelogProduct,
+            r#"select p.saq_code, p.name, p.price_cents, pc.new_availability as availability,
+                pc.changed_at as "changed_at: DateTime<Utc>"
+            from product_changes pc join products p on p.id = pc.product_id
+            where pc.changed_at >= ?1
+            and pc.new_availability in ('discontinued', 'sold_out')
+            and pc.old_availability not in ('discontinued', 'sold_out')
+            order by pc.changed_at, p.saq_code"#,
+            since_text
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let price_drops = sqlx::query_as!(
+            PriceDrop,
+            r#"select p.saq_code, p.name, pc.old_price_cents, pc.new_price_cents,
+                (pc.old_price_cents - pc.new_price_cents) * 100.0 / pc.old_price_cents
+                    as "drop_percent!: f64",
+                pc.changed_at as "changed_at: DateTime<Utc>"
+            from product_changes pc join products p on p.id = pc.product_id
+            where pc.changed_at >= ?1
+            and (pc.old_price_cents - pc.new_price_cents) * 100.0 / pc.old_price_cents >= ?2
+            order by 5 desc, p.saq_code"#,
+            since_text,
+            min_drop_percent
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(Changelog {
+            since,
+            new_arrivals,
+            delistings,
+            price_drops,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use chrono::{TimeZone, Utc};
+    use color_eyre::eyre::Result;
+
+    /// Inserts a product first crawled at `created_at`, returning its id.
+    async fn insert_created_at(db: &TestDb, saq_code: &str, created_at: &str) -> Result<i64> {
+        let id = db.insert_product(saq_code, saq_code, 1000).await?;
+        sqlx::query("update products set created_at = ?2 where id = ?1")
+            .bind(id)
+            .bind(created_at)
+            .execute(db.pool())
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Records a change to the product with the given id at `changed_at`.
+    async fn insert_change(
+        db: &TestDb,
+        product_id: i64,
+        prices: (i64, i64),
+        availabilities: (&str, &str),
+        changed_at: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability, changed_at)
+            values (?1, ?2, ?3, ?4, ?5, ?6)"#,
+        )
+        .bind(product_id)
+        .bind(prices.0)
+        .bind(prices.1)
+        .bind(availabilities.0)
+        .bind(availabilities.1)
+        .bind(changed_at)
+        .execute(db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_changelog() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let old = insert_created_at(&db, "old", "2026-01-01 00:00:00").await?;
+        insert_created_at(&db, "new", "2026-10-10 00:00:00").await?;
+
+        let stock = ("in_stock", "in_stock");
+        insert_change(&db, old, (2000, 1000), stock, "2026-10-01 00:00:00").await?;
+        insert_change(&db, old, (1000, 950), stock, "2026-10-11 00:00:00").await?;
+        insert_change(&db, old, (1000, 800), stock, "2026-10-12 00:00:00").await?;
+        insert_change(
+            &db,
+            old,
+            (800, 800),
+            ("in_stock", "discontinued"),
+            "2026-10-13 00:00:00",
+        )
+        .await?;
+
+        let changelog = db
+            .changelog(Utc.with_ymd_and_hms(2026, 10, 9, 0, 0, 0).unwrap(), 10.0)
+            .await?;
+
+        let codes = |products: &[super::ChangelogProduct]| {
+            products
+                .iter()
+                .map(|product| product.saq_code.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["new"], codes(&changelog.new_arrivals));
+        assert_eq!(vec!["old"], codes(&changelog.delistings));
+        assert_eq!("discontinued", changelog.delistings[0].availability);
+
+        assert_eq!(1, changelog.price_drops.len());
+        assert_eq!(800, changelog.price_drops[0].new_price_cents);
+        assert_eq!(20.0, changelog.price_drops[0].drop_percent);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Checking the database for corruption and broken invariants, i.e. from a
+//! cron health check.
+
+use super::Client;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sqlx::Row;
+
+/// Number of offending rows listed in a [`Problem`]'s message.
+const MAX_EXAMPLES: usize = 10;
+
+/// Something wrong with the database.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    /// The check that failed, i.e. `integrity_check`.
+    pub check: &'static str,
+    /// What's wrong.
+    pub message: String,
+}
+
+/// Lists up to [`MAX_EXAMPLES`] offending `rows`.
+fn examples(rows: &[String]) -> String {
+    let listed = rows
+        .iter()
+        .take(MAX_EXAMPLES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match rows.len() > MAX_EXAMPLES {
+        true => format!("{listed} and {} more", rows.len() - MAX_EXAMPLES),
+        false => listed,
+    }
+}
+
+impl Client {
+    /// Runs SQLite's own consistency checks along with checks of invariants
+    /// the crawler relies on, returning the problems found.
+    pub async fn check(&self) -> Result<Vec<Problem>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut problems = vec![];
+
+        let integrity: Vec<String> = sqlx::query_scalar("pragma integrity_check")
+            .fetch_all(&mut conn)
+            .await?;
+        problems.extend(
+            integrity
+                .into_iter()
+                .filter(|message| message != "ok")
+                .map(|message| Problem {
+                    check: "integrity_check",
+                    message,
+                }),
+        );
+
+        let foreign_keys =
+            sqlx::query(r#"select "table", rowid, parent from pragma_foreign_key_check"#)
+                .fetch_all(&mut conn)
+                .await?;
+        problems.extend(foreign_keys.iter().map(|row| Problem {
+            check: "foreign_key_check",
+            message: format!(
+                "row {} of {} refers to a missing row of {}",
+                row.get::<Option<i64>, _>(1)
+                    .map_or_else(|| "?".to_string(), |rowid| rowid.to_string()),
+                row.get::<String, _>(0),
+                row.get::<String, _>(2)
+            ),
+        }));
+
+        let duplicates = sqlx::query_scalar!(
+            r#"select saq_code as "saq_code!" from products group by saq_code having count(*) > 1 order by saq_code"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+        if !duplicates.is_empty() {
+            problems.push(Problem {
+                check: "unique_saq_codes",
+                message: format!(
+                    "{} SAQ codes belong to several products: {}",
+                    duplicates.len(),
+                    examples(&duplicates)
+                ),
+            });
+        }
+
+        let uncategorized = sqlx::query_scalar!(
+            r#"select saq_code from products
+            where id not in (select product_id from product_categories)
+            order by saq_code"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+        if !uncategorized.is_empty() {
+            problems.push(Problem {
+                check: "product_categories",
+                message: format!(
+                    "{} products have no category: {}",
+                    uncategorized.len(),
+                    examples(&uncategorized)
+                ),
+            });
+        }
+
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+
+    #[test]
+    fn test_examples() {
+        let rows = (0..12).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!("0, 1", examples(&rows[..2]));
+        assert_eq!("0, 1, 2, 3, 4, 5, 6, 7, 8, 9 and 2 more", examples(&rows));
+    }
+
+    #[tokio::test]
+    async fn test_check() -> Result<()> {
+        let db = TestDb::new().await?;
+        assert_eq!(Vec::<Problem>::new(), db.check().await?);
+
+        db.insert_product("123", "Uncategorized", 100).await?;
+
+        // Foreign keys are enforced, so they have to be turned off to break them
+        let mut conn = db.pool().acquire().await?;
+        sqlx::query("pragma foreign_keys = off")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("update products set producer_id = 42")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("pragma foreign_keys = on")
+            .execute(&mut conn)
+            .await?;
+        drop(conn);
+
+        let checks = db
+            .check()
+            .await?
+            .into_iter()
+            .map(|problem| problem.check)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["foreign_key_check", "product_categories"], checks);
+
+        Ok(())
+    }
+}
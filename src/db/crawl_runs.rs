@@ -0,0 +1,376 @@
+//! Bookkeeping for crawls and the errors encountered along the way.
+
+use super::{to_value_list, Client, DbSerialize};
+use crate::crawler::errors::{ErrorAction, ErrorClass};
+use crate::crawler::provenance::{FieldSource, Mismatch};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// The outcome of a crawl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlRunStatus {
+    /// The crawl went through the entire catalog.
+    Completed,
+    /// The crawl was aborted because of an error.
+    Failed,
+}
+
+/// How much of the catalog a crawl goes through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlRunMode {
+    /// The entire catalog.
+    Full,
+    /// Only the first few catalog pages, sorted by newest products first.
+    NewArrivals,
+    /// Products previously persisted as partial or missing key fields,
+    /// regardless of the catalog.
+    Completion,
+    /// The entire catalog, only fetching the pages of new products and of
+    /// those whose listing changed.
+    Refresh,
+}
+
+/// An entry in the `skip_list` table.
+#[derive(Debug, Serialize)]
+pub struct SkipListEntry {
+    /// The URL of the page being skipped.
+    pub url: String,
+    /// The string representation of the [`ErrorClass`] that got the page skipped.
+    pub error_class: String,
+    /// When the page was added to the skip list.
+    pub created_at: DateTime<Utc>,
+    /// When the page will start being crawled again.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// An entry in the `field_mismatches` table.
+#[derive(Debug, Serialize)]
+pub struct FieldMismatch {
+    /// The SAQ code of the product.
+    pub saq_code: String,
+    /// The name of the mismatched field in the `products` table.
+    pub field: String,
+    /// The value from the product's catalog listing.
+    pub listing_value: String,
+    /// The value from the product's page.
+    pub product_page_value: String,
+    /// The string representation of the [`FieldSource`] whose value was kept.
+    pub chosen: String,
+}
+
+/// The tags and note an operator attached to a crawl (see
+/// [`Config::crawl_tags`](crate::config::Config::crawl_tags)).
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CrawlRunLabel {
+    /// The labelled crawl.
+    pub crawl_run_id: i64,
+    /// The string representation of the crawl's [`CrawlRunMode`].
+    pub mode: String,
+    /// When the crawl started.
+    pub started_at: DateTime<Utc>,
+    /// The crawl's tags (i.e. `weekly`).
+    pub tags: Vec<String>,
+    /// A free-form note about the crawl (i.e. `after parser fix`).
+    pub note: Option<String>,
+}
+
+impl Client {
+    /// Inserts a new row in the `crawl_runs` table with a `running` status.
+    ///
+    /// Returns the row's `id`.
+    pub async fn start_crawl_run(&self, mode: CrawlRunMode) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+        let mode = mode.db_serialize();
+
+        // Not using `returning id` with `fetch_one`, which leaves the statement
+        // (and so the insert) pending until the connection is used again
+        let id = sqlx::query!("insert into crawl_runs (mode) values (?1)", mode)
+            .execute(&mut conn)
+            .await?
+            .last_insert_rowid();
+
+        Ok(id)
+    }
+
+    /// Sets the final `status` of a crawl and records when it finished.
+    pub async fn finish_crawl_run(&self, crawl_run_id: i64, status: CrawlRunStatus) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let status = status.db_serialize();
+
+        sqlx::query!(
+            r#"update crawl_runs set status = ?2, finished_at = (datetime('now', 'utc'))
+            where id = ?1"#,
+            crawl_run_id,
+            status
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Attaches `tags` and a `note` to a crawl, replacing any previous ones.
+    pub async fn label_crawl_run(
+        &self,
+        crawl_run_id: i64,
+        tags: &[String],
+        note: Option<&str>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let tags = serde_json::to_string(tags)?;
+
+        sqlx::query!(
+            r#"update crawl_runs set tags = ?2, note = ?3 where id = ?1"#,
+            crawl_run_id,
+            tags,
+            note
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the crawls with tags or a note, oldest first.
+    pub async fn crawl_run_labels(&self) -> Result<Vec<CrawlRunLabel>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"select id, mode, started_at as "started_at: DateTime<Utc>", tags, note
+            from crawl_runs where tags != '[]' or note is not null
+            order by id"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(CrawlRunLabel {
+                    crawl_run_id: row.id,
+                    mode: row.mode,
+                    started_at: row.started_at,
+                    tags: serde_json::from_str(&row.tags)?,
+                    note: row.note,
+                })
+            })
+            .collect()
+    }
+
+    /// Records an error encountered while crawling `url` in the `crawl_errors` table,
+    /// along with the [`ErrorAction`] taken as a result.
+    pub async fn record_crawl_error(
+        &self,
+        crawl_run_id: i64,
+        url: &str,
+        error_class: ErrorClass,
+        action: ErrorAction,
+        message: &str,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let error_class = error_class.db_serialize();
+        let action = action.db_serialize();
+
+        sqlx::query!(
+            r#"insert into crawl_errors (crawl_run_id, url, error_class, action, message)
+            values (?1, ?2, ?3, ?4, ?5)"#,
+            crawl_run_id,
+            url,
+            error_class,
+            action,
+            message
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a field of the product with the given `saq_code` whose listing
+    /// and product page values differ, along with the [`FieldSource`] whose
+    /// value was kept.
+    pub async fn record_field_mismatch(
+        &self,
+        crawl_run_id: i64,
+        saq_code: &str,
+        mismatch: &Mismatch,
+        chosen: FieldSource,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let chosen = chosen.db_serialize();
+
+        sqlx::query!(
+            r#"insert into field_mismatches
+            (crawl_run_id, saq_code, field, listing_value, product_page_value, chosen)
+            values (?1, ?2, ?3, ?4, ?5, ?6)"#,
+            crawl_run_id,
+            saq_code,
+            mismatch.field,
+            mismatch.listing,
+            mismatch.product_page,
+            chosen
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the field mismatches recorded during the given crawl, ordered by
+    /// SAQ code and field.
+    pub async fn field_mismatches(&self, crawl_run_id: i64) -> Result<Vec<FieldMismatch>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let mismatches = sqlx::query_as!(
+            FieldMismatch,
+            r#"select saq_code, field, listing_value, product_page_value, chosen
+            from field_mismatches where crawl_run_id = ?1 order by saq_code, field"#,
+            crawl_run_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(mismatches)
+    }
+
+    /// Returns the number of errors recorded during the given crawl which
+    /// weren't retried.
+    pub async fn count_crawl_errors(&self, crawl_run_id: i64) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+
+        let count = sqlx::query_scalar!(
+            r#"select count(*) as "count!: i64" from crawl_errors
+            where crawl_run_id = ?1 and (action is null or action != 'retry')"#,
+            crawl_run_id
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Returns the number of distinct crawls during which `url` failed with a
+    /// [page-specific](ErrorClass::is_page_specific) error that wasn't retried.
+    pub async fn count_failed_crawl_runs(&self, url: &str) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+        let page_specific = to_value_list(
+            ErrorClass::ALL
+                .iter()
+                .filter(|class| class.is_page_specific())
+                .map(|class| class.db_serialize()),
+        );
+
+        let count = sqlx::query_scalar!(
+            r#"select count(distinct crawl_run_id) as "count!: i64" from crawl_errors
+            where url = ?1 and error_class in (select value from json_each(?2))
+            and (action is null or action != 'retry')"#,
+            url,
+            page_specific
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Uses an upsert to add `url` to the skip list for `expiry_days` days,
+    /// pushing back the expiry if it is already present.
+    pub async fn add_to_skip_list(
+        &self,
+        url: &str,
+        error_class: ErrorClass,
+        expiry_days: u32,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let error_class = error_class.db_serialize();
+        let expiry_modifier = format!("+{expiry_days} days");
+
+        sqlx::query!(
+            r#"insert into skip_list (url, error_class, expires_at)
+            values (?1, ?2, datetime('now', 'utc', ?3))
+            on conflict do update set error_class=excluded.error_class, expires_at=excluded.expires_at"#,
+            url,
+            error_class,
+            expiry_modifier
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns all skip list entries that haven't expired yet, ordered by URL.
+    pub async fn active_skip_list(&self) -> Result<Vec<SkipListEntry>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let entries = sqlx::query_as!(
+            SkipListEntry,
+            r#"select url, error_class,
+                created_at as "created_at: DateTime<Utc>",
+                expires_at as "expires_at: DateTime<Utc>"
+            from skip_list where expires_at > datetime('now', 'utc') order by url"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Removes `url` from the skip list.
+    ///
+    /// Returns whether there was an entry to remove.
+    pub async fn remove_from_skip_list(&self, url: &str) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+
+        let result = sqlx::query!(r#"delete from skip_list where url = ?1"#, url)
+            .execute(&mut conn)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns the `id` of the most recently started [full](CrawlRunMode::Full)
+    /// crawl, if any.
+    pub async fn latest_crawl_run_id(&self) -> Result<Option<i64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let id = sqlx::query_scalar!(
+            r#"select max(id) as "id: i64" from crawl_runs where mode = 'full'"#
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Returns when the given crawl started, or `None` if there is no such crawl.
+    pub async fn crawl_run_started_at(&self, crawl_run_id: i64) -> Result<Option<DateTime<Utc>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let started_at = sqlx::query_scalar!(
+            r#"select started_at as "started_at: DateTime<Utc>" from crawl_runs where id = ?1"#,
+            crawl_run_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(started_at)
+    }
+
+    /// Returns the SAQ codes of all products persisted during the given crawl,
+    /// based on their `updated_at`.
+    pub async fn crawl_run_saq_codes(&self, crawl_run_id: i64) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let saq_codes = sqlx::query_scalar!(
+            r#"select p.saq_code from products p, crawl_runs cr
+            where cr.id = ?1 and p.updated_at >= cr.started_at
+            and p.updated_at <= coalesce(cr.finished_at, datetime('now', 'utc'))"#,
+            crawl_run_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(saq_codes)
+    }
+}
@@ -0,0 +1,200 @@
+//! Checkpoints allowing interrupted crawls to be resumed, see
+//! [`checkpoint`](crate::crawler::checkpoint).
+
+use super::{Client, CrawlRunMode, DbSerialize};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// A product that was queued but not yet persisted or skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnfinishedProduct {
+    /// The product page URL.
+    pub url: String,
+    /// The product's SKU (i.e. its SAQ code).
+    pub sku: String,
+}
+
+/// What a crawl lists products from and when it stops, which a resumed crawl
+/// has to share with the interrupted one for its checkpoint to make sense.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrawlListing {
+    /// The listing's URL, the whole catalog's or a category's.
+    pub url: String,
+    /// See [`Config::page_size`](crate::config::Config::page_size).
+    pub page_size: Option<u32>,
+    /// The number of pages after which the crawl stops, if any.
+    pub max_pages: Option<u32>,
+    /// See [`Config::max_products`](crate::config::Config::max_products).
+    pub max_products: Option<usize>,
+    /// Whether the crawl stops at the first page of products which were all
+    /// crawled recently.
+    pub until_known: bool,
+}
+
+/// How far a crawl got, as of its last checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlState {
+    /// What the crawl lists products from.
+    pub listing: CrawlListing,
+    /// The first catalog page whose products weren't all queued.
+    pub next_page: u32,
+    /// The products queued from earlier pages which weren't persisted or
+    /// skipped yet.
+    pub pending: Vec<UnfinishedProduct>,
+}
+
+impl CrawlState {
+    /// The state of a crawl of `listing` which didn't start yet.
+    pub fn new(listing: CrawlListing) -> CrawlState {
+        CrawlState {
+            listing,
+            next_page: 1,
+            pending: vec![],
+        }
+    }
+}
+
+impl Client {
+    /// Records the latest checkpoint of the given crawl, replacing any
+    /// previous one.
+    pub async fn save_crawl_state(&self, crawl_run_id: i64, state: &CrawlState) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let listing = serde_json::to_string(&state.listing)?;
+        let pending = serde_json::to_string(&state.pending)?;
+
+        sqlx::query!(
+            r#"insert into crawl_state (crawl_run_id, listing, next_page, pending) values (?1, ?2, ?3, ?4)
+            on conflict do update set listing=excluded.listing, next_page=excluded.next_page,
+                pending=excluded.pending, updated_at=datetime('now', 'utc')"#,
+            crawl_run_id,
+            listing,
+            state.next_page,
+            pending
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the checkpoint of the given crawl, once it no longer needs to
+    /// be resumed.
+    pub async fn clear_crawl_state(&self, crawl_run_id: i64) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query!(
+            "delete from crawl_state where crawl_run_id = ?1",
+            crawl_run_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the id and last checkpoint of the most recent crawl in the
+    /// given `mode`, if it didn't complete and has a checkpoint recording
+    /// its [listing](CrawlListing).
+    pub async fn resumable_crawl_run(
+        &self,
+        mode: CrawlRunMode,
+    ) -> Result<Option<(i64, CrawlState)>> {
+        let mut conn = self.pool.acquire().await?;
+        let mode = mode.db_serialize();
+
+        let row = sqlx::query!(
+            r#"select cs.crawl_run_id, cs.listing as "listing!", cs.next_page, cs.pending
+            from crawl_state cs join crawl_runs cr on cr.id = cs.crawl_run_id
+            where cr.id = (select max(id) from crawl_runs where mode = ?1)
+            and cr.status != 'completed' and cs.listing is not null"#,
+            mode
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some((
+                row.crawl_run_id,
+                CrawlState {
+                    listing: serde_json::from_str(&row.listing)?,
+                    next_page: row.next_page as u32,
+                    pending: serde_json::from_str(&row.pending)?,
+                },
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Marks an interrupted crawl as running again.
+    pub async fn resume_crawl_run(&self, crawl_run_id: i64) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query!(
+            "update crawl_runs set status = 'running', finished_at = null where id = ?1",
+            crawl_run_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use crate::db::CrawlRunStatus;
+
+    #[tokio::test]
+    async fn test_resumable_crawl_run() -> Result<()> {
+        let db = TestDb::new().await?;
+        assert_eq!(None, db.resumable_crawl_run(CrawlRunMode::Full).await?);
+
+        let listing = CrawlListing {
+            url: "https://www.saq.com/en/products/wine".to_string(),
+            page_size: Some(96),
+            max_pages: None,
+            max_products: Some(100),
+            until_known: false,
+        };
+        let state = CrawlState {
+            listing: listing.clone(),
+            next_page: 12,
+            pending: vec![UnfinishedProduct {
+                url: "https://www.saq.com/en/123".to_string(),
+                sku: "123".to_string(),
+            }],
+        };
+
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        db.save_crawl_state(crawl_run_id, &CrawlState::new(listing))
+            .await?;
+        db.save_crawl_state(crawl_run_id, &state).await?;
+        db.finish_crawl_run(crawl_run_id, CrawlRunStatus::Failed)
+            .await?;
+
+        assert_eq!(
+            Some((crawl_run_id, state.clone())),
+            db.resumable_crawl_run(CrawlRunMode::Full).await?
+        );
+        assert_eq!(None, db.resumable_crawl_run(CrawlRunMode::Refresh).await?);
+
+        db.resume_crawl_run(crawl_run_id).await?;
+        assert_eq!(
+            Some((crawl_run_id, state)),
+            db.resumable_crawl_run(CrawlRunMode::Full).await?
+        );
+
+        // Only the most recent crawl can be resumed
+        let next_crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        assert_eq!(None, db.resumable_crawl_run(CrawlRunMode::Full).await?);
+
+        db.clear_crawl_state(crawl_run_id).await?;
+        db.finish_crawl_run(next_crawl_run_id, CrawlRunStatus::Completed)
+            .await?;
+        assert_eq!(None, db.resumable_crawl_run(CrawlRunMode::Full).await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Reading every product along with its lookups and relations, for the
+//! [export](crate::export) written by `ransaq export`.
+
+use super::Client;
+use chrono::{DateTime, NaiveDate, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// A product's row, with the lookups it refers to resolved to their names.
+#[derive(Debug, Serialize)]
+pub struct ExportProduct {
+    /// The product's id, which its relations refer to.
+    #[serde(skip)]
+    pub id: i64,
+    /// The product's SAQ code.
+    pub saq_code: String,
+    /// The product's UPC code, if known.
+    pub upc_code: Option<String>,
+    /// The product's name.
+    pub name: String,
+    /// The product's description.
+    pub description: Option<String>,
+    /// The URL of an image of the product.
+    pub image_url: Option<String>,
+    /// The product's availability (i.e. `in_stock`).
+    pub availability: String,
+    /// The product's condition (i.e. `new`).
+    pub item_condition: String,
+    /// The product's current price, in cents.
+    pub price_cents: i64,
+    /// Until when the price is valid, if it's temporary.
+    pub price_valid_until: Option<NaiveDate>,
+    /// The product's price before the discount in cents, if it's on sale.
+    pub regular_price_cents: Option<i64>,
+    /// The product's producer.
+    pub producer: Option<String>,
+    /// The product's promoting agent.
+    pub promoting_agent: Option<String>,
+    /// The product's brand.
+    pub brand: Option<String>,
+    /// The product's manufacturer.
+    pub manufacturer: Option<String>,
+    /// Who sells the product.
+    pub seller: Option<String>,
+    /// The product's alcohol by volume, as a percentage.
+    pub abv_percentage: Option<f64>,
+    /// Number of containers the product comes in.
+    pub container_count: Option<i64>,
+    /// Volume of each container, in milliliters.
+    pub container_milliliters: Option<i64>,
+    /// The product's color.
+    pub color: Option<String>,
+    /// The region the product comes from.
+    pub region: Option<String>,
+    /// The country the product comes from.
+    pub country: Option<String>,
+    /// How the product relates to Québec (i.e. `made_in_quebec`).
+    pub product_of_quebec: Option<String>,
+    /// Whether the sugar content is exact (`=`) or a bound (`<` or `>`).
+    pub sugar_content_equality: Option<String>,
+    /// The product's sugar content, in grams per liter.
+    pub sugar_content_grams_per_liter: Option<f64>,
+    /// The product's regulated designation.
+    pub regulated_designation: Option<String>,
+    /// The product's designation of origin.
+    pub designation_of_origin: Option<String>,
+    /// The product's classification.
+    pub classification: Option<String>,
+    /// The product's average rating, if it has one.
+    pub rating_value: Option<f64>,
+    /// The number of ratings the average is based on.
+    pub rating_count: Option<i64>,
+    /// Whether the product was captured from its catalog listing only.
+    pub partial: bool,
+    /// When the product was first crawled.
+    pub created_at: DateTime<Utc>,
+    /// When the product was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A grape variety a product is made from.
+#[derive(Debug)]
+pub struct ExportGrapeVariety {
+    /// The product's id.
+    pub product_id: i64,
+    /// The grape variety's name.
+    pub name: String,
+    /// The share of the product made from it, if known.
+    pub percentage: Option<i64>,
+}
+
+/// A category a product belongs to.
+#[derive(Debug)]
+pub struct ExportCategory {
+    /// The product's id.
+    pub product_id: i64,
+    /// The category's name.
+    pub name: String,
+    /// The category's listing URL.
+    pub url: String,
+}
+
+/// A special feature of a product.
+#[derive(Debug)]
+pub struct ExportSpecialFeature {
+    /// The product's id.
+    pub product_id: i64,
+    /// The special feature's name.
+    pub name: String,
+}
+
+/// A dish a product goes with.
+#[derive(Debug)]
+pub struct ExportFoodPairing {
+    /// The product's id.
+    pub product_id: i64,
+    /// The dish's name.
+    pub name: String,
+}
+
+/// A product's tasting notes.
+#[derive(Debug)]
+pub struct ExportTastingNotes {
+    /// The product's id.
+    pub product_id: i64,
+    /// The product's taste tag.
+    pub taste_tag: Option<String>,
+    /// The product's aromas, as a JSON array.
+    pub aromas: String,
+    /// How acidic the product is.
+    pub acidity: Option<String>,
+    /// How sweet the product is.
+    pub sweetness: Option<String>,
+    /// The product's body.
+    pub body: Option<String>,
+    /// How the product feels in the mouth.
+    pub mouthfeel: Option<String>,
+    /// How much the product tastes of wood.
+    pub wood: Option<String>,
+    /// The recommended serving temperature.
+    pub serving_temperature: Option<String>,
+}
+
+/// An identifier of a product besides its SAQ code.
+#[derive(Debug)]
+pub struct ExportIdentifier {
+    /// The product's id.
+    pub product_id: i64,
+    /// The identifier's scheme (i.e. `gtin13`).
+    pub scheme: String,
+    /// The identifier.
+    pub value: String,
+}
+
+/// Every product and its relations, each ordered by product.
+#[derive(Debug)]
+pub struct ExportRows {
+    /// The products, by SAQ code.
+    pub products: Vec<ExportProduct>,
+    /// The products' grape varieties, by name.
+    pub grape_varieties: Vec<ExportGrapeVariety>,
+    /// The products' categories, from broadest to most specific.
+    pub categories: Vec<ExportCategory>,
+    /// The products' special features, by name.
+    pub special_features: Vec<ExportSpecialFeature>,
+    /// The products' food pairings, in the order they were added.
+    pub food_pairings: Vec<ExportFoodPairing>,
+    /// The tasting notes of the products which have some.
+    pub tasting_notes: Vec<ExportTastingNotes>,
+    /// The products' identifiers, by scheme.
+    pub identifiers: Vec<ExportIdentifier>,
+}
+
+impl Client {
+    /// Reads every product and its relations, see [`ExportRows`].
+    pub async fn export_rows(&self) -> Result<ExportRows> {
+        self.export_rows_matching(None).await
+    }
+
+    /// Reads the product with the given SAQ code and its relations, `None` if
+    /// there's no such product.
+    pub async fn export_product_rows(&self, saq_code: &str) -> Result<Option<ExportRows>> {
+        let rows = self.export_rows_matching(Some(saq_code)).await?;
+
+        Ok((!rows.products.is_empty()).then_some(rows))
+    }
+
+    /// Reads the products with the given SAQ code (all of them if `None`) and
+    /// their relations.
+    async fn export_rows_matching(&self, saq_code: Option<&str>) -> Result<ExportRows> {
+        let mut conn = self.pool.acquire().await?;
+
+        let products = sqlx::query_as!(
+            ExportProduct,
+            r#"select p.id as "id!", p.saq_code as "saq_code!", p.upc_code,
+                p.name as "name!", p.description, p.image_url,
+                p.availability as "availability!", p.item_condition as "item_condition!",
+                p.price_cents as "price_cents!",
+                p.price_valid_until as "price_valid_until: NaiveDate",
+                p.regular_price_cents, producers.name as "producer?", promoting_agents.name as "promoting_agent?",
+                brands.name as "brand?", manufacturers.name as "manufacturer?",
+                sellers.name as "seller?", p.abv_percentage, p.container_count,
+                p.container_milliliters, colors.name as "color?", regions.name as "region?",
+                countries.name as "country?", p.product_of_quebec, p.sugar_content_equality,
+                p.sugar_content_grams_per_liter,
+                regulated_designations.name as "regulated_designation?",
+                designations_of_origin.name as "designation_of_origin?",
+                classifications.name as "classification?", p.rating_value, p.rating_count,
+                p.partial as "partial!: bool",
+                p.created_at as "created_at!: DateTime<Utc>",
+                p.updated_at as "updated_at!: DateTime<Utc>"
+            from products p
+            left join producers on producers.id = p.producer_id
+            left join promoting_agents on promoting_agents.id = p.promoting_agent_id
+            left join brands on brands.id = p.brand_id
+            left join manufacturers on manufacturers.id = p.manufacturer_id
+            left join sellers on sellers.id = p.seller_id
+            left join colors on colors.id = p.color_id
+            left join regions on regions.id = p.region_id
+            left join countries on countries.id = p.country_id
+            left join regulated_designations on regulated_designations.id = p.regulated_designation_id
+            left join designations_of_origin on designations_of_origin.id = p.designation_of_origin_id
+            left join classifications on classifications.id = p.classification_id
+            where ?1 is null or p.saq_code = ?1
+            order by p.saq_code"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let grape_varieties = sqlx::query_as!(
+            ExportGrapeVariety,
+            r#"select pgv.product_id, gv.name, pgv.percentage
+            from product_grape_varieties pgv
+            join grape_varieties gv on gv.id = pgv.grape_variety_id
+            where ?1 is null or pgv.product_id in (select id from products where saq_code = ?1)
+            order by pgv.product_id, gv.name"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        // Categories are linked to products from broadest to most specific
+        let categories = sqlx::query_as!(
+            ExportCategory,
+            r#"select pc.product_id, c.name, c.url
+            from product_categories pc
+            join categories c on c.id = pc.category_id
+            where ?1 is null or pc.product_id in (select id from products where saq_code = ?1)
+            order by pc.product_id, pc.id"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let special_features = sqlx::query_as!(
+            ExportSpecialFeature,
+            r#"select psf.product_id, sf.name
+            from product_special_features psf
+            join special_features sf on sf.id = psf.special_feature_id
+            where ?1 is null or psf.product_id in (select id from products where saq_code = ?1)
+            order by psf.product_id, sf.name"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let food_pairings = sqlx::query_as!(
+            ExportFoodPairing,
+            r#"select pfp.product_id, fp.name
+            from product_food_pairings pfp
+            join food_pairings fp on fp.id = pfp.food_pairing_id
+            where ?1 is null or pfp.product_id in (select id from products where saq_code = ?1)
+            order by pfp.product_id, pfp.id"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let tasting_notes = sqlx::query_as!(
+            ExportTastingNotes,
+            r#"select product_id, taste_tag, aromas, acidity, sweetness, body, mouthfeel, wood,
+                serving_temperature
+            from tasting_notes
+            where ?1 is null or product_id in (select id from products where saq_code = ?1)
+            order by product_id"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let identifiers = sqlx::query_as!(
+            ExportIdentifier,
+            r#"select product_id, scheme, value
+            from product_identifiers
+            where ?1 is null or product_id in (select id from products where saq_code = ?1)
+            order by product_id, scheme"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(ExportRows {
+            products,
+            grape_varieties,
+            categories,
+            special_features,
+            food_pairings,
+            tasting_notes,
+            identifiers,
+        })
+    }
+}
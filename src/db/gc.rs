@@ -0,0 +1,155 @@
+//! Cleaning up lookup rows (producers, regions, categories, etc.) that no
+//! product refers to anymore.
+//!
+//! Lookups are never deleted as products change or get
+//! [purged](Client::purge_product), so long-lived databases accumulate
+//! thousands of unused ones. Crawls cache lookup ids, so this shouldn't run
+//! while one is in progress.
+
+use super::merge::PRODUCT_LOOKUP_COLUMNS;
+use super::Client;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sqlx::Connection;
+use std::collections::BTreeMap;
+
+/// Lookup tables referenced from a table linking them to products, along
+/// with that table and the referencing column.
+const LINKED_LOOKUP_TABLES: [(&str, &str, &str); 3] = [
+    (
+        "grape_varieties",
+        "product_grape_varieties",
+        "grape_variety_id",
+    ),
+    (
+        "special_features",
+        "product_special_features",
+        "special_feature_id",
+    ),
+    ("food_pairings", "product_food_pairings", "food_pairing_id"),
+];
+
+/// What [`gc`](Client::gc) deleted (or would have, for dry runs).
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    /// Number of orphaned rows in each lookup table, i.e. `producers`.
+    pub orphans: BTreeMap<&'static str, u64>,
+}
+
+impl Client {
+    /// Deletes lookup rows no product refers to. Nothing is deleted if
+    /// `dry_run` is set, but the report is the same.
+    ///
+    /// Categories are kept as long as one of their subcategories is.
+    pub async fn gc(&self, dry_run: bool) -> Result<GcReport> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        let mut report = GcReport::default();
+
+        let references = PRODUCT_LOOKUP_COLUMNS
+            .iter()
+            .map(|(column, table)| (*table, "products", *column))
+            .chain(LINKED_LOOKUP_TABLES);
+        for (table, referencing_table, column) in references {
+            // `not in` never matches if the subquery returns nulls
+            let orphans = sqlx::query(&format!(
+                "delete from {table} where id not in (select {column} from {referencing_table} where {column} is not null)"
+            ))
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+            report.orphans.insert(table, orphans);
+        }
+
+        // Deleting unused leaf categories can leave their parents unused too.
+        // Categories with price history are kept for the sake of that history.
+        let mut orphans = 0;
+        loop {
+            let deleted = sqlx::query(
+                r#"delete from categories
+                where id not in (select category_id from product_categories)
+                and id not in (select category_id from category_price_history)
+                and id not in (select parent_category_id from categories where parent_category_id is not null)"#,
+            )
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+
+            if deleted == 0 {
+                break;
+            }
+            orphans += deleted;
+        }
+        report.orphans.insert("categories", orphans);
+
+        match dry_run {
+            true => transaction.rollback().await?,
+            false => transaction.commit().await?,
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_gc() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let used_producer = db.upsert_producer("Used").await?;
+        db.upsert_producer("Unused").await?;
+        db.upsert_region("Unused").await?;
+
+        let wine = db
+            .upsert_category("Wine", "https://www.saq.com/wine", None)
+            .await?;
+        let red = db
+            .upsert_category("Red", "https://www.saq.com/wine/red", Some(wine))
+            .await?;
+        let cider = db
+            .upsert_category("Cider", "https://www.saq.com/cider", None)
+            .await?;
+        db.upsert_category("Ice", "https://www.saq.com/cider/ice", Some(cider))
+            .await?;
+
+        let product_id = db.insert_product("123", "Used", 100).await?;
+        sqlx::query("update products set producer_id = ?1")
+            .bind(used_producer)
+            .execute(db.pool())
+            .await?;
+        sqlx::query("insert into product_categories (product_id, category_id) values (?1, ?2)")
+            .bind(product_id)
+            .bind(red)
+            .execute(db.pool())
+            .await?;
+
+        let report = db.gc(true).await?;
+        assert_eq!(Some(&1), report.orphans.get("producers"));
+        assert_eq!(Some(&1), report.orphans.get("regions"));
+        assert_eq!(Some(&0), report.orphans.get("colors"));
+        assert_eq!(Some(&2), report.orphans.get("categories"));
+
+        assert_eq!(report, db.gc(false).await?);
+        assert!(db
+            .gc(false)
+            .await?
+            .orphans
+            .values()
+            .all(|orphans| *orphans == 0));
+
+        // Used rows keep their ids
+        assert_eq!(used_producer, db.upsert_producer("Used").await?);
+        assert_eq!(
+            wine,
+            db.upsert_category("Wine", "https://www.saq.com/wine", None)
+                .await?
+        );
+
+        Ok(())
+    }
+}
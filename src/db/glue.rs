@@ -1,6 +1,10 @@
 //! Serialization logic necessary to put [`saq`](crate::saq) types
 //! into the database.
 
+use super::{CrawlRunMode, CrawlRunStatus, IdentifierScheme};
+use crate::crawler::errors::{ErrorAction, ErrorClass};
+use crate::crawler::provenance::FieldSource;
+use crate::saq::detailed_info::DetailedInfoSource;
 use crate::saq::detailed_info::ProductOfQuebec;
 use crate::saq::detailed_info::SugarContentEquality;
 use crate::saq::linked_data::ItemAvailability;
@@ -64,3 +68,245 @@ impl DbSerialize for crate::saq::detailed_info::SugarContentEquality {
         }
     }
 }
+
+impl DbSerialize for DetailedInfoSource {
+    fn db_serialize(&self) -> &str {
+        match self {
+            DetailedInfoSource::Selector => "selector",
+            DetailedInfoSource::FallbackSelector => "fallback_selector",
+            DetailedInfoSource::MageInit => "mage_init",
+        }
+    }
+}
+
+impl DbSerialize for ErrorClass {
+    fn db_serialize(&self) -> &str {
+        match self {
+            ErrorClass::HttpClientError => "http_client_error",
+            ErrorClass::HttpServerError => "http_server_error",
+            ErrorClass::Network => "network",
+            ErrorClass::Parse => "parse",
+            ErrorClass::Database => "database",
+        }
+    }
+}
+
+impl DbSerialize for ErrorAction {
+    fn db_serialize(&self) -> &str {
+        match self {
+            ErrorAction::Retry => "retry",
+            ErrorAction::Skip => "skip",
+            ErrorAction::Abort => "abort",
+        }
+    }
+}
+
+impl DbSerialize for FieldSource {
+    fn db_serialize(&self) -> &str {
+        match self {
+            FieldSource::Listing => "listing",
+            FieldSource::ProductPage => "product_page",
+        }
+    }
+}
+
+impl DbSerialize for CrawlRunMode {
+    fn db_serialize(&self) -> &str {
+        match self {
+            CrawlRunMode::Full => "full",
+            CrawlRunMode::NewArrivals => "new_arrivals",
+            CrawlRunMode::Completion => "completion",
+            CrawlRunMode::Refresh => "refresh",
+        }
+    }
+}
+
+impl DbSerialize for IdentifierScheme {
+    fn db_serialize(&self) -> &str {
+        match self {
+            IdentifierScheme::Upc => "upc",
+            IdentifierScheme::Gtin => "gtin",
+            IdentifierScheme::Gtin13 => "gtin13",
+            IdentifierScheme::Mpn => "mpn",
+        }
+    }
+}
+
+impl DbSerialize for CrawlRunStatus {
+    fn db_serialize(&self) -> &str {
+        match self {
+            CrawlRunStatus::Completed => "completed",
+            CrawlRunStatus::Failed => "failed",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::{Result, WrapErr};
+    use sqlx::Executor;
+
+    /// Builds a `Vec` of every listed variant of a fieldless enum, failing to
+    /// compile if any variant is missing so new ones can't go untested.
+    macro_rules! all_variants {
+        ($ty:ident { $($variant:ident),* $(,)? }) => {{
+            #[allow(dead_code)]
+            fn exhaustive(value: &$ty) {
+                match value {
+                    $($ty::$variant)|* => {}
+                }
+            }
+
+            vec![$($ty::$variant),*]
+        }};
+    }
+
+    /// Sets `table.column` to the serialized form of each of `values` on the
+    /// row inserted by the last statement in `setup`, asserting the column's
+    /// CHECK constraint accepts all of them and rejects anything else.
+    ///
+    /// Everything happens in a transaction that is rolled back afterwards.
+    async fn assert_check_accepts<T: DbSerialize>(
+        setup: &str,
+        table: &str,
+        column: &str,
+        values: &[T],
+    ) -> Result<()> {
+        let client = TestDb::new().await?;
+        let mut tx = client.pool.begin().await?;
+
+        (&mut tx).execute(setup).await?;
+
+        let update = format!("update {table} set {column} = ?1 where id = last_insert_rowid()");
+
+        for value in values {
+            let serialized = value.db_serialize();
+            let result = sqlx::query(&update)
+                .bind(serialized)
+                .execute(&mut tx)
+                .await
+                .wrap_err_with(|| format!("{table}.{column} rejected {serialized:?}"))?;
+            assert_eq!(1, result.rows_affected());
+        }
+
+        let invalid = sqlx::query(&update)
+            .bind("not a valid value")
+            .execute(&mut tx)
+            .await;
+        assert!(
+            invalid.is_err(),
+            "{table}.{column} accepted an invalid value"
+        );
+
+        tx.rollback().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_db_serialize_matches_check_constraints() -> Result<()> {
+        let product = r#"insert into products (saq_code, name, availability, item_condition, price_cents)
+            values ('check-constraints', 'Check constraints', 'in_stock', 'new', 100)"#;
+
+        let availabilities = all_variants!(ItemAvailability {
+            BackOrder,
+            Discontinued,
+            InStock,
+            InStoreOnly,
+            LimitedAvailability,
+            OnlineOnly,
+            OutOfStock,
+            PreOrder,
+            PreSale,
+            SoldOut,
+        });
+        assert_check_accepts(product, "products", "availability", &availabilities).await?;
+
+        let item_conditions = all_variants!(OfferItemCondition {
+            Damaged,
+            New,
+            Refurbished,
+            Used,
+        });
+        assert_check_accepts(product, "products", "item_condition", &item_conditions).await?;
+
+        let products_of_quebec = all_variants!(ProductOfQuebec {
+            BottledIn,
+            MadeIn,
+            Origine,
+        });
+        assert_check_accepts(
+            product,
+            "products",
+            "product_of_quebec",
+            &products_of_quebec,
+        )
+        .await?;
+
+        let equalities = all_variants!(SugarContentEquality {
+            GreaterThan,
+            LessThan,
+            Equal,
+        });
+        assert_check_accepts(product, "products", "sugar_content_equality", &equalities).await?;
+
+        let schemes = all_variants!(IdentifierScheme {
+            Upc,
+            Gtin,
+            Gtin13,
+            Mpn,
+        });
+        let identifier = format!(
+            "{product}; insert into product_identifiers (product_id, scheme, value)
+            values (last_insert_rowid(), 'upc', 'check-constraints')"
+        );
+        assert_check_accepts(&identifier, "product_identifiers", "scheme", &schemes).await?;
+
+        let crawl_run = "insert into crawl_runs default values";
+
+        let statuses = all_variants!(CrawlRunStatus { Completed, Failed });
+        assert_check_accepts(crawl_run, "crawl_runs", "status", &statuses).await?;
+
+        let modes = all_variants!(CrawlRunMode {
+            Full,
+            NewArrivals,
+            Completion,
+            Refresh
+        });
+        assert_check_accepts(crawl_run, "crawl_runs", "mode", &modes).await?;
+
+        let error_classes = all_variants!(ErrorClass {
+            HttpClientError,
+            HttpServerError,
+            Network,
+            Parse,
+            Database,
+        });
+
+        let crawl_error = r#"insert into crawl_runs default values;
+            insert into crawl_errors (crawl_run_id, url, error_class, message)
+            values (last_insert_rowid(), 'https://www.saq.com/en/check-constraints', 'parse', '')"#;
+        assert_check_accepts(crawl_error, "crawl_errors", "error_class", &error_classes).await?;
+
+        let actions = all_variants!(ErrorAction { Retry, Skip, Abort });
+        assert_check_accepts(crawl_error, "crawl_errors", "action", &actions).await?;
+
+        let sources = all_variants!(FieldSource {
+            Listing,
+            ProductPage
+        });
+        let field_mismatch = r#"insert into crawl_runs default values;
+            insert into field_mismatches
+            (crawl_run_id, saq_code, field, listing_value, product_page_value, chosen)
+            values (last_insert_rowid(), 'check-constraints', 'name', '', '', 'listing')"#;
+        assert_check_accepts(field_mismatch, "field_mismatches", "chosen", &sources).await?;
+
+        let skip_list = r#"insert into skip_list (url, error_class, expires_at)
+            values ('https://www.saq.com/en/check-constraints', 'parse', datetime('now', 'utc'))"#;
+        assert_check_accepts(skip_list, "skip_list", "error_class", &error_classes).await?;
+
+        Ok(())
+    }
+}
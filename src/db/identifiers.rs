@@ -0,0 +1,80 @@
+//! Identifiers used to match products against other catalogs.
+
+use super::{to_value_list, Client, DbSerialize};
+use color_eyre::eyre::Result;
+use sqlx::{Connection, SqliteConnection};
+
+/// The kinds of product identifiers stored in `product_identifiers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierScheme {
+    /// The UPC code listed in the product's detailed info.
+    Upc,
+    /// <https://schema.org/gtin>
+    Gtin,
+    /// <https://schema.org/gtin13>
+    Gtin13,
+    /// <https://schema.org/mpn>
+    Mpn,
+}
+
+impl Client {
+    /// Uses upserts to make sure there are rows in `product_identifiers` for
+    /// each of the provided `(scheme, value)` pairs.
+    ///
+    /// `updated_at` and `value` are always updated to the provided values.
+    ///
+    /// Any entries in `product_identifiers` for the given `product_id` using
+    /// other schemes are subsequently deleted.
+    pub async fn ensure_product_identifiers(
+        &self,
+        product_id: i64,
+        identifiers: Vec<(IdentifierScheme, &str)>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        Client::ensure_product_identifiers_with(&mut transaction, product_id, identifiers).await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Same as [`ensure_product_identifiers`](Client::ensure_product_identifiers), on `conn`.
+    pub(super) async fn ensure_product_identifiers_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        identifiers: Vec<(IdentifierScheme, &str)>,
+    ) -> Result<()> {
+        let mut schemes = Vec::with_capacity(identifiers.len());
+
+        for (scheme, value) in identifiers {
+            let serialized_scheme = scheme.db_serialize();
+
+            sqlx::query!(
+                r#"insert into product_identifiers (product_id, scheme, value)
+                values (?1, ?2, ?3) on conflict do update set
+                updated_at=(datetime('now', 'utc')), value=excluded.value"#,
+                product_id,
+                serialized_scheme,
+                value
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            schemes.push(scheme);
+        }
+
+        let scheme_list = to_value_list(schemes.iter().map(|scheme| scheme.db_serialize()));
+
+        sqlx::query!(
+            r#"delete from product_identifiers where product_id = ?1 and scheme not in (select value from json_each(?2))"#,
+            product_id,
+            scheme_list
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
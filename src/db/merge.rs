@@ -0,0 +1,530 @@
+//! Merging another `ransaq` database (i.e. crawled from a second machine)
+//! into this one.
+//!
+//! Products are matched by SAQ code and lookups (countries, categories, etc.)
+//! by name (or URL for categories). When both databases know a product, the
+//! most recently updated version wins, along with its categories, grape
+//! varieties, special features, food pairings, tasting notes, reviews and
+//! identifiers. The price and availability
+//! history in `product_changes` is combined, starting from whichever
+//! database's first price for the product is oldest.
+//!
+//! Crawl bookkeeping (`crawl_runs`, `crawl_errors`, the skip list, etc.) only
+//! makes sense for the machine that did the crawling and is left alone.
+
+use super::Client;
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use sqlx::{Connection, Row, SqliteConnection};
+use std::collections::BTreeMap;
+use std::path::Path;
+use url::Url;
+
+/// Lookup tables made of unique names, see `generate_upserts_by_name!`.
+const LOOKUP_TABLES: [&str; 14] = [
+    "brands",
+    "manufacturers",
+    "sellers",
+    "producers",
+    "promoting_agents",
+    "colors",
+    "regions",
+    "countries",
+    "grape_varieties",
+    "regulated_designations",
+    "designations_of_origin",
+    "classifications",
+    "special_features",
+    "food_pairings",
+];
+
+/// Columns of `products` copied as is.
+const PRODUCT_COLUMNS: [&str; 27] = [
+    "saq_code",
+    "upc_code",
+    "name",
+    "description",
+    "image_url",
+    "availability",
+    "item_condition",
+    "price_cents",
+    "abv_percentage",
+    "container_count",
+    "container_milliliters",
+    "product_of_quebec",
+    "sugar_content_equality",
+    "sugar_content_grams_per_liter",
+    "created_at",
+    "updated_at",
+    "price_valid_until",
+    "description_hash",
+    "description_changed_at",
+    "partial",
+    "detailed_info_source",
+    "rating_value",
+    "rating_count",
+    "regular_price_cents",
+    "baseline_price_cents",
+    "baseline_regular_price_cents",
+    "baseline_recorded_at",
+];
+
+/// Columns of `products` referencing a lookup table, along with that table.
+pub(super) const PRODUCT_LOOKUP_COLUMNS: [(&str, &str); 11] = [
+    ("producer_id", "producers"),
+    ("promoting_agent_id", "promoting_agents"),
+    ("color_id", "colors"),
+    ("region_id", "regions"),
+    ("country_id", "countries"),
+    ("regulated_designation_id", "regulated_designations"),
+    ("designation_of_origin_id", "designations_of_origin"),
+    ("classification_id", "classifications"),
+    ("brand_id", "brands"),
+    ("manufacturer_id", "manufacturers"),
+    ("seller_id", "sellers"),
+];
+
+/// What [`merge_from`](Client::merge_from) changed.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    /// Number of names added to each lookup table, i.e. `countries`.
+    pub lookups_added: BTreeMap<&'static str, u64>,
+    /// Number of categories added.
+    pub categories_added: u64,
+    /// SAQ codes of the products that only the other database knew about.
+    pub products_added: Vec<String>,
+    /// SAQ codes of the products that were more recently updated in the other
+    /// database.
+    pub products_updated: Vec<String>,
+    /// Number of products for which this database's version was kept.
+    pub products_kept: i64,
+    /// Number of price and availability changes added to the history.
+    pub changes_added: u64,
+}
+
+/// Returns an SQL expression translating the `id` in `column` of the other
+/// database's `table` into the `id` of the row with the same name in this one.
+fn mapped_id(column: &str, table: &str) -> String {
+    format!(
+        "(select m.id from main.{table} m join other.{table} o on o.name = m.name where o.id = {column})"
+    )
+}
+
+/// Returns the most recent migration applied to the `schema` database.
+async fn schema_version(conn: &mut SqliteConnection, schema: &str) -> Result<Option<i64>> {
+    Ok(sqlx::query_scalar(&format!(
+        "select max(version) from {schema}._sqlx_migrations where success"
+    ))
+    .fetch_one(conn)
+    .await?)
+}
+
+/// Does the actual merging on behalf of [`merge_from`](Client::merge_from),
+/// with the other database attached as `other`.
+async fn merge_attached(conn: &mut SqliteConnection) -> Result<MergeReport> {
+    let version = schema_version(conn, "main").await?;
+    let other_version = schema_version(conn, "other").await?;
+    if version != other_version {
+        return Err(eyre!(
+            "both databases must be fully migrated to the same version (got {:?} and {:?})",
+            version,
+            other_version
+        ));
+    }
+
+    // Columns added by later migrations would otherwise silently be dropped
+    let unknown_columns: Vec<String> = sqlx::query_scalar(
+        "select name from pragma_table_info('products') where name != 'id' and name not in (select value from json_each(?1))",
+    )
+    .bind(serde_json::to_string(
+        &PRODUCT_COLUMNS
+            .iter()
+            .chain(PRODUCT_LOOKUP_COLUMNS.iter().map(|(column, _)| column))
+            .collect::<Vec<_>>(),
+    )?)
+    .fetch_all(&mut *conn)
+    .await?;
+    if !unknown_columns.is_empty() {
+        return Err(eyre!(
+            "don't know how to merge products columns {:?}",
+            unknown_columns
+        ));
+    }
+
+    let mut report = MergeReport::default();
+    let mut transaction = conn.begin().await?;
+
+    for table in LOOKUP_TABLES {
+        let result = sqlx::query(&format!(
+            "insert into main.{table} (name) select name from other.{table} where name not in (select name from main.{table})"
+        ))
+        .execute(&mut transaction)
+        .await?;
+        report.lookups_added.insert(table, result.rows_affected());
+    }
+
+    // Categories are added without a parent first, as parents may be new too
+    sqlx::query(
+        "create temp table merge_categories as select url from other.categories where url not in (select url from main.categories)",
+    )
+    .execute(&mut transaction)
+    .await?;
+    report.categories_added = sqlx::query(
+        "insert into main.categories (url, name) select url, name from other.categories where url in (select url from temp.merge_categories)",
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+    sqlx::query(
+        r#"update main.categories set parent_category_id = (
+            select mp.id from other.categories oc
+            join other.categories op on op.id = oc.parent_category_id
+            join main.categories mp on mp.url = op.url
+            where oc.url = categories.url
+        ) where url in (select url from temp.merge_categories)"#,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query(
+        r#"create temp table merge_products as
+        select op.saq_code, mp.id is null as added
+        from other.products op left join main.products mp on mp.saq_code = op.saq_code
+        where mp.id is null or op.updated_at > mp.updated_at"#,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    for row in sqlx::query("select saq_code, added from temp.merge_products order by saq_code")
+        .fetch_all(&mut transaction)
+        .await?
+    {
+        match row.get::<bool, _>(1) {
+            true => report.products_added.push(row.get(0)),
+            false => report.products_updated.push(row.get(0)),
+        }
+    }
+    report.products_kept = sqlx::query_scalar(
+        "select count(*) from other.products where saq_code not in (select saq_code from temp.merge_products)",
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    // The other database's history is imported below, so the triggers
+    // recording changes are suspended while products are overwritten
+    let triggers: Vec<(String, String)> = sqlx::query_as(
+        "select name, sql from main.sqlite_master where type = 'trigger' and tbl_name = 'products'",
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+    for (name, _) in &triggers {
+        sqlx::query(&format!(r#"drop trigger main."{name}""#))
+            .execute(&mut transaction)
+            .await?;
+    }
+
+    let columns = PRODUCT_COLUMNS
+        .iter()
+        .chain(PRODUCT_LOOKUP_COLUMNS.iter().map(|(column, _)| column))
+        .copied()
+        .collect::<Vec<_>>();
+    let values = PRODUCT_COLUMNS
+        .iter()
+        .map(|column| format!("op.{column}"))
+        .chain(
+            PRODUCT_LOOKUP_COLUMNS
+                .iter()
+                .map(|(column, table)| mapped_id(&format!("op.{column}"), table)),
+        )
+        .collect::<Vec<_>>();
+    // Both databases' price histories are combined below, so products keep
+    // whichever baseline (the start of their history) is earliest
+    let updates = columns
+        .iter()
+        .filter(|column| **column != "saq_code" && **column != "created_at")
+        .map(|column| match column.starts_with("baseline_") {
+            true => format!(
+                "{column} = case when excluded.baseline_recorded_at < products.baseline_recorded_at
+                    then excluded.{column} else products.{column} end"
+            ),
+            false => format!("{column} = excluded.{column}"),
+        })
+        .collect::<Vec<_>>();
+    sqlx::query(&format!(
+        r#"insert into main.products ({}) select {} from other.products op
+        where op.saq_code in (select saq_code from temp.merge_products)
+        on conflict (saq_code) do update set {}"#,
+        columns.join(", "),
+        values.join(", "),
+        updates.join(", ")
+    ))
+    .execute(&mut transaction)
+    .await?;
+
+    for (table, columns, values) in [
+        (
+            "product_categories",
+            "category_id",
+            "(select m.id from main.categories m join other.categories c on c.url = m.url where c.id = o.category_id)".to_string(),
+        ),
+        (
+            "product_grape_varieties",
+            "grape_variety_id, percentage",
+            format!("{}, o.percentage", mapped_id("o.grape_variety_id", "grape_varieties")),
+        ),
+        (
+            "product_special_features",
+            "special_feature_id",
+            mapped_id("o.special_feature_id", "special_features"),
+        ),
+        (
+            "product_food_pairings",
+            "food_pairing_id",
+            mapped_id("o.food_pairing_id", "food_pairings"),
+        ),
+        (
+            "tasting_notes",
+            "taste_tag, aromas, acidity, sweetness, body, mouthfeel, wood, serving_temperature",
+            "o.taste_tag, o.aromas, o.acidity, o.sweetness, o.body, o.mouthfeel, o.wood, o.serving_temperature".to_string(),
+        ),
+        ("product_identifiers", "scheme, value", "o.scheme, o.value".to_string()),
+        (
+            "reviews",
+            "review_hash, author, title, body, rating_value, best_rating, published_on",
+            "o.review_hash, o.author, o.title, o.body, o.rating_value, o.best_rating, o.published_on".to_string(),
+        ),
+    ] {
+        sqlx::query(&format!(
+            r#"delete from main.{table} where product_id in (
+                select id from main.products where saq_code in (select saq_code from temp.merge_products)
+            )"#
+        ))
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query(&format!(
+            r#"insert into main.{table} (product_id, {columns}, created_at, updated_at)
+            select mp.id, {values}, o.created_at, o.updated_at from other.{table} o
+            join other.products op on op.id = o.product_id
+            join main.products mp on mp.saq_code = op.saq_code
+            where op.saq_code in (select saq_code from temp.merge_products)"#
+        ))
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    report.changes_added = sqlx::query(
+        r#"insert into main.product_changes
+            (product_id, old_price_cents, new_price_cents, old_availability, new_availability,
+            old_regular_price_cents, new_regular_price_cents, changed_at)
+        select mp.id, oc.old_price_cents, oc.new_price_cents, oc.old_availability, oc.new_availability,
+            oc.old_regular_price_cents, oc.new_regular_price_cents, oc.changed_at
+        from other.product_changes oc
+        join other.products op on op.id = oc.product_id
+        join main.products mp on mp.saq_code = op.saq_code
+        where not exists (
+            select 1 from main.product_changes c
+            where c.product_id = mp.id and c.changed_at = oc.changed_at
+            and c.new_price_cents = oc.new_price_cents and c.new_availability = oc.new_availability
+        )"#,
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+
+    for (_, sql) in &triggers {
+        sqlx::query(sql).execute(&mut transaction).await?;
+    }
+
+    sqlx::query("drop table temp.merge_categories")
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query("drop table temp.merge_products")
+        .execute(&mut transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(report)
+}
+
+impl Client {
+    /// Merges the contents of the `ransaq` database at `path` into this one
+    /// (see the [module docs](self)), in a single transaction.
+    ///
+    /// Both databases must have had the same migrations applied. The merge
+    /// fails (leaving this database untouched) if a product from the other
+    /// database has the UPC code of a different product in this one.
+    pub async fn merge_from(&self, path: &Path) -> Result<MergeReport> {
+        let path = std::fs::canonicalize(path)
+            .map_err(|err| eyre!("failed to open {}: {}", path.display(), err))?;
+        let mut uri =
+            Url::from_file_path(&path).map_err(|_| eyre!("{:?} is not an absolute path", path))?;
+        uri.set_query(Some("mode=ro"));
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("attach database ?1 as other")
+            .bind(uri.to_string())
+            .execute(&mut conn)
+            .await?;
+
+        let result = merge_attached(&mut conn).await;
+
+        sqlx::query("detach database other")
+            .execute(&mut conn)
+            .await?;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use crate::db::Client;
+    use color_eyre::eyre::Result;
+
+    /// Inserts a product updated at `updated_at` into `db`, returning its `id`.
+    async fn insert_updated_at(
+        db: &Client,
+        saq_code: &str,
+        price_cents: i64,
+        country_id: Option<i64>,
+        updated_at: &str,
+    ) -> Result<i64> {
+        let id = db.insert_product(saq_code, saq_code, price_cents).await?;
+        sqlx::query("update products set country_id = ?2, updated_at = ?3 where id = ?1")
+            .bind(id)
+            .bind(country_id)
+            .bind(updated_at)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    #[tokio::test]
+    async fn test_merge_from() -> Result<()> {
+        let db = TestDb::new().await?;
+        let other = TestDb::new().await?;
+
+        // Different ids for the same names
+        db.upsert_country("Italie").await?;
+        let france = db.upsert_country("France").await?;
+        let other_france = other.upsert_country("France").await?;
+        let other_spain = other.upsert_country("Espagne").await?;
+        let wine = other
+            .upsert_category("Vin", "https://www.saq.com/en/products/wine", None)
+            .await?;
+        let red = other
+            .upsert_category(
+                "Rouge",
+                "https://www.saq.com/en/products/wine/red",
+                Some(wine),
+            )
+            .await?;
+
+        insert_updated_at(&db, "kept", 1000, Some(france), "2026-10-02 00:00:00").await?;
+        insert_updated_at(&db, "updated", 1000, Some(france), "2026-10-01 00:00:00").await?;
+        insert_updated_at(&other, "kept", 2000, None, "2026-10-01 00:00:00").await?;
+        let updated = insert_updated_at(
+            &other,
+            "updated",
+            1500,
+            Some(other_spain),
+            "2026-10-02 00:00:00",
+        )
+        .await?;
+        insert_updated_at(
+            &other,
+            "added",
+            3000,
+            Some(other_france),
+            "2026-10-02 00:00:00",
+        )
+        .await?;
+        sqlx::query("insert into product_categories (product_id, category_id) values (?1, ?2)")
+            .bind(updated)
+            .bind(red)
+            .execute(other.pool())
+            .await?;
+        sqlx::query(
+            r#"insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability, changed_at)
+            values (?1, 1200, 1500, 'in_stock', 'in_stock', '2026-10-02 00:00:00')"#,
+        )
+        .bind(updated)
+        .execute(other.pool())
+        .await?;
+        sqlx::query(
+            "update products set baseline_price_cents = 1200, baseline_recorded_at = '2026-09-01 00:00:00' where id = ?1",
+        )
+        .bind(updated)
+        .execute(other.pool())
+        .await?;
+
+        let report = db.merge_from(other.path()).await?;
+        assert_eq!(Some(&1), report.lookups_added.get("countries"));
+        assert_eq!(2, report.categories_added);
+        assert_eq!(vec!["added"], report.products_added);
+        assert_eq!(vec!["updated"], report.products_updated);
+        assert_eq!(1, report.products_kept);
+        assert_eq!(1, report.changes_added);
+
+        let products: Vec<(String, i64, Option<String>)> = sqlx::query_as(
+            r#"select p.saq_code, p.price_cents, c.name from products p
+            left join countries c on c.id = p.country_id order by p.saq_code"#,
+        )
+        .fetch_all(db.pool())
+        .await?;
+        assert_eq!(
+            vec![
+                ("added".to_string(), 3000, Some("France".to_string())),
+                ("kept".to_string(), 1000, Some("France".to_string())),
+                ("updated".to_string(), 1500, Some("Espagne".to_string())),
+            ],
+            products
+        );
+
+        let categories: Vec<(String, Option<String>)> = sqlx::query_as(
+            r#"select c.name, parent.name from product_categories pc
+            join categories c on c.id = pc.category_id
+            left join categories parent on parent.id = c.parent_category_id"#,
+        )
+        .fetch_all(db.pool())
+        .await?;
+        assert_eq!(
+            vec![("Rouge".to_string(), Some("Vin".to_string()))],
+            categories
+        );
+
+        // The other database's history started earlier
+        assert_eq!(
+            vec![1200, 1500],
+            db.price_history("updated")
+                .await?
+                .iter()
+                .map(|point| point.price_cents)
+                .collect::<Vec<_>>()
+        );
+
+        // Only the imported change was recorded, and the trigger still works
+        let changes: i64 = sqlx::query_scalar("select count(*) from product_changes")
+            .fetch_one(db.pool())
+            .await?;
+        assert_eq!(1, changes);
+        sqlx::query("update products set price_cents = 1600 where saq_code = 'updated'")
+            .execute(db.pool())
+            .await?;
+        let changes: i64 = sqlx::query_scalar("select count(*) from product_changes")
+            .fetch_one(db.pool())
+            .await?;
+        assert_eq!(2, changes);
+
+        // Merging again is a no-op
+        let report = db.merge_from(other.path()).await?;
+        assert!(report.products_added.is_empty());
+        assert!(report.products_updated.is_empty());
+        assert_eq!(0, report.changes_added);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+//! Crawl-level metrics, see [`indices`](crate::crawler::indices) and
+//! [`anomalies`](crate::crawler::anomalies), along with per-category price
+//! statistics recorded in the `category_price_history` table after each full
+//! crawl.
+
+use super::{to_value_list, Client, CrawlRunMode, DbSerialize};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// An entry in the `metrics_history` table.
+#[derive(Debug, Serialize)]
+pub struct MetricPoint {
+    /// The crawl the metric was computed for.
+    pub crawl_run_id: i64,
+    /// When the metric was computed.
+    pub created_at: DateTime<Utc>,
+    /// The metric's name.
+    pub name: String,
+    /// The metric's value.
+    pub value: f64,
+    /// Number of products the value was computed from.
+    pub products: i64,
+}
+
+/// An entry in the `category_price_history` table.
+#[derive(Debug, Serialize)]
+pub struct CategoryPricePoint {
+    /// The crawl the statistics were computed for.
+    pub crawl_run_id: i64,
+    /// When the crawl started.
+    pub started_at: DateTime<Utc>,
+    /// The category's name.
+    pub category: String,
+    /// The category's URL, as names aren't unique.
+    pub category_url: String,
+    /// Number of products the statistics were computed from.
+    pub products: i64,
+    /// The mean price of the category's products, in cents.
+    pub mean_price_cents: f64,
+    /// The median price of the category's products, in cents.
+    pub median_price_cents: f64,
+}
+
+impl Client {
+    /// Returns the average price in cents of the products with the given
+    /// `saq_codes`, along with how many of them are in the database.
+    pub async fn average_price_cents(&self, saq_codes: &[String]) -> Result<(Option<f64>, i64)> {
+        let mut conn = self.pool.acquire().await?;
+        let saq_codes = to_value_list(saq_codes);
+
+        let row = sqlx::query!(
+            r#"select avg(price_cents) as "average: f64", count(*) as "products!: i64"
+            from products where saq_code in (select value from json_each(?1))"#,
+            saq_codes
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok((row.average, row.products))
+    }
+
+    /// Records the `value` of the metric `name` for the given crawl,
+    /// replacing any previous value.
+    pub async fn record_metric(
+        &self,
+        crawl_run_id: i64,
+        name: &str,
+        value: f64,
+        products: i64,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query!(
+            r#"insert into metrics_history (crawl_run_id, name, value, products)
+            values (?1, ?2, ?3, ?4)
+            on conflict do update set value=excluded.value, products=excluded.products"#,
+            crawl_run_id,
+            name,
+            value,
+            products
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the average value of the metric `name` over the last `crawls`
+    /// completed crawls in the given `mode` started before `crawl_run_id`, if
+    /// any recorded it.
+    pub async fn metric_baseline(
+        &self,
+        name: &str,
+        mode: CrawlRunMode,
+        crawl_run_id: i64,
+        crawls: i64,
+    ) -> Result<Option<f64>> {
+        let mut conn = self.pool.acquire().await?;
+        let mode = mode.db_serialize();
+
+        let baseline = sqlx::query_scalar!(
+            r#"select avg(value) as "baseline: f64" from (
+                select mh.value from metrics_history mh
+                join crawl_runs cr on cr.id = mh.crawl_run_id
+                where mh.name = ?1 and cr.mode = ?2 and cr.status = 'completed' and cr.id < ?3
+                order by cr.id desc limit ?4
+            )"#,
+            name,
+            mode,
+            crawl_run_id,
+            crawls
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(baseline)
+    }
+
+    /// Records the mean and median price of the products in each category
+    /// against `crawl_run_id`, returning the number of categories recorded.
+    ///
+    /// Discontinued products are left out, as they keep whatever price they
+    /// were last listed at.
+    pub async fn record_category_prices(&self, crawl_run_id: i64) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+
+        // The median is the middle price, or the average of the two middle
+        // prices when there's an even number of them
+        let result = sqlx::query!(
+            r#"insert into category_price_history
+                (crawl_run_id, category_id, products, mean_price_cents, median_price_cents)
+            select ?1, category_id, count(*), avg(price_cents),
+                avg(case when position in ((total + 1) / 2, (total + 2) / 2) then price_cents end)
+            from (
+                select pc.category_id, p.price_cents,
+                    row_number() over (partition by pc.category_id order by p.price_cents) as position,
+                    count(*) over (partition by pc.category_id) as total
+                from product_categories pc join products p on p.id = pc.product_id
+                where p.availability != 'discontinued'
+            )
+            group by category_id
+            on conflict do update set
+                products=excluded.products,
+                mean_price_cents=excluded.mean_price_cents,
+                median_price_cents=excluded.median_price_cents"#,
+            crawl_run_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Returns the recorded price statistics of every category (or only those
+    /// named `category`), oldest first.
+    pub async fn category_price_history(
+        &self,
+        category: Option<&str>,
+    ) -> Result<Vec<CategoryPricePoint>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let points = sqlx::query_as!(
+            CategoryPricePoint,
+            r#"select cph.crawl_run_id, cr.started_at as "started_at: DateTime<Utc>",
+                c.name as category, c.url as category_url, cph.products,
+                cph.mean_price_cents, cph.median_price_cents
+            from category_price_history cph
+            join categories c on c.id = cph.category_id
+            join crawl_runs cr on cr.id = cph.crawl_run_id
+            where ?1 is null or c.name = ?1
+            order by c.name, c.url, cph.crawl_run_id"#,
+            category
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(points)
+    }
+
+    /// Returns the recorded values of every metric (or only `name`), oldest
+    /// first.
+    pub async fn metrics_history(&self, name: Option<&str>) -> Result<Vec<MetricPoint>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let points = sqlx::query_as!(
+            MetricPoint,
+            r#"select crawl_run_id, created_at as "created_at: DateTime<Utc>", name, value, products
+            from metrics_history where ?1 is null or name = ?1
+            order by name, crawl_run_id"#,
+            name
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crawler::indices::{record_indices, PriceIndices};
+    use crate::db::test_support::TestDb;
+    use crate::db::CrawlRunMode;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_record_indices() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        for (saq_code, price_cents) in [("bubbly", 5000), ("fizzy", 7000)] {
+            db.insert_product(saq_code, saq_code, price_cents).await?;
+        }
+
+        let indices: PriceIndices = "champagne=bubbly+fizzy+missing,unknown=missing".parse()?;
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        record_indices(&db, crawl_run_id, &indices).await?;
+        record_indices(&db, crawl_run_id, &indices).await?;
+
+        let history = db.metrics_history(None).await?;
+        assert_eq!(1, history.len());
+        assert_eq!("champagne", history[0].name);
+        assert_eq!(60.0, history[0].value);
+        assert_eq!(2, history[0].products);
+
+        assert!(db.metrics_history(Some("unknown")).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record_category_prices() -> Result<()> {
+        let db = TestDb::new().await?;
+        let wine = db
+            .upsert_category("Wine", "https://www.saq.com/en/products/wine", None)
+            .await?;
+        let beer = db
+            .upsert_category("Beer", "https://www.saq.com/en/products/beer", None)
+            .await?;
+
+        for (saq_code, price_cents, availability, category_id) in [
+            ("cheap", 1000, "in_stock", wine),
+            ("average", 2000, "in_stock", wine),
+            ("pricey", 6000, "in_stock", wine),
+            ("gone", 100_000, "discontinued", wine),
+            ("lager", 300, "in_stock", beer),
+            ("stout", 500, "in_stock", beer),
+        ] {
+            let product_id = db.insert_product(saq_code, saq_code, price_cents).await?;
+            sqlx::query("update products set availability = ?2 where id = ?1")
+                .bind(product_id)
+                .bind(availability)
+                .execute(db.pool())
+                .await?;
+            sqlx::query("insert into product_categories (product_id, category_id) values (?1, ?2)")
+                .bind(product_id)
+                .bind(category_id)
+                .execute(db.pool())
+                .await?;
+        }
+
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        assert_eq!(2, db.record_category_prices(crawl_run_id).await?);
+        // Recording again replaces the previous values
+        assert_eq!(2, db.record_category_prices(crawl_run_id).await?);
+
+        let history = db.category_price_history(None).await?;
+        assert_eq!(2, history.len());
+
+        assert_eq!("Beer", history[0].category);
+        assert_eq!(2, history[0].products);
+        assert_eq!(400.0, history[0].mean_price_cents);
+        assert_eq!(400.0, history[0].median_price_cents);
+
+        assert_eq!("Wine", history[1].category);
+        assert_eq!(3, history[1].products);
+        assert_eq!(3000.0, history[1].mean_price_cents);
+        assert_eq!(2000.0, history[1].median_price_cents);
+
+        assert_eq!(1, db.category_price_history(Some("Wine")).await?.len());
+
+        Ok(())
+    }
+}
@@ -16,7 +16,7 @@
 //!
 //! create a `.env` file with a `DATABASE_URL`,
 //!
-//! ```
+//! ```text
 //! DATABASE_URL=sqlite:ransaq.sqlite
 //! ```
 //!
@@ -37,15 +37,83 @@
 //!
 //! [^version]: You will need to be running SQLite version `3.37.0` or later
 //! due to the use of `STRICT` tables (<https://www.sqlite.org/releaselog/3_37_0.html>)
+//!
+//! ## Sample data
+//!
+//! [`Client::new_in_memory_with_sample_data`] returns an in-memory database
+//! holding a handful of products, which the examples throughout these docs
+//! run against (with `cargo test --doc`).
+//!
+//! ## Timestamps
+//!
+//! All timestamps are stored in UTC as ISO 8601 text in SQLite's
+//! `YYYY-MM-DD HH:MM:SS` format (i.e. `datetime('now', 'utc')`), which sorts and
+//! compares correctly as plain strings. Read models decode them as
+//! [`DateTime<Utc>`](chrono::DateTime) and leave converting to a local time zone
+//! to whatever displays them (see [`Config::timezone`](crate::config::Config::timezone)).
 
+mod categories;
+mod changelog;
+mod check;
+mod crawl_runs;
+mod crawl_state;
+mod export;
+mod gc;
 mod glue;
+mod identifiers;
+mod merge;
+mod metrics;
+mod persist_journal;
+mod price_history;
+mod purge;
+mod raw;
+mod reviews;
+mod sample;
+mod sample_data;
+mod schema;
+mod search;
+mod selftest;
+mod staging;
+mod status;
+mod stores;
+mod tasting;
+#[cfg(test)]
+pub(crate) mod test_support;
+mod trends;
+pub use categories::CategoryListing;
+pub use changelog::{Changelog, ChangelogProduct, PriceDrop};
+pub use check::Problem;
+pub use crawl_runs::{CrawlRunLabel, CrawlRunMode, CrawlRunStatus, FieldMismatch};
+pub use crawl_state::{CrawlListing, CrawlState, UnfinishedProduct};
+pub use export::{
+    ExportCategory, ExportFoodPairing, ExportGrapeVariety, ExportIdentifier, ExportProduct,
+    ExportRows, ExportSpecialFeature, ExportTastingNotes,
+};
+pub use gc::GcReport;
 pub use glue::DbSerialize;
+pub use identifiers::IdentifierScheme;
+pub use merge::MergeReport;
+pub use metrics::{CategoryPricePoint, MetricPoint};
+pub use price_history::PricePoint;
+pub use purge::PurgeReport;
+pub use raw::RawRows;
+pub use sample::SampleReport;
+pub use schema::{Column, ForeignKey, Schema, Table};
+pub use search::ProductSummary;
+pub use status::TableFreshness;
+pub use stores::Store;
+pub use trends::{ChurnTrend, ListingTrend, PriceTrend, Trends};
 
-use color_eyre::eyre::{eyre, Report, Result};
+use crate::saq::linked_data::Review;
+use crate::saq::tasting::TastingNotes;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
 use sqlx::sqlite::{
     SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
 };
-use sqlx::Connection;
+use sqlx::{Connection, SqliteConnection};
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -85,14 +153,25 @@ pub struct Client {
     pool: SqlitePool,
 }
 
+/// Maximum number of connections held by a [`Client`] unless specified otherwise.
+const DEFAULT_POOL_SIZE: u32 = 10;
+
+/// The migrations embedded in this build.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
 impl Client {
     /// Returns a new `Client` using the given `url` string.
     ///
     /// See [`sqlite_configuration`] for accepted `url` formats.
     pub async fn new(url: &str) -> Result<Client> {
+        Client::with_pool_size(url, DEFAULT_POOL_SIZE).await
+    }
+
+    /// Same as [`new`](Client::new), holding at most `pool_size` connections.
+    pub async fn with_pool_size(url: &str, pool_size: u32) -> Result<Client> {
         let options = sqlite_configuration(url)?;
         let pool = SqlitePoolOptions::new()
-            .max_connections(10)
+            .max_connections(pool_size)
             .connect_with(options)
             .await?;
 
@@ -103,14 +182,43 @@ impl Client {
     ///
     /// See [`sqlite_configuration`] for accepted formats.
     pub async fn new_from_env() -> Result<Client> {
+        Client::new_from_env_with_pool_size(DEFAULT_POOL_SIZE).await
+    }
+
+    /// Same as [`new_from_env`](Client::new_from_env), holding at most
+    /// `pool_size` connections.
+    pub async fn new_from_env_with_pool_size(pool_size: u32) -> Result<Client> {
         let url = std::env::var("DATABASE_URL")
             .map_err(|_| eyre!("could not find DATABASE_URL environment variable"))?;
 
-        Ok(Client::new(&url).await?)
+        Client::with_pool_size(&url, pool_size).await
+    }
+
+    /// Returns a new `Client` for an empty, fully migrated in-memory database,
+    /// which lives as long as the client (and its clones) do.
+    ///
+    /// Unlike [`migrate`](Client::migrate) this doesn't need a checkout of
+    /// the repository, as the migrations are embedded in the build.
+    pub async fn new_in_memory() -> Result<Client> {
+        // The database only lives as long as its connections, so keep a single
+        // one open for the lifetime of the pool.
+        let pool = SqlitePoolOptions::new()
+            .min_connections(1)
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(sqlite_configuration("sqlite::memory:")?)
+            .await?;
+
+        MIGRATOR.run(&pool).await?;
+
+        Ok(Client { pool })
     }
 
-    #[cfg(test)]
-    async fn migrate(&self) -> Result<()> {
+    /// Applies the migrations in `./migrations`, so this only works from a
+    /// checkout of the repository (i.e. in tests and
+    /// [simulations](crate::crawler::simulate)).
+    pub(crate) async fn migrate(&self) -> Result<()> {
         use sqlx::migrate::Migrator;
         use std::path::Path;
 
@@ -135,42 +243,102 @@ impl Client {
         let mut conn = self.pool.acquire().await?;
         let mut transaction = conn.begin().await?;
 
+        Client::ensure_product_special_features_with(
+            &mut transaction,
+            product_id,
+            special_feature_ids,
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Same as [`ensure_product_special_features`](Client::ensure_product_special_features), on `conn`.
+    async fn ensure_product_special_features_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        special_feature_ids: Vec<i64>,
+    ) -> Result<()> {
         for special_feature_id in &special_feature_ids {
-            let ins_result = sqlx::query!(
+            sqlx::query!(
                 r#"insert into product_special_features (product_id, special_feature_id) 
                 values (?1, ?2) on conflict do update set updated_at=(datetime('now', 'utc'))"#,
                 product_id,
                 special_feature_id
             )
-            .execute(&mut transaction)
-            .await;
-
-            if let Err(err) = ins_result {
-                transaction.rollback().await?;
-                return Err(Report::from(err));
-            }
+            .execute(&mut *conn)
+            .await?;
         }
 
         let special_feature_id_list = to_value_list(special_feature_ids);
 
-        let del_result = sqlx::query!(
-            r#"delete from product_special_features where product_id = ?1 and special_feature_id not in (?2)"#,
+        sqlx::query!(
+            r#"delete from product_special_features where product_id = ?1 and special_feature_id not in (select value from json_each(?2))"#,
             product_id,
             special_feature_id_list
         )
-        .execute(&mut transaction)
-        .await;
+        .execute(&mut *conn)
+        .await?;
 
-        if let Err(err) = del_result {
-            transaction.rollback().await?;
-            return Err(Report::from(err));
-        }
+        Ok(())
+    }
+
+    /// Uses upserts to make sure there are rows in `product_food_pairings` for
+    /// each of the provided `food_pairing_ids`.
+    ///
+    /// `updated_at` is always updated.
+    ///
+    /// Any entries in `product_food_pairings` for the given `product_id` that
+    /// don't reference any of the provided `food_pairing_ids` are subsequently
+    /// deleted.
+    pub async fn ensure_product_food_pairings(
+        &self,
+        product_id: i64,
+        food_pairing_ids: Vec<i64>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        Client::ensure_product_food_pairings_with(&mut transaction, product_id, food_pairing_ids)
+            .await?;
 
         transaction.commit().await?;
 
         Ok(())
     }
 
+    /// Same as [`ensure_product_food_pairings`](Client::ensure_product_food_pairings), on `conn`.
+    async fn ensure_product_food_pairings_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        food_pairing_ids: Vec<i64>,
+    ) -> Result<()> {
+        for food_pairing_id in &food_pairing_ids {
+            sqlx::query!(
+                r#"insert into product_food_pairings (product_id, food_pairing_id)
+                values (?1, ?2) on conflict do update set updated_at=(datetime('now', 'utc'))"#,
+                product_id,
+                food_pairing_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let food_pairing_id_list = to_value_list(food_pairing_ids);
+
+        sqlx::query!(
+            r#"delete from product_food_pairings where product_id = ?1 and food_pairing_id not in (select value from json_each(?2))"#,
+            product_id,
+            food_pairing_id_list
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
     /// Uses upserts to make sure there are rows in `product_grape_varieties`
     /// for each of the provided `(grape_variety_id, percentage)` pairs.
     ///
@@ -187,10 +355,28 @@ impl Client {
         let mut conn = self.pool.acquire().await?;
         let mut transaction = conn.begin().await?;
 
+        Client::ensure_product_grape_varieties_with(
+            &mut transaction,
+            product_id,
+            grape_variety_ids_and_percentages,
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Same as [`ensure_product_grape_varieties`](Client::ensure_product_grape_varieties), on `conn`.
+    async fn ensure_product_grape_varieties_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        grape_variety_ids_and_percentages: Vec<(i64, Option<u8>)>,
+    ) -> Result<()> {
         let mut variety_ids = Vec::with_capacity(grape_variety_ids_and_percentages.len());
 
         for (grape_variety_id, percentage) in grape_variety_ids_and_percentages {
-            let ins_result = sqlx::query!(
+            sqlx::query!(
                 r#"insert into product_grape_varieties (product_id, grape_variety_id, percentage)
                 values (?1, ?2, ?3) on conflict do update set
                 updated_at=(datetime('now', 'utc')), percentage=excluded.percentage"#,
@@ -198,39 +384,32 @@ impl Client {
                 grape_variety_id,
                 percentage
             )
-            .execute(&mut transaction)
-            .await;
-
-            if let Err(err) = ins_result {
-                transaction.rollback().await?;
-                return Err(Report::from(err));
-            }
+            .execute(&mut *conn)
+            .await?;
 
             variety_ids.push(grape_variety_id);
         }
 
         let variety_id_list = to_value_list(variety_ids);
 
-        let del_result = sqlx::query!(
-            r#"delete from product_grape_varieties where product_id = ?1 and grape_variety_id not in (?2)"#,
+        sqlx::query!(
+            r#"delete from product_grape_varieties where product_id = ?1 and grape_variety_id not in (select value from json_each(?2))"#,
             product_id,
             variety_id_list
         )
-        .execute(&mut transaction)
-        .await;
-
-        if let Err(err) = del_result {
-            transaction.rollback().await?;
-            return Err(Report::from(err));
-        }
-
-        transaction.commit().await?;
+        .execute(&mut *conn)
+        .await?;
 
         Ok(())
     }
 
     /// Use an upsert query to make sure a row exists in the `categories` table
-    /// with the provided `name`, and updating the `url` and `parent_id` fields.
+    /// with the provided `url`, and updating the `name` and `parent_id` fields.
+    ///
+    /// Categories are identified by their listing URL as display names change
+    /// over time (and differ between languages) and the same name can appear in
+    /// different parts of the category tree (i.e. "Red" under both "Wine" and
+    /// "Cider"). Renamed categories keep their `id`.
     ///
     /// Returns the row's `id`.
     pub async fn upsert_category(
@@ -243,21 +422,23 @@ impl Client {
 
         let upsert_id = sqlx::query_scalar!(
             r#"insert into categories (name, url, parent_category_id) values (?1, ?2, ?3)
-            on conflict do update set url=excluded.url, parent_category_id=excluded.parent_category_id 
-            where (url != excluded.url or parent_category_id != excluded.parent_category_id)
+            on conflict do update set name=excluded.name, parent_category_id=excluded.parent_category_id
+            where (name != excluded.name or parent_category_id is not excluded.parent_category_id)
             returning id as "id!""#,
             name,
             url,
             parent_id
-        ).fetch_optional(&mut conn).await?;
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
         if let Some(id) = upsert_id {
             return Ok(id);
         }
 
         Ok(sqlx::query_scalar!(
-            r#"select id as "id!" from categories where name = ?1 limit 1"#,
-            name
+            r#"select id as "id!" from categories where url = ?1 limit 1"#,
+            url
         )
         .fetch_one(&mut conn)
         .await?)
@@ -278,55 +459,60 @@ impl Client {
         let mut conn = self.pool.acquire().await?;
         let mut transaction = conn.begin().await?;
 
+        Client::ensure_product_categories_with(&mut transaction, product_id, category_ids).await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Same as [`ensure_product_categories`](Client::ensure_product_categories), on `conn`.
+    async fn ensure_product_categories_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        category_ids: Vec<i64>,
+    ) -> Result<()> {
         for category_id in &category_ids {
-            let ins_result = sqlx::query!(
+            sqlx::query!(
                 r#"insert into product_categories (product_id, category_id)
                 values (?1, ?2) on conflict do update set updated_at=(datetime('now', 'utc'))"#,
                 product_id,
                 category_id
             )
-            .execute(&mut transaction)
-            .await;
-
-            if let Err(err) = ins_result {
-                transaction.rollback().await?;
-                return Err(Report::from(err));
-            }
+            .execute(&mut *conn)
+            .await?;
         }
 
         let category_id_list = to_value_list(category_ids);
 
-        let del_result = sqlx::query!(
-            r#"delete from product_categories where product_id = ?1 and category_id not in (?2)"#,
+        sqlx::query!(
+            r#"delete from product_categories where product_id = ?1 and category_id not in (select value from json_each(?2))"#,
             product_id,
             category_id_list
         )
-        .execute(&mut transaction)
-        .await;
-
-        if let Err(err) = del_result {
-            transaction.rollback().await?;
-            return Err(Report::from(err));
-        }
-
-        transaction.commit().await?;
+        .execute(&mut *conn)
+        .await?;
 
         Ok(())
     }
 }
 
-/// Encodes a list of IDs as a comma-separated string.
+/// Encodes a list of IDs (or other values) as a JSON array.
 ///
 /// This is used as a workaround[^1] for queries like `where id in (?)` as sqlx doesn't
 /// currently support list parameters although there is currently a proposal:
-/// <https://github.com/launchbadge/sqlx/issues/875>.
+/// <https://github.com/launchbadge/sqlx/issues/875>. Queries need to expand the
+/// array using `where id in (select value from json_each(?))`.
+///
+/// Binding a plain comma-separated string instead doesn't work as SQLite treats
+/// it as a single text value, which never matches more than one ID.
 ///
 /// [^1]: <https://github.com/launchbadge/sqlx/issues/656#issuecomment-689326492>
-fn to_value_list(list: impl IntoIterator<Item = i64>) -> String {
-    list.into_iter()
-        .map(|item| item.to_string())
-        .collect::<Vec<_>>()
-        .join(",")
+fn to_value_list<T: Serialize>(list: impl IntoIterator<Item = T>) -> String {
+    let items = list.into_iter().collect::<Vec<_>>();
+
+    // Serializing numbers and strings can't fail
+    serde_json::to_string(&items).expect("list values should serialize to JSON")
 }
 
 /// Contains the necessary parameters to insert a row into
@@ -347,6 +533,8 @@ pub struct ProductUpsertFields<'a> {
     pub abv_percentage: Option<f32>,
     /// The string representation of the [`ItemAvailability`](crate::saq::linked_data::ItemAvailability) enum.
     pub availability: &'a str,
+    /// A database `id` from the `brands` table.
+    pub brand_id: Option<i64>,
     /// A database `id` from the `classifications` table.
     pub classification_id: Option<i64>,
     /// A database `id` from the `colors` table.
@@ -357,30 +545,55 @@ pub struct ProductUpsertFields<'a> {
     pub container_milliliters: Option<u32>,
     /// A database `id` from the `countries` table.
     pub country_id: Option<i64>,
-    /// The product's description.
-    pub description: &'a str,
+    /// The product's description, unless it was skipped (see [`SkippableField`](crate::config::SkippableField)).
+    pub description: Option<&'a str>,
+    /// A [hash](crate::saq::text::content_hash) of the product's description,
+    /// stored even when the description itself is skipped. `None` for
+    /// [`partial`](ProductUpsertFields::partial) products.
+    pub description_hash: Option<&'a str>,
     /// A database `id` from the `designations_of_origin` table.
     pub designation_of_origin_id: Option<i64>,
-    /// A URL for an image of the product.
-    pub image_url: &'a str,
+    /// The string representation of the [`DetailedInfoSource`](crate::saq::detailed_info::DetailedInfoSource) enum.
+    pub detailed_info_source: Option<&'a str>,
+    /// A URL for an image of the product, unless it was skipped (see [`SkippableField`](crate::config::SkippableField)).
+    pub image_url: Option<&'a str>,
     /// A string representation of the [`OfferItemCondition`](crate::saq::linked_data::OfferItemCondition) enum.
     pub item_condition: &'a str,
+    /// A database `id` from the `manufacturers` table.
+    pub manufacturer_id: Option<i64>,
     /// The product's name.
     pub name: &'a str,
-    /// The product's price in Canadian Dollars as a float.
-    pub price_cad: &'a f64,
+    /// Whether the product page was missing its JSON-LD (see
+    /// [`ExtractedProduct::partial`](crate::saq::ExtractedProduct::partial)).
+    /// Partial upserts leave the stored description and rating untouched, as
+    /// well as the regular price unless the price changed.
+    pub partial: bool,
+    /// The product's price in Canadian cents.
+    pub price_cents: i64,
+    /// The last day the current price applies, as a `YYYY-MM-DD` date.
+    pub price_valid_until: Option<&'a str>,
     /// A database `id` from the `producers` table.
     pub producer_id: Option<i64>,
     /// A string representation of the [`ProductOfQuebec`](crate::saq::detailed_info::ProductOfQuebec) enum.
     pub product_of_quebec: Option<&'a str>,
     /// A database `id` from the `promoting_agents` table.
     pub promoting_agent_id: Option<i64>,
+    /// The number of ratings the product's rating is based on.
+    pub rating_count: Option<i64>,
+    /// The product's average rating, from its JSON-LD
+    /// [`AggregateRating`](crate::saq::linked_data::AggregateRating).
+    pub rating_value: Option<f64>,
     /// A database `id` from the `regions` table.
     pub region_id: Option<i64>,
+    /// The product's price before the discount in Canadian cents, while it
+    /// is on sale.
+    pub regular_price_cents: Option<i64>,
     /// A database `id` from the `regulated_designations` table.
     pub regulated_designation_id: Option<i64>,
     /// The SAQ's unique product identifier.
     pub saq_code: &'a str,
+    /// A database `id` from the `sellers` table.
+    pub seller_id: Option<i64>,
     /// The string representation of the [`SugarContentEquality`](`crate::saq::detailed_info::SugarContentEquality`) enum.
     pub sugar_content_equality: Option<&'a str>,
     /// The number of grams of sugar per liter as a float.
@@ -389,15 +602,140 @@ pub struct ProductUpsertFields<'a> {
     pub upc_code: Option<&'a str>,
 }
 
+/// Everything stored about a product outside of its row in the `products`
+/// table, see [`Client::upsert_products`].
+///
+/// As with [`ProductUpsertFields`], the lookup rows (special features, grape
+/// varieties, etc.) must already exist.
+#[derive(Debug, Default)]
+pub struct ProductRelations<'a> {
+    /// Database `id`s from the `special_features` table.
+    pub special_feature_ids: Vec<i64>,
+    /// Database `id`s from the `grape_varieties` table, along with their
+    /// percentage of the blend.
+    pub grape_variety_ids_and_percentages: Vec<(i64, Option<u8>)>,
+    /// Database `id`s from the `food_pairings` table.
+    pub food_pairing_ids: Vec<i64>,
+    /// The product's tasting notes, if its page has any.
+    pub tasting_notes: Option<&'a TastingNotes>,
+    /// The product's reviews, or `None` to leave the stored ones alone (i.e.
+    /// for partial products, which have none to compare with).
+    pub reviews: Option<&'a [Review]>,
+    /// Database `id`s from the `categories` table.
+    pub category_ids: Vec<i64>,
+    /// The identifiers to match the product against other catalogs.
+    pub identifiers: Vec<(IdentifierScheme, &'a str)>,
+}
+
+/// How a product's catalog listing compares to what's stored, see
+/// [`Client::refresh_from_listing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingRefresh {
+    /// The product isn't in the database.
+    New,
+    /// The product's availability or price changed.
+    Changed,
+    /// The listing matches what's stored.
+    Unchanged,
+}
+
+/// A product whose description changed, see [`Client::description_changes_since`].
+#[derive(Debug)]
+pub struct DescriptionChange {
+    /// The SAQ's unique product identifier.
+    pub saq_code: String,
+    /// The product's name.
+    pub name: String,
+    /// When the new description was first seen.
+    pub changed_at: DateTime<Utc>,
+}
+
 impl Client {
     /// Use an upsert query to ensure a row with the given [`saq_code`](ProductUpsertFields::saq_code)
     /// exists in the `products` table, and update the remaining fields.
     ///
     /// If a row already exists, `updated_at` will be set to the current time and consequently
     /// differ from `created_at`.
+    ///
+    /// `description_changed_at` is also set to the current time whenever the
+    /// [`description_hash`](ProductUpsertFields::description_hash) differs from
+    /// the stored one.
     pub async fn upsert_product(&self, fields: ProductUpsertFields<'_>) -> Result<i64> {
         let mut conn = self.pool.acquire().await?;
 
+        Client::upsert_product_with(&mut conn, fields).await
+    }
+
+    /// Same as [`upsert_product`](Client::upsert_product) for multiple
+    /// products at once, also making sure their [relations](ProductRelations)
+    /// are up to date (as the `ensure_*` methods do). Returns the products'
+    /// `id`s in the same order.
+    ///
+    /// Everything happens in a single transaction (and is therefore only
+    /// committed to disk once), which is considerably faster than persisting
+    /// products one at a time. If anything fails, none of it is persisted.
+    ///
+    /// Each product still gets its own upsert statement rather than sharing a
+    /// multi-row one: SQLite doesn't guarantee the order of the `returning`
+    /// rows of a multi-row insert, and with 32 parameters per product a batch
+    /// would quickly run into its limit on bound parameters. Within one
+    /// transaction, the statements themselves cost little next to the commit.
+    pub async fn upsert_products(
+        &self,
+        products: Vec<(ProductUpsertFields<'_>, ProductRelations<'_>)>,
+    ) -> Result<Vec<i64>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        let mut ids = Vec::with_capacity(products.len());
+
+        for (fields, relations) in products {
+            let id = Client::upsert_product_with(&mut transaction, fields).await?;
+            Client::ensure_product_relations_with(&mut transaction, id, relations).await?;
+            ids.push(id);
+        }
+
+        transaction.commit().await?;
+
+        Ok(ids)
+    }
+
+    /// Runs the `ensure_*` methods matching each of the product's
+    /// `relations` on `conn`.
+    async fn ensure_product_relations_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        relations: ProductRelations<'_>,
+    ) -> Result<()> {
+        Client::ensure_product_special_features_with(
+            conn,
+            product_id,
+            relations.special_feature_ids,
+        )
+        .await?;
+        Client::ensure_product_grape_varieties_with(
+            conn,
+            product_id,
+            relations.grape_variety_ids_and_percentages,
+        )
+        .await?;
+        Client::ensure_product_food_pairings_with(conn, product_id, relations.food_pairing_ids)
+            .await?;
+        Client::ensure_tasting_notes_with(conn, product_id, relations.tasting_notes).await?;
+        if let Some(reviews) = relations.reviews {
+            Client::ensure_product_reviews_with(conn, product_id, reviews).await?;
+        }
+        Client::ensure_product_categories_with(conn, product_id, relations.category_ids).await?;
+        Client::ensure_product_identifiers_with(conn, product_id, relations.identifiers).await?;
+
+        Ok(())
+    }
+
+    /// Runs the query behind [`upsert_product`](Client::upsert_product) on `conn`.
+    async fn upsert_product_with(
+        conn: &mut SqliteConnection,
+        fields: ProductUpsertFields<'_>,
+    ) -> Result<i64> {
         // Unfortunately sqlx doesn't support named parameters yet
         // https://github.com/launchbadge/sqlx/issues/199
         let id = sqlx::query_scalar!(
@@ -405,86 +743,243 @@ impl Client {
             products (
                 abv_percentage,
                 availability, 
+                brand_id,
                 classification_id,
                 color_id, 
                 container_count, 
                 container_milliliters,
                 country_id, 
                 description, 
+                description_hash,
                 designation_of_origin_id,
+                detailed_info_source,
                 image_url,
                 item_condition, 
+                manufacturer_id,
                 name, 
-                price_cad, 
+                partial,
+                price_cents, 
+                price_valid_until,
                 producer_id, 
                 product_of_quebec,
                 promoting_agent_id, 
+                rating_count,
+                rating_value,
                 region_id,
+                regular_price_cents,
                 regulated_designation_id, 
                 saq_code, 
+                seller_id,
                 sugar_content_equality, 
                 sugar_content_grams_per_liter,
                 upc_code
             )
             values (
-                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
-                ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14,
+                ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29,
+                ?30, ?31, ?32
             )
             on conflict do update set
                 updated_at=(datetime('now', 'utc')),
                 abv_percentage=excluded.abv_percentage,
                 availability=excluded.availability, 
+                brand_id=excluded.brand_id,
                 classification_id=excluded.classification_id,
                 color_id=excluded.color_id, 
                 container_count=excluded.container_count, 
                 container_milliliters=excluded.container_milliliters,
                 country_id=excluded.country_id, 
-                description=excluded.description, 
+                description=(
+                    case when excluded.partial then products.description
+                    else excluded.description end
+                ), 
+                description_changed_at=(
+                    case when products.description_hash != excluded.description_hash
+                    then datetime('now', 'utc') else products.description_changed_at end
+                ),
+                description_hash=coalesce(excluded.description_hash, products.description_hash),
                 designation_of_origin_id=excluded.designation_of_origin_id,
+                detailed_info_source=excluded.detailed_info_source,
                 image_url=excluded.image_url,
                 item_condition=excluded.item_condition, 
+                manufacturer_id=excluded.manufacturer_id,
                 name=excluded.name, 
-                price_cad=excluded.price_cad, 
+                partial=excluded.partial,
+                price_cents=excluded.price_cents, 
+                price_valid_until=excluded.price_valid_until,
                 producer_id=excluded.producer_id, 
                 product_of_quebec=excluded.product_of_quebec,
                 promoting_agent_id=excluded.promoting_agent_id, 
+                rating_count=(
+                    case when excluded.partial then products.rating_count
+                    else excluded.rating_count end
+                ),
+                rating_value=(
+                    case when excluded.partial then products.rating_value
+                    else excluded.rating_value end
+                ),
                 region_id=excluded.region_id,
+                regular_price_cents=(
+                    case when excluded.partial and products.price_cents = excluded.price_cents
+                    then products.regular_price_cents else excluded.regular_price_cents end
+                ),
                 regulated_designation_id=excluded.regulated_designation_id, 
                 -- saq_code omitted
+                seller_id=excluded.seller_id,
                 sugar_content_equality=excluded.sugar_content_equality, 
                 sugar_content_grams_per_liter=excluded.sugar_content_grams_per_liter,
                 upc_code=excluded.upc_code
             returning id as "id!""#,
             fields.abv_percentage,
             fields.availability,
+            fields.brand_id,
             fields.classification_id,
             fields.color_id,
             fields.container_count,
             fields.container_milliliters,
             fields.country_id,
             fields.description,
+            fields.description_hash,
             fields.designation_of_origin_id,
+            fields.detailed_info_source,
             fields.image_url,
             fields.item_condition,
+            fields.manufacturer_id,
             fields.name,
-            fields.price_cad,
+            fields.partial,
+            fields.price_cents,
+            fields.price_valid_until,
             fields.producer_id,
             fields.product_of_quebec,
             fields.promoting_agent_id,
+            fields.rating_count,
+            fields.rating_value,
             fields.region_id,
+            fields.regular_price_cents,
             fields.regulated_designation_id,
             fields.saq_code,
+            fields.seller_id,
             fields.sugar_content_equality,
             fields.sugar_content_grams_per_liter,
             fields.upc_code
         )
-        .fetch_one(&mut conn)
+        .fetch_one(conn)
         .await?;
 
         Ok(id)
     }
 }
 
+impl Client {
+    /// Returns whether the product with the given `saq_code` was last updated more
+    /// than `stale_after_hours` hours ago, or `None` if it isn't in the database.
+    pub async fn is_product_stale(
+        &self,
+        saq_code: &str,
+        stale_after_hours: u32,
+    ) -> Result<Option<bool>> {
+        let mut conn = self.pool.acquire().await?;
+        let stale_modifier = format!("-{stale_after_hours} hours");
+
+        Ok(sqlx::query_scalar!(
+            r#"select updated_at < datetime('now', 'utc', ?2) as "stale!: bool"
+            from products where saq_code = ?1"#,
+            saq_code,
+            stale_modifier
+        )
+        .fetch_optional(&mut conn)
+        .await?)
+    }
+
+    /// Updates the availability and price of the product with the given
+    /// `saq_code` from its catalog listing, returning how they compare to the
+    /// stored ones.
+    ///
+    /// `updated_at` is left untouched as the rest of the product wasn't.
+    pub async fn refresh_from_listing(
+        &self,
+        saq_code: &str,
+        availability: &str,
+        price_cents: i64,
+    ) -> Result<ListingRefresh> {
+        let mut conn = self.pool.acquire().await?;
+
+        let stored = sqlx::query!(
+            r#"select availability, price_cents from products where saq_code = ?1"#,
+            saq_code
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        match stored {
+            None => Ok(ListingRefresh::New),
+            Some(stored)
+                if stored.availability == availability && stored.price_cents == price_cents =>
+            {
+                Ok(ListingRefresh::Unchanged)
+            }
+            Some(_) => {
+                sqlx::query!(
+                    r#"update products set availability = ?2, price_cents = ?3
+                    where saq_code = ?1"#,
+                    saq_code,
+                    availability,
+                    price_cents
+                )
+                .execute(&mut conn)
+                .await?;
+
+                Ok(ListingRefresh::Changed)
+            }
+        }
+    }
+
+    /// Returns the `saq_code` of products which are [partial](ProductUpsertFields::partial)
+    /// or missing their detailed info, least recently updated first.
+    ///
+    /// Products without a description are included if `missing_description`
+    /// is set, which it shouldn't be when descriptions are skipped.
+    pub async fn incomplete_products(&self, missing_description: bool) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let saq_codes = sqlx::query_scalar!(
+            r#"select saq_code from products
+            where partial
+                or (?1 and description is null)
+                or (abv_percentage is null and container_milliliters is null and country_id is null)
+            order by updated_at, saq_code"#,
+            missing_description
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(saq_codes)
+    }
+
+    /// Returns products whose description was materially rewritten at or
+    /// after `since`, most recent first.
+    pub async fn description_changes_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DescriptionChange>> {
+        let mut conn = self.pool.acquire().await?;
+        let since = since.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let changes = sqlx::query_as!(
+            DescriptionChange,
+            r#"select saq_code, name,
+                description_changed_at as "changed_at!: DateTime<Utc>"
+            from products where description_changed_at >= ?1
+            order by description_changed_at desc, saq_code"#,
+            since
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(changes)
+    }
+}
+
 /// Generates a method on [`Client`] named using the provided identifier
 /// which runs an upsert on the provided table name to make sure a row
 /// exists with the given `name`, returning the row's `id`.
@@ -532,6 +1027,9 @@ macro_rules! generate_upserts_by_name {
 }
 
 generate_upserts_by_name!(
+    upsert_brand => "brands",
+    upsert_manufacturer => "manufacturers",
+    upsert_seller => "sellers",
     upsert_producer => "producers",
     upsert_promoting_agent => "promoting_agents",
     upsert_color => "colors",
@@ -541,34 +1039,16 @@ generate_upserts_by_name!(
     upsert_regulated_designation => "regulated_designations",
     upsert_designation_of_origin => "designations_of_origin",
     upsert_classification => "classifications",
-    upsert_special_feature => "special_features"
+    upsert_special_feature => "special_features",
+    upsert_food_pairing => "food_pairings"
 );
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crawler::errors::{ErrorAction, ErrorClass};
     use paste::paste;
-    use sqlx::migrate::MigrateDatabase;
-    use tokio::sync::OnceCell;
-
-    static SHARED_CLIENT: OnceCell<Client> = OnceCell::const_new();
-
-    async fn get_client() -> Result<&'static Client> {
-        async fn init_client() -> Result<Client> {
-            let url = "sqlite:ransaq.test.sqlite";
-
-            if sqlx::Sqlite::database_exists(url).await? {
-                sqlx::Sqlite::drop_database(url).await?;
-            }
-
-            let client = Client::new(url).await?;
-            client.migrate().await?;
-
-            Ok(client)
-        }
-
-        Ok(SHARED_CLIENT.get_or_try_init(init_client).await?)
-    }
+    use test_support::TestDb;
 
     macro_rules! test_upserts_by_name {
         ($($fn:ident),*) => {
@@ -576,7 +1056,7 @@ mod tests {
                 paste! {
                     #[tokio::test]
                     async fn [<test_ $fn>]() -> Result<()> {
-                        let client = get_client().await?;
+                        let client = TestDb::new().await?;
 
                         let attempt_1 = client.$fn("Upserted Name").await?;
                         let attempt_2 = client.$fn("Upserted Name").await?;
@@ -589,7 +1069,196 @@ mod tests {
         };
     }
 
+    #[tokio::test]
+    async fn test_skip_list() -> Result<()> {
+        let client = TestDb::new().await?;
+        let url = "https://www.saq.com/en/skip-list-test";
+
+        for action in [ErrorAction::Skip, ErrorAction::Abort, ErrorAction::Retry] {
+            let crawl_run_id = client.start_crawl_run(CrawlRunMode::Full).await?;
+            client
+                .record_crawl_error(crawl_run_id, url, ErrorClass::Parse, action, "parse error")
+                .await?;
+            client
+                .record_crawl_error(
+                    crawl_run_id,
+                    url,
+                    ErrorClass::Network,
+                    ErrorAction::Skip,
+                    "network error",
+                )
+                .await?;
+            client
+                .finish_crawl_run(crawl_run_id, CrawlRunStatus::Failed)
+                .await?;
+        }
+
+        // Retried errors don't count
+        assert_eq!(2, client.count_failed_crawl_runs(url).await?);
+
+        client.add_to_skip_list(url, ErrorClass::Parse, 30).await?;
+        client.add_to_skip_list(url, ErrorClass::Parse, 30).await?;
+
+        let entries = client.active_skip_list().await?;
+        let entry = entries.iter().find(|e| e.url == url).unwrap();
+        assert_eq!("parse", entry.error_class);
+        assert!(entry.created_at <= chrono::Utc::now());
+        assert_eq!(30, (entry.expires_at - entry.created_at).num_days());
+
+        assert!(client.remove_from_skip_list(url).await?);
+        assert!(!client.remove_from_skip_list(url).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_label_crawl_run() -> Result<()> {
+        let client = TestDb::new().await?;
+
+        let unlabelled = client.start_crawl_run(CrawlRunMode::Full).await?;
+        let labelled = client.start_crawl_run(CrawlRunMode::NewArrivals).await?;
+        client
+            .label_crawl_run(
+                labelled,
+                &["weekly".to_string(), "pi".to_string()],
+                Some("after parser fix"),
+            )
+            .await?;
+        client.label_crawl_run(unlabelled, &[], None).await?;
+
+        let labels = client.crawl_run_labels().await?;
+        assert_eq!(1, labels.len());
+        assert_eq!(labelled, labels[0].crawl_run_id);
+        assert_eq!("new_arrivals", labels[0].mode);
+        assert_eq!(vec!["weekly", "pi"], labels[0].tags);
+        assert_eq!(Some("after parser fix"), labels[0].note.as_deref());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_from_listing() -> Result<()> {
+        let client = TestDb::new().await?;
+
+        assert_eq!(
+            ListingRefresh::New,
+            client
+                .refresh_from_listing("listed", "in_stock", 100)
+                .await?
+        );
+
+        client.insert_product("listed", "Listed", 100).await?;
+
+        assert_eq!(
+            ListingRefresh::Unchanged,
+            client
+                .refresh_from_listing("listed", "in_stock", 100)
+                .await?
+        );
+        assert_eq!(
+            ListingRefresh::Changed,
+            client
+                .refresh_from_listing("listed", "sold_out", 90)
+                .await?
+        );
+
+        let (availability, price_cents): (String, i64) = sqlx::query_as(
+            "select availability, price_cents from products where saq_code = 'listed'",
+        )
+        .fetch_one(client.pool())
+        .await?;
+        assert_eq!(("sold_out", 90), (availability.as_str(), price_cents));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_products() -> Result<()> {
+        let client = TestDb::new().await?;
+
+        for (saq_code, description, abv_percentage, partial) in [
+            ("complete", Some("Red"), Some(13.5), false),
+            ("partial", Some("Red"), Some(13.5), true),
+            ("no-description", None, Some(13.5), false),
+            ("no-detailed-info", Some("Red"), None, false),
+        ] {
+            sqlx::query(
+                r#"insert into products
+                (saq_code, name, description, abv_percentage, partial, availability, item_condition, price_cents)
+                values (?1, ?1, ?2, ?3, ?4, 'in_stock', 'new', 100)"#,
+            )
+            .bind(saq_code)
+            .bind(description)
+            .bind(abv_percentage)
+            .bind(partial)
+            .execute(client.pool())
+            .await?;
+        }
+
+        assert_eq!(
+            vec!["no-description", "no-detailed-info", "partial"],
+            client.incomplete_products(true).await?
+        );
+        assert_eq!(
+            vec!["no-detailed-info", "partial"],
+            client.incomplete_products(false).await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_category() -> Result<()> {
+        let client = TestDb::new().await?;
+        let base_url = "https://www.saq.com/en/products";
+
+        let wine = client
+            .upsert_category("Wine", &format!("{base_url}/wine"), None)
+            .await?;
+        let cider = client
+            .upsert_category("Cider", &format!("{base_url}/cider"), None)
+            .await?;
+
+        let red_wine = client
+            .upsert_category("Red", &format!("{base_url}/wine/red"), Some(wine))
+            .await?;
+        let red_cider = client
+            .upsert_category("Red", &format!("{base_url}/cider/red"), Some(cider))
+            .await?;
+        assert_ne!(red_wine, red_cider);
+
+        // Renamed between crawls
+        let red_wine_renamed = client
+            .upsert_category("Red wine", &format!("{base_url}/wine/red"), Some(wine))
+            .await?;
+        assert_eq!(red_wine, red_wine_renamed);
+
+        // Moved to a different URL
+        let red_wine_moved = client
+            .upsert_category("Red wine", &format!("{base_url}/wine/red-wine"), Some(wine))
+            .await?;
+        assert_ne!(red_wine, red_wine_moved);
+
+        assert_eq!(
+            wine,
+            client
+                .upsert_category("Wine", &format!("{base_url}/wine"), None)
+                .await?
+        );
+
+        let name: String = sqlx::query_scalar("select name from categories where id = ?1")
+            .bind(red_wine)
+            .fetch_one(client.pool())
+            .await?;
+        assert_eq!("Red wine", name);
+
+        Ok(())
+    }
+
     test_upserts_by_name!(
+        upsert_brand,
+        upsert_manufacturer,
+        upsert_seller,
         upsert_producer,
         upsert_promoting_agent,
         upsert_color,
@@ -599,6 +1268,7 @@ mod tests {
         upsert_regulated_designation,
         upsert_designation_of_origin,
         upsert_classification,
-        upsert_special_feature
+        upsert_special_feature,
+        upsert_food_pairing
     );
 }
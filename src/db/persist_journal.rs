@@ -0,0 +1,120 @@
+//! A journal of the products whose writes are in flight, so that products a
+//! crash left half-written can be persisted again by the next crawl.
+//!
+//! Persisting a product takes a number of separate writes (its row, then each
+//! of its relations), so a crawl dying partway through leaves it with an
+//! up-to-date row but stale or missing relations. Nothing about the product
+//! itself gives that away, which is why the intent to write it is recorded
+//! beforehand and only cleared once every write succeeded.
+
+use super::{to_value_list, Client, UnfinishedProduct};
+use color_eyre::eyre::{Report, Result};
+use sqlx::Connection;
+
+impl Client {
+    /// Records that the given products of the given crawl are about to be
+    /// persisted. Products already in the journal are left as they are.
+    pub async fn journal_persists(
+        &self,
+        crawl_run_id: i64,
+        products: &[UnfinishedProduct],
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        for product in products {
+            let ins_result = sqlx::query!(
+                r#"insert into persist_journal (url, saq_code, crawl_run_id) values (?1, ?2, ?3)
+                on conflict do nothing"#,
+                product.url,
+                product.sku,
+                crawl_run_id
+            )
+            .execute(&mut transaction)
+            .await;
+
+            if let Err(err) = ins_result {
+                transaction.rollback().await?;
+                return Err(Report::from(err));
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Removes the products at the given URLs from the journal, once all
+    /// their writes succeeded.
+    pub async fn clear_persists<I, S>(&self, urls: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut conn = self.pool.acquire().await?;
+        let urls = to_value_list(urls.into_iter().map(|url| url.as_ref().to_string()));
+
+        sqlx::query!(
+            "delete from persist_journal where url in (select value from json_each(?1))",
+            urls
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the products whose writes were interrupted, oldest first.
+    pub async fn interrupted_persists(&self) -> Result<Vec<UnfinishedProduct>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let products = sqlx::query_as!(
+            UnfinishedProduct,
+            "select url, saq_code as sku from persist_journal order by started_at, url"
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(products)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use crate::db::CrawlRunMode;
+
+    /// An [`UnfinishedProduct`] with the given SKU.
+    fn product(sku: &str) -> UnfinishedProduct {
+        UnfinishedProduct {
+            url: format!("https://www.saq.com/en/{sku}"),
+            sku: sku.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_persist_journal() -> Result<()> {
+        let db = TestDb::new().await?;
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        assert!(db.interrupted_persists().await?.is_empty());
+
+        db.journal_persists(crawl_run_id, &[product("123"), product("456")])
+            .await?;
+        db.journal_persists(crawl_run_id, &[product("456"), product("789")])
+            .await?;
+        assert_eq!(
+            vec![product("123"), product("456"), product("789")],
+            db.interrupted_persists().await?
+        );
+
+        db.clear_persists([product("123").url, product("789").url])
+            .await?;
+        assert_eq!(vec![product("456")], db.interrupted_persists().await?);
+
+        db.clear_persists(Vec::<String>::new()).await?;
+        assert_eq!(vec![product("456")], db.interrupted_persists().await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Each product's prices over time, along with their regular price while on
+//! sale, from the `product_price_history` view: the price each product was
+//! first crawled at (its baseline, stored in `products`), followed by the
+//! price changes recorded in `product_changes` (see [`trends`](super::trends)).
+//!
+//! Deleting older changes (see [`purge_before`](Client::purge_before)) moves
+//! the baseline forward, so the history never starts from a price the
+//! product didn't have.
+
+use super::Client;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// A price a product was crawled at.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct PricePoint {
+    /// The price, in cents.
+    pub price_cents: i64,
+    /// The price before the discount in cents, if the product was on sale.
+    /// Unknown (`None`) before regular prices were tracked.
+    pub regular_price_cents: Option<i64>,
+    /// When the product was first crawled at this price.
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Client {
+    /// Lists the prices of the product with the given SAQ code, oldest first.
+    /// Empty if there's no such product.
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> color_eyre::Result<()> {
+    /// let db = ransaq::db::Client::new_in_memory_with_sample_data().await?;
+    ///
+    /// // Péché Mortel went on sale on October 8th
+    /// let history = db.price_history("12345678").await?;
+    /// assert_eq!(
+    ///     vec![(1975, None), (1725, Some(1975))],
+    ///     history
+    ///         .iter()
+    ///         .map(|point| (point.price_cents, point.regular_price_cents))
+    ///         .collect::<Vec<_>>()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn price_history(&self, saq_code: &str) -> Result<Vec<PricePoint>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let history = sqlx::query_as!(
+            PricePoint,
+            r#"select h.price_cents as "price_cents!", h.regular_price_cents,
+                h.recorded_at as "recorded_at!: DateTime<Utc>"
+            from product_price_history h join products p on p.id = h.product_id
+            where p.saq_code = ?1
+            order by h.recorded_at, h.product_change_id"#,
+            saq_code
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_price_history() -> Result<()> {
+        let db = TestDb::new().await?;
+        assert_eq!(Vec::<PricePoint>::new(), db.price_history("123").await?);
+
+        db.insert_product("123", "Tracked", 1000).await?;
+        sqlx::query(
+            "update products set created_at = '2026-10-01 00:00:00', baseline_recorded_at = '2026-10-01 00:00:00'",
+        )
+            .execute(db.pool())
+            .await?;
+
+        let history = db.price_history("123").await?;
+        assert_eq!(
+            vec![1000],
+            history
+                .iter()
+                .map(|point| point.price_cents)
+                .collect::<Vec<_>>()
+        );
+
+        // Price changes are recorded by a trigger, but availability changes
+        // alone don't add to the price history
+        for (price_cents, availability) in
+            [(1000, "sold_out"), (900, "sold_out"), (950, "in_stock")]
+        {
+            sqlx::query("update products set price_cents = ?1, availability = ?2")
+                .bind(price_cents)
+                .bind(availability)
+                .execute(db.pool())
+                .await?;
+        }
+
+        // Sales starting and ending are recorded along with the regular price
+        for (price_cents, regular_price_cents) in [(800, Some(950)), (950, None)] {
+            sqlx::query("update products set price_cents = ?1, regular_price_cents = ?2")
+                .bind(price_cents)
+                .bind(regular_price_cents)
+                .execute(db.pool())
+                .await?;
+        }
+
+        let history = db.price_history("123").await?;
+        assert_eq!(
+            vec![
+                (1000, None),
+                (900, None),
+                (950, None),
+                (800, Some(950)),
+                (950, None)
+            ],
+            history
+                .iter()
+                .map(|point| (point.price_cents, point.regular_price_cents))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap(),
+            history[0].recorded_at
+        );
+
+        // The first price doesn't depend on the recorded changes
+        sqlx::query("delete from product_changes")
+            .execute(db.pool())
+            .await?;
+        let history = db.price_history("123").await?;
+        assert_eq!(1, history.len());
+        assert_eq!(1000, history[0].price_cents);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+//! Permanently deleting products and their history, i.e. to trim the dataset
+//! or honor a takedown request.
+//!
+//! Purged products are removed along with everything that refers to them
+//! (categories, grape varieties, special features, food pairings, tasting
+//! notes, reviews, identifiers, price and availability history and field
+//! mismatches). Lookups (countries, producers,
+//! etc.) are shared between products and left for [`gc`](Client::gc) to clean
+//! up. Crawl bookkeeping such as `crawl_errors` and the skip list is left alone.
+
+use super::Client;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+
+/// What [`purge_product`](Client::purge_product) or
+/// [`purge_before`](Client::purge_before) deleted (or would have, for dry runs).
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PurgeReport {
+    /// SAQ codes of the deleted products.
+    pub products: Vec<String>,
+    /// Number of price and availability changes deleted.
+    pub changes: u64,
+    /// Number of field mismatches deleted.
+    pub mismatches: u64,
+}
+
+/// Deletes the products with the given `ids` and `saq_codes` (in the same
+/// order) along with the rows referring to them.
+async fn delete_products(
+    conn: &mut SqliteConnection,
+    ids: Vec<i64>,
+    saq_codes: Vec<String>,
+) -> Result<PurgeReport> {
+    let ids_json = serde_json::to_string(&ids)?;
+    let saq_codes_json = serde_json::to_string(&saq_codes)?;
+
+    sqlx::query!(
+        "delete from product_grape_varieties where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from product_special_features where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from product_food_pairings where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from tasting_notes where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from reviews where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from product_categories where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "delete from product_identifiers where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let changes = sqlx::query!(
+        "delete from product_changes where product_id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    let mismatches = sqlx::query!(
+        "delete from field_mismatches where saq_code in (select value from json_each(?1))",
+        saq_codes_json
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "delete from products where id in (select value from json_each(?1))",
+        ids_json
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(PurgeReport {
+        products: saq_codes,
+        changes,
+        mismatches,
+    })
+}
+
+impl Client {
+    /// Deletes the product with the given SAQ code and its history. Nothing
+    /// is deleted if `dry_run` is set, but the report is the same.
+    pub async fn purge_product(&self, saq_code: &str, dry_run: bool) -> Result<PurgeReport> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        let product = sqlx::query!(
+            r#"select id as "id!: i64", saq_code from products where saq_code = ?1"#,
+            saq_code
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+
+        let (ids, saq_codes) = product.map(|p| (p.id, p.saq_code)).into_iter().unzip();
+        let report = delete_products(&mut transaction, ids, saq_codes).await?;
+
+        match dry_run {
+            true => transaction.rollback().await?,
+            false => transaction.commit().await?,
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes the products that haven't been updated since `before`, along
+    /// with the price and availability changes of the remaining products that
+    /// happened before then. Nothing is deleted if `dry_run` is set, but the
+    /// report is the same.
+    ///
+    /// The [price history](Client::price_history) of the remaining products
+    /// then starts from the last price they had before `before`, dated from
+    /// when they started selling at it.
+    pub async fn purge_before(&self, before: DateTime<Utc>, dry_run: bool) -> Result<PurgeReport> {
+        let before = before.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        let (ids, saq_codes) = sqlx::query!(
+            r#"select id as "id!: i64", saq_code from products where updated_at < ?1 order by saq_code"#,
+            before
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|p| (p.id, p.saq_code))
+        .unzip();
+
+        let mut report = delete_products(&mut transaction, ids, saq_codes).await?;
+
+        // Only changes which show up in the price history move its baseline
+        sqlx::query!(
+            r#"update products set
+                (baseline_price_cents, baseline_regular_price_cents, baseline_recorded_at) = (
+                    select pc.new_price_cents, pc.new_regular_price_cents, pc.changed_at
+                    from product_changes pc
+                    where pc.product_id = products.id and pc.changed_at < ?1
+                    and (pc.new_price_cents != pc.old_price_cents
+                        or pc.new_regular_price_cents is not pc.old_regular_price_cents)
+                    order by pc.changed_at desc, pc.id desc limit 1
+                )
+            where id in (
+                select product_id from product_changes
+                where changed_at < ?1
+                and (new_price_cents != old_price_cents
+                    or new_regular_price_cents is not old_regular_price_cents)
+            )"#,
+            before
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        report.changes += sqlx::query!("delete from product_changes where changed_at < ?1", before)
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+
+        match dry_run {
+            true => transaction.rollback().await?,
+            false => transaction.commit().await?,
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use chrono::{TimeZone, Utc};
+    use color_eyre::eyre::Result;
+
+    /// Inserts a product last updated at `updated_at` with a category and a
+    /// price change recorded at the same time, returning its id.
+    async fn insert_purgeable(db: &TestDb, saq_code: &str, updated_at: &str) -> Result<i64> {
+        let id = db.insert_product(saq_code, "Purged", 100).await?;
+        sqlx::query("update products set updated_at = ?2 where id = ?1")
+            .bind(id)
+            .bind(updated_at)
+            .execute(db.pool())
+            .await?;
+
+        let category_id = db
+            .upsert_category(saq_code, &format!("https://www.saq.com/{saq_code}"), None)
+            .await?;
+        sqlx::query("insert into product_categories (product_id, category_id) values (?1, ?2)")
+            .bind(id)
+            .bind(category_id)
+            .execute(db.pool())
+            .await?;
+
+        sqlx::query(
+            r#"insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability, changed_at)
+            values (?1, 200, 100, 'in_stock', 'in_stock', ?2)"#,
+        )
+        .bind(id)
+        .bind(updated_at)
+        .execute(db.pool())
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Counts the rows in `table`.
+    async fn count(db: &TestDb, table: &str) -> Result<i64> {
+        Ok(sqlx::query_scalar(&format!("select count(*) from {table}"))
+            .fetch_one(db.pool())
+            .await?)
+    }
+
+    #[tokio::test]
+    async fn test_purge() -> Result<()> {
+        let db = TestDb::new().await?;
+        insert_purgeable(&db, "old", "2026-01-01 12:00:00").await?;
+        insert_purgeable(&db, "recent", "2026-06-01 12:00:00").await?;
+        insert_purgeable(&db, "taken-down", "2026-06-01 12:00:00").await?;
+
+        let report = db.purge_product("taken-down", true).await?;
+        assert_eq!(vec!["taken-down".to_string()], report.products);
+        assert_eq!(1, report.changes);
+        assert_eq!(3, count(&db, "products").await?);
+
+        db.purge_product("taken-down", false).await?;
+        assert_eq!(2, count(&db, "products").await?);
+        assert_eq!(2, count(&db, "product_categories").await?);
+        assert!(db
+            .purge_product("taken-down", false)
+            .await?
+            .products
+            .is_empty());
+
+        // The recent product's price changed twice before the cutoff, and
+        // its availability once
+        sqlx::query(
+            r#"update products set baseline_price_cents = 300, baseline_recorded_at = '2026-01-01 12:00:00'
+            where saq_code = 'recent'"#,
+        )
+        .execute(db.pool())
+        .await?;
+        sqlx::query(
+            r#"insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability, new_availability, changed_at)
+            select id, 300, 250, 'in_stock', 'in_stock', '2026-02-01 12:00:00' from products where saq_code = 'recent'
+            union all
+            select id, 250, 250, 'in_stock', 'sold_out', '2026-02-15 12:00:00' from products where saq_code = 'recent'
+            union all
+            select id, 250, 200, 'sold_out', 'in_stock', '2026-04-01 12:00:00' from products where saq_code = 'recent'"#,
+        )
+        .execute(db.pool())
+        .await?;
+
+        let before = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+        let report = db.purge_before(before, false).await?;
+        assert_eq!(vec!["old".to_string()], report.products);
+        assert_eq!(3, report.changes);
+        assert_eq!(1, count(&db, "products").await?);
+        assert_eq!(1, count(&db, "product_categories").await?);
+        assert_eq!(2, count(&db, "product_changes").await?);
+
+        // Its history starts from the last price it had before the cutoff,
+        // when it started selling at it
+        let history = db.price_history("recent").await?;
+        assert_eq!(
+            vec![250, 200, 100],
+            history
+                .iter()
+                .map(|point| point.price_cents)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap(),
+            history[0].recorded_at
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Running arbitrary, read-only SQL (i.e. from the [REPL](crate::repl)).
+
+use super::Client;
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use sqlx::{Column, ConnectOptions, Connection, Row, TypeInfo, ValueRef};
+
+/// The result of a [raw query](Client::query_raw), with every value
+/// formatted as text.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RawRows {
+    /// The names of the result's columns.
+    pub columns: Vec<String>,
+    /// The result's rows, `NULL`s being represented as `None`.
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+/// Formats the value of column `index` in `row`, whatever its type.
+fn format_value(row: &sqlx::sqlite::SqliteRow, index: usize) -> Result<Option<String>> {
+    let value = row.try_get_raw(index)?;
+
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let formatted = match value.type_info().name() {
+        "INTEGER" => row.try_get::<i64, _>(index)?.to_string(),
+        "REAL" => row.try_get::<f64, _>(index)?.to_string(),
+        "BLOB" => format!("<{} byte blob>", row.try_get::<Vec<u8>, _>(index)?.len()),
+        _ => row.try_get::<String, _>(index)?,
+    };
+
+    Ok(Some(formatted))
+}
+
+/// The first keyword of each statement in `sql`, lowercased, skipping over
+/// comments as well as quoted strings and identifiers.
+fn leading_keywords(sql: &str) -> Vec<String> {
+    let mut keywords = vec![];
+    // The keyword of the statement being read, if it started
+    let mut current: Option<String> = None;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' | '[' => {
+                let end = if c == '[' { ']' } else { c };
+                chars.by_ref().find(|&next| next == end);
+                current.get_or_insert_with(String::new);
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().find(|&next| next == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                chars.by_ref().find(|&next| {
+                    let end = previous == '*' && next == '/';
+                    previous = next;
+                    end
+                });
+            }
+            ';' => keywords.extend(current.take()),
+            c if c.is_whitespace() => {}
+            c if current.is_none() => {
+                let mut keyword = c.to_lowercase().to_string();
+                while let Some(next) = chars
+                    .peek()
+                    .copied()
+                    .filter(|next| next.is_alphanumeric() || *next == '_')
+                {
+                    keyword.extend(next.to_lowercase());
+                    chars.next();
+                }
+                current = Some(keyword);
+            }
+            _ => {}
+        }
+    }
+
+    keywords.extend(current);
+    keywords
+}
+
+impl Client {
+    /// Runs `sql` and returns the resulting rows.
+    ///
+    /// The query runs on a connection of its own opened
+    /// [read-only](https://sqlite.org/c3ref/open.html), rather than one from
+    /// the pool, so anything attempting to write to the database (or to a
+    /// database it attaches) fails whatever pragmas come before it. `sql` must
+    /// be a single statement, and can't be a `vacuum` as `vacuum into` writes
+    /// a new database even from a read-only connection.
+    pub async fn query_raw(&self, sql: &str) -> Result<RawRows> {
+        match leading_keywords(sql).as_slice() {
+            [keyword] if keyword == "vacuum" => return Err(eyre!("vacuum can't be run here")),
+            [_] => {}
+            [] => return Err(eyre!("no statement to run")),
+            _ => return Err(eyre!("only one statement can be run at a time")),
+        }
+
+        let mut conn = self
+            .pool
+            .connect_options()
+            .clone()
+            .create_if_missing(false)
+            .read_only(true)
+            .connect()
+            .await?;
+
+        let result = sqlx::query(sql).fetch_all(&mut conn).await;
+        conn.close().await?;
+
+        let rows = result?;
+        let columns = match rows.first() {
+            Some(row) => row
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect(),
+            None => vec![],
+        };
+        let rows = rows
+            .iter()
+            .map(|row| (0..row.len()).map(|i| format_value(row, i)).collect())
+            .collect::<Result<_>>()?;
+
+        Ok(RawRows { columns, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    #[test]
+    fn test_leading_keywords() {
+        assert_eq!(vec!["select"], leading_keywords("SELECT 1;"));
+        assert_eq!(
+            vec!["pragma", "delete"],
+            leading_keywords("pragma query_only = off;delete from products")
+        );
+        assert_eq!(
+            vec!["vacuum"],
+            leading_keywords("-- ; comment\n/* ; */ Vacuum into 'a;b'; ; ")
+        );
+        assert_eq!(
+            vec!["select"],
+            leading_keywords(r#"select ';', "a;b", [c;d], `e;f`, 'it''s;'"#)
+        );
+        assert!(leading_keywords(" ; -- select").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_raw() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let result = db
+            .query_raw("select 1 as one, 1.5 as half, 'text' as t, null as n, x'00ff' as b")
+            .await?;
+        assert_eq!(vec!["one", "half", "t", "n", "b"], result.columns);
+        assert_eq!(
+            vec![vec![
+                Some("1".to_string()),
+                Some("1.5".to_string()),
+                Some("text".to_string()),
+                None,
+                Some("<2 byte blob>".to_string()),
+            ]],
+            result.rows
+        );
+
+        db.upsert_color("Red").await?;
+        let attached = db.path().with_extension("attached.sqlite");
+        for sql in [
+            "delete from colors".to_string(),
+            // Pragmas can't lift the restriction, even when followed by
+            // another statement
+            "pragma query_only = off; delete from colors".to_string(),
+            format!(
+                "attach database '{}' as other; create table other.t (x)",
+                attached.display()
+            ),
+            format!("attach database '{}' as other", attached.display()),
+            format!("vacuum into '{}'", attached.display()),
+        ] {
+            assert!(db.query_raw(&sql).await.is_err(), "{sql}");
+        }
+        assert!(!attached.exists());
+        assert_eq!(1, db.query_raw("select * from colors").await?.rows.len());
+
+        // The pool's connections can still write
+        db.upsert_color("White").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Product reviews from the JSON-LD of product pages (see
+//! [`Review`]).
+
+use super::{to_value_list, Client};
+use crate::saq::linked_data::Review;
+use crate::saq::text::content_hash;
+use color_eyre::eyre::Result;
+use sqlx::{Connection, SqliteConnection};
+
+/// Tells reviews apart, as they have no identifier of their own: the
+/// [content hash](content_hash) of their author, date, title and body.
+fn review_hash(review: &Review) -> String {
+    content_hash(
+        &[
+            review.author.as_ref().map(|author| author.name()),
+            review.date_published.as_deref(),
+            review.name.as_deref(),
+            review.review_body.as_deref(),
+        ]
+        .map(Option::unwrap_or_default)
+        .join("\n"),
+    )
+}
+
+impl Client {
+    /// Uses upserts to make sure there are rows in `reviews` for each of the
+    /// provided `reviews`, updating their rating and `updated_at`.
+    ///
+    /// Any of the product's reviews which aren't in `reviews` (i.e. because
+    /// they were taken down) are subsequently deleted.
+    pub async fn ensure_product_reviews(&self, product_id: i64, reviews: &[Review]) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        Client::ensure_product_reviews_with(&mut transaction, product_id, reviews).await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Same as [`ensure_product_reviews`](Client::ensure_product_reviews), on `conn`.
+    pub(super) async fn ensure_product_reviews_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        reviews: &[Review],
+    ) -> Result<()> {
+        let mut hashes = Vec::with_capacity(reviews.len());
+
+        for review in reviews {
+            let hash = review_hash(review);
+            let author = review.author.as_ref().map(|author| author.name());
+            let rating = review.review_rating.as_ref();
+            let rating_value = rating.map(|rating| rating.rating_value);
+            let best_rating = rating.and_then(|rating| rating.best_rating);
+
+            sqlx::query!(
+                r#"insert into reviews (product_id, review_hash, author, title, body, rating_value,
+                    best_rating, published_on)
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                on conflict do update set
+                    rating_value=excluded.rating_value,
+                    best_rating=excluded.best_rating,
+                    updated_at=(datetime('now', 'utc'))"#,
+                product_id,
+                hash,
+                author,
+                review.name,
+                review.review_body,
+                rating_value,
+                best_rating,
+                review.date_published
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            hashes.push(hash);
+        }
+
+        let hash_list = to_value_list(hashes);
+
+        sqlx::query!(
+            r#"delete from reviews where product_id = ?1 and review_hash not in (select value from json_each(?2))"#,
+            product_id,
+            hash_list
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
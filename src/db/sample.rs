@@ -0,0 +1,266 @@
+//! Copying a small random subset of the database into a new one (`ransaq
+//! sample-db`), to attach a dataset to bug reports rather than the whole
+//! database.
+//!
+//! The sample holds randomly picked products along with everything they
+//! refer to (lookups, categories and their parents, grape varieties, special
+//! features and food pairings), their identifiers and tasting notes, and
+//! their price and availability history, so that foreign keys hold. Crawl
+//! bookkeeping (crawl runs and their errors, the skip list, metrics) is
+//! specific to the machine that did the crawling and left out, as are stores
+//! and geocoded postal codes, which could reveal where the user lives.
+//! Reviews are left out too, as their authors and text are third parties'
+//! personal content.
+
+use super::merge::PRODUCT_LOOKUP_COLUMNS;
+use super::Client;
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use sqlx::{Connection, Row};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Tables copied along with the sampled products, in order, along with the
+/// condition rows have to meet to be copied. Rows of tables left out aren't
+/// copied at all.
+fn sampled_tables() -> Vec<(&'static str, String)> {
+    let mut tables = vec![
+        // Keeps the sample's schema version, i.e. for `ransaq merge`
+        ("_sqlx_migrations", "1".to_string()),
+        (
+            "products",
+            "id in (select id from temp.sample_products)".to_string(),
+        ),
+    ];
+
+    for (column, table) in PRODUCT_LOOKUP_COLUMNS {
+        tables.push((table, format!("id in (select {column} from main.products)")));
+    }
+
+    for table in [
+        "product_grape_varieties",
+        "product_special_features",
+        "product_food_pairings",
+        "product_categories",
+        "product_identifiers",
+        "product_changes",
+        "tasting_notes",
+    ] {
+        tables.push((
+            table,
+            "product_id in (select id from main.products)".to_string(),
+        ));
+    }
+
+    tables.extend([
+        (
+            "grape_varieties",
+            "id in (select grape_variety_id from main.product_grape_varieties)".to_string(),
+        ),
+        (
+            "special_features",
+            "id in (select special_feature_id from main.product_special_features)".to_string(),
+        ),
+        (
+            "food_pairings",
+            "id in (select food_pairing_id from main.product_food_pairings)".to_string(),
+        ),
+        (
+            "categories",
+            r#"id in (
+                with recursive ancestors(id) as (
+                    select category_id from main.product_categories
+                    union
+                    select c.parent_category_id from source.categories c
+                    join ancestors a on a.id = c.id
+                    where c.parent_category_id is not null
+                )
+                select id from ancestors
+            )"#
+            .to_string(),
+        ),
+    ]);
+
+    tables
+}
+
+/// What [`sample_into`](Client::sample_into) copied.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SampleReport {
+    /// Number of products in the sample.
+    pub products: u64,
+    /// Number of rows copied into each table, i.e. `countries`.
+    pub rows: BTreeMap<&'static str, u64>,
+}
+
+impl Client {
+    /// Creates a database at `path` with the same schema as this one, holding
+    /// up to `products` randomly picked products and the rows related to them
+    /// (see the [module docs](self)).
+    ///
+    /// Fails if there's already a file at `path`.
+    pub async fn sample_into(&self, path: &Path, products: u32) -> Result<SampleReport> {
+        if path.exists() {
+            return Err(eyre!("{:?} already exists", path));
+        }
+
+        let uri = self.attach_uri().await?;
+        let sample = Client::with_pool_size(&format!("sqlite:{}", path.display()), 1).await?;
+        let mut conn = sample.pool.acquire().await?;
+
+        sqlx::query("pragma foreign_keys = off")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("attach database ?1 as source")
+            .bind(&uri)
+            .execute(&mut conn)
+            .await?;
+
+        let schema = sqlx::query(
+            r#"select type, name, sql from source.sqlite_master
+            where sql is not null and name not like 'sqlite_%'"#,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut report = SampleReport::default();
+        let mut transaction = conn.begin().await?;
+
+        for row in schema.iter().filter(|row| row.get::<&str, _>(0) == "table") {
+            sqlx::query(row.get(2)).execute(&mut transaction).await?;
+        }
+
+        sqlx::query(
+            "create temp table sample_products as select id from source.products order by random() limit ?1",
+        )
+        .bind(products)
+        .execute(&mut transaction)
+        .await?;
+
+        // Indexes, triggers and views are only created once the tables are
+        // filled, as in `stage_in_memory`
+        for (table, condition) in sampled_tables() {
+            let copied = sqlx::query(&format!(
+                r#"insert into main."{table}" select * from source."{table}" where {condition}"#
+            ))
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+
+            match table {
+                "_sqlx_migrations" => {}
+                "products" => report.products = copied,
+                table => {
+                    report.rows.insert(table, copied);
+                }
+            }
+        }
+
+        for row in schema.iter().filter(|row| row.get::<&str, _>(0) != "table") {
+            sqlx::query(row.get(2)).execute(&mut transaction).await?;
+        }
+
+        transaction.commit().await?;
+
+        sqlx::query("detach database source")
+            .execute(&mut conn)
+            .await?;
+        drop(conn);
+
+        // Checkpoints the write-ahead log, leaving a single file behind
+        sample.pool.close().await;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use crate::db::Client;
+    use color_eyre::eyre::Result;
+
+    /// Counts the rows in `table` of `client`'s database.
+    async fn count(client: &Client, table: &str) -> Result<i64> {
+        Ok(sqlx::query_scalar(&format!("select count(*) from {table}"))
+            .fetch_one(&client.pool)
+            .await?)
+    }
+
+    #[tokio::test]
+    async fn test_sample_into() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        sqlx::query("insert into countries (name) values ('France'), ('Italy'), ('Spain')")
+            .execute(db.pool())
+            .await?;
+        for (saq_code, name, price_cents, country_id) in [
+            ("1", "Bordeaux", 1995, 1),
+            ("2", "Chianti", 2450, 2),
+            ("3", "Cahors", 1500, 1),
+        ] {
+            let id = db.insert_product(saq_code, name, price_cents).await?;
+            sqlx::query("update products set country_id = ?2 where id = ?1")
+                .bind(id)
+                .bind(country_id)
+                .execute(db.pool())
+                .await?;
+        }
+
+        for sql in [
+            "update products set price_cents = price_cents + 100",
+            r#"insert into categories (url, parent_category_id, name) values
+            ('https://www.saq.com/en/products/wine', null, 'Wine'),
+            ('https://www.saq.com/en/products/wine/red-wine', 1, 'Red wine'),
+            ('https://www.saq.com/en/products/beer', null, 'Beer')"#,
+            "insert into product_categories (product_id, category_id) values (1, 2), (2, 2), (3, 2)",
+            "insert into grape_varieties (name) values ('Merlot'), ('Gamay')",
+            "insert into product_grape_varieties (product_id, grape_variety_id) values (1, 1), (3, 1)",
+            "insert into crawl_runs (mode, note) values ('full', 'on my laptop')",
+            "insert into geocoded_postal_codes (postal_code, latitude, longitude) values ('H2V 4H1', 45.5, -73.6)",
+            "insert into reviews (product_id, review_hash, author, body) values (1, 'abc', 'Jane Doe', 'Lovely')",
+        ] {
+            sqlx::query(sql).execute(db.pool()).await?;
+        }
+
+        let path = db.path().with_extension("sample.sqlite");
+        let report = db.sample_into(&path, 10).await?;
+        assert!(db.sample_into(&path, 10).await.is_err());
+
+        let sample = Client::new(&format!("sqlite:{}", path.display())).await?;
+
+        assert_eq!(3, report.products);
+        assert_eq!(Some(&2), report.rows.get("countries"));
+        assert_eq!(2, count(&sample, "countries").await?);
+        // Only the category used, and its parent
+        assert_eq!(2, count(&sample, "categories").await?);
+        assert_eq!(1, count(&sample, "grape_varieties").await?);
+        assert_eq!(3, count(&sample, "product_changes").await?);
+        assert_eq!(0, count(&sample, "crawl_runs").await?);
+        assert_eq!(0, count(&sample, "geocoded_postal_codes").await?);
+        assert_eq!(0, count(&sample, "reviews").await?);
+
+        // The schema came along, triggers included
+        sqlx::query("update products set price_cents = 1 where saq_code = '1'")
+            .execute(&sample.pool)
+            .await?;
+        assert_eq!(4, count(&sample, "product_changes").await?);
+        sample.migrate().await?;
+        sample.pool.close().await;
+        std::fs::remove_file(&path)?;
+
+        let report = db.sample_into(&path, 1).await?;
+        assert_eq!(1, report.products);
+        let sample = Client::new(&format!("sqlite:{}", path.display())).await?;
+        let violations = sqlx::query("pragma foreign_key_check")
+            .fetch_all(&sample.pool)
+            .await?;
+        assert!(violations.is_empty());
+        assert_eq!(1, count(&sample, "countries").await?);
+        assert_eq!(2, count(&sample, "categories").await?);
+        sample.pool.close().await;
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
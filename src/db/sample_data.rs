@@ -0,0 +1,69 @@
+//! A tiny catalog embedded in the build, so that doctests and examples can
+//! run against real data without a crawl (see
+//! [`new_in_memory_with_sample_data`](Client::new_in_memory_with_sample_data)).
+//!
+//! Not to be confused with [`sample_into`](Client::sample_into), which copies a
+//! random subset of an existing database.
+
+use super::Client;
+use color_eyre::eyre::Result;
+use sqlx::Connection;
+
+/// The statements filling the sample catalog.
+const SAMPLE_DATA: &str = include_str!("sample_data.sql");
+
+impl Client {
+    /// Same as [`new_in_memory`](Client::new_in_memory), with a handful of
+    /// products (a red wine, a beer, a vodka, a champagne and a sold out
+    /// Beaujolais) along with their lookups, categories, price changes, as
+    /// well as two stores and a completed crawl.
+    ///
+    /// ```
+    /// use ransaq::db::Client;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> color_eyre::Result<()> {
+    /// let db = Client::new_in_memory_with_sample_data().await?;
+    ///
+    /// let red_wines = db.search(&"color:red".parse()?, 10).await?;
+    /// assert_eq!(
+    ///     vec!["Château Maris Minervois La Livinière 2019", "Louis Jadot Beaujolais-Villages"],
+    ///     red_wines.iter().map(|product| product.name.as_str()).collect::<Vec<_>>()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new_in_memory_with_sample_data() -> Result<Client> {
+        let db = Client::new_in_memory().await?;
+
+        let mut conn = db.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+        sqlx::query(SAMPLE_DATA).execute(&mut transaction).await?;
+        transaction.commit().await?;
+
+        Ok(db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sample_data() -> Result<()> {
+        let db = Client::new_in_memory_with_sample_data().await?;
+
+        let violations = sqlx::query("pragma foreign_key_check")
+            .fetch_all(&db.pool)
+            .await?;
+        assert!(violations.is_empty());
+
+        // Clones share the same database
+        let products: i64 = sqlx::query_scalar("select count(*) from products")
+            .fetch_one(&db.clone().pool)
+            .await?;
+        assert_eq!(5, products);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+-- A handful of products with everything related to them, loaded by
+-- `Client::new_in_memory_with_sample_data` for doctests and examples. Dates
+-- are fixed so that examples' output doesn't change from one run to the next.
+
+insert into countries (id, name) values (1, 'France'), (2, 'Canada'), (3, 'Sweden');
+insert into regions (id, name) values (1, 'Languedoc-Roussillon'), (2, 'Québec'), (3, 'Champagne'),
+  (4, 'Beaujolais');
+insert into colors (id, name) values (1, 'Red'), (2, 'White');
+insert into producers (id, name) values (1, 'Château Maris'), (2, 'Dieu du Ciel!'),
+  (3, 'The Absolut Company'), (4, 'Veuve Clicquot Ponsardin'), (5, 'Maison Louis Jadot');
+insert into grape_varieties (id, name) values (1, 'Syrah'), (2, 'Grenache'), (3, 'Pinot noir'),
+  (4, 'Chardonnay'), (5, 'Gamay');
+insert into special_features (id, name) values (1, 'Organic product');
+insert into food_pairings (id, name) values (1, 'Game'), (2, 'Grilled red meat');
+
+insert into categories (id, url, parent_category_id, name) values
+  (1, 'https://www.saq.com/en/products/wine', null, 'Wine'),
+  (2, 'https://www.saq.com/en/products/wine/red-wine', 1, 'Red wine'),
+  (3, 'https://www.saq.com/en/products/wine/sparkling-wine', 1, 'Sparkling wine'),
+  (4, 'https://www.saq.com/en/products/beer', null, 'Beer'),
+  (5, 'https://www.saq.com/en/products/spirit', null, 'Spirit');
+
+insert into products (id, saq_code, name, description, availability, item_condition, price_cents,
+  regular_price_cents, price_valid_until, producer_id, abv_percentage, container_count,
+  container_milliliters, color_id, region_id, country_id, product_of_quebec, created_at,
+  updated_at)
+values
+  (1, '13191791', 'Château Maris Minervois La Livinière 2019',
+    'A deep, concentrated red with aromas of black fruit, garrigue and spices.', 'in_stock', 'new',
+    2995, null, null, 1, 14.5, 1, 750, 1, 1, 1, null, '2026-09-01 06:00:00', '2026-10-15 06:00:00'),
+  (2, '12345678', 'Dieu du Ciel! Péché Mortel',
+    'An imperial coffee stout with roasted notes of espresso, dark chocolate and molasses.',
+    'limited_availability', 'new', 1725, 1975, '2026-11-01', 2, 9.5, 4, 341, null, 2, 2,
+    'made_in_quebec', '2026-09-01 06:00:00', '2026-10-15 06:00:00'),
+  (3, '00000026', 'Absolut Vodka',
+    'A clean, smooth vodka distilled from winter wheat grown in Åhus, Sweden.', 'in_stock', 'new',
+    3275, null, null, 3, 40, 1, 1140, null, null, 3, null, '2026-09-01 06:00:00',
+    '2026-10-15 06:00:00'),
+  (4, '11766597', 'Veuve Clicquot Brut', 'A brut champagne with notes of brioche and apple.',
+    'in_stock', 'new', 7225, null, null, 4, 12, 1, 750, 2, 3, 1, null, '2026-09-01 06:00:00',
+    '2026-10-15 06:00:00'),
+  (5, '10264010', 'Louis Jadot Beaujolais-Villages', 'A light, fruity red.', 'sold_out', 'new',
+    1895, null, null, 5, 12.5, 1, 750, 1, 4, 1, null, '2026-09-15 06:00:00',
+    '2026-10-15 06:00:00');
+
+insert into product_grape_varieties (product_id, grape_variety_id, percentage) values
+  (1, 1, 60), (1, 2, 40), (4, 3, 50), (4, 4, 30), (5, 5, 100);
+insert into product_categories (product_id, category_id) values
+  (1, 1), (1, 2), (2, 4), (3, 5), (4, 1), (4, 3), (5, 1), (5, 2);
+insert into product_special_features (product_id, special_feature_id) values (1, 1);
+insert into product_food_pairings (product_id, food_pairing_id) values (1, 1), (1, 2);
+insert into tasting_notes (product_id, taste_tag, aromas, acidity, body, serving_temperature)
+values (1, 'Aromatic and robust', '["Black fruit","Garrigue","Spices"]', 'Lively', 'Full',
+  '16 °C to 18 °C');
+insert into product_identifiers (product_id, scheme, value) values (1, 'gtin13', '3760089460186');
+
+-- Price and availability changes, written directly rather than through the
+-- trigger so that they keep their dates
+insert into product_changes (product_id, old_price_cents, new_price_cents, old_availability,
+  new_availability, old_regular_price_cents, new_regular_price_cents, changed_at)
+values
+  (1, 3195, 2995, 'in_stock', 'in_stock', null, null, '2026-10-01 06:00:00'),
+  (2, 1975, 1725, 'in_stock', 'limited_availability', null, 1975, '2026-10-08 06:00:00'),
+  (5, 1895, 1895, 'in_stock', 'sold_out', null, null, '2026-10-12 06:00:00');
+
+-- The prices products were first crawled at, from before those changes
+update products set baseline_price_cents = 3195 where id = 1;
+update products set baseline_price_cents = 1975, baseline_regular_price_cents = null where id = 2;
+
+insert into stores (saq_store_id, name, address, city, postal_code, latitude, longitude) values
+  ('23009', 'Laurier', '1 Rue Laurier', 'Montréal', 'H2V 4H1', 45.523, -73.596),
+  ('23180', 'Vieux-Québec', '1 Rue Saint-Jean', 'Québec', 'G1R 4P5', 46.812, -71.214);
+
+insert into crawl_runs (status, started_at, finished_at, mode) values
+  ('completed', '2026-10-15 06:00:00', '2026-10-15 09:30:00', 'full');
@@ -0,0 +1,327 @@
+//! Describing the tables and relations of the database, for people querying
+//! it directly (i.e. from BI tools) without reading through the migrations.
+//!
+//! The description is read from the live database, so it always matches the
+//! migrations it is at. Views (i.e. `product_price_history`) are only part of
+//! the [SQL](Schema::sql) rendering, as they have no keys to diagram. SQLite's internal tables and `_sqlx_migrations` are
+//! left out.
+
+use super::Client;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sqlx::Row;
+use std::fmt::Write;
+
+/// Excludes tables that aren't part of the data model from `sqlite_master`.
+const USER_TABLES: &str = "name not like 'sqlite_%' and tbl_name != '_sqlx_migrations'";
+
+/// A column of a [`Table`].
+#[derive(Debug, Serialize)]
+pub struct Column {
+    /// The column's name.
+    pub name: String,
+    /// The column's declared type (i.e. `integer`), empty if it has none.
+    pub data_type: String,
+    /// Whether the column is declared `not null`.
+    pub not_null: bool,
+    /// Whether the column is (part of) the primary key.
+    pub primary_key: bool,
+}
+
+/// A reference from a column of a [`Table`] to another table.
+#[derive(Debug, Serialize)]
+pub struct ForeignKey {
+    /// The referencing column.
+    pub column: String,
+    /// The referenced table.
+    pub table: String,
+    /// The referenced column.
+    pub to: String,
+}
+
+/// A table and the statements defining it.
+#[derive(Debug, Serialize)]
+pub struct Table {
+    /// The table's name.
+    pub name: String,
+    /// The table's columns, in order.
+    pub columns: Vec<Column>,
+    /// The table's references to other tables.
+    pub foreign_keys: Vec<ForeignKey>,
+    /// The statements creating the table and its indexes and triggers.
+    pub sql: Vec<String>,
+}
+
+/// A view and the statements defining it.
+#[derive(Debug, Serialize)]
+pub struct View {
+    /// The view's name.
+    pub name: String,
+    /// The statements creating the view and its triggers.
+    pub sql: Vec<String>,
+}
+
+/// The tables and views of the database.
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    /// The tables, ordered by name.
+    pub tables: Vec<Table>,
+    /// The views, in the order they were created in so that views built on
+    /// other views come after them.
+    pub views: Vec<View>,
+}
+
+impl Schema {
+    /// Renders the schema as a [Mermaid](https://mermaid.js.org) ER diagram.
+    pub fn mermaid(&self) -> String {
+        let mut out = "erDiagram\n".to_string();
+
+        for table in &self.tables {
+            writeln!(out, "    {} {{", table.name).unwrap();
+            for column in &table.columns {
+                writeln!(
+                    out,
+                    "        {} {}{}",
+                    mermaid_type(&column.data_type),
+                    column.name,
+                    key_marker(table, column)
+                )
+                .unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+
+        for table in &self.tables {
+            for foreign_key in &table.foreign_keys {
+                // Rows can only refer to an existing row, or none at all when
+                // the column is nullable
+                let cardinality = match table.column(&foreign_key.column) {
+                    Some(column) if column.not_null => "||",
+                    _ => "|o",
+                };
+                writeln!(
+                    out,
+                    "    {} {}--o{{ {} : {}",
+                    foreign_key.table, cardinality, table.name, foreign_key.column
+                )
+                .unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// Renders the schema as a [Graphviz](https://graphviz.org) graph.
+    pub fn dot(&self) -> String {
+        let mut out = "digraph schema {\n    rankdir=LR;\n    node [shape=record];\n".to_string();
+
+        for table in &self.tables {
+            let columns = table
+                .columns
+                .iter()
+                .map(|column| {
+                    format!(
+                        "{} {}{}\\l",
+                        column.name,
+                        column.data_type,
+                        key_marker(table, column)
+                    )
+                })
+                .collect::<String>();
+            writeln!(
+                out,
+                "    {} [label=\"{{{}|{}}}\"];",
+                table.name, table.name, columns
+            )
+            .unwrap();
+        }
+
+        for table in &self.tables {
+            for foreign_key in &table.foreign_keys {
+                writeln!(
+                    out,
+                    "    {} -> {} [label=\"{}\"];",
+                    table.name, foreign_key.table, foreign_key.column
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the statements creating the schema, tables first as views
+    /// depend on them.
+    pub fn sql(&self) -> String {
+        self.tables
+            .iter()
+            .flat_map(|table| &table.sql)
+            .chain(self.views.iter().flat_map(|view| &view.sql))
+            .map(|sql| format!("{sql};\n\n"))
+            .collect()
+    }
+}
+
+impl Table {
+    /// Returns the column with the given name.
+    fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+/// Returns ` PK`, ` FK` or ` PK, FK` depending on the keys `column` is part
+/// of, or nothing.
+fn key_marker(table: &Table, column: &Column) -> &'static str {
+    let foreign_key = table
+        .foreign_keys
+        .iter()
+        .any(|foreign_key| foreign_key.column == column.name);
+
+    match (column.primary_key, foreign_key) {
+        (true, true) => " PK, FK",
+        (true, false) => " PK",
+        (false, true) => " FK",
+        (false, false) => "",
+    }
+}
+
+/// Mermaid requires attributes to have a type.
+fn mermaid_type(data_type: &str) -> &str {
+    match data_type {
+        "" => "any",
+        data_type => data_type,
+    }
+}
+
+impl Client {
+    /// Describes the tables and views of the database and the relations
+    /// between tables.
+    pub async fn schema(&self) -> Result<Schema> {
+        let mut conn = self.pool.acquire().await?;
+
+        let names: Vec<String> = sqlx::query_scalar(&format!(
+            "select name from sqlite_master where type = 'table' and {USER_TABLES} order by name"
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut tables = Vec::with_capacity(names.len());
+        for name in names {
+            let columns = sqlx::query(
+                r#"select name, type, "notnull", pk from pragma_table_info(?1) order by cid"#,
+            )
+            .bind(&name)
+            .fetch_all(&mut conn)
+            .await?
+            .iter()
+            .map(|row| Column {
+                name: row.get(0),
+                // Strict tables report types in uppercase
+                data_type: row.get::<String, _>(1).to_lowercase(),
+                not_null: row.get(2),
+                primary_key: row.get::<i64, _>(3) > 0,
+            })
+            .collect();
+
+            let foreign_keys = sqlx::query(
+                r#"select "from", "table", coalesce("to", 'id') from pragma_foreign_key_list(?1) order by id, seq"#,
+            )
+            .bind(&name)
+            .fetch_all(&mut conn)
+            .await?
+            .iter()
+            .map(|row| ForeignKey {
+                column: row.get(0),
+                table: row.get(1),
+                to: row.get(2),
+            })
+            .collect();
+
+            // The table first, then its indexes and triggers
+            let sql = sqlx::query_scalar(&format!(
+                r#"select sql from sqlite_master where tbl_name = ?1 and sql is not null and {USER_TABLES}
+                order by type != 'table', type, name"#
+            ))
+            .bind(&name)
+            .fetch_all(&mut conn)
+            .await?;
+
+            tables.push(Table {
+                name,
+                columns,
+                foreign_keys,
+                sql,
+            });
+        }
+
+        let names: Vec<String> = sqlx::query_scalar(&format!(
+            "select name from sqlite_master where type = 'view' and {USER_TABLES} order by rowid"
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut views = Vec::with_capacity(names.len());
+        for name in names {
+            // The view first, then its triggers
+            let sql = sqlx::query_scalar(&format!(
+                r#"select sql from sqlite_master where tbl_name = ?1 and sql is not null and {USER_TABLES}
+                order by type != 'view', name"#
+            ))
+            .bind(&name)
+            .fetch_all(&mut conn)
+            .await?;
+
+            views.push(View { name, sql });
+        }
+
+        Ok(Schema { tables, views })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    #[tokio::test]
+    async fn test_schema() -> Result<()> {
+        let db = TestDb::new().await?;
+        let schema = db.schema().await?;
+
+        let names = schema
+            .tables
+            .iter()
+            .map(|table| table.name.as_str())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"products"));
+        assert!(!names.contains(&"_sqlx_migrations"));
+
+        let mermaid = schema.mermaid();
+        assert!(mermaid
+            .starts_with("erDiagram\n    brands {\n        integer id PK\n        text name\n"));
+        assert!(mermaid.contains("        integer producer_id FK\n"));
+        assert!(mermaid.contains("    producers |o--o{ products : producer_id\n"));
+        assert!(mermaid.contains("    products ||--o{ product_changes : product_id\n"));
+
+        let dot = schema.dot();
+        assert!(dot.contains("    products -> producers [label=\"producer_id\"];\n"));
+
+        let sql = schema.sql();
+        assert!(sql.contains("CREATE TRIGGER products__record_changes"));
+        assert!(sql.contains("CREATE TABLE \"products\""));
+        assert!(sql.contains("CREATE VIEW product_price_history"));
+
+        // The statements recreate the schema
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+        conn.execute(sql.as_str()).await?;
+        let views: Vec<String> =
+            sqlx::query_scalar("select name from sqlite_master where type = 'view'")
+                .fetch_all(&mut conn)
+                .await?;
+        assert_eq!(vec!["product_price_history"], views);
+
+        Ok(())
+    }
+}
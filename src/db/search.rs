@@ -0,0 +1,224 @@
+//! Searching products using [filter expressions](crate::filter).
+
+use super::Client;
+use crate::filter::{Condition, Filter, NumericField, TextField};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// A product matching a search.
+#[derive(Debug, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct ProductSummary {
+    /// The product's SAQ code.
+    pub saq_code: String,
+    /// The product's name.
+    pub name: String,
+    /// The product's current price, in cents.
+    pub price_cents: i64,
+    /// The product's availability (i.e. `in_stock`).
+    pub availability: String,
+    /// The name of the product's country, if known.
+    pub country: Option<String>,
+    /// The name of the product's color, if known.
+    pub color: Option<String>,
+}
+
+/// A value bound to a search query.
+enum Param {
+    /// Bound as `text`.
+    Text(String),
+    /// Bound as `real`.
+    Real(f64),
+}
+
+/// Escapes the `LIKE` wildcards in `value`, to be used with `escape '\'`.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Returns the SQL expression (with a single `?` placeholder) checking
+/// `condition` against the `products p` row, along with the value to bind.
+fn condition_sql(condition: &Condition) -> (String, Param) {
+    match condition {
+        Condition::Text {
+            field,
+            exact,
+            value,
+        } => {
+            let (test, param) = match exact {
+                true => ("= ? collate nocase", value.clone()),
+                false => (r"like ? escape '\'", format!("%{}%", escape_like(value))),
+            };
+
+            let lookup = |table: &str, column: &str| {
+                format!("p.{column} in (select id from {table} where name {test})")
+            };
+
+            let sql = match field {
+                TextField::Name => format!("p.name {test}"),
+                TextField::Code => format!("p.saq_code {test}"),
+                TextField::Availability => format!("p.availability {test}"),
+                TextField::Country => lookup("countries", "country_id"),
+                TextField::Region => lookup("regions", "region_id"),
+                TextField::Color => lookup("colors", "color_id"),
+                TextField::Producer => lookup("producers", "producer_id"),
+                TextField::Brand => lookup("brands", "brand_id"),
+                TextField::Grape => format!(
+                    r#"p.id in (select pgv.product_id from product_grape_varieties pgv
+                    join grape_varieties gv on gv.id = pgv.grape_variety_id where gv.name {test})"#
+                ),
+                TextField::Category => format!(
+                    r#"p.id in (select pc.product_id from product_categories pc
+                    join categories c on c.id = pc.category_id where c.name {test})"#
+                ),
+            };
+
+            (sql, Param::Text(param))
+        }
+        Condition::Numeric {
+            field,
+            comparison,
+            value,
+        } => {
+            let (column, value) = match field {
+                NumericField::Price => ("price_cents", (value * 100.0).round()),
+                NumericField::Abv => ("abv_percentage", *value),
+                NumericField::Volume => ("container_milliliters", *value),
+                NumericField::Sugar => ("sugar_content_grams_per_liter", *value),
+            };
+
+            (
+                format!("p.{} {} ?", column, comparison.sql_operator()),
+                Param::Real(value),
+            )
+        }
+    }
+}
+
+impl Client {
+    /// Returns up to `limit` products matching `filter`, ordered by name.
+    ///
+    /// Negated terms also match products for which the field is unknown (i.e.
+    /// `-country:France` matches products without a country).
+    pub async fn search(&self, filter: &Filter, limit: i64) -> Result<Vec<ProductSummary>> {
+        self.search_page(filter, limit, 0).await
+    }
+
+    /// Same as [`search`](Client::search), skipping the first `offset`
+    /// matching products.
+    pub async fn search_page(
+        &self,
+        filter: &Filter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ProductSummary>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let mut sql = String::from(
+            r#"select p.saq_code, p.name, p.price_cents, p.availability,
+                c.name as country, co.name as color
+            from products p
+            left join countries c on c.id = p.country_id
+            left join colors co on co.id = p.color_id
+            where 1"#,
+        );
+        let mut params = vec![];
+
+        for term in &filter.terms {
+            let (condition, param) = condition_sql(&term.condition);
+            match term.negated {
+                true => sql.push_str(&format!("\nand not coalesce(({condition}), 0)")),
+                false => sql.push_str(&format!("\nand ({condition})")),
+            }
+            params.push(param);
+        }
+
+        sql.push_str("\norder by p.name, p.saq_code limit ? offset ?");
+
+        let mut query = sqlx::query_as::<_, ProductSummary>(&sql);
+        for param in params {
+            query = match param {
+                Param::Text(value) => query.bind(value),
+                Param::Real(value) => query.bind(value),
+            };
+        }
+
+        Ok(query.bind(limit).bind(offset).fetch_all(&mut conn).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_search() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let france = db.upsert_country("France").await?;
+        let italy = db.upsert_country("Italie").await?;
+        let red = db.upsert_color("Rouge").await?;
+        let gamay = db.upsert_grape_variety("Gamay").await?;
+
+        for (saq_code, name, price_cents, country_id) in [
+            ("1", "Beaujolais 50% Gamay", 1895, Some(france)),
+            ("2", "Chianti", 2450, Some(italy)),
+            ("3", "Mystery Red", 3000, None),
+        ] {
+            let id = db.insert_product(saq_code, name, price_cents).await?;
+            sqlx::query("update products set country_id = ?2, color_id = ?3 where id = ?1")
+                .bind(id)
+                .bind(country_id)
+                .bind(red)
+                .execute(db.pool())
+                .await?;
+
+            if saq_code == "1" {
+                sqlx::query(
+                    "insert into product_grape_varieties (product_id, grape_variety_id) values (?1, ?2)",
+                )
+                .bind(id)
+                .bind(gamay)
+                .execute(db.pool())
+                .await?;
+            }
+        }
+
+        let search = |filter: &'static str| {
+            let db = &db;
+            async move {
+                let products = db.search(&filter.parse()?, 10).await?;
+                Ok::<_, color_eyre::Report>(
+                    products
+                        .into_iter()
+                        .map(|product| product.saq_code)
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+
+        assert_eq!(vec!["1", "2", "3"], search("").await?);
+        assert_eq!(
+            vec!["1"],
+            search("country:fra color:rouge grape:gamay").await?
+        );
+        assert_eq!(vec!["1", "2"], search("price<25").await?);
+        assert_eq!(vec!["2"], search("price>=24.50 price<=24.50").await?);
+        assert_eq!(vec!["2", "3"], search("-country:France").await?);
+        assert_eq!(vec!["1"], search("50%").await?);
+        assert!(search("country=fra").await?.is_empty());
+        assert_eq!(vec!["2"], search("country=italie").await?);
+
+        let limited = db.search(&"red".parse()?, 1).await?;
+        assert_eq!(1, limited.len());
+        assert_eq!(Some("Rouge".to_string()), limited[0].color);
+
+        let second = db.search_page(&"".parse()?, 1, 1).await?;
+        assert_eq!("2", second[0].saq_code);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! The database side of [`ransaq selftest`](crate::selftest): making sure the
+//! database can be written to in WAL mode and is at the migrations this build
+//! expects.
+
+use super::{Client, MIGRATOR};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use sqlx::{Connection, Row};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+impl Client {
+    /// The path of the database file, `None` for in-memory databases.
+    pub async fn database_file(&self) -> Result<Option<PathBuf>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let path: String =
+            sqlx::query_scalar("select file from pragma_database_list where name = 'main'")
+                .fetch_one(&mut conn)
+                .await?;
+
+        Ok((!path.is_empty()).then(|| PathBuf::from(path)))
+    }
+
+    /// Creates a scratch table, writes a row to it and reads it back, then
+    /// drops it, all in a committed transaction so that the write makes it to
+    /// disk.
+    pub async fn check_writable(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let mut transaction = conn.begin().await?;
+
+        sqlx::query("create table _ransaq_selftest (value text not null) strict")
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query("insert into _ransaq_selftest (value) values ('ok')")
+            .execute(&mut transaction)
+            .await?;
+        let value: String = sqlx::query_scalar("select value from _ransaq_selftest")
+            .fetch_one(&mut transaction)
+            .await?;
+        sqlx::query("drop table _ransaq_selftest")
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        match value.as_str() {
+            "ok" => Ok(()),
+            other => Err(eyre!("read back {:?} rather than \"ok\"", other)),
+        }
+    }
+
+    /// Makes sure the database is in WAL mode and that the write-ahead log
+    /// can be checkpointed, which relies on shared memory that network
+    /// filesystems don't provide.
+    pub async fn check_wal(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let journal_mode: String = sqlx::query_scalar("pragma journal_mode")
+            .fetch_one(&mut conn)
+            .await?;
+        if journal_mode != "wal" {
+            return Err(eyre!(
+                "the journal mode is {:?} rather than \"wal\"",
+                journal_mode
+            ));
+        }
+
+        let checkpoint = sqlx::query("pragma wal_checkpoint(passive)")
+            .fetch_one(&mut conn)
+            .await?;
+        if checkpoint.get::<i64, _>(0) != 0 {
+            return Err(eyre!("the write-ahead log could not be checkpointed"));
+        }
+
+        Ok(())
+    }
+
+    /// Compares the migrations applied to the database with the ones
+    /// embedded in this build, describing each difference.
+    pub async fn migration_problems(&self) -> Result<Vec<String>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query(
+            "select version, description, success, checksum from _sqlx_migrations order by version",
+        )
+        .fetch_all(&mut conn)
+        .await
+        .map_err(|err| {
+            eyre!(
+                "could not read the applied migrations (were they run with `sqlx migrate run`?): {}",
+                err
+            )
+        })?;
+
+        let mut applied = rows
+            .iter()
+            .map(|row| {
+                let version: i64 = row.get(0);
+                let fields: (String, bool, Vec<u8>) = (row.get(1), row.get(2), row.get(3));
+                (version, fields)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut problems = vec![];
+
+        for migration in MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+        {
+            match applied.remove(&migration.version) {
+                None => problems.push(format!(
+                    "migration {} ({}) hasn't been applied",
+                    migration.version, migration.description
+                )),
+                Some((_, false, _)) => problems.push(format!(
+                    "migration {} ({}) failed partway",
+                    migration.version, migration.description
+                )),
+                Some((_, true, checksum)) if checksum != *migration.checksum => {
+                    problems.push(format!(
+                        "migration {} ({}) was changed after being applied",
+                        migration.version, migration.description
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (version, (description, _, _)) in applied {
+            problems.push(format!(
+                "migration {version} ({description}) is unknown to this build, which is likely out of date"
+            ));
+        }
+
+        Ok(problems)
+    }
+
+    /// When the most recent crawl started, if any.
+    pub async fn latest_crawl_started_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let started_at = sqlx::query_scalar!(
+            r#"select max(started_at) as "started_at: DateTime<Utc>" from crawl_runs"#
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(started_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_selftest_checks() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        assert_eq!(Some(db.path()), db.database_file().await?.as_deref());
+        db.check_writable().await?;
+        db.check_writable().await?;
+        db.check_wal().await?;
+        assert!(db.migration_problems().await?.is_empty());
+        assert_eq!(None, db.latest_crawl_started_at().await?);
+
+        for sql in [
+            "delete from _sqlx_migrations where version = (select max(version) from _sqlx_migrations)",
+            "insert into _sqlx_migrations (version, description, success, checksum, execution_time) values (99990101000000, 'from the future', 1, x'00', 0)",
+            "update _sqlx_migrations set checksum = x'00' where version = (select min(version) from _sqlx_migrations)",
+        ] {
+            sqlx::query(sql).execute(db.pool()).await?;
+        }
+
+        let problems = db.migration_problems().await?;
+        assert_eq!(3, problems.len());
+        assert!(problems[0].contains("was changed after being applied"));
+        assert!(problems[1].contains("hasn't been applied"));
+        assert!(problems[2].contains("99990101000000 (from the future) is unknown"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Crawling into an in-memory copy of the database.
+//!
+//! Writing each product to disk as it is crawled is slow on devices with slow
+//! storage (i.e. a Raspberry Pi running off an SD card). Staging the crawl in
+//! memory and writing the result back in one go trades durability (an
+//! interrupted crawl loses everything it captured) for speed.
+//!
+//! Anything written to the on-disk database while a crawl is staged is
+//! overwritten when the staged copy is written back.
+
+use super::{sqlite_configuration, Client};
+use color_eyre::eyre::{eyre, Result};
+use sqlx::sqlite::{SqliteConnection, SqlitePoolOptions};
+use sqlx::{Connection, Row};
+use url::Url;
+
+/// Returns the names of the tables in the `schema` database (i.e. `main`),
+/// excluding SQLite's internal tables.
+async fn table_names(conn: &mut SqliteConnection, schema: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query(&format!(
+        "select name from {schema}.sqlite_master where type = 'table' and name not like 'sqlite_%'"
+    ))
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+impl Client {
+    /// Returns a URI for the file backing this database, suitable for
+    /// attaching it to an in-memory database.
+    ///
+    /// Attached databases inherit the flags of the connection they're attached
+    /// to, which would make them in-memory too unless `mode=rw` is specified.
+    pub(super) async fn attach_uri(&self) -> Result<String> {
+        let mut conn = self.pool.acquire().await?;
+
+        let path: String =
+            sqlx::query_scalar("select file from pragma_database_list where name = 'main'")
+                .fetch_one(&mut conn)
+                .await?;
+
+        let mut uri =
+            Url::from_file_path(&path).map_err(|_| eyre!("{:?} is not an absolute path", path))?;
+        uri.set_query(Some("mode=rw"));
+
+        Ok(uri.to_string())
+    }
+
+    /// Creates an in-memory database with the same schema and contents as this
+    /// one, to be written back with [`replace_with`](Client::replace_with).
+    pub async fn stage_in_memory(&self) -> Result<Client> {
+        let uri = self.attach_uri().await?;
+
+        // The database only lives as long as its connections, so keep a single
+        // one open for the lifetime of the pool.
+        let pool = SqlitePoolOptions::new()
+            .min_connections(1)
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(sqlite_configuration("sqlite::memory:")?)
+            .await?;
+        let staging = Client { pool };
+
+        let mut conn = staging.pool.acquire().await?;
+        sqlx::query("pragma foreign_keys = off")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("attach database ?1 as source")
+            .bind(&uri)
+            .execute(&mut conn)
+            .await?;
+
+        let schema = sqlx::query(
+            r#"select type, name, sql from source.sqlite_master
+            where sql is not null and name not like 'sqlite_%'"#,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut transaction = conn.begin().await?;
+
+        // Tables are filled before creating indexes and triggers, which
+        // would otherwise slow down (or fire during) the copy.
+        for row in schema.iter().filter(|row| row.get::<&str, _>(0) == "table") {
+            let name: &str = row.get(1);
+            sqlx::query(row.get(2)).execute(&mut transaction).await?;
+            sqlx::query(&format!(
+                r#"insert into main."{name}" select * from source."{name}""#
+            ))
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        for row in schema.iter().filter(|row| row.get::<&str, _>(0) != "table") {
+            sqlx::query(row.get(2)).execute(&mut transaction).await?;
+        }
+
+        transaction.commit().await?;
+
+        sqlx::query("detach database source")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("pragma foreign_keys = on")
+            .execute(&mut conn)
+            .await?;
+
+        Ok(staging)
+    }
+
+    /// Replaces the contents of every table in this database with the ones in
+    /// `staging` (see [`stage_in_memory`](Client::stage_in_memory)).
+    ///
+    /// This happens in a single transaction, so the database is either left
+    /// untouched or fully replaced.
+    pub async fn replace_with(&self, staging: &Client) -> Result<()> {
+        let uri = self.attach_uri().await?;
+
+        let mut conn = staging.pool.acquire().await?;
+        sqlx::query("pragma foreign_keys = off")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("attach database ?1 as target")
+            .bind(&uri)
+            .execute(&mut conn)
+            .await?;
+
+        let tables = table_names(&mut conn, "main").await?;
+
+        let mut transaction = conn.begin().await?;
+
+        for name in tables {
+            sqlx::query(&format!(r#"delete from target."{name}""#))
+                .execute(&mut transaction)
+                .await?;
+            sqlx::query(&format!(
+                r#"insert into target."{name}" select * from main."{name}""#
+            ))
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        sqlx::query("detach database target")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("pragma foreign_keys = on")
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    /// Counts the rows in `products` using `pool`.
+    async fn count_products(pool: &sqlx::SqlitePool) -> Result<i64> {
+        Ok(sqlx::query_scalar("select count(*) from products")
+            .fetch_one(pool)
+            .await?)
+    }
+
+    #[tokio::test]
+    async fn test_stage_in_memory() -> Result<()> {
+        let db = TestDb::new().await?;
+        db.insert_product("on-disk", "Staged", 100).await?;
+
+        let staging = db.stage_in_memory().await?;
+        assert_eq!(1, count_products(&staging.pool).await?);
+
+        staging.insert_product("staged", "Staged", 100).await?;
+        let color_id = staging.upsert_color("Red").await?;
+        assert_eq!(1, count_products(db.pool()).await?);
+
+        // Constraints and indexes were copied along with the tables
+        assert!(staging
+            .insert_product("staged", "Staged", 100)
+            .await
+            .is_err());
+
+        db.replace_with(&staging).await?;
+        assert_eq!(2, count_products(db.pool()).await?);
+        assert_eq!(color_id, db.upsert_color("Red").await?);
+
+        Ok(())
+    }
+}
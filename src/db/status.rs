@@ -0,0 +1,119 @@
+//! Summaries of how up to date the crawled data is.
+
+use super::{Client, CrawlRunMode, DbSerialize};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sqlx::Row;
+
+/// Tables with an `updated_at` column that crawls keep up to date.
+const TRACKED_TABLES: [&str; 8] = [
+    "products",
+    "product_categories",
+    "product_food_pairings",
+    "product_grape_varieties",
+    "product_identifiers",
+    "product_special_features",
+    "reviews",
+    "tasting_notes",
+];
+
+/// How recently a table was written to.
+#[derive(Debug, Serialize)]
+pub struct TableFreshness {
+    /// The name of the table.
+    pub table: &'static str,
+    /// Number of rows in the table.
+    pub rows: i64,
+    /// The most recent `updated_at` in the table, if it has any rows.
+    pub last_updated_at: Option<DateTime<Utc>>,
+}
+
+impl Client {
+    /// Returns when the most recent completed crawl in the given `mode`
+    /// finished, if any.
+    pub async fn last_completed_crawl_at(
+        &self,
+        mode: CrawlRunMode,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let mut conn = self.pool.acquire().await?;
+        let mode = mode.db_serialize();
+
+        let finished_at = sqlx::query_scalar!(
+            r#"select max(finished_at) as "finished_at: DateTime<Utc>" from crawl_runs
+            where mode = ?1 and status = 'completed'"#,
+            mode
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(finished_at)
+    }
+
+    /// Returns the row count and latest `updated_at` of each table crawls write to.
+    pub async fn table_freshness(&self) -> Result<Vec<TableFreshness>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut freshness = Vec::with_capacity(TRACKED_TABLES.len());
+
+        for table in TRACKED_TABLES {
+            // Table names can't be bound as parameters, but these are all constants
+            let row = sqlx::query(&format!("select count(*), max(updated_at) from {table}"))
+                .fetch_one(&mut conn)
+                .await?;
+
+            freshness.push(TableFreshness {
+                table,
+                rows: row.try_get(0)?,
+                last_updated_at: row.try_get(1)?,
+            });
+        }
+
+        Ok(freshness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use crate::db::{CrawlRunMode, CrawlRunStatus};
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_freshness() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        assert_eq!(None, db.last_completed_crawl_at(CrawlRunMode::Full).await?);
+
+        let freshness = db.table_freshness().await?;
+        assert!(freshness
+            .iter()
+            .all(|t| t.rows == 0 && t.last_updated_at.is_none()));
+
+        let crawl_run_id = db.start_crawl_run(CrawlRunMode::Full).await?;
+        db.finish_crawl_run(crawl_run_id, CrawlRunStatus::Completed)
+            .await?;
+
+        db.insert_product("freshness", "Freshness", 100).await?;
+
+        assert!(db
+            .last_completed_crawl_at(CrawlRunMode::Full)
+            .await?
+            .is_some());
+        assert_eq!(
+            None,
+            db.last_completed_crawl_at(CrawlRunMode::NewArrivals)
+                .await?
+        );
+
+        let products = db
+            .table_freshness()
+            .await?
+            .into_iter()
+            .find(|t| t.table == "products")
+            .unwrap();
+        assert_eq!(1, products.rows);
+        assert!(products.last_updated_at.is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! The SAQ store directory, and the postal codes geocoded to locate stores
+//! and the people looking for them (see [`stores`](crate::stores)).
+
+use super::Client;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// A store, as listed in the files read by `ransaq stores import`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Store {
+    /// The SAQ's identifier for the store.
+    pub saq_store_id: String,
+    /// The store's name.
+    pub name: String,
+    /// The store's street address.
+    pub address: String,
+    /// The city the store is in.
+    pub city: String,
+    /// The store's postal code (i.e. `H2X 1Y4`).
+    pub postal_code: String,
+    /// The store's latitude, if known.
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    /// The store's longitude, if known.
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+impl Client {
+    /// Uses an upsert query to make sure a row exists in the `stores` table for
+    /// the store's [`saq_store_id`](Store::saq_store_id), with the other
+    /// fields updated. Returns the row's `id`.
+    pub async fn upsert_store(&self, store: &Store) -> Result<i64> {
+        let mut conn = self.pool.acquire().await?;
+
+        let id = sqlx::query_scalar!(
+            r#"insert into stores (saq_store_id, name, address, city, postal_code, latitude, longitude)
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            on conflict do update set
+                name=excluded.name,
+                address=excluded.address,
+                city=excluded.city,
+                postal_code=excluded.postal_code,
+                latitude=excluded.latitude,
+                longitude=excluded.longitude,
+                updated_at=(datetime('now', 'utc'))
+            returning id as "id!""#,
+            store.saq_store_id,
+            store.name,
+            store.address,
+            store.city,
+            store.postal_code,
+            store.latitude,
+            store.longitude
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Lists every store, ordered by name.
+    pub async fn stores(&self) -> Result<Vec<Store>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let stores = sqlx::query_as!(
+            Store,
+            r#"select saq_store_id, name, address, city, postal_code, latitude, longitude
+            from stores
+            order by name, saq_store_id"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(stores)
+    }
+
+    /// Returns the latitude and longitude a postal code was geocoded to, if it
+    /// was geocoded before.
+    pub async fn geocoded_postal_code(&self, postal_code: &str) -> Result<Option<(f64, f64)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = sqlx::query!(
+            r#"select latitude, longitude from geocoded_postal_codes where postal_code = ?1"#,
+            postal_code
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.map(|row| (row.latitude, row.longitude)))
+    }
+
+    /// Records the latitude and longitude a postal code was geocoded to.
+    pub async fn cache_geocoded_postal_code(
+        &self,
+        postal_code: &str,
+        latitude: f64,
+        longitude: f64,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query!(
+            r#"insert into geocoded_postal_codes (postal_code, latitude, longitude)
+            values (?1, ?2, ?3)
+            on conflict do update set
+                latitude=excluded.latitude,
+                longitude=excluded.longitude,
+                geocoded_at=(datetime('now', 'utc'))"#,
+            postal_code,
+            latitude,
+            longitude
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether the geocoder didn't know a postal code when it was last asked,
+    /// less than a month ago.
+    pub async fn unlocatable_postal_code(&self, postal_code: &str) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = sqlx::query!(
+            r#"select postal_code from unlocatable_postal_codes
+            where postal_code = ?1 and looked_up_at > datetime('now', 'utc', '-1 month')"#,
+            postal_code
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Records that the geocoder doesn't know a postal code.
+    pub async fn cache_unlocatable_postal_code(&self, postal_code: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query!(
+            r#"insert into unlocatable_postal_codes (postal_code) values (?1)
+            on conflict do update set looked_up_at=(datetime('now', 'utc'))"#,
+            postal_code
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
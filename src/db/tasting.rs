@@ -0,0 +1,75 @@
+//! Products' tasting notes (see [`TastingNotes`]). Their food pairings are a
+//! lookup table like special features, see
+//! [`ensure_product_food_pairings`](Client::ensure_product_food_pairings).
+
+use super::{to_value_list, Client};
+use crate::saq::tasting::TastingNotes;
+use color_eyre::eyre::Result;
+use sqlx::SqliteConnection;
+
+impl Client {
+    /// Uses an upsert to make sure the product's row in `tasting_notes` holds
+    /// `notes`, always updating `updated_at`. The row is deleted when `notes`
+    /// is `None`, i.e. the tasting section was removed from the page.
+    pub async fn ensure_tasting_notes(
+        &self,
+        product_id: i64,
+        notes: Option<&TastingNotes>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        Client::ensure_tasting_notes_with(&mut conn, product_id, notes).await
+    }
+
+    /// Same as [`ensure_tasting_notes`](Client::ensure_tasting_notes), on `conn`.
+    pub(super) async fn ensure_tasting_notes_with(
+        conn: &mut SqliteConnection,
+        product_id: i64,
+        notes: Option<&TastingNotes>,
+    ) -> Result<()> {
+        let notes = match notes {
+            Some(notes) => notes,
+            None => {
+                sqlx::query!(
+                    "delete from tasting_notes where product_id = ?1",
+                    product_id
+                )
+                .execute(&mut *conn)
+                .await?;
+
+                return Ok(());
+            }
+        };
+
+        let aromas = to_value_list(&notes.aromas);
+
+        sqlx::query!(
+            r#"insert into tasting_notes (product_id, taste_tag, aromas, acidity, sweetness, body,
+                mouthfeel, wood, serving_temperature)
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            on conflict do update set
+                taste_tag=excluded.taste_tag,
+                aromas=excluded.aromas,
+                acidity=excluded.acidity,
+                sweetness=excluded.sweetness,
+                body=excluded.body,
+                mouthfeel=excluded.mouthfeel,
+                wood=excluded.wood,
+                serving_temperature=excluded.serving_temperature,
+                updated_at=(datetime('now', 'utc'))"#,
+            product_id,
+            notes.taste_tag,
+            aromas,
+            notes.acidity,
+            notes.sweetness,
+            notes.body,
+            notes.mouthfeel,
+            notes.wood,
+            notes.serving_temperature
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
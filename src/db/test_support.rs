@@ -0,0 +1,93 @@
+//! Database helpers for tests.
+//!
+//! Each [`TestDb`] is a brand new SQLite database in the system's temporary
+//! directory with all migrations applied, so tests can run in parallel without
+//! seeing each other's data.
+
+use super::Client;
+use color_eyre::eyre::Result;
+use sqlx::SqlitePool;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Used to give each [`TestDb`] created by this process a unique file name.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A [`Client`] for a database that is deleted when dropped.
+pub struct TestDb {
+    /// The client connected to the database.
+    client: Client,
+    /// The database file.
+    path: PathBuf,
+}
+
+impl TestDb {
+    /// Creates a new, migrated database.
+    pub async fn new() -> Result<TestDb> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("ransaq-test-{}-{}.sqlite", std::process::id(), id));
+
+        let client = Client::new(&format!("sqlite:{}", path.display())).await?;
+        client.migrate().await?;
+
+        Ok(TestDb { client, path })
+    }
+
+    /// The underlying connection pool, for making assertions using queries
+    /// the [`Client`] doesn't provide.
+    pub fn pool(&self) -> &SqlitePool {
+        &self.client.pool
+    }
+
+    /// The database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Client {
+    /// Inserts an in-stock product with the given `saq_code`, `name` and
+    /// price, returning its id.
+    ///
+    /// Other columns can be set with an `update` afterwards, which doesn't
+    /// record a [change](super::price_history) unless it touches the price or
+    /// availability.
+    pub async fn insert_product(
+        &self,
+        saq_code: &str,
+        name: &str,
+        price_cents: i64,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            r#"insert into products (saq_code, name, availability, item_condition, price_cents)
+            values (?1, ?2, 'in_stock', 'new', ?3)"#,
+        )
+        .bind(saq_code)
+        .bind(name)
+        .bind(price_cents)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+}
+
+impl Deref for TestDb {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
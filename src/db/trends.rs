@@ -0,0 +1,189 @@
+//! Trends across crawls, computed from the `product_changes` table (which a
+//! trigger fills whenever a product's price, regular price or availability
+//! changes) and the products' `created_at`. Labelled crawls are listed
+//! alongside them to help correlate shifts with operational events (i.e. a
+//! parser fix).
+
+use super::{Client, CrawlRunLabel};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// How prices changed in a category over a month.
+#[derive(Debug, Serialize)]
+pub struct PriceTrend {
+    /// The month, as `YYYY-MM`.
+    pub month: String,
+    /// The category's name.
+    pub category: String,
+    /// The average price change, as a percentage of the previous price.
+    pub average_change_percent: f64,
+    /// Number of price changes.
+    pub changes: i64,
+}
+
+/// How many products were first seen in a month.
+#[derive(Debug, Serialize)]
+pub struct ListingTrend {
+    /// The month, as `YYYY-MM`.
+    pub month: String,
+    /// Number of products first crawled during the month.
+    pub new_products: i64,
+}
+
+/// How many products changed availability in a month.
+#[derive(Debug, Serialize)]
+pub struct ChurnTrend {
+    /// The month, as `YYYY-MM`.
+    pub month: String,
+    /// Number of products whose availability changed at least once.
+    pub changed_products: i64,
+    /// Number of products known by the end of the month.
+    pub products: i64,
+    /// `changed_products` as a percentage of `products`.
+    pub churn_percent: f64,
+}
+
+/// All the trends, by month.
+#[derive(Debug, Serialize)]
+pub struct Trends {
+    /// See [`PriceTrend`].
+    pub price_changes: Vec<PriceTrend>,
+    /// See [`ListingTrend`].
+    pub new_listings: Vec<ListingTrend>,
+    /// See [`ChurnTrend`].
+    pub availability_churn: Vec<ChurnTrend>,
+    /// Crawls with tags or a note, see [`CrawlRunLabel`].
+    pub crawl_labels: Vec<CrawlRunLabel>,
+}
+
+impl Client {
+    /// Computes every [`Trends`] entry, oldest month first.
+    pub async fn trends(&self) -> Result<Trends> {
+        Ok(Trends {
+            price_changes: self.price_trends().await?,
+            new_listings: self.listing_trends().await?,
+            availability_churn: self.churn_trends().await?,
+            crawl_labels: self.crawl_run_labels().await?,
+        })
+    }
+
+    /// Average price change by category and month.
+    pub async fn price_trends(&self) -> Result<Vec<PriceTrend>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let trends = sqlx::query_as!(
+            PriceTrend,
+            r#"select strftime('%Y-%m', pc.changed_at) as "month!: String",
+                c.name as category,
+                avg((pc.new_price_cents - pc.old_price_cents) * 100.0 / pc.old_price_cents)
+                    as "average_change_percent!: f64",
+                count(*) as "changes!: i64"
+            from product_changes pc
+            join product_categories pcat on pcat.product_id = pc.product_id
+            join categories c on c.id = pcat.category_id
+            where pc.new_price_cents != pc.old_price_cents
+            group by 1, c.id
+            order by 1, c.name"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(trends)
+    }
+
+    /// Number of new products by month.
+    pub async fn listing_trends(&self) -> Result<Vec<ListingTrend>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let trends = sqlx::query_as!(
+            ListingTrend,
+            r#"select strftime('%Y-%m', created_at) as "month!: String",
+                count(*) as "new_products!: i64"
+            from products group by 1 order by 1"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(trends)
+    }
+
+    /// Number of products changing availability by month, relative to the
+    /// number of products known by then.
+    pub async fn churn_trends(&self) -> Result<Vec<ChurnTrend>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"select strftime('%Y-%m', pc.changed_at) as "month!: String",
+                count(distinct pc.product_id) as "changed_products!: i64",
+                (
+                    select count(*) from products p
+                    where strftime('%Y-%m', p.created_at) <= strftime('%Y-%m', pc.changed_at)
+                ) as "products!: i64"
+            from product_changes pc
+            where pc.new_availability != pc.old_availability
+            group by 1 order by 1"#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ChurnTrend {
+                churn_percent: row.changed_products as f64 * 100.0 / row.products.max(1) as f64,
+                month: row.month,
+                changed_products: row.changed_products,
+                products: row.products,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::test_support::TestDb;
+    use color_eyre::eyre::Result;
+
+    #[tokio::test]
+    async fn test_trends() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        for saq_code in ["trending", "steady"] {
+            db.insert_product(saq_code, saq_code, 2000).await?;
+        }
+
+        let wine = db
+            .upsert_category("Wine", "https://www.saq.com/en/products/wine", None)
+            .await?;
+        let product_id: i64 =
+            sqlx::query_scalar("select id from products where saq_code = 'trending'")
+                .fetch_one(db.pool())
+                .await?;
+        db.ensure_product_categories(product_id, vec![wine]).await?;
+
+        for (availability, price_cents) in [("in_stock", 1800), ("sold_out", 1800)] {
+            sqlx::query(
+                "update products set availability = ?1, price_cents = ?2 where saq_code = 'trending'",
+            )
+            .bind(availability)
+            .bind(price_cents)
+            .execute(db.pool())
+            .await?;
+        }
+
+        let trends = db.trends().await?;
+
+        assert_eq!(1, trends.price_changes.len());
+        assert_eq!("Wine", trends.price_changes[0].category);
+        assert_eq!(-10.0, trends.price_changes[0].average_change_percent);
+        assert_eq!(1, trends.price_changes[0].changes);
+
+        assert_eq!(1, trends.new_listings.len());
+        assert_eq!(2, trends.new_listings[0].new_products);
+
+        assert_eq!(1, trends.availability_churn.len());
+        assert_eq!(1, trends.availability_churn[0].changed_products);
+        assert_eq!(50.0, trends.availability_churn[0].churn_percent);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! Exporting the catalog as [JSON Lines](https://jsonlines.org/): one fully
+//! denormalized product per line, with its lookups resolved to names and its
+//! grape varieties, categories, special features, food pairings, tasting
+//! notes and identifiers nested in it. This is meant for `jq` pipelines and for loading into other systems
+//! without replicating the database's schema, and is written by
+//! `ransaq export`.
+//!
+//! Exports and the [API](crate::serve) meant to be redistributed can leave out
+//! the SAQ's own content (descriptions, images, etc.) by listing it in
+//! `RANSAQ_REDACT_FIELDS`, see [`redact`]. It's still kept in the database.
+//!
+//! ```json
+//! {"saq_code":"13191791","name":"Château Maris ...","price_cents":2995,...,
+//!  "grape_varieties":[{"name":"Syrah","percentage":60},...],
+//!  "categories":[{"name":"Wine","url":"https://www.saq.com/en/products/wine"},...],
+//!  "special_features":[],"food_pairings":["Game",...],
+//!  "tasting_notes":{"taste_tag":"Aromatic and robust","aromas":["Black fruit",...],...},
+//!  "identifiers":{"gtin13":"..."}}
+//! ```
+
+use crate::config::RedactableField;
+use crate::db::{self, ExportProduct, ExportRows};
+use crate::saq::tasting::TastingNotes;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// A grape variety of an [`ExportedProduct`].
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct GrapeVariety {
+    /// The grape variety's name.
+    pub name: String,
+    /// The share of the product made from it, if known.
+    pub percentage: Option<i64>,
+}
+
+/// A category of an [`ExportedProduct`].
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Category {
+    /// The category's name.
+    pub name: String,
+    /// The category's listing URL.
+    pub url: String,
+}
+
+/// A product along with everything related to it, written as a single line.
+#[derive(Debug, Serialize)]
+pub struct ExportedProduct {
+    /// The product's own fields.
+    #[serde(flatten)]
+    pub product: ExportProduct,
+    /// The grape varieties the product is made from, by name.
+    pub grape_varieties: Vec<GrapeVariety>,
+    /// The product's categories, from broadest to most specific.
+    pub categories: Vec<Category>,
+    /// The product's special features (i.e. `Organic product`), by name.
+    pub special_features: Vec<String>,
+    /// The dishes the product goes with (i.e. `Game`).
+    pub food_pairings: Vec<String>,
+    /// The product's tasting notes, if its page has some.
+    pub tasting_notes: Option<TastingNotes>,
+    /// The product's identifiers besides its SAQ code, keyed by scheme (i.e.
+    /// `gtin13`).
+    pub identifiers: BTreeMap<String, String>,
+}
+
+/// Groups `rows` by product id.
+fn by_product<T, U>(rows: Vec<T>, split: impl Fn(T) -> (i64, U)) -> HashMap<i64, Vec<U>> {
+    let mut grouped = HashMap::<i64, Vec<U>>::new();
+    for row in rows {
+        let (product_id, value) = split(row);
+        grouped.entry(product_id).or_default().push(value);
+    }
+    grouped
+}
+
+/// Nests the relations in `rows` in their products, keeping the products'
+/// order.
+pub fn denormalize(rows: ExportRows) -> Vec<ExportedProduct> {
+    let mut grape_varieties = by_product(rows.grape_varieties, |row| {
+        (
+            row.product_id,
+            GrapeVariety {
+                name: row.name,
+                percentage: row.percentage,
+            },
+        )
+    });
+    let mut categories = by_product(rows.categories, |row| {
+        (
+            row.product_id,
+            Category {
+                name: row.name,
+                url: row.url,
+            },
+        )
+    });
+    let mut special_features = by_product(rows.special_features, |row| (row.product_id, row.name));
+    let mut food_pairings = by_product(rows.food_pairings, |row| (row.product_id, row.name));
+    let mut tasting_notes = rows
+        .tasting_notes
+        .into_iter()
+        .map(|row| {
+            let notes = TastingNotes {
+                taste_tag: row.taste_tag,
+                // The column is checked to hold an array
+                aromas: serde_json::from_str(&row.aromas).unwrap_or_default(),
+                acidity: row.acidity,
+                sweetness: row.sweetness,
+                body: row.body,
+                mouthfeel: row.mouthfeel,
+                wood: row.wood,
+                serving_temperature: row.serving_temperature,
+            };
+            (row.product_id, notes)
+        })
+        .collect::<HashMap<_, _>>();
+    let mut identifiers = by_product(rows.identifiers, |row| {
+        (row.product_id, (row.scheme, row.value))
+    });
+
+    rows.products
+        .into_iter()
+        .map(|product| ExportedProduct {
+            grape_varieties: grape_varieties.remove(&product.id).unwrap_or_default(),
+            categories: categories.remove(&product.id).unwrap_or_default(),
+            special_features: special_features.remove(&product.id).unwrap_or_default(),
+            food_pairings: food_pairings.remove(&product.id).unwrap_or_default(),
+            tasting_notes: tasting_notes.remove(&product.id),
+            identifiers: identifiers
+                .remove(&product.id)
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+            product,
+        })
+        .collect()
+}
+
+/// Blanks out the given `fields` of `product`: they're written as `null` (or
+/// `[]`) rather than left out, so that redacted exports keep the same shape.
+pub fn redact(product: &mut ExportedProduct, fields: &[RedactableField]) {
+    for field in fields {
+        match field {
+            RedactableField::Description => product.product.description = None,
+            RedactableField::ImageUrl => product.product.image_url = None,
+            RedactableField::TastingNotes => product.tasting_notes = None,
+            RedactableField::FoodPairings => product.food_pairings.clear(),
+            RedactableField::Ratings => {
+                product.product.rating_value = None;
+                product.product.rating_count = None;
+            }
+        }
+    }
+}
+
+/// Reads the product with the given SAQ code along with everything related to
+/// it, `None` if there's no such product.
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> color_eyre::Result<()> {
+/// let db = ransaq::db::Client::new_in_memory_with_sample_data().await?;
+///
+/// let wine = ransaq::export::product(&db, "13191791").await?.unwrap();
+/// assert_eq!(Some("France"), wine.product.country.as_deref());
+/// assert_eq!(vec!["Game", "Grilled red meat"], wine.food_pairings);
+/// assert_eq!(Some("3760089460186"), wine.identifiers.get("gtin13").map(String::as_str));
+///
+/// assert!(ransaq::export::product(&db, "999").await?.is_none());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn product(db: &db::Client, saq_code: &str) -> Result<Option<ExportedProduct>> {
+    let rows = match db.export_product_rows(saq_code).await? {
+        Some(rows) => rows,
+        None => return Ok(None),
+    };
+
+    Ok(denormalize(rows).pop())
+}
+
+/// Writes every product in the database to `writer` as JSON Lines, with the
+/// `redacted` fields blanked out, returning the number of products written.
+pub async fn write_jsonl(
+    db: &db::Client,
+    redacted: &[RedactableField],
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut products = denormalize(db.export_rows().await?);
+
+    for product in &mut products {
+        redact(product, redacted);
+        serde_json::to_writer(&mut writer, product)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    Ok(products.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn test_write_jsonl() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        db.insert_product("123", "Red", 1995).await?;
+        db.insert_product("045", "Plain", 1000).await?;
+
+        for sql in [
+            "insert into countries (name) values ('France')",
+            "update products set country_id = 1, description = 'Deep and spicy' where saq_code = '123'",
+            "update products set availability = 'sold_out', partial = 1 where saq_code = '045'",
+            "insert into grape_varieties (name) values ('Syrah'), ('Grenache')",
+            r#"insert into product_grape_varieties (product_id, grape_variety_id, percentage)
+            values (1, 1, 60), (1, 2, null)"#,
+            r#"insert into categories (url, parent_category_id, name) values
+            ('https://www.saq.com/en/products/wine', null, 'Wine'),
+            ('https://www.saq.com/en/products/wine/red-wine', 1, 'Red wine')"#,
+            "insert into product_categories (product_id, category_id) values (1, 1), (1, 2)",
+            "insert into special_features (name) values ('Organic product')",
+            "insert into product_special_features (product_id, special_feature_id) values (1, 1)",
+            "insert into food_pairings (name) values ('Lamb'), ('Game')",
+            "insert into product_food_pairings (product_id, food_pairing_id) values (1, 2), (1, 1)",
+            r#"insert into tasting_notes (product_id, taste_tag, aromas, body)
+            values (1, 'Aromatic and robust', '["Black fruit","Spices"]', 'Full')"#,
+            r#"insert into product_identifiers (product_id, scheme, value)
+            values (1, 'gtin13', '0012345678905')"#,
+        ] {
+            sqlx::query(sql).execute(db.pool()).await?;
+        }
+
+        let mut output = vec![];
+        assert_eq!(2, write_jsonl(&db, &[], &mut output).await?);
+
+        let lines = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(2, lines.len());
+
+        // Products are ordered by SAQ code
+        assert_eq!("045", lines[0]["saq_code"]);
+        assert_eq!(true, lines[0]["partial"]);
+        assert_eq!(Value::Null, lines[0]["country"]);
+        assert_eq!(0, lines[0]["categories"].as_array().unwrap().len());
+
+        let red = &lines[1];
+        assert_eq!("Red", red["name"]);
+        assert_eq!(1995, red["price_cents"]);
+        assert_eq!("France", red["country"]);
+        assert_eq!("Deep and spicy", red["description"]);
+        assert!(red.get("id").is_none());
+        assert_eq!(
+            serde_json::json!([
+                {"name": "Grenache", "percentage": null},
+                {"name": "Syrah", "percentage": 60}
+            ]),
+            red["grape_varieties"]
+        );
+        assert_eq!(
+            vec!["Wine", "Red wine"],
+            red["categories"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|category| category["name"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            serde_json::json!(["Organic product"]),
+            red["special_features"]
+        );
+        // Food pairings are in the order they were added
+        assert_eq!(serde_json::json!(["Game", "Lamb"]), red["food_pairings"]);
+        assert_eq!(
+            serde_json::json!(["Black fruit", "Spices"]),
+            red["tasting_notes"]["aromas"]
+        );
+        assert_eq!("Full", red["tasting_notes"]["body"]);
+        assert_eq!(Value::Null, lines[0]["tasting_notes"]);
+        assert_eq!(
+            serde_json::json!({"gtin13": "0012345678905"}),
+            red["identifiers"]
+        );
+
+        // Redacted fields are blanked out rather than left out
+        let mut output = vec![];
+        let redacted = [
+            RedactableField::TastingNotes,
+            RedactableField::FoodPairings,
+            RedactableField::Description,
+        ];
+        write_jsonl(&db, &redacted, &mut output).await?;
+        let red =
+            serde_json::from_str::<Value>(String::from_utf8(output)?.lines().last().unwrap())?;
+        assert_eq!(Value::Null, red["tasting_notes"]);
+        assert_eq!(serde_json::json!([]), red["food_pairings"]);
+        assert_eq!(Value::Null, red["description"]);
+        assert_eq!(
+            serde_json::json!(["Organic product"]),
+            red["special_features"]
+        );
+
+        let plain = product(&db, "045").await?.unwrap();
+        assert!(plain.categories.is_empty());
+        let red = product(&db, "123").await?.unwrap();
+        assert_eq!(2, red.grape_varieties.len());
+        assert_eq!(1, red.identifiers.len());
+        assert!(product(&db, "999").await?.is_none());
+
+        Ok(())
+    }
+}
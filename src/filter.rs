@@ -0,0 +1,492 @@
+//! A small expression language for filtering products.
+//!
+//! Filters are whitespace-separated terms which must all match, i.e.
+//!
+//! ```text
+//! country:France color:red price<25 grape:gamay -producer:"Louis Jadot"
+//! ```
+//!
+//! - `field:value` matches products whose `field` contains `value`, ignoring case
+//! - `field=value` matches products whose `field` is exactly `value`, ignoring case
+//! - `field<value`, `field<=value`, `field>value` and `field>=value` compare
+//!   numeric fields
+//! - a plain `value` matches products whose name contains it
+//! - values containing spaces must be quoted (i.e. `region:"Vallée du Rhône"`)
+//! - a leading `-` negates a term
+//!
+//! See [`TextField`] and [`NumericField`] for available fields. Filters are
+//! turned into SQL by [`db::Client::search`](crate::db::Client::search).
+
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::Report;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+/// Fields compared as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    /// `name`
+    Name,
+    /// `code`, the product's SAQ code
+    Code,
+    /// `country`
+    Country,
+    /// `region`
+    Region,
+    /// `color`
+    Color,
+    /// `producer`
+    Producer,
+    /// `brand`
+    Brand,
+    /// `grape`, matching any of the product's grape varieties
+    Grape,
+    /// `category`, matching any of the product's categories
+    Category,
+    /// `availability`, i.e. `availability=in_stock`
+    Availability,
+}
+
+/// Fields compared as numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericField {
+    /// `price`, in dollars
+    Price,
+    /// `abv`, the alcohol percentage
+    Abv,
+    /// `ml`, the container's volume in milliliters
+    Volume,
+    /// `sugar`, in grams per liter
+    Sugar,
+}
+
+/// How a [`NumericField`] is compared with a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `=` (or `:`)
+    Eq,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Comparison {
+    /// The equivalent SQL operator.
+    pub fn sql_operator(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// What a single [`Term`] checks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// A text field containing (or being exactly) `value`.
+    Text {
+        /// The field being checked.
+        field: TextField,
+        /// Whether the field must be equal to `value` rather than contain it.
+        exact: bool,
+        /// The value to look for.
+        value: String,
+    },
+    /// A numeric field compared with `value`.
+    Numeric {
+        /// The field being checked.
+        field: NumericField,
+        /// How the field is compared with `value`.
+        comparison: Comparison,
+        /// The value to compare with.
+        value: f64,
+    },
+}
+
+/// A single, optionally negated, condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    /// Whether products must _not_ match the condition.
+    pub negated: bool,
+    /// The condition to check.
+    pub condition: Condition,
+}
+
+/// A parsed filter expression, matching products that satisfy all its terms.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    /// The terms that must all match.
+    pub terms: Vec<Term>,
+}
+
+/// Either kind of field.
+enum Field {
+    /// See [`TextField`].
+    Text(TextField),
+    /// See [`NumericField`].
+    Numeric(NumericField),
+}
+
+impl FromStr for Field {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let field = match s.to_lowercase().as_str() {
+            "name" => Field::Text(TextField::Name),
+            "code" => Field::Text(TextField::Code),
+            "country" => Field::Text(TextField::Country),
+            "region" => Field::Text(TextField::Region),
+            "color" => Field::Text(TextField::Color),
+            "producer" => Field::Text(TextField::Producer),
+            "brand" => Field::Text(TextField::Brand),
+            "grape" => Field::Text(TextField::Grape),
+            "category" => Field::Text(TextField::Category),
+            "availability" => Field::Text(TextField::Availability),
+            "price" => Field::Numeric(NumericField::Price),
+            "abv" => Field::Numeric(NumericField::Abv),
+            "ml" => Field::Numeric(NumericField::Volume),
+            "sugar" => Field::Numeric(NumericField::Sugar),
+            _ => return Err(eyre!("unknown field {:?}", s)),
+        };
+
+        Ok(field)
+    }
+}
+
+/// The operator between a field and its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    /// `:`
+    Contains,
+    /// A [`Comparison`], `=` also meaning an exact text match.
+    Compare(Comparison),
+}
+
+/// Walks through the characters of a filter expression.
+struct Parser<'a> {
+    /// The expression being parsed.
+    input: &'a str,
+    /// The remaining characters, along with their byte offset.
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// The byte offset of the next character.
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |(i, _)| *i)
+    }
+
+    /// Consumes the next character if it is `c`.
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if(|(_, next)| *next == c).is_some()
+    }
+
+    /// Consumes whitespace, returning whether there is anything left.
+    fn skip_whitespace(&mut self) -> bool {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        self.chars.peek().is_some()
+    }
+
+    /// Consumes characters up to whitespace or one of `stop`.
+    fn word(&mut self, stop: &[char]) -> &'a str {
+        let start = self.position();
+        while self
+            .chars
+            .next_if(|(_, c)| !c.is_whitespace() && !stop.contains(c))
+            .is_some()
+        {}
+        &self.input[start..self.position()]
+    }
+
+    /// Parses a value, which is either a quoted string or a word.
+    fn value(&mut self) -> Result<String> {
+        let start = self.position();
+
+        if !self.eat('"') {
+            let value = self.word(&[]);
+            if value.is_empty() {
+                return Err(eyre!("expected a value at position {}", start));
+            }
+            return Ok(value.to_string());
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((_, c)) => value.push(c),
+                None => return Err(eyre!("unterminated quote at position {}", start)),
+            }
+        }
+    }
+
+    /// Parses the operator following a field name, if any.
+    fn operator(&mut self) -> Option<Operator> {
+        let operator = if self.eat(':') {
+            Operator::Contains
+        } else if self.eat('=') {
+            Operator::Compare(Comparison::Eq)
+        } else if self.eat('<') {
+            match self.eat('=') {
+                true => Operator::Compare(Comparison::Le),
+                false => Operator::Compare(Comparison::Lt),
+            }
+        } else if self.eat('>') {
+            match self.eat('=') {
+                true => Operator::Compare(Comparison::Ge),
+                false => Operator::Compare(Comparison::Gt),
+            }
+        } else {
+            return None;
+        };
+
+        Some(operator)
+    }
+
+    /// Parses a single term.
+    fn term(&mut self) -> Result<Term> {
+        let negated = self.eat('-');
+        let start = self.position();
+
+        let key = match self.chars.peek() {
+            Some((_, '"')) => "",
+            _ => self.word(&[':', '=', '<', '>']),
+        };
+
+        let operator = match self.operator() {
+            Some(operator) => operator,
+            None => {
+                // A plain value, matched against the name
+                let value = match key {
+                    "" => self.value()?,
+                    key => key.to_string(),
+                };
+                return Ok(Term {
+                    negated,
+                    condition: Condition::Text {
+                        field: TextField::Name,
+                        exact: false,
+                        value,
+                    },
+                });
+            }
+        };
+
+        let field = key
+            .parse::<Field>()
+            .map_err(|err| eyre!("{} at position {}", err, start))?;
+        let value = self.value()?;
+
+        let condition = match (field, operator) {
+            (Field::Text(field), Operator::Contains) => Condition::Text {
+                field,
+                exact: false,
+                value,
+            },
+            (Field::Text(field), Operator::Compare(Comparison::Eq)) => Condition::Text {
+                field,
+                exact: true,
+                value,
+            },
+            (Field::Text(_), Operator::Compare(_)) => {
+                return Err(eyre!(
+                    "{:?} can only be matched with : or = (position {})",
+                    key,
+                    start
+                ))
+            }
+            (Field::Numeric(field), operator) => Condition::Numeric {
+                field,
+                comparison: match operator {
+                    Operator::Contains => Comparison::Eq,
+                    Operator::Compare(comparison) => comparison,
+                },
+                value: value
+                    .parse()
+                    .map_err(|_| eyre!("expected a number for {:?}, got {:?}", key, value))?,
+            },
+        };
+
+        Ok(Term { negated, condition })
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Report;
+
+    /// Parses a filter expression (see the [module docs](self)).
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: s,
+            chars: s.char_indices().peekable(),
+        };
+        let mut terms = vec![];
+
+        while parser.skip_whitespace() {
+            terms.push(parser.term()?);
+        }
+
+        Ok(Filter { terms })
+    }
+}
+
+impl fmt::Display for Filter {
+    /// Formats the filter back into an equivalent expression.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            if term.negated {
+                f.write_str("-")?;
+            }
+
+            match &term.condition {
+                Condition::Text {
+                    field,
+                    exact,
+                    value,
+                } => {
+                    let operator = if *exact { "=" } else { ":" };
+                    write!(f, "{}{}{:?}", text_field_name(*field), operator, value)?;
+                }
+                Condition::Numeric {
+                    field,
+                    comparison,
+                    value,
+                } => {
+                    let name = match field {
+                        NumericField::Price => "price",
+                        NumericField::Abv => "abv",
+                        NumericField::Volume => "ml",
+                        NumericField::Sugar => "sugar",
+                    };
+                    write!(f, "{}{}{}", name, comparison.sql_operator(), value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of `field` in filter expressions.
+fn text_field_name(field: TextField) -> &'static str {
+    match field {
+        TextField::Name => "name",
+        TextField::Code => "code",
+        TextField::Country => "country",
+        TextField::Region => "region",
+        TextField::Color => "color",
+        TextField::Producer => "producer",
+        TextField::Brand => "brand",
+        TextField::Grape => "grape",
+        TextField::Category => "category",
+        TextField::Availability => "availability",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shorthand for a non-negated text condition.
+    fn text(field: TextField, exact: bool, value: &str) -> Term {
+        Term {
+            negated: false,
+            condition: Condition::Text {
+                field,
+                exact,
+                value: value.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let filter: Filter =
+            r#"country:France color=red price<25 abv>=12.5 -grape:"pinot noir" chablis"#
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            vec![
+                text(TextField::Country, false, "France"),
+                text(TextField::Color, true, "red"),
+                Term {
+                    negated: false,
+                    condition: Condition::Numeric {
+                        field: NumericField::Price,
+                        comparison: Comparison::Lt,
+                        value: 25.0,
+                    },
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Numeric {
+                        field: NumericField::Abv,
+                        comparison: Comparison::Ge,
+                        value: 12.5,
+                    },
+                },
+                Term {
+                    negated: true,
+                    ..text(TextField::Grape, false, "pinot noir")
+                },
+                text(TextField::Name, false, "chablis"),
+            ],
+            filter.terms
+        );
+
+        assert_eq!(
+            r#"country:"France" color="red" price<25 abv>=12.5 -grape:"pinot noir" name:"chablis""#,
+            filter.to_string()
+        );
+        assert_eq!(filter, filter.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        assert_eq!(Filter::default(), "   ".parse().unwrap());
+        assert_eq!(
+            vec![text(TextField::Name, false, "côtes du rhône")],
+            r#" "côtes du rhône" "#.parse::<Filter>().unwrap().terms
+        );
+        assert_eq!(
+            vec![text(TextField::Region, false, "Vallée du Rhône")],
+            r#"REGION:"Vallée du Rhône""#.parse::<Filter>().unwrap().terms
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |s: &str| s.parse::<Filter>().unwrap_err().to_string();
+
+        assert_eq!(
+            r#"unknown field "vintage" at position 0"#,
+            error("vintage:2015")
+        );
+        assert_eq!(
+            r#""country" can only be matched with : or = (position 4)"#,
+            error("red country<France")
+        );
+        assert_eq!(
+            r#"expected a number for "price", got "cheap""#,
+            error("price<cheap")
+        );
+        assert_eq!(
+            "unterminated quote at position 7",
+            error(r#"region:"Loire"#)
+        );
+        assert_eq!("expected a value at position 6", error("color:"));
+    }
+}
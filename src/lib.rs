@@ -0,0 +1,59 @@
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+//! A crawler for the [SAQ](https://www.saq.com/en/)'s product catalog.
+//!
+//! It was written primarily as an interesting challenge and an opportunity
+//! to learn several Rust libraries and features.
+//!
+//! ## Setup
+//!
+//! - Run `cargo doc --open` to view the docs
+//! - See the [`db`] module docs for database setup
+//! - See the [`config`] module docs for available settings
+//! - Run `cargo run -- --help` for a list of commands
+//! - Run `cargo bench` to benchmark the product page parsers
+//! - Run `cargo run -- simulate` to benchmark crawls against a synthetic
+//!   catalog served locally, see [`crawler::simulate`]
+//! - Run `cargo run -- parser-regress <dir>` to see how parser changes affect
+//!   a directory of archived pages, see [`regress`]
+//! - Run `cargo run -- selftest` before deploying to a new machine to check
+//!   that crawls can run there, see [`selftest`]
+//! - Run `cargo run -- serve` to serve the crawled catalog over a read-only
+//!   HTTP API, see [`serve`]
+//! - Run `cargo +nightly fuzz run <target>` (using [`cargo-fuzz`](https://github.com/rust-fuzz/cargo-fuzz))
+//!   to fuzz the [detailed info](saq::detailed_info) parsers, see `fuzz/fuzz_targets` for available targets
+//!
+//! ## Features
+//!
+//! - `crawler` (default): everything but the [`saq`] page parsers, which can be
+//!   used on their own with `default-features = false` as they don't depend on
+//!   `tokio`, `sqlx` or `reqwest`
+
+#[cfg(feature = "crawler")]
+pub mod changelog;
+#[cfg(feature = "crawler")]
+pub mod cli;
+#[cfg(feature = "crawler")]
+pub mod config;
+#[cfg(feature = "crawler")]
+pub mod crawler;
+#[cfg(feature = "crawler")]
+pub mod db;
+#[cfg(feature = "crawler")]
+pub mod export;
+#[cfg(feature = "crawler")]
+pub mod filter;
+#[cfg(feature = "crawler")]
+pub mod log_filter;
+#[cfg(feature = "crawler")]
+pub mod regress;
+#[cfg(feature = "crawler")]
+pub mod repl;
+pub mod saq;
+#[cfg(feature = "crawler")]
+pub mod selftest;
+#[cfg(feature = "crawler")]
+pub mod serve;
+#[cfg(feature = "crawler")]
+pub mod stores;
@@ -0,0 +1,145 @@
+//! Changing what gets logged without restarting, i.e. to turn on trace
+//! logging for one module during a live crawl without losing its progress.
+//!
+//! When `RANSAQ_LOG_FILTER_FILE` is set (see [`config`](crate::config)), that
+//! file is checked for changes every [`WATCH_INTERVAL`]. While it exists, its
+//! contents replace the filter set through `RUST_LOG` (using the same
+//! [syntax](tracing_subscriber::EnvFilter)), and removing it (or leaving it
+//! empty) restores `RUST_LOG`'s:
+//!
+//! ```text
+//! $ echo 'ransaq::crawler=trace,info' > log-filter
+//! $ rm log-filter
+//! ```
+//!
+//! Filters that fail to parse are logged and ignored, keeping the current one.
+
+use color_eyre::eyre::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+/// How often the filter file is checked for changes.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A handle on the filter of the global tracing subscriber.
+pub struct LogFilter {
+    /// Swaps the subscriber's filter.
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// The filter set through `RUST_LOG`, restored when the filter file goes
+    /// away.
+    default_directives: String,
+}
+
+impl LogFilter {
+    /// Installs the global tracing subscriber, filtered according to
+    /// `RUST_LOG` until [`watch`](LogFilter::watch) says otherwise.
+    pub fn init() -> LogFilter {
+        let default_directives = std::env::var("RUST_LOG").unwrap_or_default();
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(&default_directives));
+
+        tracing_subscriber::registry()
+            .with(layer)
+            .with(fmt::layer())
+            .init();
+
+        LogFilter {
+            handle,
+            default_directives,
+        }
+    }
+
+    /// Replaces the filter with `directives`, or with `RUST_LOG`'s if `None`.
+    fn set(&self, directives: Option<&str>) -> Result<()> {
+        let filter = EnvFilter::try_new(directives.unwrap_or(&self.default_directives))?;
+        self.handle.reload(filter)?;
+
+        Ok(())
+    }
+
+    /// Applies the contents of the file at `path` if it changed since it was
+    /// `last_seen` (its modification time, `None` if it didn't exist),
+    /// returning whether it did.
+    fn refresh(&self, path: &Path, last_seen: &mut Option<Option<SystemTime>>) -> Result<bool> {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if *last_seen == Some(modified) {
+            return Ok(false);
+        }
+        *last_seen = Some(modified);
+
+        let directives = match modified {
+            Some(_) => std::fs::read_to_string(path)?.trim().to_string(),
+            None => String::new(),
+        };
+        self.set((!directives.is_empty()).then_some(directives.as_str()))?;
+
+        Ok(true)
+    }
+
+    /// Keeps the filter in sync with the file at `path` (see the
+    /// [module docs](self)), forever.
+    pub async fn watch(self, path: PathBuf) {
+        let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+        // Not knowing what the file was like at startup, it gets applied on
+        // the first tick
+        let mut last_seen = None;
+
+        loop {
+            ticker.tick().await;
+            match self.refresh(&path, &mut last_seen) {
+                Ok(false) => {}
+                Ok(true) => {
+                    let filter = self.handle.with_current(ToString::to_string);
+                    info!(path = %path.display(), filter = ?filter.ok(), "log filter changed");
+                }
+                Err(err) => warn!(?err, path = %path.display(), "failed to change log filter"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh() -> Result<()> {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let filter = LogFilter {
+            handle,
+            default_directives: "info".to_string(),
+        };
+        let current = || filter.handle.with_current(ToString::to_string).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("ransaq-log-filter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("log-filter");
+        let mut last_seen = None;
+
+        assert!(filter.refresh(&path, &mut last_seen)?);
+        assert_eq!("info", current());
+        assert!(!filter.refresh(&path, &mut last_seen)?);
+
+        std::fs::write(&path, "ransaq::crawler=trace,warn\n")?;
+        assert!(filter.refresh(&path, &mut last_seen)?);
+        assert!(current().contains("ransaq::crawler=trace"));
+        assert!(!filter.refresh(&path, &mut last_seen)?);
+
+        std::fs::remove_file(&path)?;
+        assert!(filter.refresh(&path, &mut last_seen)?);
+        assert_eq!("info", current());
+
+        std::fs::write(&path, "ransaq=[")?;
+        assert!(filter.refresh(&path, &mut last_seen).is_err());
+        assert_eq!("info", current());
+
+        std::fs::remove_dir_all(&dir)?;
+        drop(layer);
+
+        Ok(())
+    }
+}
@@ -1,29 +1,26 @@
 #![warn(missing_docs)]
 #![warn(clippy::missing_docs_in_private_items)]
 
-//! A crawler for the [SAQ](https://www.saq.com/en/)'s product catalog.
-//!
-//! It was written primarily as an interesting challenge and an opportunity
-//! to learn several Rust libraries and features.
-//!
-//! ## Setup
-//!
-//! - Run `cargo doc --open` to view the docs
-//! - See the [`db`] module docs for database setup
-
-mod crawler;
-mod db;
-mod saq;
+//! Command-line entry point for [`ransaq`](ransaq), see the library docs for
+//! details.
 
+use clap::Parser;
 use color_eyre::eyre::Result;
+use ransaq::log_filter::LogFilter;
+use ransaq::{cli, config};
 use tracing::warn;
-use tracing_subscriber::EnvFilter;
 
 /// Global setup for the application
+/// - Loads the settings of the given [profile](config#profiles), if any
 /// - Loads additional environment variables from `.env` (using [`dotenv`](dotenv))
 /// - Initializes [`color_eyre`](color_eyre)
-/// - Initializes [`tracing_subscriber`](tracing_subscriber)
-fn setup() -> Result<()> {
+/// - Initializes [`tracing_subscriber`](tracing_subscriber) with a
+///   [reloadable](ransaq::log_filter) filter
+fn setup(profile: Option<&str>) -> Result<LogFilter> {
+    if let Some(profile) = profile {
+        config::load_profile(profile)?;
+    }
+
     if let Err(e) = dotenv::dotenv() {
         warn!("failed to load .env file: {}", e);
     }
@@ -38,19 +35,25 @@ fn setup() -> Result<()> {
         std::env::set_var("RUST_LOG", "ransaq=trace,info")
     }
 
-    tracing_subscriber::fmt::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-
-    Ok(())
+    Ok(LogFilter::init())
 }
 
-/// Kicks off a full crawl
+/// Parses command-line arguments and runs the requested command
 #[tokio::main]
 async fn main() -> Result<()> {
-    setup()?;
+    let cli = cli::Cli::parse();
+    let profile = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("RANSAQ_PROFILE").ok());
+
+    let log_filter = setup(profile.as_deref())?;
 
-    crawler::crawl().await?;
+    let config = config::Config::from_env()?;
+    if let Some(path) = config.log_filter_file.clone() {
+        tokio::spawn(log_filter.watch(path));
+    }
+    cli::run(cli, &config).await?;
 
     Ok(())
 }
@@ -0,0 +1,235 @@
+//! Snapshot testing of the [`saq`] parsers against archived pages, used by
+//! `ransaq parser-regress`.
+//!
+//! Each `<name>.html` page in a directory is parsed and the result compared
+//! with the JSON snapshot committed as `<name>.json` in a snapshots directory
+//! (`snapshots/` next to the pages by default). Product pages are parsed with
+//! [`parse_product_page`], anything else with [`parse_catalog_page`], and
+//! pages neither parser accepts are snapshotted as errors. Differences are
+//! listed field by field, so a parser change shows exactly what it changes
+//! across every archived page before the snapshots are updated.
+//!
+//! The pages in `fixtures/` have snapshots of their own, checked by the tests
+//! below.
+
+use crate::saq::{parse_catalog_page, parse_product_page};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Parses `html`, returning what gets snapshotted.
+pub fn snapshot(html: &str) -> Result<Value> {
+    let product_err = match parse_product_page(html) {
+        Ok(product) => return Ok(json!({ "product": product })),
+        Err(err) => err,
+    };
+
+    let catalog_err = match parse_catalog_page(html) {
+        Ok(catalog) => return Ok(json!({ "catalog": catalog })),
+        Err(err) => err,
+    };
+
+    Ok(json!({
+        "error": {
+            "product": format!("{product_err:#}"),
+            "catalog": format!("{catalog_err:#}"),
+        }
+    }))
+}
+
+/// A value that differs between a snapshot and the current parser output.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Difference {
+    /// Where the value is, i.e. `$.product.detailed_info.size`.
+    pub path: String,
+    /// The value in the snapshot, `None` if it wasn't there.
+    pub old: Option<Value>,
+    /// The value now, `None` if it's gone.
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(missing)".to_string(),
+        };
+
+        write!(
+            f,
+            "{}: {} → {}",
+            self.path,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// Lists the values which differ between `old` and `new`, descending into
+/// objects and arrays so that only the innermost changes are reported.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    diff_at("$".to_string(), Some(old), Some(new), &mut differences);
+    differences
+}
+
+/// Adds the differences between `old` and `new` at `path` to `differences`.
+fn diff_at(
+    path: String,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    differences: &mut Vec<Difference>,
+) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                diff_at(
+                    format!("{path}.{key}"),
+                    old.get(key),
+                    new.get(key),
+                    differences,
+                );
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                diff_at(format!("{path}[{i}]"), old.get(i), new.get(i), differences);
+            }
+        }
+        (old, new) if old != new => differences.push(Difference {
+            path,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// How a page's parser output compares with its snapshot.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "status", content = "differences", rename_all = "snake_case")]
+pub enum Outcome {
+    /// The page has no snapshot yet.
+    New,
+    /// The output matches the snapshot.
+    Unchanged,
+    /// The output differs from the snapshot.
+    Changed(Vec<Difference>),
+}
+
+/// A page checked against its snapshot.
+#[derive(Debug, Serialize)]
+pub struct PageResult {
+    /// The page's file name.
+    pub page: String,
+    /// How it compares with its snapshot.
+    pub outcome: Outcome,
+}
+
+/// Lists the `.html` pages in `dir`, sorted by name.
+fn pages(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pages = vec![];
+
+    for entry in std::fs::read_dir(dir).wrap_err_with(|| format!("failed to read {dir:?}"))? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new("html")) {
+            pages.push(path);
+        }
+    }
+
+    pages.sort();
+    Ok(pages)
+}
+
+/// Checks every page in `dir` against its snapshot in `snapshots`, writing
+/// new and changed snapshots if `update` is set.
+pub fn run(dir: &Path, snapshots: &Path, update: bool) -> Result<Vec<PageResult>> {
+    let mut results = vec![];
+
+    for page in pages(dir)? {
+        let html =
+            std::fs::read_to_string(&page).wrap_err_with(|| format!("failed to read {page:?}"))?;
+        let output = snapshot(&html)?;
+
+        let name = page
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| eyre!("{:?} isn't a valid page name", page))?;
+        let snapshot_path = snapshots.join(name).with_extension("json");
+
+        let outcome = match std::fs::read_to_string(&snapshot_path) {
+            Ok(json) => {
+                let old: Value = serde_json::from_str(&json)
+                    .wrap_err_with(|| format!("failed to parse {snapshot_path:?}"))?;
+                let differences = diff(&old, &output);
+                match differences.is_empty() {
+                    true => Outcome::Unchanged,
+                    false => Outcome::Changed(differences),
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Outcome::New,
+            Err(err) => return Err(err.into()),
+        };
+
+        if update && outcome != Outcome::Unchanged {
+            std::fs::create_dir_all(snapshots)?;
+            std::fs::write(
+                &snapshot_path,
+                serde_json::to_string_pretty(&output)? + "\n",
+            )?;
+        }
+
+        results.push(PageResult {
+            page: name.to_string(),
+            outcome,
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({"a": 1, "b": {"c": [1, 2, 3]}, "d": "same"});
+        let new = json!({"a": 2, "b": {"c": [1, 4]}, "d": "same", "e": null});
+
+        assert_eq!(
+            vec![
+                "$.a: 1 → 2",
+                "$.b.c[1]: 2 → 4",
+                "$.b.c[2]: 3 → (missing)",
+                "$.e: (missing) → null",
+            ],
+            diff(&old, &new)
+                .iter()
+                .map(|difference| difference.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+
+        let results = run(&fixtures, &fixtures.join("snapshots"), false).unwrap();
+        assert_eq!(4, results.len());
+        for result in results {
+            assert_eq!(
+                Outcome::Unchanged,
+                result.outcome,
+                "{} doesn't match its snapshot, run `ransaq parser-regress fixtures` to see why",
+                result.page
+            );
+        }
+    }
+}
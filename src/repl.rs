@@ -0,0 +1,321 @@
+//! An interactive prompt for exploring the database with SQL.
+//!
+//! Statements run read-only (see [`query_raw`](db::Client::query_raw)) and may
+//! span several lines, ending with a `;`. Lines starting with a `.` are
+//! commands instead, see [`HELP`], including `.find` to search products with a
+//! [filter expression](crate::filter). Previous statements can be listed with
+//! `.history` and run again with `!<number>` (or `!!` for the last one).
+
+use crate::db::{self, ProductSummary, RawRows};
+use crate::filter::Filter;
+use color_eyre::eyre::Result;
+use std::io::{BufRead, Write};
+
+/// Printed by the `.help` command.
+pub const HELP: &str = "\
+Statements end with a ; and may span several lines.
+
+.help       show this message
+.find <filter>
+            list products matching a filter (i.e. .find country:France price<25)
+.tables     list the tables
+.history    list previous statements
+!<number>   run a previous statement again (!! for the last one)
+.quit       exit (as does ctrl-d)";
+
+/// What to do after reading a line of input.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    /// Wait for more lines to complete the current statement.
+    Continue,
+    /// Run a statement.
+    Run(String),
+    /// Search products with a filter expression.
+    Find(String),
+    /// Print a message.
+    Print(String),
+    /// Exit the prompt.
+    Quit,
+}
+
+/// Accumulates input lines into statements, keeping track of the history.
+#[derive(Debug, Default)]
+struct Prompt {
+    /// The lines of the statement being typed so far.
+    buffer: String,
+    /// Previously run statements, oldest first.
+    history: Vec<String>,
+}
+
+impl Prompt {
+    /// The text printed before reading each line.
+    fn prefix(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            "ransaq> "
+        } else {
+            "   ...> "
+        }
+    }
+
+    /// Handles a line of input.
+    fn push_line(&mut self, line: &str) -> Action {
+        let line = line.trim();
+
+        if self.buffer.is_empty() {
+            if line.is_empty() {
+                return Action::Continue;
+            }
+
+            if let Some(reference) = line.strip_prefix('!') {
+                return self.recall(reference);
+            }
+
+            match line {
+                ".help" => return Action::Print(HELP.to_string()),
+                ".tables" => {
+                    return self.record(
+                        "select name from sqlite_master where type = 'table' order by name;"
+                            .to_string(),
+                    )
+                }
+                ".history" => {
+                    return Action::Print(
+                        self.history
+                            .iter()
+                            .enumerate()
+                            .map(|(i, statement)| format!("{:>4}  {}", i + 1, statement))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    )
+                }
+                ".quit" | ".exit" => return Action::Quit,
+                _ if line.starts_with(".find ") => {
+                    if self.history.last().map(String::as_str) != Some(line) {
+                        self.history.push(line.to_string());
+                    }
+                    return Action::Find(line[".find ".len()..].to_string());
+                }
+                _ if line.starts_with('.') => {
+                    return Action::Print(format!("unknown command {line:?}, see .help"))
+                }
+                _ => {}
+            }
+        }
+
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line);
+
+        if !line.ends_with(';') {
+            return Action::Continue;
+        }
+
+        let statement = std::mem::take(&mut self.buffer);
+        self.record(statement)
+    }
+
+    /// Adds `statement` to the history and runs it.
+    fn record(&mut self, statement: String) -> Action {
+        if self.history.last() != Some(&statement) {
+            self.history.push(statement.clone());
+        }
+
+        Action::Run(statement)
+    }
+
+    /// Runs the statement `reference` points to again (i.e. `3` or `!`).
+    fn recall(&mut self, reference: &str) -> Action {
+        let statement = if reference == "!" {
+            self.history.last()
+        } else {
+            reference
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| self.history.get(number.checked_sub(1)?))
+        };
+
+        match statement.cloned() {
+            Some(statement) if statement.starts_with(".find ") => self.push_line(&statement),
+            Some(statement) => self.record(statement),
+            None => Action::Print(format!("no statement !{reference} in history")),
+        }
+    }
+}
+
+/// Converts search results into rows, to be printed like query results.
+fn product_rows(products: Vec<ProductSummary>) -> RawRows {
+    RawRows {
+        columns: [
+            "saq_code",
+            "name",
+            "price_cents",
+            "availability",
+            "country",
+            "color",
+        ]
+        .map(String::from)
+        .to_vec(),
+        rows: products
+            .into_iter()
+            .map(|product| {
+                vec![
+                    Some(product.saq_code),
+                    Some(product.name),
+                    Some(product.price_cents.to_string()),
+                    Some(product.availability),
+                    product.country,
+                    product.color,
+                ]
+            })
+            .collect(),
+    }
+}
+
+/// Maximum number of products listed by `.find`.
+const FIND_LIMIT: i64 = 1000;
+
+/// Prints `result` as tab-separated lines, pausing every `page_size` rows
+/// (unless it is 0) until enter is pressed. Returns whether to keep reading
+/// input, which stops if `input` ends while paused.
+fn print_rows(result: &RawRows, page_size: usize, input: &mut impl BufRead) -> Result<bool> {
+    let mut stdout = std::io::stdout().lock();
+
+    if result.rows.is_empty() {
+        eprintln!("(no rows)");
+        return Ok(true);
+    }
+
+    writeln!(stdout, "{}", result.columns.join("\t"))?;
+
+    for (i, row) in result.rows.iter().enumerate() {
+        if page_size > 0 && i > 0 && i % page_size == 0 {
+            stdout.flush()?;
+            eprint!(
+                "-- {} more rows, press enter to continue or q to stop --",
+                result.rows.len() - i
+            );
+
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                return Ok(false);
+            }
+            if answer.trim() == "q" {
+                break;
+            }
+        }
+
+        let values = row
+            .iter()
+            .map(|value| value.as_deref().unwrap_or("NULL"))
+            .collect::<Vec<_>>();
+        writeln!(stdout, "{}", values.join("\t"))?;
+    }
+
+    eprintln!("({} rows)", result.rows.len());
+
+    Ok(true)
+}
+
+/// Runs the prompt against `db` until the input ends or `.quit` is entered.
+///
+/// Results are paged every `page_size` rows, 0 disabling paging.
+pub async fn run(db: &db::Client, page_size: usize) -> Result<()> {
+    let mut prompt = Prompt::default();
+    let mut input = std::io::stdin().lock();
+
+    eprintln!("Enter .help for usage hints.");
+
+    loop {
+        eprint!("{}", prompt.prefix());
+        std::io::stderr().flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+
+        match prompt.push_line(&line) {
+            Action::Continue => {}
+            Action::Print(message) => eprintln!("{message}"),
+            Action::Quit => return Ok(()),
+            Action::Run(statement) => match db.query_raw(&statement).await {
+                Ok(result) => {
+                    if !print_rows(&result, page_size, &mut input)? {
+                        return Ok(());
+                    }
+                }
+                Err(err) => eprintln!("error: {err}"),
+            },
+            Action::Find(filter) => {
+                let products = match filter.parse::<Filter>() {
+                    Ok(filter) => db.search(&filter, FIND_LIMIT).await,
+                    Err(err) => Err(err),
+                };
+                match products {
+                    Ok(products) => {
+                        if !print_rows(&product_rows(products), page_size, &mut input)? {
+                            return Ok(());
+                        }
+                    }
+                    Err(err) => eprintln!("error: {err}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_line() {
+        let mut prompt = Prompt::default();
+
+        assert_eq!(Action::Continue, prompt.push_line(""));
+        assert_eq!(Action::Continue, prompt.push_line("select *"));
+        assert_eq!("   ...> ", prompt.prefix());
+        assert_eq!(
+            Action::Run("select *\nfrom products;".to_string()),
+            prompt.push_line("  from products;\n")
+        );
+        assert_eq!("ransaq> ", prompt.prefix());
+
+        assert_eq!(
+            Action::Run("select 1;".to_string()),
+            prompt.push_line("select 1;")
+        );
+        assert_eq!(Action::Run("select 1;".to_string()), prompt.push_line("!!"));
+        assert_eq!(
+            Action::Run("select *\nfrom products;".to_string()),
+            prompt.push_line("!1")
+        );
+        assert_eq!(3, prompt.history.len());
+        assert_eq!(
+            Action::Print("no statement !5 in history".to_string()),
+            prompt.push_line("!5")
+        );
+        assert_eq!(
+            Action::Print(
+                "   1  select *\nfrom products;\n   2  select 1;\n   3  select *\nfrom products;"
+                    .to_string()
+            ),
+            prompt.push_line(".history")
+        );
+
+        assert_eq!(
+            Action::Find("color:red price<20".to_string()),
+            prompt.push_line(".find color:red price<20")
+        );
+        assert_eq!(
+            Action::Find("color:red price<20".to_string()),
+            prompt.push_line("!4")
+        );
+        assert_eq!(4, prompt.history.len());
+
+        assert!(matches!(prompt.push_line(".nope"), Action::Print(_)));
+        assert_eq!(Action::Quit, prompt.push_line(".quit"));
+    }
+}
@@ -0,0 +1,338 @@
+//! Keeping the cookies set by the SAQ website, so that it keeps serving pages
+//! the same way (i.e. locale and currency) and session-gated endpoints work
+//! without negotiating a new session for each request.
+//!
+//! A [`CookieJar`] is shared by a [`Client`](super::Client) and its clones,
+//! and can be [saved](CookieJar::save) to a JSON file to be
+//! [loaded](CookieJar::load) by the next run (see `RANSAQ_COOKIE_JAR`).
+//! Session cookies (those without an expiry) are saved too, as each run is
+//! meant to pick up where the last one left off.
+//!
+//! This handles the subset of [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265)
+//! the site relies on: the `Domain`, `Path`, `Expires`, `Max-Age` and `Secure`
+//! attributes. Cookies set by redirects which are followed automatically
+//! aren't seen.
+
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use reqwest::{Request, Url};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A cookie set by a response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Cookie {
+    /// The cookie's name.
+    name: String,
+    /// The cookie's value.
+    value: String,
+    /// The host the cookie was set by, or the domain it was set for.
+    domain: String,
+    /// Whether the cookie is only sent to `domain` itself rather than its
+    /// subdomains too, which is the case when it's set without a `Domain`.
+    host_only: bool,
+    /// The path (and sub-paths) the cookie is sent to.
+    path: String,
+    /// Whether the cookie is only sent over HTTPS.
+    secure: bool,
+    /// When the cookie expires, `None` for session cookies.
+    expires: Option<DateTime<Utc>>,
+}
+
+/// Parses the date of an `Expires` attribute (i.e. `Wed, 21 Oct 2015
+/// 07:28:00 GMT`, sometimes with dashes between the date's parts).
+fn parse_expires(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(&value.replace('-', " "))
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// The path a cookie set by `url` without a `Path` applies to: the URL's
+/// "directory".
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => url.path()[..i].to_string(),
+    }
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header received from `url` at `now`, ignoring it
+    /// if it's malformed or sets a cookie for another site.
+    fn parse(set_cookie: &str, url: &Url, now: DateTime<Utc>) -> Option<Cookie> {
+        let host = url.host_str()?.to_lowercase();
+        let mut parts = set_cookie.split(';');
+
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+
+            match key.trim().to_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+                    if domain.is_empty() {
+                        continue;
+                    }
+                    if host != domain && !host.ends_with(&format!(".{domain}")) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "expires" => cookie.expires = cookie.expires.or_else(|| parse_expires(value)),
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                _ => {}
+            }
+        }
+
+        // Max-Age takes precedence over Expires
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(now + Duration::seconds(max_age.clamp(-1, 400 * 24 * 3600)));
+        }
+
+        Some(cookie)
+    }
+
+    /// Whether the cookie has expired as of `now`.
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    /// Whether the cookie should be sent along with a request to `url`.
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return false,
+        };
+
+        let domain_matches = match self.host_only {
+            true => host == self.domain,
+            false => host == self.domain || host.ends_with(&format!(".{}", self.domain)),
+        };
+
+        let path = url.path();
+        let path_matches = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+
+        domain_matches && path_matches && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Cookies shared by a [`Client`](super::Client) and its clones, see the
+/// [module docs](self).
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    /// The cookies received so far.
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Reads the cookies saved to `path`. The jar is empty if the file
+    /// doesn't exist yet.
+    pub fn load(path: &Path) -> Result<CookieJar> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(CookieJar::default())
+            }
+            Err(err) => return Err(err).wrap_err_with(|| format!("failed to read {path:?}")),
+        };
+
+        let cookies = serde_json::from_str(&json)
+            .wrap_err_with(|| format!("failed to parse cookie jar {path:?}"))?;
+
+        Ok(CookieJar {
+            cookies: Mutex::new(cookies),
+        })
+    }
+
+    /// Writes the cookies which haven't expired to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let now = Utc::now();
+        let cookies = self
+            .cookies
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        std::fs::write(path, serde_json::to_string_pretty(&cookies)?)
+            .wrap_err_with(|| format!("failed to write {path:?}"))
+    }
+
+    /// Number of cookies in the jar, expired or not.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    /// Whether the jar holds no cookies at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keeps the cookies set by a response from `url` with the given
+    /// `headers`, received at `now`. Cookies replace those with the same name,
+    /// domain and path, and expired ones are removed.
+    pub(super) fn store(&self, url: &Url, headers: &HeaderMap, now: DateTime<Utc>) {
+        let received = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| Cookie::parse(value, url, now))
+            .collect::<Vec<_>>();
+        if received.is_empty() {
+            return;
+        }
+
+        let mut cookies = self.cookies.lock().unwrap();
+        for cookie in received {
+            cookies.retain(|existing| {
+                (&existing.name, &existing.domain, &existing.path)
+                    != (&cookie.name, &cookie.domain, &cookie.path)
+            });
+            cookies.push(cookie);
+        }
+        cookies.retain(|cookie| !cookie.is_expired(now));
+    }
+
+    /// The `Cookie` header to send along with a request to `url` at `now`, if
+    /// any cookies apply. Cookies with longer paths come first.
+    fn header(&self, url: &Url, now: DateTime<Utc>) -> Option<String> {
+        let cookies = self.cookies.lock().unwrap();
+        let mut matching = cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(url))
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return None;
+        }
+
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        Some(
+            matching
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Adds the cookies that apply to `request` to it.
+    pub(super) fn add_to(&self, request: &mut Request) {
+        let header = self
+            .header(request.url(), Utc::now())
+            .and_then(|header| HeaderValue::from_str(&header).ok());
+
+        if let Some(header) = header {
+            request.headers_mut().insert(COOKIE, header);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cookie_jar() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+        let url = |url: &str| Url::parse(url).unwrap();
+        let headers = |set_cookies: &[&str]| {
+            let mut headers = HeaderMap::new();
+            for set_cookie in set_cookies {
+                headers.append(SET_COOKIE, HeaderValue::from_str(set_cookie).unwrap());
+            }
+            headers
+        };
+
+        let jar = CookieJar::default();
+        jar.store(
+            &url("https://www.saq.com/en/products"),
+            &headers(&[
+                "store=en; Domain=.saq.com; Path=/",
+                "session=abc; Path=/; Secure; HttpOnly",
+                "listing=grid",
+                "tracker=1; Domain=example.com",
+                "old=1; Expires=Wed, 21-Oct-2015 07:28:00 GMT",
+            ]),
+            now,
+        );
+        assert_eq!(3, jar.len());
+
+        assert_eq!(
+            Some("listing=grid; store=en; session=abc".to_string()),
+            jar.header(&url("https://www.saq.com/en/13191791"), now)
+        );
+        // Host-only and secure cookies stay on the host that set them, over
+        // HTTPS
+        assert_eq!(
+            Some("store=en".to_string()),
+            jar.header(&url("https://cdn.saq.com/image.png"), now)
+        );
+        assert_eq!(
+            Some("store=en".to_string()),
+            jar.header(&url("http://www.saq.com/fr"), now)
+        );
+        assert_eq!(None, jar.header(&url("https://example.com/"), now));
+
+        // Cookies get replaced, and removed once expired
+        jar.store(
+            &url("https://www.saq.com/en/products"),
+            &headers(&[
+                "store=fr; Domain=saq.com; Path=/",
+                "session=; Path=/; Max-Age=0",
+            ]),
+            now,
+        );
+        jar.store(
+            &url("https://www.saq.com/"),
+            &headers(&["session=def; Path=/; Max-Age=60"]),
+            now,
+        );
+        assert_eq!(
+            Some("store=fr; session=def".to_string()),
+            jar.header(&url("https://www.saq.com/"), now)
+        );
+        assert_eq!(
+            Some("store=fr".to_string()),
+            jar.header(&url("https://www.saq.com/"), now + Duration::minutes(2))
+        );
+
+        let path = std::env::temp_dir().join(format!("ransaq-cookies-{}.json", std::process::id()));
+        jar.save(&path).unwrap();
+        let loaded = CookieJar::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let later = now + Duration::minutes(2);
+        assert_eq!(
+            Some("listing=grid; store=fr".to_string()),
+            loaded.header(&url("https://www.saq.com/en/products"), later)
+        );
+
+        assert!(CookieJar::load(&path).unwrap().is_empty());
+    }
+}
@@ -4,11 +4,25 @@
 use color_eyre::eyre::{eyre, Result, WrapErr};
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::str::FromStr;
 
+/// Where in a product page its Detailed Info was found, see
+/// [`SiteProfile::extract_detailed_info`](super::SiteProfile::extract_detailed_info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetailedInfoSource {
+    /// The section matched by the primary selector.
+    Selector,
+    /// Markup matched by one of the fallback selectors.
+    FallbackSelector,
+    /// A JSON blob embedded in a `data-mage-init` attribute.
+    MageInit,
+}
+
 /// Data extracted from the Detailed Info section of product pages.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct DetailedInfo {
     /// The product's producer (i.e. "The Absolut Company")
     pub producer: Option<String>,
@@ -137,18 +151,24 @@ lazy_static! {
 }
 
 /// Converts a string indicating the alcohol by volume percentage into a float.
-fn parse_abv(text: &str) -> Result<f32> {
+pub fn parse_abv(text: &str) -> Result<f32> {
     let num = ABV_RE
         .captures(text)
         .and_then(|c| c.get(1))
         .ok_or_else(|| eyre!("failed to match {:?}", text))?
         .as_str();
 
-    f32::from_str(num).wrap_err_with(|| format!("failed to parse {num:?} as float"))
+    let abv = f32::from_str(num).wrap_err_with(|| format!("failed to parse {num:?} as float"))?;
+
+    if !(0.0..=100.0).contains(&abv) {
+        return Err(eyre!("{:?} is not a valid percentage", text));
+    }
+
+    Ok(abv)
 }
 
 /// The product's size
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Size {
     /// The number of containers for the given product
     pub container_count: u8,
@@ -162,7 +182,7 @@ lazy_static! {
 }
 
 /// Converts the product's size string (i.e. "6 x 200ml") into a [`Size`]
-fn parse_size(text: &str) -> Result<Size> {
+pub fn parse_size(text: &str) -> Result<Size> {
     let captures = SIZE_RE
         .captures(text)
         .ok_or_else(|| eyre!("failed to match {:?}", text))?;
@@ -180,12 +200,22 @@ fn parse_size(text: &str) -> Result<Size> {
         f32::from_str(num_text).wrap_err_with(|| format!("failed to parse {num_text:?} as f32"))?;
     let unit = captures.get(5).expect("non-optional capture").as_str();
 
-    let container_milliliters = match unit {
-        "mL" | "ml" => num.ceil() as u32,
-        "L" => (num * 1000.0).ceil() as u32,
+    if container_count == 0 {
+        return Err(eyre!("{:?} has no containers", text));
+    }
+
+    let milliliters = match unit {
+        "mL" | "ml" => num.ceil(),
+        "L" => (num * 1000.0).ceil(),
         _ => unreachable!("not permitted by regex"),
     };
 
+    // `as` saturates instead of failing, so check the range explicitly
+    if !(1.0..u32::MAX as f32).contains(&milliliters) {
+        return Err(eyre!("{:?} is not a valid container size", text));
+    }
+    let container_milliliters = milliliters as u32;
+
     Ok(Size {
         container_count,
         container_milliliters,
@@ -193,7 +223,7 @@ fn parse_size(text: &str) -> Result<Size> {
 }
 
 /// The product's sugar content.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct SugarContent {
     /// The quantity of sugar expressed as grams per liter
     pub grams_per_liter: f32,
@@ -202,7 +232,7 @@ pub struct SugarContent {
 }
 
 /// Whether the actual sugar quantity is less than, greater than, or equal to `grams_per_liter`.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum SugarContentEquality {
     /// The actual sugar content is **greater than** what's indicated.
     GreaterThan,
@@ -218,7 +248,7 @@ lazy_static! {
 }
 
 /// Converts the string representation of the product's sugar content into a [`SugarContent`].
-fn parse_sugar_content(text: &str) -> Result<SugarContent> {
+pub fn parse_sugar_content(text: &str) -> Result<SugarContent> {
     let captures = SUGAR_CONTENT_RE
         .captures(text)
         .ok_or_else(|| eyre!("failed to match {:?}", text))?;
@@ -234,6 +264,12 @@ fn parse_sugar_content(text: &str) -> Result<SugarContent> {
     let grams_per_liter =
         f32::from_str(num_text).wrap_err_with(|| format!("failed to parse {num_text:?} as f32"))?;
 
+    // A liter of anything weighs nowhere near this much, but overly long
+    // numbers would otherwise be parsed as infinity.
+    if !grams_per_liter.is_finite() {
+        return Err(eyre!("{:?} is not a valid sugar content", text));
+    }
+
     Ok(SugarContent {
         equality,
         grams_per_liter,
@@ -241,7 +277,7 @@ fn parse_sugar_content(text: &str) -> Result<SugarContent> {
 }
 
 /// The grape variety and percentage of it present in the product
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct GrapeVariety {
     /// The grape variety name (i.e. chardonnay).
     pub name: String,
@@ -256,7 +292,7 @@ lazy_static! {
 
 /// Converts the string representaiton of the grape varieties present in the product to
 /// a `Vec` of [`GrapeVariety`].
-fn parse_grape_varieties(text: &str) -> Result<Vec<GrapeVariety>> {
+pub fn parse_grape_varieties(text: &str) -> Result<Vec<GrapeVariety>> {
     let mut varieties = vec![];
 
     for part in text.split(',') {
@@ -273,6 +309,9 @@ fn parse_grape_varieties(text: &str) -> Result<Vec<GrapeVariety>> {
             percentage = Some(u8::from_str(percentage_text).wrap_err_with(|| {
                 format!("failed to parse percentage from {part:?} ({percentage_text:?}) as u8",)
             })?);
+            if percentage > Some(100) {
+                return Err(eyre!("{:?} is not a valid percentage", part));
+            }
             name = part[0..offset].trim();
         }
 
@@ -293,7 +332,7 @@ fn parse_grape_varieties(text: &str) -> Result<Vec<GrapeVariety>> {
 ///
 /// <https://www.lapresse.ca/gourmand/alcools/2020-06-04/les-produits-quebecois-mieux-identifies-par-la-saq>
 /// <https://www.laterre.ca/actualites/economie/nouvelle-distinction-entre-les-produits-du-quebec-a-la-saq>
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum ProductOfQuebec {
     /// The final product was bottled in Québec.
     BottledIn,
@@ -329,6 +368,12 @@ mod tests {
 
         let wrong_format_err = parse_abv(" 12 ").unwrap_err();
         assert_eq!("failed to match \" 12 \"", wrong_format_err.to_string());
+
+        let out_of_range_err = parse_abv("120 %").unwrap_err();
+        assert_eq!(
+            "\"120 %\" is not a valid percentage",
+            out_of_range_err.to_string()
+        );
     }
 
     #[test]
@@ -348,6 +393,10 @@ mod tests {
         let big_l = parse_size("750 mL").unwrap();
         assert_eq!(1, big_l.container_count);
         assert_eq!(750, big_l.container_milliliters);
+
+        assert!(parse_size("0 x 750 ml").is_err());
+        assert!(parse_size("0 ml").is_err());
+        assert!(parse_size("99999999 L").is_err());
     }
 
     #[test]
@@ -392,6 +441,8 @@ mod tests {
         assert_eq!(1, six.len());
         assert_eq!("Muscat de N.Y.", six[0].name);
         assert_eq!(Some(25), six[0].percentage);
+
+        assert!(parse_grape_varieties("Merlot 200 %").is_err());
     }
 
     #[test]
@@ -407,5 +458,8 @@ mod tests {
         let three = parse_sugar_content("2.9 g/L").unwrap();
         assert_eq!(2.9, three.grams_per_liter);
         assert_eq!(SugarContentEquality::Equal, three.equality);
+
+        let too_long = format!("{} g/L", "9".repeat(50));
+        assert!(parse_sugar_content(&too_long).is_err());
     }
 }
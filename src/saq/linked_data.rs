@@ -1,9 +1,13 @@
 //! Just enough JSON-LD/Schema.org support to parse what we need
 
-use serde::Deserialize;
+use super::money::Price;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
 
 /// The subset of [`Thing`](https://schema.org/Thing) included in the SAQ's JSON-LD
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "@type")]
 pub enum LinkedData {
     /// <https://schema.org/WebSite>
@@ -13,14 +17,33 @@ pub enum LinkedData {
     /// A [`WebPage`]
     WebPage(WebPage),
     /// A [`Product`]
-    Product(Product),
+    Product(Box<Product>),
     /// Fallback for anything else
     #[serde(other)]
     Other,
 }
 
+impl LinkedData {
+    /// The `@type` of the entry (or `"Other"` if it isn't one we parse),
+    /// along with the type of its main entity for [`WebPage`]s.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LinkedData::WebSite => "WebSite",
+            LinkedData::BreadcrumbList(_) => "BreadcrumbList",
+            LinkedData::WebPage(page) => match &page.main_entity {
+                Some(Entity::OfferCatalog(_)) => "WebPage(OfferCatalog)",
+                Some(Entity::ItemList(_)) => "WebPage(ItemList)",
+                Some(Entity::Other) => "WebPage(Other)",
+                None => "WebPage",
+            },
+            LinkedData::Product(_) => "Product",
+            LinkedData::Other => "Other",
+        }
+    }
+}
+
 /// <https://schema.org/BreadcrumbList>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct BreadcrumbList {
     /// A partial implementation of [`itemListElement`](https://schema.org/itemListElement)
     /// that assumes only [`ListItem`](https://schema.org/ListItem)s are present.
@@ -29,20 +52,20 @@ pub struct BreadcrumbList {
 }
 
 /// <https://schema.org/itemListElement>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "@type")]
 pub enum ItemListElement {
     /// A [`ListItem`]
     ListItem(ListItem),
     /// A [`Product`]
-    Product(Product),
+    Product(Box<Product>),
     /// Fallback for anything else
     #[serde(other)]
     Other,
 }
 
 /// <https://schema.org/itemListElement>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct ListItem {
     /// The entity represented
     pub item: Item,
@@ -51,7 +74,7 @@ pub struct ListItem {
 }
 
 /// <https://schema.org/ListItem>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Item {
     /// <https://www.w3.org/TR/2014/REC-json-ld-20140116/#node-identifiers>
     #[serde(rename(deserialize = "@id"))]
@@ -61,7 +84,7 @@ pub struct Item {
 }
 
 /// <https://schema.org/WebPage>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct WebPage {
     /// <https://schema.org/mainEntity>
     #[serde(rename(deserialize = "mainEntity"))]
@@ -72,18 +95,21 @@ pub struct WebPage {
 
 /// Subset of [`Thing`](<https://schema.org/Thing>) included in the SAQ's
 /// [`WebPage`]s.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "@type")]
 pub enum Entity {
     /// An [`OfferCatalog`]
     OfferCatalog(OfferCatalog),
+    /// An [`ItemList`], used instead of an [`OfferCatalog`] by some
+    /// promotional listings.
+    ItemList(ItemList),
     /// Fallback for anything else
     #[serde(other)]
     Other,
 }
 
 /// <https://schema.org/OfferCatalog>
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct OfferCatalog {
     /// <https://schema.org/name>
     pub name: String,
@@ -97,8 +123,16 @@ pub struct OfferCatalog {
     pub item_list_element: Vec<ItemListElement>,
 }
 
+/// <https://schema.org/ItemList>
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ItemList {
+    /// <https://schema.org/itemListElement>
+    #[serde(rename(deserialize = "itemListElement"))]
+    pub item_list_element: Vec<ItemListElement>,
+}
+
 /// <https://schema.org/Product>
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Product {
     /// <https://schema.org/description>
     pub description: String,
@@ -114,10 +148,201 @@ pub struct Product {
     pub sku: String,
     /// <https://schema.org/category>
     pub category: Option<String>,
+    /// <https://schema.org/brand>
+    ///
+    /// This often differs from the [`producer`](super::detailed_info::DetailedInfo::producer),
+    /// particularly for spirits.
+    pub brand: Option<NamedEntity>,
+    /// <https://schema.org/manufacturer>
+    pub manufacturer: Option<NamedEntity>,
+    /// <https://schema.org/gtin>
+    pub gtin: Option<String>,
+    /// <https://schema.org/gtin13>
+    pub gtin13: Option<String>,
+    /// <https://schema.org/mpn>
+    pub mpn: Option<String>,
+    /// <https://schema.org/aggregateRating>
+    #[serde(default, rename(deserialize = "aggregateRating"))]
+    pub aggregate_rating: Option<AggregateRating>,
+    /// <https://schema.org/review>, given either as a single [`Review`] or
+    /// an array of them.
+    #[serde(
+        default,
+        rename(deserialize = "review"),
+        deserialize_with = "one_or_many"
+    )]
+    pub reviews: Vec<Review>,
+}
+
+/// A JSON number, or a string holding one (i.e. `"4.5"`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    /// The number itself.
+    Number(T),
+    /// The number's text.
+    Text(String),
+}
+
+impl<T: FromStr> NumberOrString<T>
+where
+    T::Err: Display,
+{
+    /// The number, parsing it if it was given as a string.
+    fn parse<E: de::Error>(self) -> Result<T, E> {
+        match self {
+            NumberOrString::Number(number) => Ok(number),
+            NumberOrString::Text(text) => text.trim().parse().map_err(E::custom),
+        }
+    }
+}
+
+/// Deserializes a number given either as a JSON number or a string.
+fn number_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    NumberOrString::deserialize(deserializer)?.parse()
+}
+
+/// Same as [`number_or_string`] for optional numbers.
+fn optional_number_or_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    Option::<NumberOrString<T>>::deserialize(deserializer)?
+        .map(NumberOrString::parse)
+        .transpose()
+}
+
+/// A single value or an array of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    /// The array form.
+    Many(Vec<T>),
+    /// The single value form.
+    One(T),
+}
+
+/// Deserializes either a single value or an array of them into a [`Vec`].
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(values) => values,
+        OneOrMany::One(value) => vec![value],
+    })
+}
+
+/// <https://schema.org/AggregateRating>
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AggregateRating {
+    /// <https://schema.org/ratingValue>
+    #[serde(
+        rename(deserialize = "ratingValue"),
+        deserialize_with = "number_or_string"
+    )]
+    pub rating_value: f64,
+    /// <https://schema.org/bestRating>, 5 when left out.
+    #[serde(
+        default,
+        rename(deserialize = "bestRating"),
+        deserialize_with = "optional_number_or_string"
+    )]
+    pub best_rating: Option<f64>,
+    /// <https://schema.org/ratingCount>
+    #[serde(
+        default,
+        rename(deserialize = "ratingCount"),
+        deserialize_with = "optional_number_or_string"
+    )]
+    pub rating_count: Option<i64>,
+    /// <https://schema.org/reviewCount>
+    #[serde(
+        default,
+        rename(deserialize = "reviewCount"),
+        deserialize_with = "optional_number_or_string"
+    )]
+    pub review_count: Option<i64>,
+}
+
+impl AggregateRating {
+    /// The number of ratings the value is based on, falling back to the
+    /// number of reviews when the page only gives that.
+    pub fn count(&self) -> Option<i64> {
+        self.rating_count.or(self.review_count)
+    }
+}
+
+/// <https://schema.org/Rating>
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Rating {
+    /// <https://schema.org/ratingValue>
+    #[serde(
+        rename(deserialize = "ratingValue"),
+        deserialize_with = "number_or_string"
+    )]
+    pub rating_value: f64,
+    /// <https://schema.org/bestRating>, 5 when left out.
+    #[serde(
+        default,
+        rename(deserialize = "bestRating"),
+        deserialize_with = "optional_number_or_string"
+    )]
+    pub best_rating: Option<f64>,
+}
+
+/// <https://schema.org/Review>
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Review {
+    /// <https://schema.org/author>
+    pub author: Option<NamedEntity>,
+    /// <https://schema.org/datePublished>
+    #[serde(rename(deserialize = "datePublished"))]
+    pub date_published: Option<String>,
+    /// <https://schema.org/name>, the review's title.
+    pub name: Option<String>,
+    /// <https://schema.org/reviewBody>
+    #[serde(rename(deserialize = "reviewBody"))]
+    pub review_body: Option<String>,
+    /// <https://schema.org/reviewRating>
+    #[serde(rename(deserialize = "reviewRating"))]
+    pub review_rating: Option<Rating>,
+}
+
+/// A [`Brand`](https://schema.org/Brand), [`Organization`](https://schema.org/Organization)
+/// or [`Person`](https://schema.org/Person), which can either be given as a
+/// plain name or as an object.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum NamedEntity {
+    /// The plain text form (i.e. `"brand": "Absolut"`)
+    Name(String),
+    /// The object form (i.e. `"brand": {"@type": "Brand", "name": "Absolut"}`)
+    Object {
+        /// <https://schema.org/name>
+        name: String,
+    },
+}
+
+impl NamedEntity {
+    /// The entity's name, regardless of the form it was given in.
+    pub fn name(&self) -> &str {
+        match self {
+            NamedEntity::Name(name) | NamedEntity::Object { name } => name,
+        }
+    }
 }
 
 /// <https://schema.org/Offer>
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Offer {
     /// See [`ItemAvailability`]
     pub availability: ItemAvailability,
@@ -125,17 +350,42 @@ pub struct Offer {
     #[serde(rename(deserialize = "itemCondition"))]
     pub item_condition: OfferItemCondition,
     /// <https://schema.org/price>
-    pub price: f64,
+    pub price: Price,
     /// <https://schema.org/priceCurrency>
     #[serde(rename(deserialize = "priceCurrency"))]
     pub price_currency: String,
+    /// <https://schema.org/priceValidUntil>
+    ///
+    /// Usually present when the product is on sale, see
+    /// [`price_valid_until_date`](Offer::price_valid_until_date).
+    #[serde(rename(deserialize = "priceValidUntil"))]
+    pub price_valid_until: Option<String>,
+    /// <https://schema.org/seller>
+    pub seller: Option<NamedEntity>,
     /// <https://schema.org/url>
     ///
     /// For the SAQ this is the product page URL.
     pub url: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl Offer {
+    /// The date part of [`price_valid_until`](Offer::price_valid_until) in
+    /// `YYYY-MM-DD` form, which may otherwise be a full date and time.
+    ///
+    /// Returns `None` if it isn't an ISO 8601 date.
+    pub fn price_valid_until_date(&self) -> Option<&str> {
+        let date = self.price_valid_until.as_deref()?.get(..10)?;
+
+        let is_date = date.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+
+        is_date.then_some(date)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 /// <https://schema.org/ItemAvailability>
 pub enum ItemAvailability {
     /// <http://schema.org/BackOrder>
@@ -171,7 +421,7 @@ pub enum ItemAvailability {
 }
 
 /// <https://schema.org/OfferItemCondition>
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum OfferItemCondition {
     /// <https://schema.org/DamagedCondition>
     #[serde(rename(deserialize = "DamagedCondition"))]
@@ -186,3 +436,74 @@ pub enum OfferItemCondition {
     #[serde(rename(deserialize = "UsedCondition"))]
     Used,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_entity() {
+        let name: NamedEntity = serde_json::from_str(r#""Absolut""#).unwrap();
+        assert_eq!("Absolut", name.name());
+
+        let object: NamedEntity =
+            serde_json::from_str(r#"{"@type": "Brand", "name": "Absolut"}"#).unwrap();
+        assert_eq!("Absolut", object.name());
+    }
+
+    #[test]
+    fn test_ratings_and_reviews() {
+        let product: Product = serde_json::from_str(
+            r#"{"name": "Absolut Vodka", "description": "", "image": "", "sku": "26",
+            "offers": {"availability": "http://schema.org/InStock",
+                "itemCondition": "NewCondition", "price": 32.75, "priceCurrency": "CAD",
+                "url": "https://www.saq.com/en/26"},
+            "aggregateRating": {"@type": "AggregateRating", "ratingValue": "4.5",
+                "reviewCount": 12},
+            "review": {"@type": "Review", "author": {"@type": "Person", "name": "Marie"},
+                "reviewRating": {"@type": "Rating", "ratingValue": 4, "bestRating": "5"}}}"#,
+        )
+        .unwrap();
+
+        let rating = product.aggregate_rating.unwrap();
+        assert_eq!(4.5, rating.rating_value);
+        assert_eq!(Some(12), rating.count());
+        assert_eq!(1, product.reviews.len());
+        assert_eq!("Marie", product.reviews[0].author.as_ref().unwrap().name());
+        assert_eq!(
+            Some(Rating {
+                rating_value: 4.0,
+                best_rating: Some(5.0)
+            }),
+            product.reviews[0].review_rating
+        );
+
+        let invalid = serde_json::from_str::<AggregateRating>(r#"{"ratingValue": "great"}"#);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_price_valid_until_date() {
+        let offer = |price_valid_until: Option<&str>| Offer {
+            availability: ItemAvailability::InStock,
+            item_condition: OfferItemCondition::New,
+            price: Price::from_cents(1995),
+            price_currency: "CAD".to_string(),
+            price_valid_until: price_valid_until.map(String::from),
+            seller: None,
+            url: "https://www.saq.com/en/13191791".to_string(),
+        };
+
+        assert_eq!(None, offer(None).price_valid_until_date());
+        assert_eq!(
+            Some("2026-11-01"),
+            offer(Some("2026-11-01")).price_valid_until_date()
+        );
+        assert_eq!(
+            Some("2026-11-01"),
+            offer(Some("2026-11-01T23:59:59-04:00")).price_valid_until_date()
+        );
+        assert_eq!(None, offer(Some("Nov 1, 2026")).price_valid_until_date());
+        assert_eq!(None, offer(Some("2026")).price_valid_until_date());
+    }
+}
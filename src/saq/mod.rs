@@ -1,137 +1,44 @@
 //! HTTP logic to interact with the SAQ website
-
+//!
+//! Parsing is done by pure functions ([`parse_catalog_page`] and
+//! [`parse_product_page`]) which don't depend on anything but the HTML they're
+//! given, and remain available with default features disabled (i.e. to use them
+//! from WASM). [`Client`] fetches pages and hands them over to these, and
+//! requires the `crawler` feature.
+//!
+//! Both parse SAQ pages, and have [`SiteProfile`] counterparts for similar
+//! storefronts (see [`profile`]).
+
+#[cfg(feature = "crawler")]
+mod client;
+#[cfg(feature = "crawler")]
+pub mod cookies;
 pub mod detailed_info;
 pub mod linked_data;
-
-use color_eyre::eyre::{eyre, Result, WrapErr};
+pub mod money;
+pub mod profile;
+#[cfg(feature = "crawler")]
+pub mod rate_limit;
+#[cfg(feature = "crawler")]
+pub mod retry;
+pub mod sitemap;
+pub mod tasting;
+pub mod text;
+pub mod url;
+
+#[cfg(feature = "crawler")]
+pub use client::{
+    AllowedHosts, CatalogOrder, Client, PageBody, ProductPage, Proxies, RequestCounter,
+    SpooledBody, PAGE_SIZES,
+};
+pub use profile::SiteProfile;
+
+use color_eyre::eyre::{eyre, Result};
 use lazy_static::lazy_static;
-use linked_data::{Entity, ItemListElement, LinkedData, OfferCatalog, Product, WebPage};
-use reqwest::Url;
+use linked_data::{Entity, ItemList, ItemListElement, LinkedData, OfferCatalog, Product, WebPage};
 use scraper::Selector;
-use std::{collections::HashMap, time::Instant};
-use tracing::{info, info_span};
-
-/// Provides a number of methods to interact with the SAQ website
-///
-/// Note - `Client` is both `Sync` and cheap to `Clone` thanks to
-/// [`reqwest::Client`] being wrapped in an `Arc`.
-#[derive(Clone)]
-pub struct Client {
-    /// The HTTP client to use.
-    reqwest_client: reqwest::Client,
-}
-
-/// The HTTP User-Agent used for all requests. This was used as an easy default
-/// during development so it is not know whether something that better reflects
-/// the intended use would cause requests to be blocked or throttled.
-const DEFAULT_USER_AGENT: &str =
-    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:94.0) Gecko/20100101 Firefox/94.0";
-
-impl Client {
-    /// Builds a `Client`
-    pub fn new() -> Result<Client> {
-        let reqwest_client = reqwest::Client::builder()
-            .user_agent(DEFAULT_USER_AGENT)
-            .build()?;
-
-        Ok(Client { reqwest_client })
-    }
-}
-
-lazy_static! {
-    #[doc(hidden)]
-    static ref CURRENT_PAGE_SELECTOR: Selector =
-        Selector::parse(".pages .pages-items .current .page span:nth-child(2)").unwrap();
-}
-
-impl Client {
-    /// Fetches a single page of the SAQ product catalog using the default sorting
-    /// (by availability), and returns a list of JSON-LD [`Product`] entries.
-    ///
-    /// Will return `None` if `page_number` has reached past the end.
-    ///
-    /// The enpoint also provides the following query parameters
-    /// - `product_list_limit` (defaults to `24`)
-    /// - `product_list_order` (defaults to `availability`)
-    /// however including them or deviating from the defaults adds a nontrivial
-    /// amount of latency.
-    pub async fn page(&self, page_number: u32) -> Result<Option<Vec<Product>>> {
-        let url = Url::parse_with_params(
-            "https://www.saq.com/en/products",
-            &[("p", &page_number.to_string())],
-        )?;
-
-        let span = info_span!("page", %url);
-        let span_guard = span.enter();
-
-        info!("request");
-        let start = Instant::now();
-
-        let res = self
-            .reqwest_client
-            .get(url)
-            .header("accept", "text/html")
-            .send()
-            .await?;
-
-        info!(status = %res.status(), time = ?start.elapsed() , "response");
-
-        let body = res.text().await?;
-        let document = scraper::html::Html::parse_document(&body);
-
-        let current_page = document
-            .select(&CURRENT_PAGE_SELECTOR)
-            .map(|e| {
-                let page_number = e.text().collect::<String>();
-                page_number.parse::<u32>().wrap_err_with(|| {
-                    format!("failed to convert page number {page_number:?} to integer")
-                })
-            })
-            .next()
-            .ok_or_else(|| eyre!("could not find pagination on page"))??;
-
-        // saq.com's pagination wraps around rather than render an empy page
-        if current_page != page_number {
-            return Ok(None);
-        }
-
-        let linked_data = extract_linked_data(&document)?;
-
-        let products = linked_data
-            .iter()
-            .find_map(|ld| {
-                if let LinkedData::WebPage(WebPage {
-                    main_entity:
-                        Some(Entity::OfferCatalog(OfferCatalog {
-                            item_list_element, ..
-                        })),
-                    ..
-                }) = ld
-                {
-                    Some(
-                        item_list_element
-                            .iter()
-                            .filter_map(|e| {
-                                if let ItemListElement::Product(product) = e {
-                                    Some(product)
-                                } else {
-                                    None
-                                }
-                            })
-                            .cloned()
-                            .collect::<Vec<_>>(),
-                    )
-                } else {
-                    None
-                }
-            })
-            .unwrap();
-
-        drop(span_guard);
-
-        Ok(Some(products))
-    }
-}
+use serde::Serialize;
+use std::fmt;
 
 lazy_static! {
     #[doc(hidden)]
@@ -141,7 +48,7 @@ lazy_static! {
 
 /// Finds the JSON-LD `<script>` tag on the page and parses its contents into
 /// [`LinkedData`] entries using [`serde_json`].
-fn extract_linked_data(document: &scraper::Html) -> Result<Vec<LinkedData>> {
+pub fn extract_linked_data(document: &scraper::Html) -> Result<Vec<LinkedData>> {
     Ok(document
         .select(&LD_SCRIPT_SELECTOR)
         .map(|e| serde_json::from_str::<LinkedData>(&e.inner_html()))
@@ -149,12 +56,29 @@ fn extract_linked_data(document: &scraper::Html) -> Result<Vec<LinkedData>> {
 }
 
 /// Contains all the data extracted from a product page
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ExtractedProduct {
     /// Parsed JSON-LD data contained in a `<script>` tag
     pub linked_data: Vec<LinkedData>,
     /// Product metadata from the "Detailed Info" section of the page
     pub detailed_info: detailed_info::DetailedInfo,
+    /// Where the "Detailed Info" was found, to track down pages that only
+    /// parse thanks to a fallback.
+    pub detailed_info_source: detailed_info::DetailedInfoSource,
+    /// Tasting notes from the tasting section of the page, if it has one
+    pub tasting_notes: Option<tasting::TastingNotes>,
+    /// Dishes the product goes with, from the food pairing section of the
+    /// page
+    pub food_pairings: Vec<String>,
+    /// The price before the discount when the product is on sale, from the
+    /// page's price box. The JSON-LD [`Offer`](linked_data::Offer) only has
+    /// the discounted price, and the sale's end as its
+    /// [`price_valid_until`](linked_data::Offer::price_valid_until).
+    pub regular_price: Option<money::Price>,
+    /// Whether the page was missing its JSON-LD [`Product`], which was filled
+    /// in from the catalog listing instead (see
+    /// [`fall_back_to_listing`](ExtractedProduct::fall_back_to_listing)).
+    pub partial: bool,
 }
 
 impl ExtractedProduct {
@@ -165,13 +89,41 @@ impl ExtractedProduct {
             .linked_data
             .iter()
             .find_map(|ld| match ld {
-                LinkedData::Product(product) => Some(product),
+                LinkedData::Product(product) => Some(product.as_ref()),
                 _ => None,
             })
             .ok_or_else(|| eyre!("missing product linked data"))?;
 
         Ok(ld_product)
     }
+
+    /// Same as [`get_ld_product`](ExtractedProduct::get_ld_product), allowing
+    /// the [`Product`] to be modified.
+    pub fn get_ld_product_mut(&mut self) -> Result<&mut Product> {
+        let ld_product = self
+            .linked_data
+            .iter_mut()
+            .find_map(|ld| match ld {
+                LinkedData::Product(product) => Some(product.as_mut()),
+                _ => None,
+            })
+            .ok_or_else(|| eyre!("missing product linked data"))?;
+
+        Ok(ld_product)
+    }
+
+    /// Uses `listing` (the product's entry in the catalog) as the JSON-LD
+    /// [`Product`] if the page didn't include one, in which case the product
+    /// is flagged as [`partial`](ExtractedProduct::partial).
+    pub fn fall_back_to_listing(&mut self, listing: impl FnOnce() -> Product) {
+        if self.get_ld_product().is_ok() {
+            return;
+        }
+
+        self.linked_data
+            .push(LinkedData::Product(Box::new(listing())));
+        self.partial = true;
+    }
 }
 
 /// One of the product's categories.
@@ -185,8 +137,10 @@ pub struct Category {
 }
 
 impl ExtractedProduct {
-    /// Converts a [`LinkedData::BreadcrumbList`] (if present) to [`Category`] entries.
-    pub fn extract_categories(&self) -> Result<Vec<Category>> {
+    /// Converts a [`LinkedData::BreadcrumbList`] (if present) to [`Category`] entries,
+    /// with [canonical](url::canonicalize) URLs, keeping only the
+    /// [categories](SiteProfile::is_category_url) of `site`.
+    pub fn extract_categories(&self, site: &SiteProfile) -> Result<Vec<Category>> {
         let ld_breadcrumbs = self
             .linked_data
             .iter()
@@ -202,10 +156,11 @@ impl ExtractedProduct {
             .filter_map(|li| {
                 // Breadcrumbs include the home page, products, and the product itself.
                 // We're only interested in product categories.
-                if li.item.id.starts_with("https://www.saq.com/en/products/") {
+                let url = url::canonicalize(&li.item.id);
+                if site.is_category_url(&url) {
                     Some(Category {
                         name: li.item.name.clone(),
-                        url: li.item.id.clone(),
+                        url,
                     })
                 } else {
                     None
@@ -217,62 +172,206 @@ impl ExtractedProduct {
     }
 }
 
-lazy_static! {
-    #[doc(hidden)]
-    static ref DETAILED_INFO_SELECTOR: Selector =
-        scraper::Selector::parse("#product-data-item-additional ul li [data-th]").unwrap();
+/// Traverses through the "Detailed Info" section of an SAQ product page, see
+/// [`SiteProfile::extract_detailed_info`].
+pub fn extract_detailed_info(document: &scraper::Html) -> Result<detailed_info::DetailedInfo> {
+    let (detailed_info, _) = SiteProfile::saq().extract_detailed_info(document)?;
+    Ok(detailed_info)
 }
 
-/// Traverses through the "Detailed Info" section of the product page to key-value
-/// pairs (i.e. "Designation of origin" -> "Mercurey") which are further processed
-/// into a [`DetailedInfo`](detailed_info::DetailedInfo) struct.
-fn extract_detailed_info(document: &scraper::Html) -> Result<detailed_info::DetailedInfo> {
-    let detailed_info_hash = document
-        .select(&DETAILED_INFO_SELECTOR)
-        .filter_map(|e| {
-            e.value().attr("data-th").map(|key| {
-                (
-                    key.to_owned(),
-                    e.text().collect::<String>().trim().to_owned(),
-                )
-            })
-        })
-        .collect::<HashMap<_, _>>();
+/// The data extracted from a page of the product catalog.
+#[derive(Debug, Serialize)]
+pub struct CatalogPage {
+    /// The page number highlighted in the pagination.
+    ///
+    /// saq.com's pagination wraps around rather than render an empty page, so
+    /// this differs from the requested page number past the last page.
+    pub current_page: u32,
+    /// The products listed on the page.
+    pub products: Vec<Product>,
+}
+
+/// Extracts the current page number and the JSON-LD [`Product`] entries from
+/// the HTML of an SAQ catalog page.
+pub fn parse_catalog_page(html: &str) -> Result<CatalogPage> {
+    SiteProfile::saq().parse_catalog_page(html)
+}
 
-    detailed_info::DetailedInfo::from_hash_map(detailed_info_hash)
+/// The error returned by [`parse_catalog_page`] when a page doesn't list
+/// products in any of the ways we know of.
+#[derive(Debug)]
+pub struct MissingCatalogProducts {
+    /// The [types](LinkedData::type_name) of the JSON-LD entries that were found instead.
+    pub linked_data_types: Vec<&'static str>,
 }
 
-impl Client {
-    /// Fetch and extract data from a product page
-    pub async fn product(&self, product: &Product) -> Result<ExtractedProduct> {
-        let product_url = &product.offers.url;
+impl fmt::Display for MissingCatalogProducts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "could not find products in catalog page linked data (found {:?})",
+            self.linked_data_types
+        )
+    }
+}
 
-        let span = info_span!("product", %product_url);
-        let span_guard = span.enter();
+impl std::error::Error for MissingCatalogProducts {}
 
-        info!("request");
-        let start = Instant::now();
+/// Finds the products listed in a catalog page's JSON-LD.
+///
+/// These are usually the elements of the `WebPage`'s `OfferCatalog`, but
+/// promotional listings have been seen using an `ItemList` instead, or
+/// top-level `Product` entries. Pages without any of these return a
+/// [`MissingCatalogProducts`] error.
+///
+/// `linked_data` is consumed so that products can be moved out of it rather
+/// than cloned.
+fn catalog_products(linked_data: Vec<LinkedData>) -> Result<Vec<Product>, MissingCatalogProducts> {
+    let linked_data_types = linked_data
+        .iter()
+        .map(LinkedData::type_name)
+        .collect::<Vec<_>>();
+    let mut top_level = vec![];
+
+    for ld in linked_data {
+        match ld {
+            LinkedData::WebPage(WebPage {
+                main_entity:
+                    Some(Entity::OfferCatalog(OfferCatalog {
+                        item_list_element, ..
+                    })),
+                ..
+            })
+            | LinkedData::WebPage(WebPage {
+                main_entity: Some(Entity::ItemList(ItemList { item_list_element })),
+                ..
+            }) => {
+                return Ok(item_list_element
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        ItemListElement::Product(product) => Some(*product),
+                        _ => None,
+                    })
+                    .collect());
+            }
+            LinkedData::Product(product) => top_level.push(*product),
+            _ => {}
+        }
+    }
+
+    if !top_level.is_empty() {
+        return Ok(top_level);
+    }
 
-        let res = self
-            .reqwest_client
-            .get(product_url)
-            .header("accept", "text/html")
-            .send()
-            .await?;
+    Err(MissingCatalogProducts { linked_data_types })
+}
 
-        info!(status = %res.status(), time = ?start.elapsed() , "response");
+/// Returns the URL of the English product page for the given `saq_code`, the
+/// inverse of [`sitemap::product_saq_code`].
+pub fn product_url(saq_code: &str) -> String {
+    format!("https://www.saq.com/en/{saq_code}")
+}
 
-        let body = res.text().await?;
-        let document = scraper::Html::parse_document(&body);
+/// Extracts the JSON-LD, "Detailed Info" and tasting data from the HTML of an
+/// SAQ product page.
+pub fn parse_product_page(html: &str) -> Result<ExtractedProduct> {
+    SiteProfile::saq().parse_product_page(html)
+}
 
-        let linked_data = extract_linked_data(&document)?;
-        let detailed_info = extract_detailed_info(&document)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_product_page() {
+        let extracted =
+            parse_product_page(include_str!("../../fixtures/product_wine.html")).unwrap();
+
+        let ld_product = extracted.get_ld_product().unwrap();
+        assert_eq!("13191791", ld_product.sku);
+        assert_eq!("13191791", extracted.detailed_info.saq_code);
+        assert_eq!(
+            3,
+            extracted
+                .detailed_info
+                .grape_varieties
+                .as_ref()
+                .unwrap()
+                .len()
+        );
+
+        let tasting_notes = extracted.tasting_notes.as_ref().unwrap();
+        assert_eq!(
+            Some("Aromatic and robust"),
+            tasting_notes.taste_tag.as_deref()
+        );
+        assert_eq!(
+            vec!["Black fruit", "Garrigue", "Spices"],
+            tasting_notes.aromas
+        );
+        assert_eq!(
+            vec!["Game", "Grilled red meat", "Hard cheeses"],
+            extracted.food_pairings
+        );
+
+        let beer = parse_product_page(include_str!("../../fixtures/product_beer.html")).unwrap();
+        assert!(beer.tasting_notes.is_none());
+        assert!(beer.food_pairings.is_empty());
+
+        let categories = extracted.extract_categories(SiteProfile::saq()).unwrap();
+        assert_eq!(
+            vec!["Wine", "Red wine"],
+            categories
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
 
-        drop(span_guard);
+    #[test]
+    fn test_parse_catalog_page() {
+        let page = parse_catalog_page(include_str!("../../fixtures/catalog_page.html")).unwrap();
+
+        assert_eq!(2, page.current_page);
+        assert_eq!(
+            vec!["13191791", "12345678"],
+            page.products
+                .iter()
+                .map(|p| p.sku.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "https://www.saq.com/en/13191791",
+            page.products[0].offers.url
+        );
+    }
 
-        Ok(ExtractedProduct {
-            linked_data,
-            detailed_info,
-        })
+    #[test]
+    fn test_catalog_products_fallbacks() {
+        let parse = |json: &str| -> Vec<LinkedData> { serde_json::from_str(json).unwrap() };
+        let product = r#"{"@type": "Product", "name": "Péché Mortel", "description": "",
+            "image": "", "sku": "12345678", "offers": {"@type": "Offer",
+            "availability": "http://schema.org/InStock", "itemCondition": "NewCondition",
+            "price": 17.25, "priceCurrency": "CAD", "url": "https://www.saq.com/en/12345678"}}"#;
+
+        let item_list = parse(&format!(
+            r#"[{{"@type": "WebPage", "mainEntity": {{"@type": "ItemList",
+            "itemListElement": [{product}]}}}}]"#
+        ));
+        assert_eq!(1, catalog_products(item_list).unwrap().len());
+
+        let top_level = parse(&format!(r#"[{{"@type": "WebSite"}}, {product}]"#));
+        assert_eq!(1, catalog_products(top_level).unwrap().len());
+
+        let missing = parse(r#"[{"@type": "WebSite"}, {"@type": "WebPage"}, {"@type": "Event"}]"#);
+        let err = catalog_products(missing).unwrap_err();
+        assert_eq!(vec!["WebSite", "WebPage", "Other"], err.linked_data_types);
+        assert!(parse_catalog_page(
+            r#"<div class="pages"><ul class="pages-items"><li class="current"><strong class="page">
+            <span>Page</span><span>1</span></strong></li></ul></div>"#
+        )
+        .unwrap_err()
+        .downcast_ref::<MissingCatalogProducts>()
+        .is_some());
     }
 }
@@ -0,0 +1,165 @@
+//! Exact representation of prices, which are listed in dollars and cents.
+//!
+//! Prices are stored as an integer number of cents so that they can be
+//! compared for equality (i.e. to detect price changes) without the drift
+//! that comes with floating point arithmetic.
+
+use color_eyre::eyre::{eyre, Result};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// An amount of money in cents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price {
+    /// The total number of cents.
+    cents: i64,
+}
+
+impl Price {
+    /// Creates a price from a number of `cents`.
+    pub fn from_cents(cents: i64) -> Price {
+        Price { cents }
+    }
+
+    /// The total number of cents.
+    pub fn cents(&self) -> i64 {
+        self.cents
+    }
+}
+
+/// Parses a decimal amount (i.e. `"17.25"`) into a [`Price`].
+///
+/// Amounts with more than two decimal places are rejected rather than
+/// rounded, unless the extra digits are all zeroes.
+pub fn parse_price(text: &str) -> Result<Price> {
+    let text = text.trim();
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (dollars, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if dollars.is_empty() || !is_digits(dollars) || !is_digits(fraction) {
+        return Err(eyre!("{:?} is not a valid price", text));
+    }
+
+    let cents_part = fraction.get(..2).unwrap_or(fraction);
+    if fraction.len() > 2 && fraction[2..].chars().any(|c| c != '0') {
+        return Err(eyre!("{:?} has fractional cents", text));
+    }
+
+    let dollars = dollars
+        .parse::<i64>()
+        .map_err(|_| eyre!("{:?} is out of range", text))?;
+    let cents = format!("{cents_part:0<2}").parse::<i64>()?;
+
+    let total = dollars
+        .checked_mul(100)
+        .and_then(|d| d.checked_add(cents))
+        .ok_or_else(|| eyre!("{:?} is out of range", text))?;
+
+    Ok(Price::from_cents(if negative { -total } else { total }))
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, cents / 100, cents % 100)
+    }
+}
+
+/// Accepts prices given either as JSON numbers or strings.
+struct PriceVisitor;
+
+impl<'de> Visitor<'de> for PriceVisitor {
+    type Value = Price;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a price as a number or a string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Price, E> {
+        v.checked_mul(100)
+            .map(Price::from_cents)
+            .ok_or_else(|| E::custom(format!("{v} is out of range")))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Price, E> {
+        i64::try_from(v)
+            .map_err(|_| E::custom(format!("{v} is out of range")))
+            .and_then(|v| self.visit_i64(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Price, E> {
+        // Formatting yields the shortest representation that round-trips,
+        // i.e. the digits as they appeared in the original JSON.
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Price, E> {
+        parse_price(v).map_err(|err| E::custom(format!("{err}")))
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Price, D::Error> {
+        deserializer.deserialize_any(PriceVisitor)
+    }
+}
+
+/// Prices are written the way they're [displayed](fmt::Display) (i.e.
+/// `"17.25"`), which they can be parsed back from.
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(1725, parse_price("17.25").unwrap().cents());
+        assert_eq!(1720, parse_price("17.2").unwrap().cents());
+        assert_eq!(1700, parse_price("17").unwrap().cents());
+        assert_eq!(1700, parse_price("17.").unwrap().cents());
+        assert_eq!(1725, parse_price("17.2500").unwrap().cents());
+        assert_eq!(-5, parse_price("-0.05").unwrap().cents());
+
+        assert!(parse_price("17.255").is_err());
+        assert!(parse_price(".25").is_err());
+        assert!(parse_price("$17.25").is_err());
+        assert!(parse_price("").is_err());
+        assert!(parse_price("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("17.25", Price::from_cents(1725).to_string());
+        assert_eq!("0.05", Price::from_cents(5).to_string());
+        assert_eq!("-1.50", Price::from_cents(-150).to_string());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let prices: Vec<Price> = serde_json::from_str(r#"[29.95, 0.1, 17, "4.99"]"#).unwrap();
+        assert_eq!(
+            vec![2995, 10, 1700, 499],
+            prices.iter().map(Price::cents).collect::<Vec<_>>()
+        );
+
+        assert!(serde_json::from_str::<Price>("29.999").is_err());
+
+        let serialized = serde_json::to_string(&Price::from_cents(2995)).unwrap();
+        assert_eq!(r#""29.95""#, serialized);
+        assert_eq!(
+            2995,
+            serde_json::from_str::<Price>(&serialized).unwrap().cents()
+        );
+    }
+}
@@ -0,0 +1,683 @@
+//! Describing storefronts built like the SAQ website, so that the parsers can
+//! be pointed at them.
+//!
+//! Other liquor boards' Magento storefronts publish the same JSON-LD and
+//! "Detailed Info" markup, but differ in where things live: the host and URL
+//! paths, the selectors for the "Detailed Info", tasting and food pairing
+//! sections, the regular price of products on sale and the pagination,
+//! and the language pages should be requested in (see
+//! [`check_language`](SiteProfile::check_language)). A [`SiteProfile`] captures
+//! these differences. [`SiteProfile::saq`] is the built-in default, and others
+//! can be [loaded](SiteProfile::load) from a JSON file (see
+//! `RANSAQ_SITE_PROFILE`) in which any setting left out falls back to the SAQ
+//! one:
+//!
+//! ```json
+//! {
+//!   "name": "Example",
+//!   "base_url": "https://shop.example.com",
+//!   "catalog_path": "/en/catalog",
+//!   "product_path": "/en/p/{code}",
+//!   "category_path": "/en/catalog/",
+//!   "accept_language": "en-CA",
+//!   "locale_params": {"lang": "en"}
+//! }
+//! ```
+//!
+//! Sitemaps and [canonical URLs](super::url) remain specific to the SAQ
+//! website.
+
+use super::detailed_info::{DetailedInfo, DetailedInfoSource};
+use super::money::{self, Price};
+use super::tasting::{self, TastingNotes};
+use super::{catalog_products, extract_linked_data, CatalogPage, ExtractedProduct};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use lazy_static::lazy_static;
+use scraper::Selector;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/// Placeholder for the product code in [`SiteProfile::product_path`].
+const CODE_PLACEHOLDER: &str = "{code}";
+
+/// The settings of a [`SiteProfile`] as written in a profile file, before its
+/// selectors are parsed.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProfileSettings {
+    /// See [`SiteProfile::name`].
+    name: String,
+    /// See [`SiteProfile::base_url`].
+    base_url: String,
+    /// See [`SiteProfile::catalog_path`].
+    catalog_path: String,
+    /// See [`SiteProfile::product_path`].
+    product_path: String,
+    /// See [`SiteProfile::category_path`].
+    category_path: String,
+    /// See [`SiteProfile::accept_language`].
+    accept_language: Option<String>,
+    /// See [`SiteProfile::locale_params`].
+    locale_params: BTreeMap<String, String>,
+    /// See [`SiteProfile::detailed_info_selector`].
+    detailed_info_selector: String,
+    /// See [`SiteProfile::detailed_info_fallback_selectors`].
+    detailed_info_fallback_selectors: Vec<String>,
+    /// See [`SiteProfile::tasting_selector`].
+    tasting_selector: String,
+    /// See [`SiteProfile::food_pairing_selector`].
+    food_pairing_selector: String,
+    /// See [`SiteProfile::regular_price_selector`].
+    regular_price_selector: String,
+    /// See [`SiteProfile::current_page_selector`].
+    current_page_selector: String,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        ProfileSettings {
+            name: "SAQ".to_string(),
+            base_url: "https://www.saq.com".to_string(),
+            catalog_path: "/en/products".to_string(),
+            product_path: "/en/{code}".to_string(),
+            category_path: "/en/products/".to_string(),
+            accept_language: Some("en-CA".to_string()),
+            locale_params: BTreeMap::from([("___store".to_string(), "en".to_string())]),
+            detailed_info_selector: "#product-data-item-additional ul li [data-th]".to_string(),
+            detailed_info_fallback_selectors: vec![
+                "#product-attribute-specs-table [data-th]".to_string(),
+                ".additional-attributes [data-th]".to_string(),
+            ],
+            tasting_selector: "#product-data-item-tasting [data-th]".to_string(),
+            food_pairing_selector: "#product-data-item-pairing li".to_string(),
+            regular_price_selector: ".product-info-price .old-price [data-price-amount]"
+                .to_string(),
+            current_page_selector: ".pages .pages-items .current .page span:nth-child(2)"
+                .to_string(),
+        }
+    }
+}
+
+/// Parses a CSS selector from a profile setting.
+fn parse_selector(setting: &str, selector: &str) -> Result<Selector> {
+    Selector::parse(selector).map_err(|err| eyre!("invalid {} {:?}: {:?}", setting, selector, err))
+}
+
+impl TryFrom<ProfileSettings> for SiteProfile {
+    type Error = color_eyre::Report;
+
+    fn try_from(settings: ProfileSettings) -> Result<Self> {
+        if !settings.product_path.contains(CODE_PLACEHOLDER) {
+            return Err(eyre!(
+                "product_path {:?} must contain {}",
+                settings.product_path,
+                CODE_PLACEHOLDER
+            ));
+        }
+
+        Ok(SiteProfile {
+            detailed_info_selector: parse_selector(
+                "detailed_info_selector",
+                &settings.detailed_info_selector,
+            )?,
+            detailed_info_fallback_selectors: settings
+                .detailed_info_fallback_selectors
+                .iter()
+                .map(|selector| parse_selector("detailed_info_fallback_selectors", selector))
+                .collect::<Result<_>>()?,
+            tasting_selector: parse_selector("tasting_selector", &settings.tasting_selector)?,
+            food_pairing_selector: parse_selector(
+                "food_pairing_selector",
+                &settings.food_pairing_selector,
+            )?,
+            regular_price_selector: parse_selector(
+                "regular_price_selector",
+                &settings.regular_price_selector,
+            )?,
+            current_page_selector: parse_selector(
+                "current_page_selector",
+                &settings.current_page_selector,
+            )?,
+            name: settings.name,
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            catalog_path: settings.catalog_path,
+            product_path: settings.product_path,
+            category_path: settings.category_path,
+            accept_language: settings.accept_language,
+            locale_params: settings.locale_params,
+        })
+    }
+}
+
+/// Where a storefront's pages are and how to find things in them, see the
+/// [module docs](self).
+#[derive(Debug, Clone)]
+pub struct SiteProfile {
+    /// Used in logs (i.e. `SAQ`).
+    pub name: String,
+    /// The storefront's scheme and host, without a trailing slash (i.e.
+    /// `https://www.saq.com`).
+    pub base_url: String,
+    /// The path of the product catalog (i.e. `/en/products`), whose pages are
+    /// requested with a `p` query parameter.
+    pub catalog_path: String,
+    /// The path of a product page, with `{code}` standing in for the product
+    /// code (i.e. `/en/{code}`).
+    pub product_path: String,
+    /// The path under which category listings live (i.e. `/en/products/`),
+    /// used to tell categories apart from the other breadcrumbs.
+    pub category_path: String,
+    /// The `Accept-Language` header sent with every request (i.e. `en-CA`),
+    /// whose primary language pages are expected to be in. `None` sends no
+    /// header and accepts pages in any language.
+    pub accept_language: Option<String>,
+    /// Query parameters explicitly asking for the expected language, added
+    /// when a page comes back in another one (i.e. Magento's `___store`).
+    pub locale_params: BTreeMap<String, String>,
+    /// Matches the entries of a product page's "Detailed Info" section, whose
+    /// `data-th` attribute holds the entry's name.
+    pub detailed_info_selector: Selector,
+    /// Tried in order when
+    /// [`detailed_info_selector`](SiteProfile::detailed_info_selector) finds
+    /// nothing (i.e. on redesigned pages), matching the same kind of
+    /// elements.
+    pub detailed_info_fallback_selectors: Vec<Selector>,
+    /// Matches the entries of a product page's tasting section, whose
+    /// `data-th` attribute holds the entry's name.
+    pub tasting_selector: Selector,
+    /// Matches each dish of a product page's food pairing section.
+    pub food_pairing_selector: Selector,
+    /// Matches the crossed out regular price of a product on sale, whose
+    /// `data-price-amount` attribute holds the amount (i.e. `19.75`).
+    pub regular_price_selector: Selector,
+    /// Matches the current page number in a catalog page's pagination.
+    pub current_page_selector: Selector,
+}
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref MAGE_INIT_SELECTOR: Selector = Selector::parse("[data-mage-init]").unwrap();
+}
+
+/// Collects the `data-th` attributes of the elements matched by `selector` and
+/// their text into "Detailed Info" key-value pairs.
+fn select_detailed_info(document: &scraper::Html, selector: &Selector) -> HashMap<String, String> {
+    document
+        .select(selector)
+        .filter_map(|e| {
+            e.value().attr("data-th").map(|key| {
+                (
+                    key.to_owned(),
+                    e.text().collect::<String>().trim().to_owned(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// The text of a JSON scalar, `None` for anything else.
+fn json_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Finds "Detailed Info" key-value pairs in a `data-mage-init` blob: either an
+/// object keyed by labels (i.e. `{"SAQ code": "13191791", ...}`) or a list of
+/// `{"label": ..., "value": ...}` objects, including an `SAQ code` either way.
+/// The first match found in a depth-first search wins.
+fn mage_init_detailed_info(value: &Value) -> Option<HashMap<String, String>> {
+    match value {
+        Value::Object(object) if object.contains_key("SAQ code") => Some(
+            object
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), json_text(value)?)))
+                .collect(),
+        ),
+        Value::Array(items) => {
+            let pairs = items
+                .iter()
+                .map(|item| {
+                    Some((
+                        json_text(item.get("label")?)?,
+                        json_text(item.get("value")?)?,
+                    ))
+                })
+                .collect::<Option<HashMap<_, _>>>();
+
+            match pairs {
+                Some(pairs) if pairs.contains_key("SAQ code") => Some(pairs),
+                _ => items.iter().find_map(mage_init_detailed_info),
+            }
+        }
+        Value::Object(object) => object.values().find_map(mage_init_detailed_info),
+        _ => None,
+    }
+}
+
+/// Finds the language a page declares on its `<html>` tag (i.e. `en` for
+/// `<html lang="en">`), without parsing the whole document.
+pub fn document_language(html: &str) -> Option<&str> {
+    let tag = &html[find_ignoring_case(html, "<html")?..];
+    let tag = &tag[..tag.find('>')?];
+
+    let value = &tag[find_ignoring_case(tag, " lang=")? + " lang=".len()..];
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+
+    Some(&value[..value.find(quote)?])
+}
+
+/// The position of `needle` (which must be ASCII) in `haystack`, ignoring
+/// ASCII case.
+fn find_ignoring_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// The error returned by [`SiteProfile::check_language`] for a page in the
+/// wrong language.
+#[derive(Debug)]
+pub struct WrongLanguage {
+    /// The language pages are expected to be in (i.e. `en`).
+    pub expected: String,
+    /// The language the page declares (i.e. `fr`).
+    pub found: String,
+}
+
+impl fmt::Display for WrongLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "page is in {:?} rather than {:?}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for WrongLanguage {}
+
+/// The primary subtag of a language tag, lowercased (i.e. `en` for `en-CA`).
+fn primary_language(tag: &str) -> String {
+    tag.split(['-', '_', ',', ';'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref SAQ: SiteProfile = SiteProfile::try_from(ProfileSettings::default()).unwrap();
+}
+
+impl Default for SiteProfile {
+    fn default() -> Self {
+        SiteProfile::saq().clone()
+    }
+}
+
+impl SiteProfile {
+    /// The SAQ website, the built-in default.
+    pub fn saq() -> &'static SiteProfile {
+        &SAQ
+    }
+
+    /// Parses a profile from JSON, see the [module docs](self).
+    pub fn from_json(json: &str) -> Result<SiteProfile> {
+        let settings: ProfileSettings = serde_json::from_str(json)?;
+        SiteProfile::try_from(settings)
+    }
+
+    /// Reads a profile from the JSON file at `path`.
+    pub fn load(path: &Path) -> Result<SiteProfile> {
+        let json =
+            std::fs::read_to_string(path).wrap_err_with(|| format!("failed to read {path:?}"))?;
+        SiteProfile::from_json(&json).wrap_err_with(|| format!("failed to parse {path:?}"))
+    }
+
+    /// The URL of the product catalog.
+    pub fn catalog_url(&self) -> String {
+        format!("{}{}", self.base_url, self.catalog_path)
+    }
+
+    /// The URL of the listing of `category`, given either as a path under
+    /// [`category_path`](SiteProfile::category_path) (i.e. `wine/red-wine`)
+    /// or as a full category URL. Listings are paginated like the catalog.
+    pub fn category_url(&self, category: &str) -> Result<String> {
+        let category = category.trim();
+        if self.is_category_url(category) {
+            return Ok(category.trim_end_matches('/').to_string());
+        }
+        if category.contains("://") {
+            return Err(eyre!(
+                "{} isn't a category listing of the {} site",
+                category,
+                self.name
+            ));
+        }
+
+        let path = category.trim_matches('/');
+        if path.is_empty() {
+            return Err(eyre!("the category can't be empty"));
+        }
+
+        Ok(format!(
+            "{}{}/{}",
+            self.base_url,
+            self.category_path.trim_end_matches('/'),
+            path
+        ))
+    }
+
+    /// The URL of the page of the product with the given code.
+    pub fn product_url(&self, code: &str) -> String {
+        format!(
+            "{}{}",
+            self.base_url,
+            self.product_path.replace(CODE_PLACEHOLDER, code)
+        )
+    }
+
+    /// Checks that `html` is in the language of the
+    /// [`accept_language`](SiteProfile::accept_language) header, returning a
+    /// [`WrongLanguage`] error if it isn't. Pages which don't declare a
+    /// language pass.
+    pub fn check_language(&self, html: &str) -> Result<(), WrongLanguage> {
+        let expected = match &self.accept_language {
+            Some(accept_language) => primary_language(accept_language),
+            None => return Ok(()),
+        };
+
+        match document_language(html).map(primary_language) {
+            Some(found) if found != expected => Err(WrongLanguage { expected, found }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `url` is a category listing.
+    pub fn is_category_url(&self, url: &str) -> bool {
+        url.strip_prefix(&self.base_url)
+            .map(|path| path.starts_with(&self.category_path))
+            .unwrap_or(false)
+    }
+
+    /// Traverses through the "Detailed Info" section of the product page to
+    /// key-value pairs (i.e. "Designation of origin" -> "Mercurey") which are
+    /// further processed into a [`DetailedInfo`] struct.
+    ///
+    /// When the [primary selector](SiteProfile::detailed_info_selector) finds
+    /// nothing, the [fallback ones](SiteProfile::detailed_info_fallback_selectors)
+    /// are tried in order, then the page's `data-mage-init` JSON blobs. Where
+    /// the data was found is returned alongside it.
+    pub fn extract_detailed_info(
+        &self,
+        document: &scraper::Html,
+    ) -> Result<(DetailedInfo, DetailedInfoSource)> {
+        let primary = select_detailed_info(document, &self.detailed_info_selector);
+
+        let (detailed_info_hash, source) = if !primary.is_empty() {
+            (primary, DetailedInfoSource::Selector)
+        } else if let Some(fallback) = self
+            .detailed_info_fallback_selectors
+            .iter()
+            .map(|selector| select_detailed_info(document, selector))
+            .find(|fallback| !fallback.is_empty())
+        {
+            (fallback, DetailedInfoSource::FallbackSelector)
+        } else if let Some(mage_init) = document
+            .select(&MAGE_INIT_SELECTOR)
+            .filter_map(|e| serde_json::from_str(e.value().attr("data-mage-init")?).ok())
+            .find_map(|blob: Value| mage_init_detailed_info(&blob))
+        {
+            (mage_init, DetailedInfoSource::MageInit)
+        } else {
+            (primary, DetailedInfoSource::Selector)
+        };
+
+        Ok((DetailedInfo::from_hash_map(detailed_info_hash)?, source))
+    }
+
+    /// Extracts the tasting notes and food pairings of the product page, if
+    /// it has them. Unlike the "Detailed Info", neither is required.
+    pub fn extract_tasting(&self, document: &scraper::Html) -> (Option<TastingNotes>, Vec<String>) {
+        let tasting_notes =
+            TastingNotes::from_hash_map(select_detailed_info(document, &self.tasting_selector));
+
+        let food_pairings = tasting::parse_food_pairings(
+            document
+                .select(&self.food_pairing_selector)
+                .map(|e| e.text().collect::<String>()),
+        );
+
+        (tasting_notes, food_pairings)
+    }
+
+    /// Extracts the regular price of the product page if the product is on
+    /// sale, the JSON-LD only having the discounted one. Amounts that don't
+    /// parse are ignored rather than failing the whole page.
+    pub fn extract_regular_price(&self, document: &scraper::Html) -> Option<Price> {
+        document
+            .select(&self.regular_price_selector)
+            .filter_map(|e| e.value().attr("data-price-amount"))
+            .find_map(|amount| money::parse_price(amount.trim()).ok())
+    }
+
+    /// Extracts the current page number and the JSON-LD
+    /// [`Product`](super::linked_data::Product) entries from the HTML of a
+    /// catalog page.
+    pub fn parse_catalog_page(&self, html: &str) -> Result<CatalogPage> {
+        let document = scraper::Html::parse_document(html);
+
+        let current_page = document
+            .select(&self.current_page_selector)
+            .map(|e| {
+                let page_number = e.text().collect::<String>();
+                page_number.parse::<u32>().wrap_err_with(|| {
+                    format!("failed to convert page number {page_number:?} to integer")
+                })
+            })
+            .next()
+            .ok_or_else(|| eyre!("could not find pagination on page"))??;
+
+        let linked_data = extract_linked_data(&document)?;
+        let products = catalog_products(linked_data)?;
+
+        Ok(CatalogPage {
+            current_page,
+            products,
+        })
+    }
+
+    /// Extracts the JSON-LD, "Detailed Info", tasting data and regular price
+    /// from the HTML of a product page.
+    pub fn parse_product_page(&self, html: &str) -> Result<ExtractedProduct> {
+        let document = scraper::Html::parse_document(html);
+
+        let linked_data = extract_linked_data(&document)?;
+        let (detailed_info, detailed_info_source) = self.extract_detailed_info(&document)?;
+        let (tasting_notes, food_pairings) = self.extract_tasting(&document);
+        let regular_price = self.extract_regular_price(&document);
+
+        Ok(ExtractedProduct {
+            linked_data,
+            detailed_info,
+            detailed_info_source,
+            tasting_notes,
+            food_pairings,
+            regular_price,
+            partial: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let profile = SiteProfile::from_json(
+            r#"{"name": "Example", "base_url": "https://shop.example.com/",
+            "catalog_path": "/en/catalog", "product_path": "/en/p/{code}",
+            "category_path": "/en/catalog/", "accept_language": "en-CA",
+            "detailed_info_selector": ".specs [data-th]",
+            "current_page_selector": ".pager .active"}"#,
+        )
+        .unwrap();
+
+        assert_eq!("https://shop.example.com/en/catalog", profile.catalog_url());
+        assert_eq!(
+            "https://shop.example.com/en/p/123",
+            profile.product_url("123")
+        );
+        assert!(profile.is_category_url("https://shop.example.com/en/catalog/wine"));
+        assert_eq!(
+            "https://shop.example.com/en/catalog/wine/red-wine",
+            profile.category_url("/wine/red-wine/").unwrap()
+        );
+        assert_eq!(
+            "https://shop.example.com/en/catalog/wine",
+            profile
+                .category_url("https://shop.example.com/en/catalog/wine/")
+                .unwrap()
+        );
+        assert!(profile
+            .category_url("https://www.saq.com/en/products/wine")
+            .is_err());
+        assert!(profile.category_url(" / ").is_err());
+        assert!(!profile.is_category_url("https://shop.example.com/en/p/123"));
+        assert!(!profile.is_category_url("https://www.saq.com/en/products/wine"));
+
+        let page = profile
+            .parse_catalog_page(
+                r#"<ul class="pager"><li class="active">3</li></ul>
+                <script type="application/ld+json">{"@type": "WebPage"}</script>"#,
+            )
+            .unwrap_err();
+        assert!(page.to_string().contains("could not find products"));
+
+        let product = profile
+            .parse_product_page(
+                r#"<ul class="specs"><li data-th="SAQ code">123</li>
+                <li data-th="Country">Canada</li></ul>"#,
+            )
+            .unwrap();
+        assert_eq!("123", product.detailed_info.saq_code);
+
+        // Settings left out fall back to the SAQ ones
+        let profile = SiteProfile::from_json(r#"{"base_url": "http://127.0.0.1:1234"}"#).unwrap();
+        assert_eq!("http://127.0.0.1:1234/en/products", profile.catalog_url());
+
+        assert_eq!(Some("en-CA"), profile.accept_language.as_deref());
+
+        assert!(SiteProfile::from_json(r#"{"product_path": "/en/"}"#).is_err());
+        assert!(SiteProfile::from_json(r#"{"current_page_selector": "[["}"#).is_err());
+        assert!(SiteProfile::from_json(r#"{"catalog": "/en/products"}"#).is_err());
+    }
+
+    #[test]
+    fn test_check_language() {
+        assert_eq!(
+            Some("en"),
+            document_language(include_str!("../../fixtures/product_wine.html"))
+        );
+        assert_eq!(
+            Some("fr-CA"),
+            document_language("<!doctype html>\n<HTML class='no-js' LANG='fr-CA'><body>")
+        );
+        assert_eq!(None, document_language("<html><body lang=\"fr\">"));
+        assert_eq!(None, document_language("<p>no html tag</p>"));
+
+        let saq = SiteProfile::saq();
+        assert!(saq.check_language(r#"<html lang="en">"#).is_ok());
+        assert!(saq.check_language(r#"<html lang="EN-us">"#).is_ok());
+        assert!(saq.check_language("<html>").is_ok());
+        let err = saq.check_language(r#"<html lang="fr">"#).unwrap_err();
+        assert_eq!(r#"page is in "fr" rather than "en""#, err.to_string());
+
+        let any = SiteProfile::from_json(r#"{"accept_language": null}"#).unwrap();
+        assert!(any.check_language(r#"<html lang="fr">"#).is_ok());
+    }
+
+    #[test]
+    fn test_detailed_info_fallbacks() {
+        let saq = SiteProfile::saq();
+        let extract = |html: &str| {
+            saq.extract_detailed_info(&scraper::Html::parse_document(html))
+                .map(|(info, source)| (info.saq_code, info.country, source))
+        };
+
+        let wine = include_str!("../../fixtures/product_wine.html");
+        assert_eq!(DetailedInfoSource::Selector, extract(wine).unwrap().2);
+
+        assert_eq!(
+            (
+                "123".to_string(),
+                Some("France".to_string()),
+                DetailedInfoSource::FallbackSelector
+            ),
+            extract(
+                r#"<table id="product-attribute-specs-table"><tr>
+                <td data-th="SAQ code"> 123 </td><td data-th="Country">France</td>
+                </tr></table>"#
+            )
+            .unwrap()
+        );
+
+        // Either shape of data-mage-init blob, skipping unrelated ones
+        for blob in [
+            r#"{"productAttributes": {"SAQ code": 123, "Country": "France"}}"#,
+            r#"{"specs": {"rows": [{"label": "SAQ code", "value": "123"},
+                {"label": "Country", "value": "France"}]}}"#,
+        ] {
+            let html = format!(
+                r#"<div data-mage-init='{{"menu": {{"delay": 300}}}}'></div>
+                <div data-mage-init='{blob}'></div><div data-mage-init='not json'></div>"#
+            );
+            assert_eq!(
+                (
+                    "123".to_string(),
+                    Some("France".to_string()),
+                    DetailedInfoSource::MageInit
+                ),
+                extract(&html).unwrap()
+            );
+        }
+
+        assert!(extract(r#"<div data-mage-init='{"menu": {}}'></div>"#).is_err());
+    }
+
+    #[test]
+    fn test_extract_regular_price() {
+        let saq = SiteProfile::saq();
+        let extract = |html: &str| {
+            saq.extract_regular_price(&scraper::Html::parse_document(html))
+                .map(|price| price.cents())
+        };
+
+        let beer = include_str!("../../fixtures/product_beer.html");
+        assert_eq!(Some(1975), extract(beer));
+        assert_eq!(
+            None,
+            extract(include_str!("../../fixtures/product_wine.html"))
+        );
+
+        // Amounts that don't parse are skipped
+        assert_eq!(
+            Some(2450),
+            extract(
+                r#"<div class="product-info-price"><span class="old-price">
+                <span data-price-amount="24,50"></span><span data-price-amount=" 24.50"></span>
+                </span></div>"#
+            )
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Limiting the rate at which a [`Client`](super::Client) sends requests, so
+//! that crawls stay polite however many workers are fetching pages.
+//!
+//! This is a [token bucket](https://en.wikipedia.org/wiki/Token_bucket): it
+//! refills at the configured number of requests per second and holds up to a
+//! second's worth of them, so short bursts are allowed but the average rate
+//! never exceeds the limit.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Tokens available as of a given time.
+#[derive(Debug)]
+struct Bucket {
+    /// Number of requests that can be sent right away.
+    tokens: f64,
+    /// When `tokens` was last brought up to date.
+    updated: Instant,
+}
+
+/// Shared between the clones of a [`Client`](super::Client), see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Requests allowed per second.
+    rate: f64,
+    /// Maximum number of tokens the bucket holds.
+    capacity: f64,
+    /// The bucket's current state.
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate` requests per second, which must be
+    /// positive. The bucket starts full.
+    pub fn new(rate: f64) -> RateLimiter {
+        let capacity = rate.max(1.0);
+
+        RateLimiter {
+            rate,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token as of `now` if one is available, otherwise returns how
+    /// long until there will be.
+    fn try_take(&self, now: Instant) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_take(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_take() {
+        let limiter = RateLimiter::new(2.0);
+        let start = limiter.bucket.lock().unwrap().updated;
+
+        // A second's worth of requests can go out right away
+        assert_eq!(None, limiter.try_take(start));
+        assert_eq!(None, limiter.try_take(start));
+        assert_eq!(Some(Duration::from_millis(500)), limiter.try_take(start));
+
+        // Then one every half second
+        let later = start + Duration::from_millis(500);
+        assert_eq!(None, limiter.try_take(later));
+        assert_eq!(Some(Duration::from_millis(500)), limiter.try_take(later));
+
+        // The bucket doesn't fill up past its capacity
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!(None, limiter.try_take(much_later));
+        assert_eq!(None, limiter.try_take(much_later));
+        assert!(limiter.try_take(much_later).is_some());
+
+        // Slower rates still allow a single request at a time
+        let slow = RateLimiter::new(0.5);
+        let start = slow.bucket.lock().unwrap().updated;
+        assert_eq!(None, slow.try_take(start));
+        assert_eq!(Some(Duration::from_secs(2)), slow.try_take(start));
+    }
+}
@@ -0,0 +1,91 @@
+//! Retrying requests to the SAQ website which failed for transient reasons
+//! (server errors, rate limiting, timeouts and connection errors) with
+//! exponential backoff, so that a single hiccup doesn't fail a whole page.
+//!
+//! Delays use "equal jitter": half of the exponential delay is always waited,
+//! and the other half is random so that concurrent workers don't all retry at
+//! the same time.
+
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How a [`Client`](super::Client) retries failed requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times a request is sent before giving up, including the
+    /// first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each subsequent one.
+    pub base_delay: Duration,
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the given failed `attempt` (starting at `1`),
+    /// with `jitter` between `0.0` and `1.0` picking where in the random half
+    /// of the delay to land.
+    pub fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        exponential / 2 + exponential.mul_f64(jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Whether a response with this status is worth retrying.
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether a request which failed without a response is worth retrying.
+pub fn is_transient_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || (err.is_request() && !err.is_builder())
+}
+
+/// A random number between `0.0` and `1.0`, good enough to spread retries.
+pub fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(Duration::from_millis(250), policy.delay(1, 0.0));
+        assert_eq!(Duration::from_millis(500), policy.delay(1, 1.0));
+        assert_eq!(Duration::from_millis(1500), policy.delay(3, 0.5));
+        assert_eq!(Duration::from_secs(30), policy.delay(20, 1.0));
+        assert_eq!(Duration::from_secs(30), policy.delay(u32::MAX, 1.0));
+
+        assert!((0.0..1.0).contains(&jitter()));
+    }
+
+    #[test]
+    fn test_is_transient_status() {
+        assert!(is_transient_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::OK));
+    }
+}
@@ -0,0 +1,157 @@
+//! Product discovery through the SAQ's XML sitemaps, independently of the
+//! paginated catalog used by [`Client::page`].
+
+#[cfg(feature = "crawler")]
+use super::Client;
+#[cfg(feature = "crawler")]
+use color_eyre::eyre::Result;
+use lazy_static::lazy_static;
+use scraper::Selector;
+#[cfg(feature = "crawler")]
+use tracing::info;
+use url::Url;
+
+/// The root sitemap, which may either list pages directly or link to other sitemaps.
+#[cfg(feature = "crawler")]
+const SITEMAP_URL: &str = "https://www.saq.com/sitemap.xml";
+
+/// Upper bound on the number of sitemaps fetched, in case they link to each other.
+#[cfg(feature = "crawler")]
+const MAX_SITEMAPS: usize = 100;
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref SITEMAP_LOC_SELECTOR: Selector = Selector::parse("sitemap > loc").unwrap();
+    #[doc(hidden)]
+    static ref URL_LOC_SELECTOR: Selector = Selector::parse("url > loc").unwrap();
+}
+
+/// The contents of a single sitemap file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Sitemap {
+    /// Links to other sitemaps (when this is a sitemap index).
+    pub sitemaps: Vec<String>,
+    /// Page URLs.
+    pub urls: Vec<String>,
+}
+
+/// Extracts the `<loc>` entries of a [sitemap](https://www.sitemaps.org/protocol.html).
+pub fn parse_sitemap(xml: &str) -> Sitemap {
+    let document = scraper::Html::parse_document(xml);
+    let locs = |selector: &Selector| {
+        document
+            .select(selector)
+            .map(|e| e.text().collect::<String>().trim().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    Sitemap {
+        sitemaps: locs(&SITEMAP_LOC_SELECTOR),
+        urls: locs(&URL_LOC_SELECTOR),
+    }
+}
+
+/// Returns the SAQ code of the product at `url`, if it is an English product
+/// page (i.e. `https://www.saq.com/en/13191791`).
+pub fn product_saq_code(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let mut segments = url.path_segments()?;
+
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("en"), Some(code), None)
+            if !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            Some(code.to_owned())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(feature = "crawler")]
+impl Client {
+    /// Walks the sitemaps starting from [`SITEMAP_URL`] and returns the URLs
+    /// of all English product pages.
+    pub async fn sitemap_product_urls(&self) -> Result<Vec<String>> {
+        let mut pending = vec![SITEMAP_URL.to_owned()];
+        let mut fetched = 0;
+        let mut urls = vec![];
+
+        while let Some(sitemap_url) = pending.pop() {
+            if fetched == MAX_SITEMAPS {
+                break;
+            }
+            fetched += 1;
+
+            info!(%sitemap_url, "fetching sitemap");
+
+            let url = self.checked_url(&sitemap_url)?;
+            let body = self
+                .send(|| self.reqwest_client.get(url.clone()))
+                .await?
+                .text()
+                .await?;
+
+            let sitemap = parse_sitemap(&body);
+            pending.extend(sitemap.sitemaps);
+            urls.extend(
+                sitemap
+                    .urls
+                    .into_iter()
+                    .filter(|url| product_saq_code(url).is_some()),
+            );
+        }
+
+        Ok(urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sitemap() {
+        let index = parse_sitemap(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://www.saq.com/media/sitemaps/en/sitemap_1.xml</loc></sitemap>
+                <sitemap><loc>https://www.saq.com/media/sitemaps/en/sitemap_2.xml</loc></sitemap>
+            </sitemapindex>"#,
+        );
+        assert_eq!(2, index.sitemaps.len());
+        assert!(index.urls.is_empty());
+
+        let urlset = parse_sitemap(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://www.saq.com/en/13191791</loc><changefreq>daily</changefreq></url>
+                <url><loc> https://www.saq.com/en/products/wine </loc></url>
+            </urlset>"#,
+        );
+        assert_eq!(
+            vec![
+                "https://www.saq.com/en/13191791",
+                "https://www.saq.com/en/products/wine"
+            ],
+            urlset.urls
+        );
+    }
+
+    #[test]
+    fn test_product_saq_code() {
+        assert_eq!(
+            Some("13191791".to_string()),
+            product_saq_code("https://www.saq.com/en/13191791")
+        );
+        assert_eq!(None, product_saq_code("https://www.saq.com/fr/13191791"));
+        assert_eq!(
+            None,
+            product_saq_code("https://www.saq.com/en/products/wine")
+        );
+        assert_eq!(None, product_saq_code("https://www.saq.com/en/"));
+        assert_eq!(
+            Some("13191791".to_string()),
+            product_saq_code(&crate::saq::product_url("13191791"))
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Parsing the tasting notes and food pairings found alongside the Detailed
+//! Info section of (mostly wine) product pages.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Data extracted from the tasting section of product pages, whose entries
+/// are key-value pairs like those of the Detailed Info section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TastingNotes {
+    /// The SAQ's taste tag summing up the product's style (i.e. "Aromatic
+    /// and supple")
+    pub taste_tag: Option<String>,
+    /// The product's aromas (i.e. "Black fruit", "Spices")
+    pub aromas: Vec<String>,
+    /// How acidic the product is (i.e. "Lively")
+    pub acidity: Option<String>,
+    /// How sweet the product is (i.e. "Dry")
+    pub sweetness: Option<String>,
+    /// The product's body (i.e. "Full")
+    pub body: Option<String>,
+    /// How the product feels in the mouth (i.e. "Firm")
+    pub mouthfeel: Option<String>,
+    /// How much the product tastes of wood (i.e. "Present")
+    pub wood: Option<String>,
+    /// The recommended serving temperature (i.e. "16 °C to 18 °C")
+    pub serving_temperature: Option<String>,
+}
+
+impl TastingNotes {
+    /// Creates a [`TastingNotes`] struct from key-value pairs extracted from
+    /// the tasting section of the page, `None` if none of the known entries
+    /// are there (i.e. spirits and beers, which don't have tasting notes).
+    pub fn from_hash_map(mut map: HashMap<String, String>) -> Option<Self> {
+        let mut remove = |key: &str| map.remove(key).filter(|text| !text.is_empty());
+
+        let notes = TastingNotes {
+            taste_tag: remove("Taste tag"),
+            aromas: remove("Aromas")
+                .map(|text| split_list(&text))
+                .unwrap_or_default(),
+            acidity: remove("Acidity"),
+            sweetness: remove("Sweetness"),
+            body: remove("Body"),
+            mouthfeel: remove("Mouthfeel"),
+            wood: remove("Wood"),
+            serving_temperature: remove("Serving temperature"),
+        };
+
+        (notes != TastingNotes::default()).then_some(notes)
+    }
+}
+
+/// Splits a comma-separated list (i.e. "Black fruit, garrigue"), capitalizing
+/// the first letter of each entry so that the same one is always written the
+/// same way.
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Cleans up the entries of the food pairing section of the page (i.e.
+/// "Grilled red meat"), dropping blank and repeated ones while keeping the
+/// page's order.
+pub fn parse_food_pairings<I, S>(entries: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut pairings: Vec<String> = Vec::new();
+
+    for entry in entries {
+        let entry = entry
+            .as_ref()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !entry.is_empty() && !pairings.contains(&entry) {
+            pairings.push(entry);
+        }
+    }
+
+    pairings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_hash_map() {
+        let map = HashMap::from([
+            ("Taste tag".to_string(), "Aromatic and robust".to_string()),
+            (
+                "Aromas".to_string(),
+                "black fruit, garrigue, ,spices".to_string(),
+            ),
+            ("Body".to_string(), "Full".to_string()),
+            ("Wood".to_string(), "".to_string()),
+        ]);
+
+        assert_eq!(
+            Some(TastingNotes {
+                taste_tag: Some("Aromatic and robust".to_string()),
+                aromas: vec![
+                    "Black fruit".to_string(),
+                    "Garrigue".to_string(),
+                    "Spices".to_string()
+                ],
+                body: Some("Full".to_string()),
+                ..TastingNotes::default()
+            }),
+            TastingNotes::from_hash_map(map)
+        );
+
+        let unrelated = HashMap::from([("Country".to_string(), "France".to_string())]);
+        assert_eq!(None, TastingNotes::from_hash_map(unrelated));
+    }
+
+    #[test]
+    fn test_parse_food_pairings() {
+        assert_eq!(
+            vec!["Game", "Grilled red meat"],
+            parse_food_pairings(["  Game ", "", "Grilled\n   red meat", "Game"])
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Helpers to compare free-form text scraped from product pages.
+
+use sha2::{Digest, Sha256};
+
+/// Normalizes `text` so that purely cosmetic edits (i.e. re-wrapping,
+/// capitalization or swapping straight quotes for curly ones) compare equal.
+pub fn normalize(text: &str) -> String {
+    let mapped = text.chars().flat_map(char::to_lowercase).map(|c| match c {
+        '\u{2018}' | '\u{2019}' | '\u{2032}' => '\'',
+        '\u{201C}' | '\u{201D}' | '\u{00AB}' | '\u{00BB}' => '"',
+        '\u{2013}' | '\u{2014}' => '-',
+        '\u{00A0}' | '\u{202F}' => ' ',
+        c => c,
+    });
+
+    mapped
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Hex-encoded SHA-256 of the [normalized](normalize) `text`, used to detect
+/// when a description is materially rewritten.
+pub fn content_hash(text: &str) -> String {
+    Sha256::digest(normalize(text).as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            "a deep, \"concentrated\" red - it's full-bodied.",
+            normalize("  A deep,\n“concentrated”  red – it’s\u{00A0}full-bodied. ")
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash("A deep red.\nFull-bodied."),
+            content_hash("a deep red. full-bodied.")
+        );
+        assert_ne!(content_hash("A deep red."), content_hash("A deep white."));
+        assert_eq!(64, content_hash("").len());
+    }
+}
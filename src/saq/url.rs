@@ -0,0 +1,113 @@
+//! Canonical forms of SAQ URLs.
+//!
+//! The same page can be linked to in cosmetically different ways (tracking
+//! parameters, trailing slashes, `saq.com` vs `www.saq.com`, or the French
+//! version of a product page). URLs are [canonicalized](canonicalize) before
+//! being stored or compared so that these don't end up as separate rows.
+
+use super::product_url;
+use super::sitemap::product_saq_code;
+use ::url::Url;
+
+/// Query parameters added by marketing campaigns and analytics, which don't
+/// change the page being linked to.
+const TRACKING_PARAMS: [&str; 5] = ["gclid", "fbclid", "mc_cid", "mc_eid", "_ga"];
+
+/// Whether the query parameter `name` is only used for tracking.
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+/// Returns the canonical form of an SAQ `url`:
+///
+/// - Always `https://www.saq.com`
+/// - Without tracking parameters, fragments or trailing slashes
+/// - Product pages in English (i.e. `https://www.saq.com/fr/13191791` becomes
+///   `https://www.saq.com/en/13191791`)
+///
+/// Category URLs can't be translated as their paths are localized too, so
+/// those are otherwise left as is. URLs which can't be parsed or don't point to
+/// the SAQ website are returned unchanged.
+pub fn canonicalize(url: &str) -> String {
+    let mut parsed = match Url::parse(url.trim()) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+
+    match parsed.host_str() {
+        Some("www.saq.com" | "saq.com") => {}
+        _ => return url.to_string(),
+    }
+
+    // Neither of these can fail for an http(s) URL with a host
+    let _ = parsed.set_scheme("https");
+    let _ = parsed.set_host(Some("www.saq.com"));
+    parsed.set_fragment(None);
+
+    let params = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+
+    // Query parameters (i.e. `?p=2`) only matter for listings
+    if parsed.query().is_none() {
+        let english = match path.strip_prefix("/fr/") {
+            Some(rest) => format!("https://www.saq.com/en/{rest}"),
+            None => parsed.to_string(),
+        };
+        if let Some(saq_code) = product_saq_code(&english) {
+            return product_url(&saq_code);
+        }
+    }
+
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        for url in [
+            "https://www.saq.com/en/13191791",
+            "http://saq.com/en/13191791/",
+            "https://www.saq.com/fr/13191791",
+            "https://www.saq.com/en/13191791?utm_source=newsletter&utm_medium=email#reviews",
+        ] {
+            assert_eq!(
+                "https://www.saq.com/en/13191791",
+                canonicalize(url),
+                "{url}"
+            );
+        }
+
+        assert_eq!(
+            "https://www.saq.com/en/products/wine/red-wine",
+            canonicalize("https://www.saq.com/en/products/wine/red-wine/?gclid=abc")
+        );
+        assert_eq!(
+            "https://www.saq.com/fr/produits/vin",
+            canonicalize("https://saq.com/fr/produits/vin/")
+        );
+        assert_eq!(
+            "https://www.saq.com/en/products?p=2",
+            canonicalize("https://www.saq.com/en/products?p=2&fbclid=abc")
+        );
+        assert_eq!(
+            "https://www.saq.com/en/13191791?p=2",
+            canonicalize("https://www.saq.com/en/13191791?p=2")
+        );
+        assert_eq!("https://example.com/", canonicalize("https://example.com/"));
+        assert_eq!("not a url", canonicalize("not a url"));
+    }
+}
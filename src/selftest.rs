@@ -0,0 +1,278 @@
+//! Checking that a machine can run a crawl to completion (`ransaq
+//! selftest`), catching deployment problems before a four hour crawl fails
+//! at hour three:
+//!
+//! - `database_writable`: a scratch table can be written to and read back
+//! - `wal`: the database is in WAL mode, its write-ahead log can be
+//!   checkpointed, and it isn't on a network filesystem (WAL relies on
+//!   shared memory, which those don't provide)
+//! - `migrations`: the migrations applied to the database are the ones this
+//!   build expects
+//! - `https`: the [site](crate::saq::SiteProfile) can be reached with the
+//!   crawl's request settings (allowed hosts, retries, cookies)
+//! - `clock`: the clock agrees with the site's (within
+//!   [`MAX_CLOCK_SKEW_SECS`]) and isn't behind the most recent crawl
+
+use crate::config::Config;
+use crate::crawler::saq_client;
+use crate::db;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// How far the clock may be from the site's before the `clock` check fails.
+/// Product timestamps and cookie expiries are only as good as the clock.
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// Filesystem types (as listed in `/proc/self/mounts`) backed by another
+/// machine.
+const NETWORK_FILESYSTEMS: [&str; 10] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "fuse.sshfs",
+    "fuse.glusterfs",
+];
+
+/// Whether a [`Check`] passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Nothing's wrong.
+    Pass,
+    /// The check couldn't be run, i.e. the clock can't be compared with the
+    /// site's when the site can't be reached.
+    Skip,
+    /// A crawl would likely fail.
+    Fail,
+}
+
+/// The outcome of one of the checks listed in the [module docs](self).
+#[derive(Debug, Serialize)]
+pub struct Check {
+    /// The check's name, i.e. `wal`.
+    pub check: &'static str,
+    /// Whether it passed.
+    pub status: Status,
+    /// What was checked, or what's wrong.
+    pub message: String,
+}
+
+impl Check {
+    /// A check that passed.
+    fn pass(check: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            check,
+            status: Status::Pass,
+            message: message.into(),
+        }
+    }
+
+    /// A check that failed.
+    fn fail(check: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            check,
+            status: Status::Fail,
+            message: message.into(),
+        }
+    }
+
+    /// A check that couldn't be run.
+    fn skip(check: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            check,
+            status: Status::Skip,
+            message: message.into(),
+        }
+    }
+}
+
+/// Finds the filesystem holding `path` in `mounts` (in the format of
+/// `/proc/self/mounts`), returning its mount point and type.
+fn filesystem_of(path: &Path, mounts: &str) -> Option<(PathBuf, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces in mount points are escaped as `\040`
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point, fs_type.to_string()))
+        })
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+}
+
+/// Runs the `wal` check on top of [`check_wal`](db::Client::check_wal).
+async fn check_wal(db: &db::Client) -> Check {
+    if let Err(err) = db.check_wal().await {
+        return Check::fail("wal", format!("{err:#}"));
+    }
+
+    let path = match db.database_file().await {
+        Ok(Some(path)) => path,
+        Ok(None) => return Check::pass("wal", "the database is in memory"),
+        Err(err) => return Check::fail("wal", format!("{err:#}")),
+    };
+
+    // Only Linux lists mounts there, elsewhere the checkpoint has to do
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    match filesystem_of(&path, &mounts) {
+        Some((mount_point, fs_type)) if NETWORK_FILESYSTEMS.contains(&fs_type.as_str()) => {
+            Check::fail(
+                "wal",
+                format!(
+                    "{} is on a network filesystem ({} mounted at {}), where WAL mode isn't safe",
+                    path.display(),
+                    fs_type,
+                    mount_point.display()
+                ),
+            )
+        }
+        Some((_, fs_type)) => Check::pass("wal", format!("checkpointed the log on {fs_type}")),
+        None => Check::pass("wal", "checkpointed the log"),
+    }
+}
+
+/// Runs the `clock` check, comparing `now` with the site's clock and the
+/// start of the most recent crawl.
+fn check_clock(
+    now: DateTime<Utc>,
+    server_date: Option<DateTime<Utc>>,
+    latest_crawl: Option<DateTime<Utc>>,
+) -> Check {
+    if let Some(started_at) = latest_crawl.filter(|started_at| *started_at > now) {
+        return Check::fail(
+            "clock",
+            format!("the clock is behind the start of the latest crawl ({started_at})"),
+        );
+    }
+
+    match server_date {
+        Some(server_date) => {
+            let skew = (now - server_date).num_seconds();
+            if skew.abs() > MAX_CLOCK_SKEW_SECS {
+                Check::fail(
+                    "clock",
+                    format!("the clock is {skew} seconds off from the site's ({server_date})"),
+                )
+            } else {
+                Check::pass(
+                    "clock",
+                    format!("within {} seconds of the site's", skew.abs()),
+                )
+            }
+        }
+        None => Check::skip("clock", "the site's clock is unknown"),
+    }
+}
+
+/// Runs every check listed in the [module docs](self), in order.
+pub async fn run(db: &db::Client, config: &Config) -> Vec<Check> {
+    let mut checks = vec![];
+
+    checks.push(match db.check_writable().await {
+        Ok(()) => Check::pass("database_writable", "wrote and read back a scratch table"),
+        Err(err) => Check::fail("database_writable", format!("{err:#}")),
+    });
+
+    checks.push(check_wal(db).await);
+
+    checks.push(match db.migration_problems().await {
+        Ok(problems) if problems.is_empty() => Check::pass("migrations", "up to date"),
+        Ok(problems) => Check::fail("migrations", problems.join("; ")),
+        Err(err) => Check::fail("migrations", format!("{err:#}")),
+    });
+
+    let start = Instant::now();
+    let ping = match saq_client(config) {
+        Ok(client) => client.ping().await,
+        Err(err) => Err(err),
+    };
+    let server_date = match ping {
+        Ok(server_date) => {
+            checks.push(Check::pass(
+                "https",
+                format!(
+                    "reached {} in {} ms",
+                    config.site.base_url,
+                    start.elapsed().as_millis()
+                ),
+            ));
+            server_date
+        }
+        Err(err) => {
+            checks.push(Check::fail("https", format!("{err:#}")));
+            None
+        }
+    };
+
+    checks.push(match db.latest_crawl_started_at().await {
+        Ok(latest_crawl) => check_clock(Utc::now(), server_date, latest_crawl),
+        Err(err) => Check::fail("clock", format!("{err:#}")),
+    });
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_filesystem_of() {
+        let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+            nas:/export /mnt/nas nfs4 rw,vers=4.2 0 0\n\
+            //nas/share /mnt/nas/my\\040share cifs rw 0 0\n";
+
+        assert_eq!(
+            Some((PathBuf::from("/"), "ext4".to_string())),
+            filesystem_of(Path::new("/home/ransaq/ransaq.sqlite"), mounts)
+        );
+        assert_eq!(
+            Some((PathBuf::from("/mnt/nas"), "nfs4".to_string())),
+            filesystem_of(Path::new("/mnt/nas/ransaq.sqlite"), mounts)
+        );
+        assert_eq!(
+            Some((PathBuf::from("/mnt/nas/my share"), "cifs".to_string())),
+            filesystem_of(Path::new("/mnt/nas/my share/ransaq.sqlite"), mounts)
+        );
+        // Mount points only match whole path components
+        assert_eq!(
+            Some((PathBuf::from("/"), "ext4".to_string())),
+            filesystem_of(Path::new("/mnt/nas2/ransaq.sqlite"), mounts)
+        );
+        assert_eq!(None, filesystem_of(Path::new("/ransaq.sqlite"), ""));
+    }
+
+    #[test]
+    fn test_check_clock() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+
+        let status = |server_date, latest_crawl| check_clock(now, server_date, latest_crawl).status;
+        assert_eq!(Status::Skip, status(None, None));
+        assert_eq!(
+            Status::Pass,
+            status(
+                Some(now - Duration::seconds(20)),
+                Some(now - Duration::hours(2))
+            )
+        );
+        assert_eq!(
+            Status::Fail,
+            status(Some(now + Duration::minutes(10)), None)
+        );
+        assert_eq!(
+            Status::Fail,
+            status(Some(now), Some(now + Duration::days(1)))
+        );
+    }
+}
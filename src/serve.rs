@@ -0,0 +1,404 @@
+//! A read-only HTTP API over the crawled catalog, run by `ransaq serve`, so
+//! other apps can consume it without reading the database themselves.
+//!
+//! Every response is JSON:
+//!
+//! - `GET /products` lists products by name (see
+//!   [`ProductSummary`](db::ProductSummary)), accepting
+//!   - `q`, a [filter expression](crate::filter) (i.e.
+//!     `/products?q=country:France+price<25`)
+//!   - `limit` (50 by default, at most 500) and `offset`, to page through
+//!     results
+//! - `GET /products/<saq_code>` returns a product along with everything
+//!   related to it, in the same shape as [exported](crate::export) ones and
+//!   with the same fields [redacted](crate::export::redact)
+//! - `GET /categories` lists every category along with its number of products
+//!   (see [`CategoryListing`](db::CategoryListing))
+//! - `GET /stores` lists every store (see [`Store`](db::Store)), or with
+//!   `near=<postal code>` the ones [nearest](crate::stores) to it along with
+//!   their distance, accepting `limit` (10 by default). The API never asks
+//!   the geocoder, so only postal codes located before (i.e. with `ransaq
+//!   stores near`) are accepted
+//!
+//! Errors are returned as `{"error": "..."}` with a matching status code.
+//!
+//! The server speaks HTTP/1.1 (through [`hyper`]) and is meant to sit behind a
+//! reverse proxy if exposed beyond the local network.
+
+use crate::config::RedactableField;
+use crate::db;
+use crate::export;
+use crate::filter::Filter;
+use crate::stores;
+use color_eyre::eyre::Result;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Server, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{debug, error};
+use url::Url;
+
+/// Number of products listed by `/products` unless `limit` is given.
+const DEFAULT_LIMIT: i64 = 50;
+
+/// Maximum `limit` accepted by `/products`.
+const MAX_LIMIT: i64 = 500;
+
+/// Number of stores listed by `/stores?near=...` unless `limit` is given.
+const DEFAULT_STORES_LIMIT: i64 = 10;
+
+/// How long clients get to send a request's headers before being
+/// disconnected.
+const HEADER_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What requests are answered with.
+struct Api {
+    /// The crawled catalog.
+    db: db::Client,
+    /// Fields blanked out of `/products/<saq_code>`.
+    redacted: Vec<RedactableField>,
+}
+
+/// A response to a request.
+#[derive(Debug)]
+struct Response {
+    /// The status code and reason, i.e. `404 Not Found`.
+    status: &'static str,
+    /// The response's body.
+    body: Value,
+}
+
+impl Response {
+    /// A successful response with `body`.
+    fn ok(body: impl Serialize) -> Result<Response> {
+        Ok(Response {
+            status: "200 OK",
+            body: serde_json::to_value(body)?,
+        })
+    }
+
+    /// An error response with the given `status`.
+    fn error(status: &'static str, message: impl fmt::Display) -> Response {
+        Response {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    /// A `404 Not Found` response.
+    fn not_found() -> Response {
+        Response::error("404 Not Found", "not found")
+    }
+}
+
+impl From<Response> for hyper::Response<Body> {
+    fn from(response: Response) -> hyper::Response<Body> {
+        let mut answer = hyper::Response::new(Body::from(response.body.to_string()));
+        *answer.status_mut() = StatusCode::from_bytes(&response.status.as_bytes()[..3])
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        answer
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        answer
+    }
+}
+
+/// Parses the query parameter `name` of `url` as a non-negative integer, or
+/// returns a `400 Bad Request` response if it isn't one.
+fn integer_param(url: &Url, name: &str, default: i64) -> Result<i64, Response> {
+    match url.query_pairs().find(|(key, _)| key == name) {
+        None => Ok(default),
+        Some((_, value)) => value
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n >= 0)
+            .ok_or_else(|| {
+                Response::error(
+                    "400 Bad Request",
+                    format!("{name} must be a non-negative integer, got {value:?}"),
+                )
+            }),
+    }
+}
+
+/// Answers `GET /products`.
+async fn products(db: &db::Client, url: &Url) -> Result<Response> {
+    let query = url
+        .query_pairs()
+        .find(|(key, _)| key == "q")
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default();
+    let filter = match query.parse::<Filter>() {
+        Ok(filter) => filter,
+        Err(err) => return Ok(Response::error("400 Bad Request", err)),
+    };
+
+    let (limit, offset) = match (
+        integer_param(url, "limit", DEFAULT_LIMIT),
+        integer_param(url, "offset", 0),
+    ) {
+        (Ok(limit), Ok(offset)) => (limit.min(MAX_LIMIT), offset),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+
+    let products = db.search_page(&filter, limit, offset).await?;
+
+    Response::ok(json!({
+        "products": products,
+        "limit": limit,
+        "offset": offset,
+    }))
+}
+
+/// Answers `GET /products/<saq_code>`.
+async fn product(api: &Api, saq_code: &str) -> Result<Response> {
+    match export::product(&api.db, saq_code).await? {
+        Some(mut product) => {
+            export::redact(&mut product, &api.redacted);
+            Response::ok(product)
+        }
+        None => Ok(Response::not_found()),
+    }
+}
+
+/// Answers `GET /stores`.
+async fn stores(api: &Api, url: &Url) -> Result<Response> {
+    let all = api.db.stores().await?;
+
+    let postal_code = match url.query_pairs().find(|(key, _)| key == "near") {
+        Some((_, postal_code)) => postal_code.into_owned(),
+        None => return Response::ok(all),
+    };
+    if stores::normalize_postal_code(&postal_code).is_none() {
+        return Ok(Response::error(
+            "400 Bad Request",
+            format!("{postal_code:?} isn't a valid postal code"),
+        ));
+    }
+    let limit = match integer_param(url, "limit", DEFAULT_STORES_LIMIT) {
+        Ok(limit) => limit.min(MAX_LIMIT) as usize,
+        Err(response) => return Ok(response),
+    };
+
+    match stores::locate_cached(&api.db, &postal_code).await? {
+        Some(origin) => Response::ok(stores::nearest(all, &origin, limit)),
+        None => Ok(Response::error(
+            "404 Not Found",
+            format!("{postal_code:?} wasn't located yet"),
+        )),
+    }
+}
+
+/// Answers a request for `target` (its path and query).
+async fn route(api: &Api, method: &str, target: &str) -> Response {
+    if method != "GET" {
+        return Response::error("405 Method Not Allowed", "only GET requests are supported");
+    }
+
+    let url = match target
+        .starts_with('/')
+        .then(|| Url::parse(&format!("http://localhost{target}")).ok())
+        .flatten()
+    {
+        Some(url) => url,
+        None => return Response::error("400 Bad Request", "invalid request target"),
+    };
+
+    let segments = url
+        .path()
+        .trim_end_matches('/')
+        .split('/')
+        .skip(1)
+        .collect::<Vec<_>>();
+
+    let response = match segments.as_slice() {
+        ["products"] => products(&api.db, &url).await,
+        ["products", saq_code] => product(api, saq_code).await,
+        ["categories"] => api.db.categories().await.and_then(Response::ok),
+        ["stores"] => stores(api, &url).await,
+        _ => Ok(Response::not_found()),
+    };
+
+    response.unwrap_or_else(|err| {
+        error!(?err, target, "failed to answer request");
+        Response::error("500 Internal Server Error", "internal error")
+    })
+}
+
+/// Answers `request` (see [`route`]).
+async fn handle(api: &Api, request: Request<Body>) -> hyper::Response<Body> {
+    let method = request.method().as_str();
+    let target = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |target| target.as_str());
+
+    let response = route(api, method, target).await;
+    debug!(method, target, status = response.status, "answered request");
+
+    response.into()
+}
+
+/// Serves the API on `listener`, reading from `db` and blanking out the
+/// `redacted` fields of products, until the process is stopped.
+pub async fn serve(
+    db: db::Client,
+    redacted: Vec<RedactableField>,
+    listener: TcpListener,
+) -> Result<()> {
+    let api = Arc::new(Api { db, redacted });
+    let make_service = make_service_fn(move |_| {
+        let api = api.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let api = api.clone();
+                async move { Ok::<_, Infallible>(handle(&api, request).await) }
+            }))
+        }
+    });
+
+    Server::builder(AddrIncoming::from_listener(listener)?)
+        .http1_header_read_timeout(HEADER_READ_TIMEOUT)
+        .serve(make_service)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_serve() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        for (saq_code, name, price_cents) in [
+            ("123", "Bordeaux", 1995),
+            ("456", "Chianti", 2450),
+            ("789", "Cahors", 1500),
+        ] {
+            db.insert_product(saq_code, name, price_cents).await?;
+        }
+
+        for sql in [
+            "insert into countries (name) values ('France')",
+            "update products set country_id = 1 where saq_code in ('123', '789')",
+            "update products set image_url = 'https://www.saq.com/media/' || saq_code || '.png' where saq_code != '789'",
+            r#"insert into categories (url, parent_category_id, name) values
+            ('https://www.saq.com/en/products/wine', null, 'Wine'),
+            ('https://www.saq.com/en/products/wine/red-wine', 1, 'Red wine')"#,
+            "insert into product_categories (product_id, category_id) values (1, 1), (1, 2), (2, 1)",
+            r#"insert into stores (saq_store_id, name, address, city, postal_code, latitude, longitude)
+            values ('1', 'Laurier', '1 Rue Laurier', 'Montréal', 'H2V 4H1', 45.523, -73.596),
+            ('2', 'Vieux-Québec', '1 Rue Saint-Jean', 'Québec', 'G1R 4P5', 46.812, -71.214)"#,
+            r#"insert into geocoded_postal_codes (postal_code, latitude, longitude)
+            values ('G1R 4P5', 46.812, -71.214)"#,
+        ] {
+            sqlx::query(sql).execute(db.pool()).await?;
+        }
+
+        let api = Api {
+            db: (*db).clone(),
+            redacted: vec![RedactableField::ImageUrl],
+        };
+        let get = |target: &'static str| {
+            let api = &api;
+            async move {
+                let response = route(api, "GET", target).await;
+                (response.status, response.body)
+            }
+        };
+
+        let (status, body) = get("/products?q=country:france&limit=1&offset=1").await;
+        assert_eq!("200 OK", status);
+        assert_eq!(1, body["limit"]);
+        assert_eq!(
+            json!(["789"]),
+            json!(body["products"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|product| &product["saq_code"])
+                .collect::<Vec<_>>())
+        );
+        assert_eq!(
+            3,
+            get("/products/").await.1["products"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+        assert_eq!(MAX_LIMIT, get("/products?limit=9999").await.1["limit"]);
+        assert_eq!("400 Bad Request", get("/products?limit=-1").await.0);
+        assert_eq!("400 Bad Request", get("/products?q=colour:red").await.0);
+
+        let (status, body) = get("/products/123").await;
+        assert_eq!("200 OK", status);
+        assert_eq!("France", body["country"]);
+        assert_eq!(2, body["categories"].as_array().unwrap().len());
+        assert_eq!(Value::Null, body["image_url"]);
+        assert_eq!("404 Not Found", get("/products/999").await.0);
+
+        let (status, body) = get("/categories").await;
+        assert_eq!("200 OK", status);
+        assert_eq!(
+            json!([
+                {"id": 2, "name": "Red wine", "url": "https://www.saq.com/en/products/wine/red-wine",
+                    "parent_category_id": 1, "product_count": 1},
+                {"id": 1, "name": "Wine", "url": "https://www.saq.com/en/products/wine",
+                    "parent_category_id": null, "product_count": 2},
+            ]),
+            body
+        );
+
+        let (status, body) = get("/stores").await;
+        assert_eq!("200 OK", status);
+        assert_eq!(2, body.as_array().unwrap().len());
+        let (status, body) = get("/stores?near=g1r4p5&limit=1").await;
+        assert_eq!("200 OK", status);
+        assert_eq!(1, body.as_array().unwrap().len());
+        assert_eq!("Vieux-Québec", body[0]["name"]);
+        assert_eq!(0.0, body[0]["distance_km"]);
+        assert_eq!("400 Bad Request", get("/stores?near=90210").await.0);
+        // Postal codes which weren't located before aren't looked up
+        assert_eq!("404 Not Found", get("/stores?near=H2V4H1").await.0);
+
+        assert_eq!("404 Not Found", get("/warehouses").await.0);
+        assert_eq!(
+            "405 Method Not Allowed",
+            route(&api, "POST", "/products").await.status
+        );
+
+        // Over HTTP
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let url = format!("http://{}/products/456", listener.local_addr()?);
+        let server = tokio::spawn(serve((*db).clone(), vec![], listener));
+
+        let response = reqwest::get(url).await?;
+        assert_eq!(reqwest::StatusCode::OK, response.status());
+        assert_eq!(
+            Some("application/json"),
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+        );
+        let body = serde_json::from_str::<Value>(&response.text().await?)?;
+        assert_eq!("Chianti", body["name"]);
+        assert_eq!("https://www.saq.com/media/456.png", body["image_url"]);
+
+        server.abort();
+
+        Ok(())
+    }
+}
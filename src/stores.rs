@@ -0,0 +1,467 @@
+//! The SAQ store directory: importing the store list, locating stores, and
+//! finding the ones nearest to a postal code (`ransaq stores`, and the
+//! [API's](crate::serve) `/stores` endpoint).
+//!
+//! Store lists are JSON arrays of [`Store`]s, i.e.
+//!
+//! ```json
+//! [{"saq_store_id": "23009", "name": "Du Parc", "address": "5610 Avenue du Parc",
+//!   "city": "Montréal", "postal_code": "H2V 4H1", "latitude": 45.5216, "longitude": -73.6006}]
+//! ```
+//!
+//! Stores listed without coordinates get those of their postal code.
+//! Postal codes are geocoded using a
+//! [Nominatim](https://nominatim.org/release-docs/latest/api/Search/)-compatible
+//! endpoint (see `RANSAQ_GEOCODER_URL`) at most once a second, through the
+//! crawler's proxies, and only once each as the results are kept in the
+//! database. Postal codes the geocoder doesn't know are only asked about again
+//! a month later.
+
+use crate::db::{self, Store};
+use crate::saq::{self, AllowedHosts, Proxies};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+use url::Url;
+
+/// The Earth's mean radius, in kilometers.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Sent along with geocoding requests, as Nominatim's usage policy requires
+/// identifying the application.
+const USER_AGENT: &str = "ransaq (https://github.com/davidcornu/ransaq)";
+
+/// How long the geocoder gets to answer.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A point on the Earth.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Coordinates {
+    /// The latitude, in degrees.
+    pub latitude: f64,
+    /// The longitude, in degrees.
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    /// The coordinates of `store`, if known.
+    pub fn of(store: &Store) -> Option<Coordinates> {
+        match (store.latitude, store.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The great-circle distance to `other` in kilometers, using the
+    /// [haversine formula](https://en.wikipedia.org/wiki/Haversine_formula).
+    pub fn distance_km(&self, other: &Coordinates) -> f64 {
+        let (latitude, other_latitude) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let half_latitude_delta = (other_latitude - latitude) / 2.0;
+        let half_longitude_delta = (other.longitude - self.longitude).to_radians() / 2.0;
+
+        let haversine = half_latitude_delta.sin().powi(2)
+            + latitude.cos() * other_latitude.cos() * half_longitude_delta.sin().powi(2);
+
+        2.0 * EARTH_RADIUS_KM * haversine.sqrt().asin()
+    }
+}
+
+/// Formats a Canadian postal code the way Canada Post does (i.e. `h2v4h1`
+/// becomes `H2V 4H1`), `None` if it isn't one.
+pub fn normalize_postal_code(postal_code: &str) -> Option<String> {
+    let compact = postal_code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    let valid = compact.len() == 6
+        && compact.chars().enumerate().all(|(i, c)| match i % 2 {
+            0 => c.is_ascii_alphabetic(),
+            _ => c.is_ascii_digit(),
+        });
+
+    valid.then(|| format!("{} {}", &compact[..3], &compact[3..]))
+}
+
+/// A place returned by the geocoder.
+#[derive(Debug, Deserialize)]
+struct Place {
+    /// The latitude, as a decimal string.
+    lat: String,
+    /// The longitude, as a decimal string.
+    lon: String,
+}
+
+/// Returns the coordinates of `postal_code` if it was geocoded before,
+/// without asking the geocoder.
+pub async fn locate_cached(db: &db::Client, postal_code: &str) -> Result<Option<Coordinates>> {
+    let postal_code = normalize_postal_code(postal_code)
+        .ok_or_else(|| eyre!("{postal_code:?} isn't a valid postal code"))?;
+
+    let coordinates = db
+        .geocoded_postal_code(&postal_code)
+        .await?
+        .map(|(latitude, longitude)| Coordinates {
+            latitude,
+            longitude,
+        });
+
+    Ok(coordinates)
+}
+
+/// Looks up the coordinates of postal codes, see the [module docs](self).
+pub struct Geocoder {
+    /// Sends requests to the geocoder, and nowhere else.
+    client: saq::Client,
+    /// The geocoder's search endpoint.
+    url: Url,
+}
+
+impl Geocoder {
+    /// Creates a geocoder sending requests to the search endpoint at `url`
+    /// through `proxies`, if there are any. Its host is the only one allowed,
+    /// so redirects elsewhere are refused.
+    pub fn new(url: Url, proxies: &Proxies) -> Result<Geocoder> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| eyre!("the geocoder URL {url} has no host"))?;
+
+        let mut client = saq::Client::with_allowed_hosts(AllowedHosts(vec![host.to_lowercase()]))?
+            .rate_limit(1.0);
+        if !proxies.0.is_empty() {
+            client = client.with_proxies(proxies)?;
+        }
+
+        Ok(Geocoder { client, url })
+    }
+
+    /// Asks the geocoder for the coordinates of a (normalized) postal code.
+    async fn lookup(&self, postal_code: &str) -> Result<Option<Coordinates>> {
+        let mut url = self.url.clone();
+        url.query_pairs_mut().extend_pairs([
+            ("postalcode", postal_code),
+            ("country", "ca"),
+            ("format", "json"),
+            ("limit", "1"),
+        ]);
+
+        let places = tokio::time::timeout(
+            LOOKUP_TIMEOUT,
+            self.client.get_json::<Vec<Place>>(&url, USER_AGENT),
+        )
+        .await
+        .map_err(|_| eyre!("the geocoder didn't answer within {LOOKUP_TIMEOUT:?}"))??;
+        places
+            .first()
+            .map(|place| {
+                Ok(Coordinates {
+                    latitude: place.lat.parse()?,
+                    longitude: place.lon.parse()?,
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns the coordinates of `postal_code`, only asking the geocoder if
+    /// they aren't in the database yet. `None` if the geocoder doesn't know
+    /// the postal code.
+    pub async fn locate(&self, db: &db::Client, postal_code: &str) -> Result<Option<Coordinates>> {
+        let postal_code = normalize_postal_code(postal_code)
+            .ok_or_else(|| eyre!("{postal_code:?} isn't a valid postal code"))?;
+
+        if let Some(coordinates) = locate_cached(db, &postal_code).await? {
+            return Ok(Some(coordinates));
+        }
+        if db.unlocatable_postal_code(&postal_code).await? {
+            return Ok(None);
+        }
+
+        let coordinates = self
+            .lookup(&postal_code)
+            .await
+            .wrap_err_with(|| format!("failed to geocode {postal_code}"))?;
+
+        match coordinates {
+            Some(coordinates) => {
+                db.cache_geocoded_postal_code(
+                    &postal_code,
+                    coordinates.latitude,
+                    coordinates.longitude,
+                )
+                .await?
+            }
+            None => db.cache_unlocatable_postal_code(&postal_code).await?,
+        }
+
+        Ok(coordinates)
+    }
+}
+
+/// The outcome of [`import`].
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// Number of stores imported.
+    pub imported: usize,
+    /// Number of stores located using their postal code.
+    pub geocoded: usize,
+    /// The `saq_store_id`s of stores which couldn't be located, and therefore
+    /// won't be found by [`nearest`].
+    pub unlocated: Vec<String>,
+}
+
+/// Reads a store list (see the [module docs](self)) from `path`.
+pub fn read_store_list(path: &Path) -> Result<Vec<Store>> {
+    let json =
+        std::fs::read_to_string(path).wrap_err_with(|| format!("failed to read {path:?}"))?;
+
+    serde_json::from_str(&json).wrap_err_with(|| format!("failed to parse store list {path:?}"))
+}
+
+/// Adds `stores` to the database (updating the ones already in it), locating
+/// those without coordinates using their postal code.
+pub async fn import(
+    db: &db::Client,
+    geocoder: &Geocoder,
+    stores: Vec<Store>,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    for mut store in stores {
+        if let Some(postal_code) = normalize_postal_code(&store.postal_code) {
+            store.postal_code = postal_code;
+        }
+
+        if Coordinates::of(&store).is_none() {
+            match geocoder.locate(db, &store.postal_code).await {
+                Ok(Some(coordinates)) => {
+                    store.latitude = Some(coordinates.latitude);
+                    store.longitude = Some(coordinates.longitude);
+                    report.geocoded += 1;
+                }
+                Ok(None) => report.unlocated.push(store.saq_store_id.clone()),
+                Err(err) => {
+                    warn!(?err, saq_store_id = %store.saq_store_id, "failed to locate store");
+                    report.unlocated.push(store.saq_store_id.clone());
+                }
+            }
+        }
+
+        db.upsert_store(&store).await?;
+        report.imported += 1;
+    }
+
+    Ok(report)
+}
+
+/// A store along with how far it is from somewhere.
+#[derive(Debug, Serialize)]
+pub struct NearbyStore {
+    /// The store.
+    #[serde(flatten)]
+    pub store: Store,
+    /// How far the store is, in kilometers.
+    pub distance_km: f64,
+}
+
+/// Returns the `limit` stores closest to `origin`, closest first. Stores
+/// without coordinates are left out.
+///
+/// ```
+/// use ransaq::stores::{nearest, Coordinates};
+///
+/// # #[tokio::main]
+/// # async fn main() -> color_eyre::Result<()> {
+/// let db = ransaq::db::Client::new_in_memory_with_sample_data().await?;
+///
+/// let montreal = Coordinates {
+///     latitude: 45.5017,
+///     longitude: -73.5673,
+/// };
+/// let closest = nearest(db.stores().await?, &montreal, 1);
+/// assert_eq!("Laurier", closest[0].store.name);
+/// assert!(closest[0].distance_km < 5.0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn nearest(stores: Vec<Store>, origin: &Coordinates, limit: usize) -> Vec<NearbyStore> {
+    let mut nearby = stores
+        .into_iter()
+        .filter_map(|store| {
+            let distance_km = Coordinates::of(&store)?.distance_km(origin);
+            Some(NearbyStore { store, distance_km })
+        })
+        .collect::<Vec<_>>();
+
+    nearby.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    nearby.truncate(limit);
+    nearby
+}
+
+/// Returns the `limit` stores closest to `postal_code`, closest first.
+pub async fn near(
+    db: &db::Client,
+    geocoder: &Geocoder,
+    postal_code: &str,
+    limit: usize,
+) -> Result<Vec<NearbyStore>> {
+    let origin = geocoder
+        .locate(db, postal_code)
+        .await?
+        .ok_or_else(|| eyre!("could not locate {postal_code:?}"))?;
+
+    Ok(nearest(db.stores().await?, &origin, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+    use hyper::server::conn::AddrIncoming;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Server};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_distance_and_postal_codes() {
+        let montreal = Coordinates {
+            latitude: 45.5017,
+            longitude: -73.5673,
+        };
+        let quebec = Coordinates {
+            latitude: 46.8139,
+            longitude: -71.2080,
+        };
+        assert!((montreal.distance_km(&quebec) - 233.0).abs() < 1.0);
+        assert_eq!(0.0, montreal.distance_km(&montreal));
+
+        assert_eq!(
+            Some("H2V 4H1".to_string()),
+            normalize_postal_code(" h2v4h1")
+        );
+        assert_eq!(
+            Some("H2V 4H1".to_string()),
+            normalize_postal_code("H2V 4H1")
+        );
+        assert_eq!(None, normalize_postal_code("H2V 4H"));
+        assert_eq!(None, normalize_postal_code("90210"));
+        assert_eq!(None, normalize_postal_code("H2V 4HÉ"));
+    }
+
+    #[tokio::test]
+    async fn test_near() -> Result<()> {
+        let db = TestDb::new().await?;
+        // Nothing listens there, so any lookup would fail
+        let geocoder = Geocoder::new(
+            Url::parse("http://127.0.0.1:9/search")?,
+            &Proxies::default(),
+        )?;
+
+        db.cache_geocoded_postal_code("H2V 4H1", 45.5216, -73.6006)
+            .await?;
+        db.cache_geocoded_postal_code("G1R 4P5", 46.8123, -71.2145)
+            .await?;
+
+        let store =
+            |id: &str, name: &str, postal_code: &str, coordinates: Option<(f64, f64)>| Store {
+                saq_store_id: id.to_string(),
+                name: name.to_string(),
+                address: "1 Rue Principale".to_string(),
+                city: "Somewhere".to_string(),
+                postal_code: postal_code.to_string(),
+                latitude: coordinates.map(|(latitude, _)| latitude),
+                longitude: coordinates.map(|(_, longitude)| longitude),
+            };
+
+        let report = import(
+            &db,
+            &geocoder,
+            vec![
+                store("1", "Vieux-Québec", "g1r4p5", None),
+                store("2", "Laurier", "H2V 4H1", Some((45.5230, -73.5960))),
+                store("3", "Sherbrooke", "J1H 1A1", None),
+                store("4", "Atwater", "H3J 1E4", Some((45.4809, -73.5780))),
+            ],
+        )
+        .await?;
+        assert_eq!(4, report.imported);
+        assert_eq!(1, report.geocoded);
+        assert_eq!(vec!["3"], report.unlocated);
+
+        let stores = db.stores().await?;
+        assert_eq!(
+            "G1R 4P5",
+            stores
+                .iter()
+                .find(|s| s.saq_store_id == "1")
+                .unwrap()
+                .postal_code
+        );
+
+        let nearby = near(&db, &geocoder, "h2v 4h1", 2).await?;
+        assert_eq!(
+            vec!["Laurier", "Atwater"],
+            nearby
+                .iter()
+                .map(|nearby| nearby.store.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(nearby[0].distance_km < 1.0);
+
+        assert!(near(&db, &geocoder, "not a postal code", 2).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locate_unknown() -> Result<()> {
+        let db = TestDb::new().await?;
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+        let url = Url::parse(&format!("http://{}/search", listener.local_addr()?))?;
+        // Answers every search with no places, counting the requests
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        let make_service = make_service_fn(move |_| {
+            let counted = counted.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    counted.fetch_add(1, Ordering::Relaxed);
+                    async { Ok::<_, Infallible>(hyper::Response::new(Body::from("[]"))) }
+                }))
+            }
+        });
+        let server = tokio::spawn(
+            Server::builder(AddrIncoming::from_listener(listener)?).serve(make_service),
+        );
+
+        // Misses are cached too
+        let geocoder = Geocoder::new(url, &Proxies::default())?;
+        assert_eq!(None, geocoder.locate(&db, "J1H 1A1").await?);
+        assert_eq!(None, geocoder.locate(&db, "j1h1a1").await?);
+        assert_eq!(1, requests.load(Ordering::Relaxed));
+        assert_eq!(None, locate_cached(&db, "J1H 1A1").await?);
+
+        // Only the geocoder's host can be contacted
+        let elsewhere = Geocoder::new(
+            Url::parse("http://127.0.0.2:9/search")?,
+            &Proxies::default(),
+        )?;
+        assert!(elsewhere
+            .client
+            .get_json::<Vec<Place>>(&Url::parse("https://www.saq.com/")?, USER_AGENT)
+            .await
+            .is_err());
+
+        server.abort();
+
+        Ok(())
+    }
+}